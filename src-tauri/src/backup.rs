@@ -0,0 +1,635 @@
+// src-tauri/src/backup.rs
+//
+// Database Backups
+// ================
+// Snapshots of `storyteller.db` in `app_data/backups/`, written with
+// `VACUUM INTO` (a consistent copy taken while the app keeps running):
+//
+//   storyteller_<kind>_<unix secs>.db    database only
+//   storyteller_<kind>_<unix secs>.zip   database + generated images/portraits
+//
+// A second snapshot in the same second gets a "-2", "-3", ... suffix after
+// the seconds.
+//
+// kind is "manual", "scheduled", "pre_restore" or "pre_migration_v<N>"
+// (see migrations.rs). The scheduler takes a snapshot every
+// `AppConfig::backup_interval_hours` and keeps the newest
+// `AppConfig::backup_retention` scheduled backups; the other kinds are only
+// deleted by the user.
+//
+// Restoring never swaps files under an open connection. The backup is copied
+// to a staging file and migrated to the current schema, then its rows replace
+// the live tables in one transaction on the pool's only connection — every
+// other query waits for it, and a failure leaves the live data untouched.
+// The current database is backed up ("pre_restore") first.
+
+use serde::Serialize;
+use sqlx::sqlite::{SqliteConnection, SqlitePool, SqlitePoolOptions};
+use sqlx::{Connection, Row};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager, State};
+
+use crate::config::ConfigState;
+use crate::error::{AppError, ErrorKind};
+use crate::state::OllamaState;
+
+/// Folders under app_data bundled by media backups. Image rows store
+/// absolute paths, so restored files go back to the same place.
+const MEDIA_DIRS: &[&str] = &["generated_images", "scene_images", "master_portraits", "character_masters"];
+
+/// Name of the database inside a .zip backup.
+const DB_ENTRY: &str = "storyteller.db";
+
+/// How often the scheduler checks whether a backup is due.
+const SCHEDULER_TICK_SECS: u64 = 15 * 60;
+
+// ============================================================================
+// TYPES
+// ============================================================================
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BackupInfo {
+    pub file_name: String,
+    pub path: String,
+    pub kind: String,
+    /// Unix seconds
+    pub created_at: u64,
+    pub size_bytes: u64,
+    pub includes_media: bool,
+    /// Orders snapshots taken in the same second
+    #[serde(skip)]
+    sequence: u32,
+}
+
+/// Split the "<secs>" or "<secs>-<n>" of a backup name into (secs, n).
+fn parse_stamp(stamp: &str) -> Option<(u64, u32)> {
+    match stamp.split_once('-') {
+        Some((secs, n)) => Some((secs.parse().ok()?, n.parse().ok()?)),
+        None => Some((stamp.parse().ok()?, 1)),
+    }
+}
+
+/// Split "storyteller_<kind>_<secs>[-<n>].<db|zip>" into (kind, secs, n, is_zip).
+fn split_backup_name(file_name: &str) -> Option<(&str, u64, u32, bool)> {
+    let rest = file_name.strip_prefix("storyteller_")?;
+    let (stem, is_zip) = match rest.strip_suffix(".zip") {
+        Some(stem) => (stem, true),
+        None => (rest.strip_suffix(".db")?, false),
+    };
+    let (kind, stamp) = stem.rsplit_once('_')?;
+    if kind.is_empty() {
+        return None;
+    }
+    let (secs, n) = parse_stamp(stamp)?;
+    Some((kind, secs, n, is_zip))
+}
+
+/// Split "storyteller_<kind>_<secs>[-<n>].<db|zip>" into (kind, secs, is_zip).
+fn parse_backup_name(file_name: &str) -> Option<(String, u64, bool)> {
+    let (kind, secs, _, is_zip) = split_backup_name(file_name)?;
+    Some((kind.to_string(), secs, is_zip))
+}
+
+fn backup_info(path: &Path) -> Option<BackupInfo> {
+    let file_name = path.file_name()?.to_string_lossy().to_string();
+    let (kind, created_at, sequence, includes_media) = split_backup_name(&file_name)?;
+    let kind = kind.to_string();
+    Some(BackupInfo {
+        path: path.to_string_lossy().to_string(),
+        size_bytes: std::fs::metadata(path).map(|m| m.len()).unwrap_or(0),
+        file_name,
+        kind,
+        created_at,
+        includes_media,
+        sequence,
+    })
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn app_data_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app dir: {}", e))
+}
+
+pub fn backup_dir(app_data: &Path) -> PathBuf {
+    app_data.join("backups")
+}
+
+// ============================================================================
+// SNAPSHOTS
+// ============================================================================
+
+/// Add every file under `dir` to the archive, named relative to `root`.
+fn zip_dir<W: Write + std::io::Seek>(zip: &mut zip::ZipWriter<W>, root: &Path, dir: &Path) -> Result<(), String> {
+    // Images are already compressed
+    let options = zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    for entry in std::fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            zip_dir(zip, root, &path)?;
+            continue;
+        }
+        let name = path
+            .strip_prefix(root)
+            .map_err(|e| e.to_string())?
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().to_string())
+            .collect::<Vec<_>>()
+            .join("/");
+        zip.start_file(name, options).map_err(|e| format!("Zip write error: {}", e))?;
+        let mut file = std::fs::File::open(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        std::io::copy(&mut file, zip).map_err(|e| format!("Zip write error: {}", e))?;
+    }
+    Ok(())
+}
+
+/// Write `db_file` plus the media folders of `media_root` to `zip_path`.
+fn write_media_zip(zip_path: &Path, db_file: &Path, media_root: &Path) -> Result<(), String> {
+    let file = std::fs::File::create(zip_path).map_err(|e| format!("Failed to create backup: {}", e))?;
+    let mut zip = zip::ZipWriter::new(file);
+
+    zip.start_file(DB_ENTRY, zip::write::SimpleFileOptions::default())
+        .map_err(|e| format!("Zip write error: {}", e))?;
+    let mut db = std::fs::File::open(db_file).map_err(|e| format!("Failed to read snapshot: {}", e))?;
+    std::io::copy(&mut db, &mut zip).map_err(|e| format!("Zip write error: {}", e))?;
+
+    for dir in MEDIA_DIRS {
+        let path = media_root.join(dir);
+        if path.is_dir() {
+            zip_dir(&mut zip, media_root, &path)?;
+        }
+    }
+    zip.finish().map_err(|e| format!("Zip write error: {}", e))?;
+    Ok(())
+}
+
+/// Claim an unused "storyteller_<kind>_<secs>[-<n>]" name by creating its
+/// empty .db file (VACUUM INTO writes into an empty file), so snapshots taken
+/// in the same second never overwrite or fail on each other.
+fn reserve_stem(backup_dir: &Path, kind: &str) -> Result<(String, PathBuf), String> {
+    let base = format!("storyteller_{}_{}", kind, now_secs());
+    let mut n = 1;
+    loop {
+        let stem = if n == 1 { base.clone() } else { format!("{}-{}", base, n) };
+        n += 1;
+        if backup_dir.join(format!("{}.zip", stem)).exists() {
+            continue;
+        }
+        let db_path = backup_dir.join(format!("{}.db", stem));
+        match std::fs::OpenOptions::new().write(true).create_new(true).open(&db_path) {
+            Ok(_) => return Ok((stem, db_path)),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(format!("Failed to create backup: {}", e)),
+        }
+    }
+}
+
+/// Snapshot the database into `backup_dir`. With `media_root` (the app data
+/// folder) the generated images and portraits are bundled into a .zip.
+pub async fn snapshot(
+    pool: &SqlitePool,
+    backup_dir: &Path,
+    kind: &str,
+    media_root: Option<&Path>,
+) -> Result<BackupInfo, String> {
+    std::fs::create_dir_all(backup_dir).map_err(|e| format!("Failed to create backup dir: {}", e))?;
+    let (stem, db_path) = reserve_stem(backup_dir, kind)?;
+
+    let vacuumed = sqlx::query("VACUUM INTO ?")
+        .bind(db_path.to_string_lossy().to_string())
+        .execute(pool)
+        .await;
+    if let Err(e) = vacuumed {
+        let _ = std::fs::remove_file(&db_path);
+        return Err(format!("Failed to back up database: {}", e));
+    }
+
+    let path = match media_root {
+        Some(root) => {
+            let zip_path = backup_dir.join(format!("{}.zip", stem));
+            let zipped = write_media_zip(&zip_path, &db_path, root);
+            let _ = std::fs::remove_file(&db_path);
+            if let Err(e) = zipped {
+                let _ = std::fs::remove_file(&zip_path);
+                return Err(e);
+            }
+            zip_path
+        }
+        None => db_path,
+    };
+
+    let info = backup_info(&path).ok_or_else(|| format!("Unreadable backup name {}", path.display()))?;
+    println!("[Backup] Wrote {} ({} bytes)", info.file_name, info.size_bytes);
+    Ok(info)
+}
+
+/// All backups in `backup_dir`, newest first.
+pub fn list(backup_dir: &Path) -> Vec<BackupInfo> {
+    let mut backups: Vec<BackupInfo> = match std::fs::read_dir(backup_dir) {
+        Ok(entries) => entries.flatten().filter_map(|e| backup_info(&e.path())).collect(),
+        Err(_) => Vec::new(),
+    };
+    backups.sort_by(|a, b| {
+        (b.created_at, b.sequence)
+            .cmp(&(a.created_at, a.sequence))
+            .then_with(|| b.file_name.cmp(&a.file_name))
+    });
+    backups
+}
+
+/// Delete all but the newest `keep` scheduled backups. 0 keeps everything.
+/// Manual and safety (pre_restore, pre_migration) backups are never rotated.
+pub fn rotate(backup_dir: &Path, keep: usize) {
+    if keep == 0 {
+        return;
+    }
+    let scheduled = list(backup_dir).into_iter().filter(|b| b.kind == "scheduled");
+    for old in scheduled.skip(keep) {
+        match std::fs::remove_file(&old.path) {
+            Ok(()) => println!("[Backup] Rotated out {}", old.file_name),
+            Err(e) => println!("[Backup] Failed to delete {}: {}", old.file_name, e),
+        }
+    }
+}
+
+// ============================================================================
+// RESTORE
+// ============================================================================
+
+/// Copy the backup's database (extracting it from a .zip) to `staging`.
+fn stage_database(backup: &Path, is_zip: bool, staging: &Path) -> Result<(), String> {
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{}", staging.display(), suffix));
+    }
+    if !is_zip {
+        std::fs::copy(backup, staging).map_err(|e| format!("Failed to stage backup: {}", e))?;
+        return Ok(());
+    }
+    let file = std::fs::File::open(backup).map_err(|e| format!("Cannot open backup: {}", e))?;
+    let mut archive = zip::ZipArchive::new(file).map_err(|e| format!("Invalid backup archive: {}", e))?;
+    let mut entry = archive
+        .by_name(DB_ENTRY)
+        .map_err(|e| format!("Backup has no database: {}", e))?;
+    let mut out = std::fs::File::create(staging).map_err(|e| format!("Failed to stage backup: {}", e))?;
+    std::io::copy(&mut entry, &mut out).map_err(|e| format!("Failed to stage backup: {}", e))?;
+    Ok(())
+}
+
+/// Put the media files of a .zip backup back under `media_root`. Files
+/// created since the backup are left alone.
+fn restore_media(backup: &Path, media_root: &Path) -> Result<usize, String> {
+    let file = std::fs::File::open(backup).map_err(|e| format!("Cannot open backup: {}", e))?;
+    let mut archive = zip::ZipArchive::new(file).map_err(|e| format!("Invalid backup archive: {}", e))?;
+    let mut restored = 0;
+
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i).map_err(|e| format!("Zip read error: {}", e))?;
+        let Some(name) = entry.enclosed_name() else {
+            continue;
+        };
+        let in_media_dir = name
+            .components()
+            .next()
+            .map(|c| MEDIA_DIRS.iter().any(|d| c.as_os_str() == *d))
+            .unwrap_or(false);
+        if !in_media_dir || entry.is_dir() {
+            continue;
+        }
+        let out = media_root.join(&name);
+        if let Some(parent) = out.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let mut f = std::fs::File::create(&out).map_err(|e| format!("Cannot create file: {}", e))?;
+        std::io::copy(&mut entry, &mut f).map_err(|e| format!("Extract error: {}", e))?;
+        restored += 1;
+    }
+    Ok(restored)
+}
+
+/// Replace every table's rows with those of the attached `restored` database.
+async fn copy_tables(conn: &mut SqliteConnection) -> Result<(), String> {
+    let mut tx = conn.begin().await.map_err(|e| e.to_string())?;
+
+    let tables: Vec<String> = sqlx::query(
+        "SELECT name FROM main.sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'",
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?
+    .iter()
+    .map(|r| r.get("name"))
+    .collect();
+
+    for table in &tables {
+        // Both schemas are migrated to the same version, but columns added by
+        // ALTER may sit in a different order — copy by name
+        let columns: Vec<String> = sqlx::query("SELECT name FROM pragma_table_info(?)")
+            .bind(table)
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?
+            .iter()
+            .map(|r| format!("\"{}\"", r.get::<String, _>("name")))
+            .collect();
+        let columns = columns.join(", ");
+
+        sqlx::query(&format!("DELETE FROM main.\"{}\"", table))
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| format!("Clearing {}: {}", table, e))?;
+        sqlx::query(&format!(
+            "INSERT INTO main.\"{0}\" ({1}) SELECT {1} FROM restored.\"{0}\"",
+            table, columns
+        ))
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| format!("Restoring {}: {}", table, e))?;
+    }
+
+    // AUTOINCREMENT counters, so new rows don't reuse ids of the live data
+    let has_sequence: i64 = sqlx::query(
+        "SELECT COUNT(*) FROM restored.sqlite_master WHERE name = 'sqlite_sequence'",
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?
+    .get(0);
+    if has_sequence > 0 {
+        sqlx::query("DELETE FROM main.sqlite_sequence")
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        sqlx::query("INSERT INTO main.sqlite_sequence (name, seq) SELECT name, seq FROM restored.sqlite_sequence")
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+
+    tx.commit().await.map_err(|e| e.to_string())
+}
+
+/// Restore `file_name` from `backup_dir` into the live database, backing the
+/// current one up first. Returns the "pre_restore" backup.
+pub async fn restore(
+    pool: &SqlitePool,
+    backup_dir: &Path,
+    file_name: &str,
+    media_root: &Path,
+) -> Result<BackupInfo, String> {
+    // Only plain backup names — never a path out of the backups folder
+    let (_, _, is_zip) = parse_backup_name(file_name)
+        .filter(|_| Path::new(file_name).file_name().map(|n| n == file_name).unwrap_or(false))
+        .ok_or_else(|| format!("'{}' is not a backup", file_name))?;
+    let backup = backup_dir.join(file_name);
+    if !backup.is_file() {
+        return Err(format!("Backup {} not found", file_name));
+    }
+
+    let safety = snapshot(pool, backup_dir, "pre_restore", None).await?;
+
+    // Bring the backup up to the current schema on a separate connection
+    let staging = backup_dir.join("restore_staging.db");
+    stage_database(&backup, is_zip, &staging)?;
+    let staging_pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect(&format!("sqlite:{}", staging.display()))
+        .await
+        .map_err(|e| format!("Failed to open backup: {}", e))?;
+    let migrated = crate::migrations::migrate(&staging_pool, None).await;
+    staging_pool.close().await;
+    migrated?;
+
+    // Holding the pool's only connection keeps every other query out
+    let mut conn = pool.acquire().await.map_err(|e| e.to_string())?;
+    sqlx::query("PRAGMA foreign_keys = OFF")
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| e.to_string())?;
+    let attached = sqlx::query("ATTACH DATABASE ? AS restored")
+        .bind(staging.to_string_lossy().to_string())
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| format!("Failed to attach backup: {}", e));
+    let copied = match attached {
+        Ok(_) => {
+            let copied = copy_tables(&mut conn).await;
+            sqlx::query("DETACH DATABASE restored").execute(&mut *conn).await.ok();
+            copied
+        }
+        Err(e) => Err(e),
+    };
+    sqlx::query("PRAGMA foreign_keys = ON").execute(&mut *conn).await.ok();
+    drop(conn);
+    let _ = std::fs::remove_file(&staging);
+    copied.map_err(|e| format!("Restore failed, nothing was changed: {}", e))?;
+
+    if is_zip {
+        let files = restore_media(&backup, media_root)?;
+        println!("[Backup] Restored {} media files", files);
+    }
+    println!("[Backup] Restored {} (previous data saved as {})", file_name, safety.file_name);
+    Ok(safety)
+}
+
+// ============================================================================
+// SCHEDULER
+// ============================================================================
+
+/// Take a "scheduled" backup whenever the newest one is older than
+/// `backup_interval_hours`. Runs for the lifetime of the app.
+pub async fn run_scheduler(app: AppHandle) {
+    let Ok(app_data) = app_data_dir(&app) else {
+        return;
+    };
+    let dir = backup_dir(&app_data);
+
+    loop {
+        let (interval_hours, retention, include_media) = {
+            let config = app.state::<ConfigState>();
+            let cfg = config.0.lock().unwrap();
+            (cfg.backup_interval_hours, cfg.backup_retention, cfg.backup_include_media)
+        };
+
+        if interval_hours > 0 {
+            let last = list(&dir)
+                .into_iter()
+                .find(|b| b.kind == "scheduled")
+                .map(|b| b.created_at)
+                .unwrap_or(0);
+            if now_secs().saturating_sub(last) >= interval_hours as u64 * 3600 {
+                let pool = app.state::<OllamaState>().db.clone();
+                let media_root = include_media.then_some(app_data.as_path());
+                match snapshot(&pool, &dir, "scheduled", media_root).await {
+                    Ok(_) => rotate(&dir, retention),
+                    Err(e) => println!("[Backup] Scheduled backup failed: {}", e),
+                }
+            }
+        }
+
+        tokio::time::sleep(std::time::Duration::from_secs(SCHEDULER_TICK_SECS)).await;
+    }
+}
+
+// ============================================================================
+// COMMANDS
+// ============================================================================
+
+/// Back up now. `include_media` defaults to `AppConfig::backup_include_media`.
+///
+/// Frontend usage:
+/// const backup = await invoke('create_backup', { includeMedia: true });
+#[tauri::command]
+pub async fn create_backup(
+    include_media: Option<bool>,
+    state: State<'_, OllamaState>,
+    config: State<'_, ConfigState>,
+    app: AppHandle,
+) -> Result<BackupInfo, AppError> {
+    let default_media = config.0.lock().unwrap().backup_include_media;
+    let app_data = app_data_dir(&app)?;
+    let dir = backup_dir(&app_data);
+    let media_root = include_media.unwrap_or(default_media).then_some(app_data.as_path());
+
+    Ok(snapshot(&state.db, &dir, "manual", media_root).await?)
+}
+
+/// All backups, newest first.
+#[tauri::command]
+pub async fn list_backups(app: AppHandle) -> Result<Vec<BackupInfo>, AppError> {
+    Ok(list(&backup_dir(&app_data_dir(&app)?)))
+}
+
+/// Replace all stories, characters and scenes with a backup's. The current
+/// data is backed up first (returned). Reload everything afterwards.
+#[tauri::command]
+pub async fn restore_backup(
+    file_name: String,
+    state: State<'_, OllamaState>,
+    app: AppHandle,
+) -> Result<BackupInfo, AppError> {
+    let app_data = app_data_dir(&app)?;
+    let safety = restore(&state.db, &backup_dir(&app_data), &file_name, &app_data)
+        .await
+        .map_err(|e| AppError::new(ErrorKind::Database, e))?;
+    *state.current_chat_id.lock().unwrap() = None;
+    Ok(safety)
+}
+
+#[tauri::command]
+pub async fn delete_backup(file_name: String, app: AppHandle) -> Result<(), AppError> {
+    let dir = backup_dir(&app_data_dir(&app)?);
+    let backup = list(&dir)
+        .into_iter()
+        .find(|b| b.file_name == file_name)
+        .ok_or_else(|| AppError::not_found(format!("Backup {} not found", file_name)))?;
+    std::fs::remove_file(&backup.path)
+        .map_err(|e| AppError::new(ErrorKind::Io, format!("Failed to delete backup: {}", e)))?;
+    Ok(())
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_backup_name() {
+        assert_eq!(
+            parse_backup_name("storyteller_pre_migration_v3_1700000000.db"),
+            Some(("pre_migration_v3".to_string(), 1700000000, false))
+        );
+        assert_eq!(
+            parse_backup_name("storyteller_scheduled_1700000000.zip"),
+            Some(("scheduled".to_string(), 1700000000, true))
+        );
+        assert_eq!(parse_backup_name("restore_staging.db"), None);
+        assert_eq!(parse_backup_name("storyteller_manual_x.db"), None);
+        assert_eq!(parse_backup_name("storyteller_1700000000.db"), None);
+        assert_eq!(
+            parse_backup_name("storyteller_manual_1700000000-2.db"),
+            Some(("manual".to_string(), 1700000000, false))
+        );
+        assert_eq!(parse_backup_name("storyteller_manual_1700000000-x.db"), None);
+    }
+
+    #[test]
+    fn test_rotate_only_deletes_scheduled_backups() {
+        let dir = std::env::temp_dir().join(format!("storyengine_rotate_test_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let names = [
+            "storyteller_pre_migration_v3_1700000000.db",
+            "storyteller_manual_1700000001.db",
+            "storyteller_scheduled_1700000002.db",
+            "storyteller_pre_restore_1700000003.db",
+            "storyteller_scheduled_1700000004.zip",
+            "storyteller_scheduled_1700000005.db",
+        ];
+        for name in names {
+            std::fs::write(dir.join(name), b"").unwrap();
+        }
+
+        rotate(&dir, 1);
+        let mut kept: Vec<String> = list(&dir).into_iter().map(|b| b.file_name).collect();
+        kept.sort();
+        assert_eq!(
+            kept,
+            vec![
+                "storyteller_manual_1700000001.db",
+                "storyteller_pre_migration_v3_1700000000.db",
+                "storyteller_pre_restore_1700000003.db",
+                "storyteller_scheduled_1700000005.db",
+            ]
+        );
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_and_restore() {
+        let dir = std::env::temp_dir().join(format!("storyengine_backup_test_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect(&format!("sqlite:{}?mode=rwc", dir.join("storyteller.db").display()))
+            .await
+            .unwrap();
+        crate::migrations::migrate(&pool, None).await.unwrap();
+        sqlx::query("INSERT INTO story_premises (title, description) VALUES ('Kept', '')")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let backups = backup_dir(&dir);
+        let backup = snapshot(&pool, &backups, "manual", None).await.unwrap();
+
+        // A second snapshot right away gets its own name and lists first
+        let again = snapshot(&pool, &backups, "manual", None).await.unwrap();
+        assert_ne!(again.file_name, backup.file_name);
+        assert_eq!(list(&backups)[0].file_name, again.file_name);
+
+        sqlx::query("DELETE FROM story_premises").execute(&pool).await.unwrap();
+
+        let safety = restore(&pool, &backups, &backup.file_name, &dir).await.unwrap();
+        assert_eq!(safety.kind, "pre_restore");
+        let title: String = sqlx::query("SELECT title FROM story_premises")
+            .fetch_one(&pool)
+            .await
+            .unwrap()
+            .get(0);
+        assert_eq!(title, "Kept");
+
+        assert!(restore(&pool, &backups, "../storyteller.db", &dir).await.is_err());
+        pool.close().await;
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,254 @@
+// src-tauri/src/cancellation.rs
+//
+// Generation Cancellation Registry
+// ==================================
+// Tracks in-flight story turns and image generations so the frontend can
+// abort them with `cancel_generation`.
+//
+// Each long-running command registers a job under a key: "chat:{id}" for
+// story turns, or a job id supplied by the frontend for image generation.
+// Cancelling a job:
+//   - resolves `GenerationJob::cancelled()`, so any future wrapped in
+//     `GenerationJob::run()` (e.g. the Ollama HTTP request) is dropped,
+//     which closes the connection and stops Ollama generating
+//   - makes the ComfyUI pipelines interrupt their prompt and remove it
+//     from the queue (see `comfyui::client::cancel_prompt`)
+//
+// Commands call `GenerationJob::check()` before writing to the database, so
+// an aborted turn never leaves a half-saved user message behind.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::ops::Deref;
+use std::sync::{Arc, Mutex};
+use tauri::State;
+use tokio::sync::watch;
+
+/// Error string returned by every command that was aborted by the user.
+/// The frontend matches on it to suppress the error banner.
+pub const CANCELLED_ERROR: &str = "Generation cancelled";
+
+/// Registry key for the story turn running in a chat.
+pub fn chat_job_key(chat_id: i64) -> String {
+    format!("chat:{}", chat_id)
+}
+
+// ============================================================================
+// JOB
+// ============================================================================
+
+/// A single cancellable unit of work.
+pub struct GenerationJob {
+    key: String,
+    cancel_tx: watch::Sender<bool>,
+}
+
+impl GenerationJob {
+    fn new(key: String) -> Self {
+        let (cancel_tx, _) = watch::channel(false);
+        Self { key, cancel_tx }
+    }
+
+    /// The key the job is registered under.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.cancel_tx.borrow()
+    }
+
+    /// Returns `Err(CANCELLED_ERROR)` if the job has been cancelled.
+    pub fn check(&self) -> Result<(), String> {
+        if self.is_cancelled() {
+            Err(CANCELLED_ERROR.to_string())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Resolves once the job is cancelled (never resolves otherwise).
+    pub async fn cancelled(&self) {
+        let mut rx = self.cancel_tx.subscribe();
+        let _ = rx.wait_for(|c| *c).await;
+    }
+
+    /// Run a future, dropping it as soon as the job is cancelled.
+    pub async fn run<T, E, F>(&self, fut: F) -> Result<T, E>
+    where
+        F: Future<Output = Result<T, E>>,
+        E: From<String>,
+    {
+        tokio::select! {
+            res = fut => res,
+            _ = self.cancelled() => Err(CANCELLED_ERROR.to_string().into()),
+        }
+    }
+
+    fn cancel(&self) {
+        // send_replace stores the value even when nobody is subscribed yet
+        self.cancel_tx.send_replace(true);
+    }
+}
+
+// ============================================================================
+// REGISTRY
+// ============================================================================
+
+/// Tauri-managed registry of running jobs, keyed by chat/job id.
+#[derive(Default)]
+pub struct CancelRegistry {
+    jobs: Mutex<HashMap<String, Arc<GenerationJob>>>,
+}
+
+impl CancelRegistry {
+    /// Register a new job. It is removed again when the guard is dropped.
+    /// A job already running under the same key keeps running but can no
+    /// longer be cancelled by key.
+    pub fn register(&self, key: impl Into<String>) -> JobGuard<'_> {
+        let job = Arc::new(GenerationJob::new(key.into()));
+        if let Ok(mut jobs) = self.jobs.lock() {
+            jobs.insert(job.key.clone(), job.clone());
+        }
+        JobGuard { registry: self, job }
+    }
+
+    /// Cancel the job with the given key, or every running job if `key` is None.
+    /// Returns the keys that were cancelled.
+    pub fn cancel(&self, key: Option<&str>) -> Vec<String> {
+        let jobs = match self.jobs.lock() {
+            Ok(jobs) => jobs,
+            Err(_) => return Vec::new(),
+        };
+        jobs.values()
+            .filter(|job| key.map_or(true, |k| job.key == k))
+            .map(|job| {
+                job.cancel();
+                job.key.clone()
+            })
+            .collect()
+    }
+
+    fn unregister(&self, job: &Arc<GenerationJob>) {
+        if let Ok(mut jobs) = self.jobs.lock() {
+            // Only remove our own entry — a newer job may have reused the key
+            if jobs.get(&job.key).map_or(false, |j| Arc::ptr_eq(j, job)) {
+                jobs.remove(&job.key);
+            }
+        }
+    }
+}
+
+/// Keeps a job registered for as long as the owning command is running.
+pub struct JobGuard<'a> {
+    registry: &'a CancelRegistry,
+    job: Arc<GenerationJob>,
+}
+
+impl Deref for JobGuard<'_> {
+    type Target = GenerationJob;
+
+    fn deref(&self) -> &GenerationJob {
+        &self.job
+    }
+}
+
+impl Drop for JobGuard<'_> {
+    fn drop(&mut self) {
+        self.registry.unregister(&self.job);
+    }
+}
+
+// ============================================================================
+// COMMANDS
+// ============================================================================
+
+/// Cancel an in-flight story turn or image generation.
+///
+/// Pass `chatId` to cancel the story turn for that chat, `jobId` to cancel an
+/// image job, or neither to cancel everything. Returns the number of jobs
+/// that were signalled.
+///
+/// ## Frontend usage
+/// ```typescript
+/// await invoke('cancel_generation', { chatId, jobId: null });
+/// ```
+#[tauri::command]
+pub fn cancel_generation(
+    chat_id: Option<i64>,
+    job_id: Option<String>,
+    registry: State<'_, CancelRegistry>,
+) -> Result<usize, String> {
+    let key = job_id.or_else(|| chat_id.map(chat_job_key));
+    let cancelled = registry.cancel(key.as_deref());
+    println!(
+        "[Cancel] Requested cancel for {:?} — {} job(s) signalled: {:?}",
+        key,
+        cancelled.len(),
+        cancelled
+    );
+    Ok(cancelled.len())
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancel_by_key() {
+        let registry = CancelRegistry::default();
+        let a = registry.register(chat_job_key(1));
+        let b = registry.register(chat_job_key(2));
+
+        let cancelled = registry.cancel(Some("chat:1"));
+        assert_eq!(cancelled, vec!["chat:1".to_string()]);
+        assert!(a.is_cancelled());
+        assert!(!b.is_cancelled());
+        assert_eq!(a.check().unwrap_err(), CANCELLED_ERROR);
+    }
+
+    #[test]
+    fn test_cancel_all() {
+        let registry = CancelRegistry::default();
+        let a = registry.register("portrait");
+        let b = registry.register("scene-image");
+        assert_eq!(registry.cancel(None).len(), 2);
+        assert!(a.is_cancelled() && b.is_cancelled());
+    }
+
+    #[test]
+    fn test_guard_drop_unregisters() {
+        let registry = CancelRegistry::default();
+        {
+            let _job = registry.register("chat:5");
+        }
+        assert!(registry.cancel(Some("chat:5")).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_run_aborts_pending_future() {
+        let registry = CancelRegistry::default();
+        let job = registry.register("chat:9");
+        registry.cancel(Some("chat:9"));
+
+        let result: Result<(), String> = job
+            .run(async {
+                tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+                Ok(())
+            })
+            .await;
+        assert_eq!(result.unwrap_err(), CANCELLED_ERROR);
+    }
+
+    #[tokio::test]
+    async fn test_run_passes_through_result() {
+        let registry = CancelRegistry::default();
+        let job = registry.register("chat:3");
+        let result = job.run(async { Ok::<_, String>(42) }).await;
+        assert_eq!(result.unwrap(), 42);
+    }
+}
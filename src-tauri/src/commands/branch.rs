@@ -0,0 +1,450 @@
+// src-tauri/src/commands/branch.rs
+//
+// Story branch commands for StoryEngine.
+// A story can have several timelines ("what if" paths). Each timeline is a
+// chat; `story_premises.chat_id` points at the one being played.
+//
+// Branches don't copy history. A branch chat stores only its own messages and
+// a parent pointer: its parent chat plus the last parent message it shares
+// (`shared_until_id`). A chat's full history is found by walking those
+// pointers up to the root — see LINEAGE_CTE, used by every message reader.
+//
+// Chat-scoped derived state is cloned at fork time: context summaries, story
+// memory and emotional states (from the fork turn's snapshot). Scenes are
+// shared places, so each timeline parks its active scene and that scene's
+// roster in `story_branches` while another timeline is active.
+
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqliteConnection};
+use tauri::State;
+
+use crate::commands::history::parse_roster_additions;
+use crate::error::AppError;
+use crate::state::OllamaState;
+use crate::text_gen::memory::copy_memory;
+use crate::text_gen::orchestrator::restore_emotional_states;
+
+// ============================================================================
+// TIMELINE QUERIES
+// ============================================================================
+
+/// Prefix for queries over a chat's timeline. Binds one parameter (the chat
+/// id, which must be the first bind) and defines `lineage(chat_id, until_id)`:
+/// the chat itself plus every ancestor chat, bounded by the fork point.
+pub const LINEAGE_CTE: &str = "WITH RECURSIVE lineage(chat_id, until_id) AS ( \
+     SELECT ?, NULL \
+     UNION ALL \
+     SELECT b.parent_chat_id, b.shared_until_id FROM story_branches b \
+     INNER JOIN lineage l ON b.chat_id = l.chat_id \
+     WHERE b.parent_chat_id IS NOT NULL) ";
+
+/// Join restricting `messages m` to the rows in `lineage`.
+pub const IN_LINEAGE: &str =
+    "INNER JOIN lineage l ON l.chat_id = m.chat_id AND (l.until_id IS NULL OR m.id <= l.until_id)";
+
+/// True if a branch shares `message_id` (or anything after it) from `chat_id`.
+/// Shared turns can't be regenerated or switched in place — fork instead.
+pub async fn is_shared_with_branch<'e, E>(executor: E, chat_id: i64, message_id: i64) -> Result<bool, String>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Sqlite>,
+{
+    let row = sqlx::query("SELECT 1 FROM story_branches WHERE parent_chat_id = ? AND shared_until_id >= ? LIMIT 1")
+        .bind(chat_id)
+        .bind(message_id)
+        .fetch_optional(executor)
+        .await
+        .map_err(|e| format!("Failed to check story branches: {}", e))?;
+    Ok(row.is_some())
+}
+
+// ============================================================================
+// TYPES
+// ============================================================================
+
+/// One timeline of a story. The root has no parent.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoryBranch {
+    pub id: i64,
+    pub story_id: i64,
+    pub chat_id: i64,
+    pub parent_chat_id: Option<i64>,
+    /// Last parent message included in this branch's history
+    pub shared_until_id: Option<i64>,
+    pub name: String,
+    /// Shared turns + the branch's own turns
+    pub turn_count: i64,
+    /// The timeline currently being played
+    pub is_active: bool,
+    pub created_at: Option<String>,
+}
+
+const BRANCH_SELECT: &str = "SELECT b.id, b.story_id, b.chat_id, b.parent_chat_id, b.shared_until_id, b.name, b.created_at, \
+     b.shared_turns + (SELECT COUNT(*) FROM messages m WHERE m.chat_id = b.chat_id AND m.role = 'user') AS turn_count, \
+     COALESCE(sp.chat_id = b.chat_id, 0) AS is_active \
+     FROM story_branches b INNER JOIN story_premises sp ON sp.id = b.story_id";
+
+fn row_to_branch(r: &sqlx::sqlite::SqliteRow) -> StoryBranch {
+    StoryBranch {
+        id: r.get("id"),
+        story_id: r.get("story_id"),
+        chat_id: r.get("chat_id"),
+        parent_chat_id: r.get("parent_chat_id"),
+        shared_until_id: r.get("shared_until_id"),
+        name: r.get("name"),
+        turn_count: r.get("turn_count"),
+        is_active: r.get("is_active"),
+        created_at: r.get("created_at"),
+    }
+}
+
+// ============================================================================
+// HELPERS
+// ============================================================================
+
+/// The story a chat belongs to, as a branch or as its original chat.
+async fn story_for_chat(db: &sqlx::SqlitePool, chat_id: i64) -> Result<Option<i64>, String> {
+    let row = sqlx::query(
+        "SELECT story_id FROM story_branches WHERE chat_id = ?1 \
+         UNION ALL SELECT id FROM story_premises WHERE chat_id = ?1 LIMIT 1",
+    )
+    .bind(chat_id)
+    .fetch_optional(db)
+    .await
+    .map_err(|e| format!("Failed to look up story for chat: {}", e))?;
+    Ok(row.map(|r| r.get(0)))
+}
+
+/// Register the story's current chat as the root timeline if the story has
+/// never been forked.
+async fn ensure_root_branch(db: &sqlx::SqlitePool, story_id: i64) -> Result<(), String> {
+    sqlx::query(
+        "INSERT INTO story_branches (story_id, chat_id, name) \
+         SELECT sp.id, sp.chat_id, 'Main' FROM story_premises sp \
+         WHERE sp.id = ? AND sp.chat_id IS NOT NULL \
+           AND NOT EXISTS (SELECT 1 FROM story_branches b WHERE b.story_id = sp.id)",
+    )
+    .bind(story_id)
+    .execute(db)
+    .await
+    .map_err(|e| format!("Failed to register main timeline: {}", e))?;
+    Ok(())
+}
+
+/// Character ids in a scene's roster.
+async fn scene_roster(conn: &mut SqliteConnection, scene_id: i64) -> Result<Vec<i64>, String> {
+    Ok(sqlx::query("SELECT character_id FROM scene_characters WHERE scene_id = ? ORDER BY character_id")
+        .bind(scene_id)
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| format!("Failed to load scene roster: {}", e))?
+        .iter()
+        .map(|r| r.get("character_id"))
+        .collect())
+}
+
+/// Remember the story's active scene and its roster on the timeline being left.
+async fn park_scene_state(conn: &mut SqliteConnection, story_id: i64, chat_id: i64) -> Result<(), String> {
+    let active_scene_id: Option<i64> = sqlx::query("SELECT active_scene_id FROM story_premises WHERE id = ?")
+        .bind(story_id)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| format!("Failed to load active scene: {}", e))?
+        .and_then(|r| r.get("active_scene_id"));
+    let roster = match active_scene_id {
+        Some(scene_id) => Some(serde_json::to_string(&scene_roster(conn, scene_id).await?).map_err(|e| e.to_string())?),
+        None => None,
+    };
+
+    sqlx::query("UPDATE story_branches SET active_scene_id = ?, scene_roster = ? WHERE chat_id = ?")
+        .bind(active_scene_id)
+        .bind(roster)
+        .bind(chat_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| format!("Failed to save branch scene state: {}", e))?;
+    Ok(())
+}
+
+/// Roster of `scene_id` as of the fork point: the parent timeline's roster
+/// (parked, if the scene is the one it parked) minus the characters its
+/// later turns brought into the scene.
+async fn roster_at_fork(
+    conn: &mut SqliteConnection,
+    parent_chat_id: i64,
+    shared_until_id: i64,
+    scene_id: i64,
+) -> Result<Vec<i64>, String> {
+    let parked: Option<String> =
+        sqlx::query("SELECT scene_roster FROM story_branches WHERE chat_id = ? AND active_scene_id = ?")
+            .bind(parent_chat_id)
+            .bind(scene_id)
+            .fetch_optional(&mut *conn)
+            .await
+            .map_err(|e| format!("Failed to load branch scene state: {}", e))?
+            .and_then(|r| r.get("scene_roster"));
+    let mut roster = match parked {
+        Some(json) => serde_json::from_str(&json).unwrap_or_default(),
+        None => scene_roster(conn, scene_id).await?,
+    };
+
+    let later_additions = sqlx::query(
+        "SELECT roster_additions FROM messages \
+         WHERE chat_id = ? AND id > ? AND scene_id = ? AND role = 'assistant' AND is_selected = 1",
+    )
+    .bind(parent_chat_id)
+    .bind(shared_until_id)
+    .bind(scene_id)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| format!("Failed to load later roster changes: {}", e))?;
+    for row in &later_additions {
+        let added = parse_roster_additions(row.get::<Option<String>, _>("roster_additions").as_deref());
+        roster.retain(|id| !added.contains(id));
+    }
+    Ok(roster)
+}
+
+/// Make `chat_id` the story's active timeline and restore its scene state.
+async fn activate_branch(conn: &mut SqliteConnection, story_id: i64, chat_id: i64) -> Result<(), String> {
+    let row = sqlx::query("SELECT active_scene_id, scene_roster FROM story_branches WHERE story_id = ? AND chat_id = ?")
+        .bind(story_id)
+        .bind(chat_id)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| format!("Failed to load branch: {}", e))?
+        .ok_or_else(|| format!("Chat {} is not a timeline of story {}", chat_id, story_id))?;
+    let active_scene_id: Option<i64> = row.get("active_scene_id");
+    let roster: Option<String> = row.get("scene_roster");
+
+    sqlx::query("UPDATE story_premises SET chat_id = ?, active_scene_id = ? WHERE id = ?")
+        .bind(chat_id)
+        .bind(active_scene_id)
+        .bind(story_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| format!("Failed to switch timeline: {}", e))?;
+
+    if let (Some(scene_id), Some(roster)) = (active_scene_id, roster) {
+        let ids: Vec<i64> = serde_json::from_str(&roster).unwrap_or_default();
+        sqlx::query("DELETE FROM scene_characters WHERE scene_id = ?")
+            .bind(scene_id)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| format!("Failed to restore scene roster: {}", e))?;
+        for character_id in ids {
+            sqlx::query("INSERT OR IGNORE INTO scene_characters (scene_id, character_id) VALUES (?, ?)")
+                .bind(scene_id)
+                .bind(character_id)
+                .execute(&mut *conn)
+                .await
+                .ok();
+        }
+    }
+    Ok(())
+}
+
+async fn load_branch(db: &sqlx::SqlitePool, chat_id: i64) -> Result<StoryBranch, String> {
+    let row = sqlx::query(&format!("{} WHERE b.chat_id = ?", BRANCH_SELECT))
+        .bind(chat_id)
+        .fetch_one(db)
+        .await
+        .map_err(|e| format!("Failed to load branch: {}", e))?;
+    Ok(row_to_branch(&row))
+}
+
+// ============================================================================
+// BRANCH COMMANDS
+// ============================================================================
+
+/// Fork the story at a message and switch to the new branch.
+///
+/// Forking at an assistant message keeps that turn and continues after it;
+/// forking at a user message keeps everything before it, so the turn can be
+/// played differently. Only selected responses can be forked.
+#[tauri::command]
+pub async fn fork_story_at(
+    message_id: i64,
+    name: Option<String>,
+    state: State<'_, OllamaState>,
+) -> Result<StoryBranch, AppError> {
+    let db = &state.db;
+
+    let msg = sqlx::query("SELECT chat_id, role, is_selected FROM messages WHERE id = ?")
+        .bind(message_id)
+        .fetch_optional(db)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to load message: {}", e)))?
+        .ok_or_else(|| AppError::not_found(format!("Message {} not found", message_id)))?;
+    let parent_chat_id: i64 = msg.get("chat_id");
+    let role: String = msg.get("role");
+    let is_selected: bool = msg.get("is_selected");
+    if !is_selected {
+        return Err(AppError::invalid_input("Select this response before forking from it"));
+    }
+    let shared_until_id = if role == "user" { message_id - 1 } else { message_id };
+
+    let story_id = story_for_chat(db, parent_chat_id)
+        .await?
+        .ok_or_else(|| AppError::invalid_input("Message is not part of a story"))?;
+
+    ensure_root_branch(db, story_id).await?;
+
+    // The new chat, its branch row and the cloned state land together or not at all
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| AppError::database(format!("Failed to start transaction: {}", e)))?;
+    let active_chat_id: Option<i64> = sqlx::query("SELECT chat_id FROM story_premises WHERE id = ?")
+        .bind(story_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to load story: {}", e)))?
+        .and_then(|r| r.get("chat_id"));
+    if let Some(cid) = active_chat_id {
+        park_scene_state(&mut tx, story_id, cid).await?;
+    }
+
+    // Turns in the shared history, newest first — the first is the fork turn
+    let shared_turns = sqlx::query(&format!(
+        "{}SELECT m.id, m.scene_id, m.emotion_snapshot FROM messages m {} \
+         WHERE m.is_selected = 1 AND m.role = 'assistant' AND m.id <= ? ORDER BY m.id DESC",
+        LINEAGE_CTE, IN_LINEAGE
+    ))
+    .bind(parent_chat_id)
+    .bind(shared_until_id)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| AppError::database(format!("Failed to load shared history: {}", e)))?;
+    let fork_turn = shared_turns.first();
+
+    let active_scene_id: Option<i64> = fork_turn.and_then(|r| r.get("scene_id"));
+    let roster = match active_scene_id {
+        Some(scene_id) => {
+            let ids = roster_at_fork(&mut tx, parent_chat_id, shared_until_id, scene_id).await?;
+            Some(serde_json::to_string(&ids).map_err(|e| e.to_string())?)
+        }
+        None => None,
+    };
+
+    let name = name
+        .map(|n| n.trim().to_string())
+        .filter(|n| !n.is_empty())
+        .unwrap_or_else(|| format!("Branch at turn {}", shared_turns.len()));
+    let chat_id = sqlx::query("INSERT INTO chats (title) VALUES (?)")
+        .bind(&name)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to create branch chat: {}", e)))?
+        .last_insert_rowid();
+
+    sqlx::query(
+        "INSERT INTO story_branches \
+         (story_id, chat_id, parent_chat_id, shared_until_id, shared_turns, name, active_scene_id, scene_roster) \
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(story_id)
+    .bind(chat_id)
+    .bind(parent_chat_id)
+    .bind(shared_until_id)
+    .bind(shared_turns.len() as i64)
+    .bind(&name)
+    .bind(active_scene_id)
+    .bind(roster)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| AppError::database(format!("Failed to create branch: {}", e)))?;
+
+    // Clone the parent's derived state up to the fork point
+    sqlx::query(
+        "INSERT INTO context_summaries (chat_id, first_turn, last_turn, last_message_id, summary) \
+         SELECT ?, first_turn, last_turn, last_message_id, summary FROM context_summaries \
+         WHERE chat_id = ? AND last_message_id <= ?",
+    )
+    .bind(chat_id)
+    .bind(parent_chat_id)
+    .bind(shared_until_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| AppError::database(format!("Failed to copy summaries: {}", e)))?;
+    let memory_entries = copy_memory(&mut tx, parent_chat_id, chat_id, shared_until_id).await?;
+
+    let snapshot: Option<String> = fork_turn.and_then(|r| r.get("emotion_snapshot"));
+    match snapshot {
+        Some(snapshot) => restore_emotional_states(&mut *tx, chat_id, &snapshot).await?,
+        // Turns saved before snapshots existed: best effort, the parent's current states
+        None if fork_turn.is_some() => {
+            sqlx::query(
+                "INSERT INTO character_emotional_states \
+                 (chat_id, character_name, current_emotion, emotion_intensity, emotion_cause, lingering_emotions) \
+                 SELECT ?, character_name, current_emotion, emotion_intensity, emotion_cause, lingering_emotions \
+                 FROM character_emotional_states WHERE chat_id = ?",
+            )
+            .bind(chat_id)
+            .bind(parent_chat_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| AppError::database(format!("Failed to copy emotional states: {}", e)))?;
+        }
+        None => {}
+    }
+
+    activate_branch(&mut tx, story_id, chat_id).await?;
+    tx.commit()
+        .await
+        .map_err(|e| AppError::database(format!("Failed to commit branch: {}", e)))?;
+    println!(
+        "[Branch] Forked story {} at message {} into chat {} ({} shared turns, {} memory entries)",
+        story_id, message_id, chat_id, shared_turns.len(), memory_entries
+    );
+
+    Ok(load_branch(db, chat_id).await?)
+}
+
+/// Every timeline of a story, oldest first. Build the tree from
+/// `parent_chat_id`; the entry without a parent is the original run.
+#[tauri::command]
+pub async fn list_story_branches(
+    story_id: i64,
+    state: State<'_, OllamaState>,
+) -> Result<Vec<StoryBranch>, AppError> {
+    ensure_root_branch(&state.db, story_id).await?;
+    let rows = sqlx::query(&format!("{} WHERE b.story_id = ? ORDER BY b.id ASC", BRANCH_SELECT))
+        .bind(story_id)
+        .fetch_all(&state.db)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to list branches: {}", e)))?;
+    Ok(rows.iter().map(row_to_branch).collect())
+}
+
+/// Switch the story to another of its timelines. Reload the story afterwards.
+#[tauri::command]
+pub async fn switch_story_branch(
+    story_id: i64,
+    chat_id: i64,
+    state: State<'_, OllamaState>,
+) -> Result<(), AppError> {
+    ensure_root_branch(&state.db, story_id).await?;
+    let mut tx = state
+        .db
+        .begin()
+        .await
+        .map_err(|e| AppError::database(format!("Failed to start transaction: {}", e)))?;
+    let active_chat_id: Option<i64> = sqlx::query("SELECT chat_id FROM story_premises WHERE id = ?")
+        .bind(story_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to load story: {}", e)))?
+        .and_then(|r| r.get("chat_id"));
+    if active_chat_id == Some(chat_id) {
+        return Ok(());
+    }
+    if let Some(cid) = active_chat_id {
+        park_scene_state(&mut tx, story_id, cid).await?;
+    }
+    activate_branch(&mut tx, story_id, chat_id).await?;
+    tx.commit()
+        .await
+        .map_err(|e| AppError::database(format!("Failed to switch timeline: {}", e)))?;
+    println!("[Branch] Story {} switched to chat {}", story_id, chat_id);
+    Ok(())
+}
@@ -9,6 +9,7 @@
 
 use tauri::State;
 use crate::state::OllamaState;
+use crate::error::AppError;
 use crate::models::{CharacterProfile, CharacterLookup, SceneCharacter};
 use sqlx::Row;
 
@@ -54,7 +55,7 @@ fn row_to_profile(r: &sqlx::sqlite::SqliteRow) -> CharacterProfile {
 pub async fn add_character(
     character: CharacterProfile,
     state: State<'_, OllamaState>,
-) -> Result<i64, String> {
+) -> Result<i64, AppError> {
     let art_style = character.art_style.clone().unwrap_or_else(|| "Realistic".to_string());
 
     let result = sqlx::query(
@@ -89,7 +90,7 @@ pub async fn add_character(
     .bind(character.is_pov.unwrap_or(false) as i64)
     .execute(&state.db)
     .await
-    .map_err(|e| format!("Failed to add character: {}", e))?;
+    .map_err(|e| AppError::database(format!("Failed to add character: {}", e)))?;
 
     Ok(result.last_insert_rowid())
 }
@@ -101,7 +102,7 @@ pub async fn get_character_by_name(
     name: String,
     story_id: Option<i64>,
     state: State<'_, OllamaState>,
-) -> Result<Option<CharacterProfile>, String> {
+) -> Result<Option<CharacterProfile>, AppError> {
     let row = if let Some(sid) = story_id {
         sqlx::query(
             r#"
@@ -118,8 +119,7 @@ pub async fn get_character_by_name(
         .bind(&name)
         .bind(sid)
         .fetch_optional(&state.db)
-        .await
-        .map_err(|e| e.to_string())?
+        .await?
     } else {
         sqlx::query(
             r#"
@@ -134,8 +134,7 @@ pub async fn get_character_by_name(
         )
         .bind(&name)
         .fetch_optional(&state.db)
-        .await
-        .map_err(|e| e.to_string())?
+        .await?
     };
 
     Ok(row.as_ref().map(row_to_profile))
@@ -146,7 +145,7 @@ pub async fn get_character_by_name(
 pub async fn get_character_by_id(
     id: i64,
     state: State<'_, OllamaState>,
-) -> Result<Option<CharacterProfile>, String> {
+) -> Result<Option<CharacterProfile>, AppError> {
     let row = sqlx::query(
         r#"
         SELECT id, story_id, name, age, gender, skin_tone, hair_style,
@@ -159,8 +158,7 @@ pub async fn get_character_by_id(
     )
     .bind(id)
     .fetch_optional(&state.db)
-    .await
-    .map_err(|e| e.to_string())?;
+    .await?;
 
     Ok(row.as_ref().map(row_to_profile))
 }
@@ -170,7 +168,7 @@ pub async fn get_character_by_id(
 pub async fn update_character(
     character: CharacterProfile,
     state: State<'_, OllamaState>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     let art_style = character.art_style.clone().unwrap_or_else(|| "Realistic".to_string());
 
     sqlx::query(
@@ -223,7 +221,7 @@ pub async fn update_character(
     .bind(&character.id)
     .execute(&state.db)
     .await
-    .map_err(|e| format!("Failed to update character: {}", e))?;
+    .map_err(|e| AppError::database(format!("Failed to update character: {}", e)))?;
 
     Ok(())
 }
@@ -233,12 +231,12 @@ pub async fn update_character(
 pub async fn delete_character_by_id(
     id: i64,
     state: State<'_, OllamaState>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     sqlx::query("DELETE FROM characters WHERE id = ?")
         .bind(id)
         .execute(&state.db)
         .await
-        .map_err(|e| format!("Failed to delete character: {}", e))?;
+        .map_err(|e| AppError::database(format!("Failed to delete character: {}", e)))?;
 
     Ok(())
 }
@@ -248,7 +246,7 @@ pub async fn delete_character_by_id(
 pub async fn delete_character(
     id: i64,
     state: State<'_, OllamaState>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     delete_character_by_id(id, state).await
 }
 
@@ -260,7 +258,7 @@ pub async fn list_characters_for_story(
     story_id: Option<i64>,
     content_rating_filter: Option<String>,
     state: State<'_, OllamaState>,
-) -> Result<Vec<CharacterProfile>, String> {
+) -> Result<Vec<CharacterProfile>, AppError> {
     use sqlx::QueryBuilder;
     use sqlx::Sqlite;
 
@@ -288,8 +286,7 @@ pub async fn list_characters_for_story(
 
     let rows = builder.build()
         .fetch_all(&state.db)
-        .await
-        .map_err(|e| e.to_string())?;
+        .await?;
 
     Ok(rows.iter().map(row_to_profile).collect())
 }
@@ -301,7 +298,7 @@ pub async fn list_characters_for_story(
 pub async fn list_all_characters(
     content_rating_filter: Option<String>,
     state: State<'_, OllamaState>,
-) -> Result<Vec<CharacterProfile>, String> {
+) -> Result<Vec<CharacterProfile>, AppError> {
     let rating_clause = match content_rating_filter.as_deref() {
         Some("sfw") => " WHERE content_rating = 'sfw'",
         _ => "",
@@ -316,8 +313,7 @@ pub async fn list_all_characters(
     );
     let rows = sqlx::query(&sql)
         .fetch_all(&state.db)
-        .await
-        .map_err(|e| e.to_string())?;
+        .await?;
 
     Ok(rows.iter().map(row_to_profile).collect())
 }
@@ -333,7 +329,7 @@ pub async fn add_character_to_story(
     character_id: i64,
     story_id: i64,
     state: State<'_, OllamaState>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     sqlx::query(
         "INSERT OR IGNORE INTO story_characters (story_id, character_id) VALUES (?, ?)"
     )
@@ -341,7 +337,7 @@ pub async fn add_character_to_story(
     .bind(character_id)
     .execute(&state.db)
     .await
-    .map_err(|e| format!("Failed to add character to story: {}", e))?;
+    .map_err(|e| AppError::database(format!("Failed to add character to story: {}", e)))?;
 
     Ok(())
 }
@@ -353,7 +349,7 @@ pub async fn remove_character_from_story(
     character_id: i64,
     story_id: i64,
     state: State<'_, OllamaState>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     sqlx::query(
         "DELETE FROM story_characters WHERE story_id = ? AND character_id = ?"
     )
@@ -361,7 +357,7 @@ pub async fn remove_character_from_story(
     .bind(character_id)
     .execute(&state.db)
     .await
-    .map_err(|e| format!("Failed to remove character from story: {}", e))?;
+    .map_err(|e| AppError::database(format!("Failed to remove character from story: {}", e)))?;
 
     Ok(())
 }
@@ -373,7 +369,7 @@ pub async fn link_character_to_story(
     character_id: i64,
     story_id: i64,
     state: State<'_, OllamaState>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     add_character_to_story(character_id, story_id, state).await
 }
 
@@ -389,7 +385,7 @@ pub async fn lookup_scene_characters(
     scene_characters: Vec<SceneCharacter>,
     story_id: Option<i64>,
     state: State<'_, OllamaState>,
-) -> Result<Vec<(SceneCharacter, Option<CharacterLookup>)>, String> {
+) -> Result<Vec<(SceneCharacter, Option<CharacterLookup>)>, AppError> {
     let mut results = Vec::new();
 
     for scene_char in scene_characters {
@@ -407,8 +403,7 @@ pub async fn lookup_scene_characters(
             .bind(&scene_char.name)
             .bind(sid)
             .fetch_optional(&state.db)
-            .await
-            .map_err(|e| e.to_string())?;
+            .await?;
 
             if r.is_none() {
                 // No global fallback when story-scoped — avoids same-name collisions
@@ -425,8 +420,7 @@ pub async fn lookup_scene_characters(
             )
             .bind(&scene_char.name)
             .fetch_optional(&state.db)
-            .await
-            .map_err(|e| e.to_string())?
+            .await?
         };
 
         let lookup = row.map(|r| CharacterLookup {
@@ -452,7 +446,7 @@ pub async fn set_character_master_image(
     id: i64,
     image_path: String,
     state: State<'_, OllamaState>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     sqlx::query(
         "UPDATE characters SET master_image_path = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?"
     )
@@ -460,7 +454,7 @@ pub async fn set_character_master_image(
     .bind(id)
     .execute(&state.db)
     .await
-    .map_err(|e| format!("Failed to update master image: {}", e))?;
+    .map_err(|e| AppError::database(format!("Failed to update master image: {}", e)))?;
 
     Ok(())
 }
@@ -473,7 +467,7 @@ pub async fn list_characters_by_art_style(
     art_style: Option<String>,
     exclude_ids: Vec<i64>,
     state: State<'_, OllamaState>,
-) -> Result<Vec<CharacterProfile>, String> {
+) -> Result<Vec<CharacterProfile>, AppError> {
     use sqlx::QueryBuilder;
     use sqlx::Sqlite;
 
@@ -503,8 +497,7 @@ pub async fn list_characters_by_art_style(
 
     let rows = builder.build()
         .fetch_all(&state.db)
-        .await
-        .map_err(|e| e.to_string())?;
+        .await?;
 
     Ok(rows.iter().map(row_to_profile).collect())
 }
@@ -517,7 +510,7 @@ pub async fn search_characters(
     story_id: Option<i64>,
     limit: Option<i32>,
     state: State<'_, OllamaState>,
-) -> Result<Vec<CharacterProfile>, String> {
+) -> Result<Vec<CharacterProfile>, AppError> {
     let search_pattern = format!("%{}%", query);
     let max_results = limit.unwrap_or(10);
 
@@ -539,8 +532,7 @@ pub async fn search_characters(
         .bind(sid)
         .bind(max_results)
         .fetch_all(&state.db)
-        .await
-        .map_err(|e| e.to_string())?
+        .await?
     } else {
         sqlx::query(
             r#"
@@ -557,8 +549,7 @@ pub async fn search_characters(
         .bind(&search_pattern)
         .bind(max_results)
         .fetch_all(&state.db)
-        .await
-        .map_err(|e| e.to_string())?
+        .await?
     };
 
     Ok(rows.iter().map(row_to_profile).collect())
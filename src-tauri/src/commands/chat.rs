@@ -1,14 +1,16 @@
 use tauri::State;
 use crate::state::OllamaState;
+use crate::error::AppError;
 use crate::models::{Message, ChatResponse};
+use crate::commands::branch::{IN_LINEAGE, LINEAGE_CTE};
+use crate::text_gen::turns::set_turn_image;
 use sqlx::Row;
 
 #[tauri::command]
-pub async fn get_chat_list(state: State<'_, OllamaState>) -> Result<Vec<ChatResponse>, String> {
+pub async fn get_chat_list(state: State<'_, OllamaState>) -> Result<Vec<ChatResponse>, AppError> {
     let rows = sqlx::query("SELECT id, title FROM chats ORDER BY created_at DESC")
         .fetch_all(&state.db)
-        .await
-        .map_err(|e| e.to_string())?;
+        .await?;
 
     Ok(rows
         .iter()
@@ -20,11 +22,10 @@ pub async fn get_chat_list(state: State<'_, OllamaState>) -> Result<Vec<ChatResp
 }
 
 #[tauri::command]
-pub async fn new_chat(state: State<'_, OllamaState>) -> Result<i64, String> {
+pub async fn new_chat(state: State<'_, OllamaState>) -> Result<i64, AppError> {
     let result = sqlx::query("INSERT INTO chats (title) VALUES ('New Chat')")
         .execute(&state.db)
-        .await
-        .map_err(|e| e.to_string())?;
+        .await?;
 
     let new_id = result.last_insert_rowid();
     *state.current_chat_id.lock().unwrap() = Some(new_id);
@@ -32,18 +33,18 @@ pub async fn new_chat(state: State<'_, OllamaState>) -> Result<i64, String> {
 }
 
 #[tauri::command]
-pub async fn load_chat(id: i64, state: State<'_, OllamaState>) -> Result<Vec<Message>, String> {
-    let rows = sqlx::query(
-        "SELECT m.id as message_id, m.role, m.content, i.file_path as image_path \
-         FROM messages m \
+pub async fn load_chat(id: i64, state: State<'_, OllamaState>) -> Result<Vec<Message>, AppError> {
+    let rows = sqlx::query(&format!(
+        "{}SELECT m.id as message_id, m.role, m.content, i.file_path as image_path \
+         FROM messages m {} \
          LEFT JOIN images i ON i.message_id = m.id \
-         WHERE m.chat_id = ? \
-         ORDER BY m.timestamp ASC",
-    )
+         WHERE m.is_selected = 1 \
+         ORDER BY m.id ASC",
+        LINEAGE_CTE, IN_LINEAGE
+    ))
     .bind(id)
     .fetch_all(&state.db)
-    .await
-    .map_err(|e| e.to_string())?;
+    .await?;
 
     let messages: Vec<Message> = rows
         .iter()
@@ -63,7 +64,7 @@ pub async fn load_chat(id: i64, state: State<'_, OllamaState>) -> Result<Vec<Mes
 }
 
 #[tauri::command]
-pub async fn delete_chats(ids: Vec<i64>, state: State<'_, OllamaState>) -> Result<(), String> {
+pub async fn delete_chats(ids: Vec<i64>, state: State<'_, OllamaState>) -> Result<(), AppError> {
     for id in ids {
         sqlx::query("DELETE FROM images WHERE chat_id = ?")
             .bind(id)
@@ -85,7 +86,7 @@ pub async fn delete_chats(ids: Vec<i64>, state: State<'_, OllamaState>) -> Resul
 }
 
 #[tauri::command]
-pub async fn clear_history(id: i64, state: State<'_, OllamaState>) -> Result<(), String> {
+pub async fn clear_history(id: i64, state: State<'_, OllamaState>) -> Result<(), AppError> {
     sqlx::query("DELETE FROM images WHERE chat_id = ?")
         .bind(id)
         .execute(&state.db)
@@ -104,8 +105,8 @@ pub async fn clear_history(id: i64, state: State<'_, OllamaState>) -> Result<(),
     Ok(())
 }
 
-/// Save (or replace) the generated image for a specific assistant message.
-/// Deletes any existing image record for the message first, then inserts the new one.
+/// Save (or replace) the generated image for a specific assistant message
+/// and link it to the message's turn (see `set_turn_image`).
 /// Used by "Illustrate Scene" and "Redraw Image" in the story view.
 #[tauri::command]
 pub async fn save_image_for_message(
@@ -113,24 +114,10 @@ pub async fn save_image_for_message(
     chat_id: i64,
     file_path: String,
     state: State<'_, OllamaState>,
-) -> Result<(), String> {
-    // Remove any existing image for this message (handles the "redraw" case)
-    sqlx::query("DELETE FROM images WHERE message_id = ?")
-        .bind(message_id)
-        .execute(&state.db)
+) -> Result<(), AppError> {
+    set_turn_image(&state.db, message_id, chat_id, &file_path)
         .await
-        .ok();
-
-    sqlx::query(
-        "INSERT INTO images (message_id, chat_id, file_path) VALUES (?, ?, ?)",
-    )
-    .bind(message_id)
-    .bind(chat_id)
-    .bind(&file_path)
-    .execute(&state.db)
-    .await
-    .map_err(|e| format!("Failed to save image record: {}", e))?;
-
+        .map_err(AppError::database)?;
     Ok(())
 }
 
@@ -139,12 +126,11 @@ pub async fn set_chat_character(
     chat_id: i64,
     character_id: Option<i64>,
     state: State<'_, OllamaState>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     sqlx::query("UPDATE chats SET character_id = ? WHERE id = ?")
         .bind(character_id)
         .bind(chat_id)
         .execute(&state.db)
-        .await
-        .map_err(|e| e.to_string())?;
+        .await?;
     Ok(())
 }
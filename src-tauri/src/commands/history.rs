@@ -0,0 +1,326 @@
+// src-tauri/src/commands/history.rs
+//
+// Rewind / edit commands for StoryEngine.
+// Any past turn can be edited in place or used as a rewind point.
+//
+// Every assistant message carries the state snapshot of its turn (written by
+// the orchestrator's save_turn_to_db):
+//   scene_id          — the story's active scene after the turn
+//   roster_additions  — characters sync_scene_from_turn added to that scene
+//   emotion_snapshot  — all character_emotional_states of the chat
+//
+// Rewinding deletes the chat's later messages and their images, undoes their
+// roster additions and restores the active scene and emotional states of the
+// turn rewound to. Summaries, story memory and recall vectors built from
+// removed or edited text are dropped and rebuilt on the next turn.
+//
+// Messages shared with a story branch (see commands::branch) are read-only.
+
+use sqlx::{Row, SqliteConnection};
+use tauri::State;
+
+use crate::commands::branch::{is_shared_with_branch, IN_LINEAGE, LINEAGE_CTE};
+use crate::error::AppError;
+use crate::state::OllamaState;
+use crate::text_gen::context::{extract_story_text, replace_story_text};
+use crate::text_gen::memory::drop_memory_after;
+use crate::text_gen::orchestrator::restore_emotional_states;
+
+// ============================================================================
+// HELPERS
+// ============================================================================
+
+/// Drop summaries and memory that cover messages after `keep_until`, plus
+/// the recall vectors of those messages.
+async fn invalidate_derived(conn: &mut SqliteConnection, chat_id: i64, keep_until: i64) -> Result<(), String> {
+    sqlx::query("DELETE FROM context_summaries WHERE chat_id = ? AND last_message_id > ?")
+        .bind(chat_id)
+        .bind(keep_until)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| format!("Failed to invalidate context_summaries: {}", e))?;
+    drop_memory_after(conn, chat_id, keep_until).await?;
+    sqlx::query("DELETE FROM turn_embeddings WHERE message_id > ? AND message_id IN (SELECT id FROM messages WHERE chat_id = ?)")
+        .bind(keep_until)
+        .bind(chat_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| format!("Failed to invalidate recall index: {}", e))?;
+    Ok(())
+}
+
+/// The ids in a `roster_additions` column (JSON id array).
+pub(crate) fn parse_roster_additions(json: Option<&str>) -> Vec<i64> {
+    json.and_then(|j| serde_json::from_str(j).ok()).unwrap_or_default()
+}
+
+/// Add back (`present`) or remove the characters a turn added to its scene's
+/// roster.
+pub(crate) async fn set_roster_additions(
+    conn: &mut SqliteConnection,
+    scene_id: i64,
+    additions: &[i64],
+    present: bool,
+) -> Result<(), String> {
+    let sql = if present {
+        "INSERT OR IGNORE INTO scene_characters (scene_id, character_id) VALUES (?, ?)"
+    } else {
+        "DELETE FROM scene_characters WHERE scene_id = ? AND character_id = ?"
+    };
+    for character_id in additions {
+        sqlx::query(sql)
+            .bind(scene_id)
+            .bind(character_id)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| format!("Failed to restore scene roster: {}", e))?;
+    }
+    Ok(())
+}
+
+/// Make `scene_id` the active scene of the chat's story timeline.
+pub(crate) async fn set_active_scene(
+    conn: &mut SqliteConnection,
+    chat_id: i64,
+    scene_id: Option<i64>,
+) -> Result<(), String> {
+    // The chat is either the story's active timeline or a parked branch
+    sqlx::query("UPDATE story_premises SET active_scene_id = ? WHERE chat_id = ?")
+        .bind(scene_id)
+        .bind(chat_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| format!("Failed to restore active scene: {}", e))?;
+    sqlx::query("UPDATE story_branches SET active_scene_id = ?, scene_roster = NULL WHERE chat_id = ?")
+        .bind(scene_id)
+        .bind(chat_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| format!("Failed to restore branch scene: {}", e))?;
+    Ok(())
+}
+
+/// Active scene and emotion snapshot saved with the last selected turn
+/// before `before_id` in the chat's timeline, None if there is no such turn.
+pub(crate) async fn turn_state_before(
+    conn: &mut SqliteConnection,
+    chat_id: i64,
+    before_id: i64,
+) -> Result<Option<(Option<i64>, Option<String>)>, String> {
+    let turn = sqlx::query(&format!(
+        "{}SELECT m.scene_id, m.emotion_snapshot FROM messages m {} \
+         WHERE m.is_selected = 1 AND m.role = 'assistant' AND m.id < ? ORDER BY m.id DESC LIMIT 1",
+        LINEAGE_CTE, IN_LINEAGE
+    ))
+    .bind(chat_id)
+    .bind(before_id)
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(|e| format!("Failed to load turn state: {}", e))?;
+
+    Ok(turn.map(|row| (row.get("scene_id"), row.get("emotion_snapshot"))))
+}
+
+/// Restore the active scene and emotional states saved with the last turn at
+/// or before `keep_until` in the chat's timeline. No turn left = fresh state.
+async fn restore_turn_state(conn: &mut SqliteConnection, chat_id: i64, keep_until: i64) -> Result<(), String> {
+    let (scene_id, snapshot) = turn_state_before(conn, chat_id, keep_until + 1)
+        .await?
+        .unwrap_or((None, Some("[]".to_string())));
+    set_active_scene(conn, chat_id, scene_id).await?;
+
+    // Turns saved before snapshots existed keep the current emotional states
+    if let Some(snapshot) = snapshot {
+        restore_emotional_states(&mut *conn, chat_id, &snapshot).await?;
+    }
+    Ok(())
+}
+
+// ============================================================================
+// REWIND / EDIT COMMANDS
+// ============================================================================
+
+/// Rewind a chat to a past message: an assistant message keeps its turn, a
+/// user message removes its own turn as well. A branch rewinds at most to
+/// its fork point. Returns how many turns were removed.
+#[tauri::command]
+pub async fn rewind_story_to(
+    chat_id: i64,
+    message_id: i64,
+    state: State<'_, OllamaState>,
+) -> Result<usize, AppError> {
+    // Messages, roster, scene and emotions are rewound together or not at all
+    let mut tx = state
+        .db
+        .begin()
+        .await
+        .map_err(|e| AppError::database(format!("Failed to start transaction: {}", e)))?;
+
+    let target = sqlx::query(&format!(
+        "{}SELECT m.role FROM messages m {} WHERE m.id = ? AND m.is_selected = 1",
+        LINEAGE_CTE, IN_LINEAGE
+    ))
+    .bind(chat_id)
+    .bind(message_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| AppError::database(format!("Failed to load message: {}", e)))?
+    .ok_or_else(|| AppError::not_found(format!("Message {} is not part of chat {}", message_id, chat_id)))?;
+    let role: String = target.get("role");
+    let keep_until = if role == "user" { message_id - 1 } else { message_id };
+
+    // A branch can't drop the turns it shares with its parent
+    let fork_point: Option<i64> = sqlx::query("SELECT shared_until_id FROM story_branches WHERE chat_id = ?")
+        .bind(chat_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to load branch: {}", e)))?
+        .and_then(|r| r.get("shared_until_id"));
+    if fork_point.map_or(false, |shared_until| keep_until < shared_until) {
+        return Err(AppError::invalid_input(
+            "This turn comes before the branch's fork point — fork from it instead",
+        ));
+    }
+
+    if is_shared_with_branch(&mut *tx, chat_id, keep_until + 1).await? {
+        return Err(AppError::invalid_input("Later turns are shared with a story branch — fork from this turn instead"));
+    }
+
+    let removed = sqlx::query(
+        "SELECT id, role, scene_id, roster_additions FROM messages WHERE chat_id = ? AND id > ?",
+    )
+    .bind(chat_id)
+    .bind(keep_until)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| AppError::database(format!("Failed to load messages: {}", e)))?;
+    if removed.is_empty() {
+        return Ok(0);
+    }
+
+    // Undo roster additions, newest turn first
+    for row in removed.iter().rev() {
+        let Some(scene_id) = row.get::<Option<i64>, _>("scene_id") else {
+            continue;
+        };
+        let additions = parse_roster_additions(row.get("roster_additions"));
+        set_roster_additions(&mut tx, scene_id, &additions, false)
+            .await
+            .map_err(AppError::database)?;
+    }
+
+    let image_paths: Vec<String> = sqlx::query(
+        "SELECT i.file_path FROM images i INNER JOIN messages m ON m.id = i.message_id \
+         WHERE m.chat_id = ? AND m.id > ?",
+    )
+    .bind(chat_id)
+    .bind(keep_until)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| AppError::database(format!("Failed to load images: {}", e)))?
+    .iter()
+    .map(|r| r.get("file_path"))
+    .collect();
+
+    invalidate_derived(&mut tx, chat_id, keep_until).await?;
+    sqlx::query("DELETE FROM images WHERE message_id IN (SELECT id FROM messages WHERE chat_id = ? AND id > ?)")
+        .bind(chat_id)
+        .bind(keep_until)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to delete images: {}", e)))?;
+    sqlx::query("DELETE FROM messages WHERE chat_id = ? AND id > ?")
+        .bind(chat_id)
+        .bind(keep_until)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to delete messages: {}", e)))?;
+
+    restore_turn_state(&mut tx, chat_id, keep_until).await?;
+    tx.commit()
+        .await
+        .map_err(|e| AppError::database(format!("Failed to commit rewind: {}", e)))?;
+
+    // Files go only once the rows are gone for good
+    for path in &image_paths {
+        let _ = std::fs::remove_file(path);
+    }
+
+    let turns = removed
+        .iter()
+        .filter(|r| r.get::<String, _>("role") == "user")
+        .count();
+    println!(
+        "[History] Rewound chat {} to message {} ({} turns, {} messages, {} images removed)",
+        chat_id, keep_until, turns, removed.len(), image_paths.len()
+    );
+    Ok(turns)
+}
+
+/// Edit the text of a past message in place. For an assistant message
+/// `content` is the new story text; scene, characters and flags are kept, so
+/// the turn's state snapshot stays valid. Its summary hint described the old
+/// text and is cleared. Later turns are kept.
+#[tauri::command]
+pub async fn edit_story_message(
+    message_id: i64,
+    content: String,
+    state: State<'_, OllamaState>,
+) -> Result<(), AppError> {
+    let content = content.trim();
+    if content.is_empty() {
+        return Err(AppError::invalid_input("Message text cannot be empty"));
+    }
+
+    let mut tx = state
+        .db
+        .begin()
+        .await
+        .map_err(|e| AppError::database(format!("Failed to start transaction: {}", e)))?;
+
+    let msg = sqlx::query("SELECT chat_id, role, content FROM messages WHERE id = ?")
+        .bind(message_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to load message: {}", e)))?
+        .ok_or_else(|| AppError::not_found(format!("Message {} not found", message_id)))?;
+    let chat_id: i64 = msg.get("chat_id");
+    let role: String = msg.get("role");
+    let old_content: String = msg.get("content");
+
+    if is_shared_with_branch(&mut *tx, chat_id, message_id).await? {
+        return Err(AppError::invalid_input("This message is shared with a story branch — fork from it instead"));
+    }
+
+    let new_content = match role.as_str() {
+        "assistant" if extract_story_text(&old_content) == content => return Ok(()),
+        "assistant" => replace_story_text(&old_content, content),
+        "user" if old_content == content => return Ok(()),
+        "user" => content.to_string(),
+        _ => return Err(AppError::invalid_input("Only user and assistant messages can be edited")),
+    };
+
+    sqlx::query("UPDATE messages SET content = ? WHERE id = ?")
+        .bind(&new_content)
+        .bind(message_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to update message: {}", e)))?;
+    if role == "assistant" {
+        sqlx::query("UPDATE turns SET story_text = ?, summary_hint = '' WHERE assistant_message_id = ?")
+            .bind(content)
+            .bind(message_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| AppError::database(format!("Failed to update turn: {}", e)))?;
+    }
+
+    // Summaries, memory and recall vectors from this turn on are rebuilt
+    invalidate_derived(&mut tx, chat_id, message_id - 1).await?;
+    tx.commit()
+        .await
+        .map_err(|e| AppError::database(format!("Failed to commit edit: {}", e)))?;
+
+    println!("[History] Edited {} message {} in chat {}", role, message_id, chat_id);
+    Ok(())
+}
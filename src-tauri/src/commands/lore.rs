@@ -0,0 +1,181 @@
+// src-tauri/src/commands/lore.rs
+//
+// Lorebook commands for StoryEngine.
+// Lore entries hold world facts (factions, magic rules, places) that are
+// injected into the story prompt when one of their keys shows up in recent
+// turns — see text_gen::lorebook for activation.
+//
+// Scope: story_id set = only that story; story_id NULL = every story.
+
+use serde::Deserialize;
+use tauri::State;
+
+use crate::error::AppError;
+use crate::models::{LoreEntry, LorePosition};
+use crate::state::OllamaState;
+use crate::text_gen::lorebook::lore_from_row;
+
+// ============================================================================
+// INPUT
+// ============================================================================
+
+/// Editable fields of a lore entry, as sent by the frontend.
+#[derive(Debug, Deserialize)]
+pub struct LoreEntryInput {
+    #[serde(default)]
+    pub story_id: Option<i64>,
+    pub name: String,
+    #[serde(default)]
+    pub keys: Vec<String>,
+    pub content: String,
+    #[serde(default)]
+    pub priority: i64,
+    #[serde(default)]
+    pub position: LorePosition,
+    #[serde(default)]
+    pub always_on: bool,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// Trim keys, drop empty ones and reject entries that could never fire.
+fn validate(entry: &LoreEntryInput) -> Result<Vec<String>, AppError> {
+    if entry.name.trim().is_empty() {
+        return Err(AppError::invalid_input("Lore entry name cannot be empty"));
+    }
+    if entry.content.trim().is_empty() {
+        return Err(AppError::invalid_input("Lore entry content cannot be empty"));
+    }
+    let keys: Vec<String> = entry
+        .keys
+        .iter()
+        .map(|k| k.trim().to_string())
+        .filter(|k| !k.is_empty())
+        .collect();
+    if keys.is_empty() && !entry.always_on {
+        return Err(AppError::invalid_input("Lore entry needs at least one key unless it is always on"));
+    }
+    Ok(keys)
+}
+
+// ============================================================================
+// LORE CRUD
+// ============================================================================
+
+/// Create a lore entry and return its id.
+#[tauri::command]
+pub async fn create_lore_entry(
+    entry: LoreEntryInput,
+    state: State<'_, OllamaState>,
+) -> Result<i64, AppError> {
+    let keys = validate(&entry)?;
+    let keys_json = serde_json::to_string(&keys).map_err(|e| e.to_string())?;
+
+    let result = sqlx::query(
+        "INSERT INTO lore_entries (story_id, name, keys, content, priority, position, always_on, enabled)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
+    )
+    .bind(entry.story_id)
+    .bind(entry.name.trim())
+    .bind(&keys_json)
+    .bind(entry.content.trim())
+    .bind(entry.priority)
+    .bind(entry.position.as_str())
+    .bind(entry.always_on)
+    .bind(entry.enabled)
+    .execute(&state.db)
+    .await?;
+
+    Ok(result.last_insert_rowid())
+}
+
+/// Replace all editable fields of a lore entry.
+#[tauri::command]
+pub async fn update_lore_entry(
+    id: i64,
+    entry: LoreEntryInput,
+    state: State<'_, OllamaState>,
+) -> Result<(), AppError> {
+    let keys = validate(&entry)?;
+    let keys_json = serde_json::to_string(&keys).map_err(|e| e.to_string())?;
+
+    let result = sqlx::query(
+        "UPDATE lore_entries
+         SET story_id=?, name=?, keys=?, content=?, priority=?, position=?, always_on=?, enabled=?
+         WHERE id=?"
+    )
+    .bind(entry.story_id)
+    .bind(entry.name.trim())
+    .bind(&keys_json)
+    .bind(entry.content.trim())
+    .bind(entry.priority)
+    .bind(entry.position.as_str())
+    .bind(entry.always_on)
+    .bind(entry.enabled)
+    .bind(id)
+    .execute(&state.db)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::not_found(format!("Lore entry {} not found", id)));
+    }
+    Ok(())
+}
+
+/// Delete a lore entry by id.
+#[tauri::command]
+pub async fn delete_lore_entry(
+    id: i64,
+    state: State<'_, OllamaState>,
+) -> Result<(), AppError> {
+    sqlx::query("DELETE FROM lore_entries WHERE id=?")
+        .bind(id)
+        .execute(&state.db)
+        .await?;
+    Ok(())
+}
+
+// ============================================================================
+// LORE QUERIES
+// ============================================================================
+
+/// Entries visible to a story (its own + global), or only global entries
+/// when `storyId` is null. Disabled entries are included for editing.
+#[tauri::command]
+pub async fn list_lore_entries(
+    story_id: Option<i64>,
+    state: State<'_, OllamaState>,
+) -> Result<Vec<LoreEntry>, AppError> {
+    let rows = sqlx::query(
+        "SELECT id, story_id, name, keys, content, priority, position, always_on, enabled, created_at
+         FROM lore_entries
+         WHERE story_id IS NULL OR story_id = ?
+         ORDER BY story_id IS NULL, priority DESC, name COLLATE NOCASE"
+    )
+    .bind(story_id)
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(rows.iter().map(lore_from_row).collect())
+}
+
+/// Fetch a single lore entry.
+#[tauri::command]
+pub async fn get_lore_entry(
+    id: i64,
+    state: State<'_, OllamaState>,
+) -> Result<Option<LoreEntry>, AppError> {
+    let row = sqlx::query(
+        "SELECT id, story_id, name, keys, content, priority, position, always_on, enabled, created_at
+         FROM lore_entries WHERE id=?"
+    )
+    .bind(id)
+    .fetch_optional(&state.db)
+    .await?;
+
+    Ok(row.as_ref().map(lore_from_row))
+}
@@ -1,4 +1,7 @@
+pub mod branch;
 pub mod chat;
 pub mod character;
+pub mod history;
+pub mod lore;
 pub mod scene;
 pub mod story;
\ No newline at end of file
@@ -11,6 +11,7 @@
 
 use tauri::State;
 use crate::state::{OllamaState, SceneHintState};
+use crate::error::AppError;
 use crate::models::{Scene, SceneWithCharacters, CharacterProfile};
 use sqlx::Row;
 
@@ -72,7 +73,7 @@ pub async fn create_scene(
     time_of_day: Option<String>,
     mood: Option<String>,
     state: State<'_, OllamaState>,
-) -> Result<i64, String> {
+) -> Result<i64, AppError> {
     let result = sqlx::query(
         "INSERT INTO scenes (name, description, location, location_type, time_of_day, mood)
          VALUES (?, ?, ?, ?, ?, ?)"
@@ -84,8 +85,7 @@ pub async fn create_scene(
     .bind(&time_of_day)
     .bind(&mood)
     .execute(&state.db)
-    .await
-    .map_err(|e| e.to_string())?;
+    .await?;
 
     Ok(result.last_insert_rowid())
 }
@@ -101,7 +101,7 @@ pub async fn update_scene(
     time_of_day: Option<String>,
     mood: Option<String>,
     state: State<'_, OllamaState>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     sqlx::query(
         "UPDATE scenes
          SET name=?, description=?, location=?, location_type=?, time_of_day=?, mood=?
@@ -115,8 +115,7 @@ pub async fn update_scene(
     .bind(&mood)
     .bind(id)
     .execute(&state.db)
-    .await
-    .map_err(|e| e.to_string())?;
+    .await?;
 
     Ok(())
 }
@@ -126,12 +125,11 @@ pub async fn update_scene(
 pub async fn delete_scene(
     id: i64,
     state: State<'_, OllamaState>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     sqlx::query("DELETE FROM scenes WHERE id=?")
         .bind(id)
         .execute(&state.db)
-        .await
-        .map_err(|e| e.to_string())?;
+        .await?;
     Ok(())
 }
 
@@ -144,7 +142,7 @@ pub async fn delete_scene(
 pub async fn list_scenes_for_story(
     story_id: i64,
     state: State<'_, OllamaState>,
-) -> Result<Vec<Scene>, String> {
+) -> Result<Vec<Scene>, AppError> {
     let rows = sqlx::query(
         "SELECT s.* FROM scenes s
          JOIN story_scenes ss ON ss.scene_id = s.id
@@ -153,8 +151,7 @@ pub async fn list_scenes_for_story(
     )
     .bind(story_id)
     .fetch_all(&state.db)
-    .await
-    .map_err(|e| e.to_string())?;
+    .await?;
 
     Ok(rows.iter().map(row_to_scene).collect())
 }
@@ -163,11 +160,10 @@ pub async fn list_scenes_for_story(
 #[tauri::command]
 pub async fn list_all_scenes(
     state: State<'_, OllamaState>,
-) -> Result<Vec<Scene>, String> {
+) -> Result<Vec<Scene>, AppError> {
     let rows = sqlx::query("SELECT * FROM scenes ORDER BY created_at ASC")
         .fetch_all(&state.db)
-        .await
-        .map_err(|e| e.to_string())?;
+        .await?;
 
     Ok(rows.iter().map(row_to_scene).collect())
 }
@@ -182,15 +178,14 @@ pub async fn link_scene_to_story(
     scene_id: i64,
     story_id: i64,
     state: State<'_, OllamaState>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     sqlx::query(
         "INSERT OR IGNORE INTO story_scenes (story_id, scene_id) VALUES (?, ?)"
     )
     .bind(story_id)
     .bind(scene_id)
     .execute(&state.db)
-    .await
-    .map_err(|e| e.to_string())?;
+    .await?;
     Ok(())
 }
 
@@ -200,15 +195,14 @@ pub async fn unlink_scene_from_story(
     scene_id: i64,
     story_id: i64,
     state: State<'_, OllamaState>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     sqlx::query(
         "DELETE FROM story_scenes WHERE story_id=? AND scene_id=?"
     )
     .bind(story_id)
     .bind(scene_id)
     .execute(&state.db)
-    .await
-    .map_err(|e| e.to_string())?;
+    .await?;
     Ok(())
 }
 
@@ -222,15 +216,14 @@ pub async fn add_character_to_scene(
     scene_id: i64,
     character_id: i64,
     state: State<'_, OllamaState>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     sqlx::query(
         "INSERT OR IGNORE INTO scene_characters (scene_id, character_id) VALUES (?, ?)"
     )
     .bind(scene_id)
     .bind(character_id)
     .execute(&state.db)
-    .await
-    .map_err(|e| e.to_string())?;
+    .await?;
     Ok(())
 }
 
@@ -240,15 +233,14 @@ pub async fn remove_character_from_scene(
     scene_id: i64,
     character_id: i64,
     state: State<'_, OllamaState>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     sqlx::query(
         "DELETE FROM scene_characters WHERE scene_id=? AND character_id=?"
     )
     .bind(scene_id)
     .bind(character_id)
     .execute(&state.db)
-    .await
-    .map_err(|e| e.to_string())?;
+    .await?;
     Ok(())
 }
 
@@ -257,7 +249,7 @@ pub async fn remove_character_from_scene(
 pub async fn get_scene_characters(
     scene_id: i64,
     state: State<'_, OllamaState>,
-) -> Result<Vec<CharacterProfile>, String> {
+) -> Result<Vec<CharacterProfile>, AppError> {
     let rows = sqlx::query(
         "SELECT c.* FROM characters c
          JOIN scene_characters sc ON sc.character_id = c.id
@@ -266,8 +258,7 @@ pub async fn get_scene_characters(
     )
     .bind(scene_id)
     .fetch_all(&state.db)
-    .await
-    .map_err(|e| e.to_string())?;
+    .await?;
 
     Ok(rows.iter().map(row_to_profile).collect())
 }
@@ -277,12 +268,11 @@ pub async fn get_scene_characters(
 pub async fn get_scene_with_characters(
     scene_id: i64,
     state: State<'_, OllamaState>,
-) -> Result<SceneWithCharacters, String> {
+) -> Result<SceneWithCharacters, AppError> {
     let row = sqlx::query("SELECT * FROM scenes WHERE id=?")
         .bind(scene_id)
         .fetch_one(&state.db)
-        .await
-        .map_err(|e| e.to_string())?;
+        .await?;
 
     let scene = row_to_scene(&row);
 
@@ -294,8 +284,7 @@ pub async fn get_scene_with_characters(
     )
     .bind(scene_id)
     .fetch_all(&state.db)
-    .await
-    .map_err(|e| e.to_string())?;
+    .await?;
 
     Ok(SceneWithCharacters {
         scene,
@@ -314,13 +303,12 @@ pub async fn set_active_scene(
     story_id: i64,
     scene_id: Option<i64>,
     state: State<'_, OllamaState>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     sqlx::query("UPDATE story_premises SET active_scene_id=? WHERE id=?")
         .bind(scene_id)
         .bind(story_id)
         .execute(&state.db)
-        .await
-        .map_err(|e| e.to_string())?;
+        .await?;
     Ok(())
 }
 
@@ -329,7 +317,7 @@ pub async fn set_active_scene(
 pub async fn get_active_scene(
     story_id: i64,
     state: State<'_, OllamaState>,
-) -> Result<Option<Scene>, String> {
+) -> Result<Option<Scene>, AppError> {
     let row = sqlx::query(
         "SELECT s.* FROM scenes s
          JOIN story_premises sp ON sp.active_scene_id = s.id
@@ -337,8 +325,7 @@ pub async fn get_active_scene(
     )
     .bind(story_id)
     .fetch_optional(&state.db)
-    .await
-    .map_err(|e| e.to_string())?;
+    .await?;
 
     Ok(row.as_ref().map(row_to_scene))
 }
@@ -359,7 +346,7 @@ pub async fn create_scene_from_llm_output(
     time_of_day: Option<String>,
     mood: Option<String>,
     state: State<'_, OllamaState>,
-) -> Result<i64, String> {
+) -> Result<i64, AppError> {
     let result = sqlx::query(
         "INSERT INTO scenes (name, description, location, location_type, time_of_day, mood)
          VALUES (?, ?, ?, ?, ?, ?)"
@@ -371,8 +358,7 @@ pub async fn create_scene_from_llm_output(
     .bind(&time_of_day)
     .bind(&mood)
     .execute(&state.db)
-    .await
-    .map_err(|e| e.to_string())?;
+    .await?;
 
     let scene_id = result.last_insert_rowid();
 
@@ -380,15 +366,13 @@ pub async fn create_scene_from_llm_output(
         .bind(story_id)
         .bind(scene_id)
         .execute(&state.db)
-        .await
-        .map_err(|e| e.to_string())?;
+        .await?;
 
     sqlx::query("UPDATE story_premises SET active_scene_id=? WHERE id=?")
         .bind(scene_id)
         .bind(story_id)
         .execute(&state.db)
-        .await
-        .map_err(|e| e.to_string())?;
+        .await?;
 
     Ok(scene_id)
 }
@@ -411,16 +395,15 @@ pub async fn set_scene_hint(
     scene_id: i64,
     state: State<'_, OllamaState>,
     hint_state: State<'_, SceneHintState>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     // Load scene details
     let scene_row = sqlx::query(
         "SELECT name, location, time_of_day, mood FROM scenes WHERE id = ?"
     )
     .bind(scene_id)
     .fetch_optional(&state.db)
-    .await
-    .map_err(|e| e.to_string())?
-    .ok_or_else(|| "Scene not found".to_string())?;
+    .await?
+    .ok_or_else(|| AppError::not_found("Scene not found"))?;
 
     let name: String = scene_row.get("name");
     let location: Option<String> = scene_row.get("location");
@@ -435,8 +418,7 @@ pub async fn set_scene_hint(
     )
     .bind(scene_id)
     .fetch_all(&state.db)
-    .await
-    .map_err(|e| e.to_string())?;
+    .await?;
 
     let char_names: Vec<String> = char_rows.iter().map(|r| r.get("name")).collect();
 
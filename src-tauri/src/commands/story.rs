@@ -11,15 +11,20 @@
 //   - Export stories as JSON (HTML/PDF planned for future)
 //
 // This module extends the existing story_premises, chats, messages, characters,
-// and images tables. It adds new columns to story_premises for richer session
-// tracking (last_played_at, current_location, compressed_history, etc.)
+// and images tables. Its story_premises columns for richer session tracking
+// (last_played_at, current_location, compressed_history, etc.) are added by
+// the story_manager migration (see migrations.rs).
 
 use serde::{Deserialize, Serialize};
 use serde_json;
 use sqlx::Row;
 use tauri::{AppHandle, Manager, State};
 
-use crate::text_gen::context::{CompressedHistory, ConversationContext, StoryTurn};
+use crate::commands::branch::{IN_LINEAGE, LINEAGE_CTE};
+use crate::error::AppError;
+use crate::text_gen::context::{load_latest_summary, CompressedHistory, StoryTurn};
+use crate::text_gen::turns::load_story_turns;
+use crate::text_gen::prompts::{GenerationOverrides, StoryBible, MIN_NUM_CTX};
 use crate::models::{CharacterProfile, StoryPremise};
 use crate::state::OllamaState;
 
@@ -94,6 +99,8 @@ pub enum ExportFormat {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExportedStory {
     pub meta: ExportedMeta,
+    #[serde(default)]
+    pub bible: StoryBible,
     pub characters: Vec<CharacterProfile>,
     pub compressed_history: CompressedHistory,
     pub turns: Vec<ExportedTurn>,
@@ -121,74 +128,6 @@ pub struct ExportedTurn {
     pub image_path: Option<String>,
 }
 
-// ============================================================================
-// DATABASE MIGRATION
-// ============================================================================
-
-/// Run migration to add story manager columns to story_premises.
-/// Called from state.rs setup_database. Safe to call multiple times.
-pub async fn run_migrations(pool: &sqlx::SqlitePool) {
-    // =========================================================================
-    // FIX: Ensure created_at exists on story_premises.
-    // On databases created before this column was added to the CREATE TABLE
-    // statement, the column may be missing. This ALTER is idempotent —
-    // .ok() silently swallows the "duplicate column" error from SQLite.
-    // =========================================================================
-    sqlx::query(
-        "ALTER TABLE story_premises ADD COLUMN created_at DATETIME DEFAULT CURRENT_TIMESTAMP"
-    )
-    .execute(pool).await.ok();
-
-    // Add new columns to story_premises (silently fails if already exist)
-    sqlx::query("ALTER TABLE story_premises ADD COLUMN last_played_at DATETIME DEFAULT CURRENT_TIMESTAMP")
-        .execute(pool).await.ok();
-    sqlx::query("ALTER TABLE story_premises ADD COLUMN current_location TEXT")
-        .execute(pool).await.ok();
-    sqlx::query("ALTER TABLE story_premises ADD COLUMN compressed_history_json TEXT DEFAULT '{}'")
-        .execute(pool).await.ok();
-    sqlx::query("ALTER TABLE story_premises ADD COLUMN chat_id INTEGER REFERENCES chats(id)")
-        .execute(pool).await.ok();
-    sqlx::query("ALTER TABLE story_premises ADD COLUMN thumbnail_path TEXT")
-        .execute(pool).await.ok();
-    sqlx::query("ALTER TABLE story_premises ADD COLUMN content_rating TEXT DEFAULT 'sfw'")
-        .execute(pool).await.ok();
-
-    // Index for fast story lookups by last played
-    sqlx::query("CREATE INDEX IF NOT EXISTS idx_story_premises_last_played ON story_premises(last_played_at DESC)")
-        .execute(pool).await.ok();
-
-    // Index for chat -> story relationship
-    sqlx::query("CREATE INDEX IF NOT EXISTS idx_story_premises_chat ON story_premises(chat_id)")
-        .execute(pool).await.ok();
-
-    // Ensure every existing story has a linked chat (for stories created before this requirement)
-    let orphan_stories = sqlx::query(
-        "SELECT id, title FROM story_premises WHERE chat_id IS NULL"
-    )
-    .fetch_all(pool)
-    .await
-    .unwrap_or_default();
-
-    for story in &orphan_stories {
-        let story_id: i64 = story.get("id");
-        let title: String = story.get("title");
-        if let Ok(result) = sqlx::query("INSERT INTO chats (title) VALUES (?)")
-            .bind(&title)
-            .execute(pool)
-            .await
-        {
-            let chat_id = result.last_insert_rowid();
-            sqlx::query("UPDATE story_premises SET chat_id = ? WHERE id = ?")
-                .bind(chat_id)
-                .bind(story_id)
-                .execute(pool)
-                .await
-                .ok();
-            println!("[StoryManager] Migration: created chat {} for orphan story {}", chat_id, story_id);
-        }
-    }
-}
-
 // ============================================================================
 // COMMANDS
 // ============================================================================
@@ -202,7 +141,7 @@ pub async fn create_story(
     initial_character_ids: Option<Vec<i64>>,
     content_rating: Option<String>,
     state: State<'_, OllamaState>,
-) -> Result<i64, String> {
+) -> Result<i64, AppError> {
     let rating = content_rating.unwrap_or_else(|| "sfw".to_string());
 
     // 1. Create a chat for this story
@@ -212,7 +151,7 @@ pub async fn create_story(
     .bind(&title)
     .execute(&state.db)
     .await
-    .map_err(|e| format!("Failed to create chat: {}", e))?;
+    .map_err(|e| AppError::database(format!("Failed to create chat: {}", e)))?;
 
     let chat_id = chat_result.last_insert_rowid();
 
@@ -227,7 +166,7 @@ pub async fn create_story(
     .bind(&rating)
     .execute(&state.db)
     .await
-    .map_err(|e| format!("Failed to create story: {}", e))?;
+    .map_err(|e| AppError::database(format!("Failed to create story: {}", e)))?;
 
     let story_id = story_result.last_insert_rowid();
 
@@ -259,7 +198,7 @@ pub async fn create_story(
 pub async fn load_story(
     story_id: i64,
     state: State<'_, OllamaState>,
-) -> Result<StorySession, String> {
+) -> Result<StorySession, AppError> {
     // 1. Load the story premise
     let story_row = sqlx::query(
         "SELECT id, title, description, created_at, last_played_at,
@@ -269,8 +208,8 @@ pub async fn load_story(
     .bind(story_id)
     .fetch_optional(&state.db)
     .await
-    .map_err(|e| format!("DB error: {}", e))?
-    .ok_or_else(|| format!("Story {} not found", story_id))?;
+    .map_err(|e| AppError::database(format!("DB error: {}", e)))?
+    .ok_or_else(|| AppError::not_found(format!("Story {} not found", story_id)))?;
 
     let title: String = story_row.get("title");
     let description: String = story_row.get("description");
@@ -282,7 +221,7 @@ pub async fn load_story(
     let content_rating: String = story_row.get::<Option<String>, _>("content_rating").unwrap_or_else(|| "sfw".to_string());
 
     // 2. Parse compressed history
-    let compressed_history: CompressedHistory = compressed_json
+    let mut compressed_history: CompressedHistory = compressed_json
         .and_then(|json_str| serde_json::from_str(&json_str).ok())
         .unwrap_or_default();
 
@@ -300,7 +239,7 @@ pub async fn load_story(
     .bind(story_id)
     .fetch_all(&state.db)
     .await
-    .map_err(|e| format!("Failed to load characters: {}", e))?;
+    .map_err(|e| AppError::database(format!("Failed to load characters: {}", e)))?;
 
     let characters: Vec<CharacterProfile> = char_rows
         .iter()
@@ -335,54 +274,17 @@ pub async fn load_story(
     let mut total_turns: usize = compressed_history.compressed_turn_ids.len();
 
     if let Some(cid) = chat_id {
-        let msg_rows = sqlx::query(
-            "SELECT m.id, m.role, m.content, i.file_path as image_path
-             FROM messages m
-             LEFT JOIN images i ON i.message_id = m.id
-             WHERE m.chat_id = ?
-             ORDER BY m.timestamp ASC"
-        )
-        .bind(cid)
-        .fetch_all(&state.db)
-        .await
-        .map_err(|e| format!("Failed to load messages: {}", e))?;
-
-        // Build turns via existing context logic (uses role+content only)
-        let rows: Vec<(String, String)> = msg_rows
-            .iter()
-            .map(|r| (r.get::<String, _>("role"), r.get::<String, _>("content")))
-            .collect();
-
-        let conversation = ConversationContext::from_db_rows(&rows);
-        recent_turns = conversation.turns;
+        // Turns carry their assistant message id and image path
+        recent_turns = load_story_turns(&state.db, cid).await?;
         total_turns += recent_turns.len();
 
-        // Pair up assistant message IDs and image paths per turn (same ordering as from_db_rows)
-        let mut turn_extras: Vec<(i64, Option<String>)> = Vec::new();
-        let mut i = 0;
-        while i < msg_rows.len() {
-            let role: String = msg_rows[i].get("role");
-            if role == "user" {
-                let next = i + 1;
-                if next < msg_rows.len() {
-                    let next_role: String = msg_rows[next].get("role");
-                    if next_role == "assistant" {
-                        let msg_id: i64 = msg_rows[next].get("id");
-                        let image_path: Option<String> = msg_rows[next].get("image_path");
-                        turn_extras.push((msg_id, image_path));
-                        i += 2;
-                        continue;
-                    }
-                }
-            }
-            i += 1;
-        }
-
-        // Enrich turns with their DB message_id and image_path
-        for (idx, turn) in recent_turns.iter_mut().enumerate() {
-            if let Some((msg_id, image_path)) = turn_extras.get(idx) {
-                turn.message_id = Some(*msg_id);
-                turn.image_path = image_path.clone();
+        // Summaries in context_summaries leave messages intact, so every turn
+        // is already in recent_turns. Legacy summaries (first_turn 0) replaced
+        // deleted turns and keep the count from compressed_history_json.
+        if let Some(summary) = load_latest_summary(&state.db, cid).await? {
+            if summary.first_turn > 0 {
+                compressed_history = summary.to_compressed_history();
+                total_turns = recent_turns.len();
             }
         }
     }
@@ -427,7 +329,7 @@ pub async fn save_story_state(
     current_location: Option<String>,
     thumbnail_path: Option<String>,
     state: State<'_, OllamaState>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     // Build dynamic UPDATE query based on what's provided
     let mut updates: Vec<String> = vec![
         "last_played_at = CURRENT_TIMESTAMP".to_string(),
@@ -465,7 +367,7 @@ pub async fn save_story_state(
     query
         .execute(&state.db)
         .await
-        .map_err(|e| format!("Failed to save story state: {}", e))?;
+        .map_err(|e| AppError::database(format!("Failed to save story state: {}", e)))?;
 
     println!(
         "[StoryManager] Auto-saved story {} (location={:?}, has_compression={}, has_thumb={})",
@@ -484,7 +386,7 @@ pub async fn save_story_state(
 pub async fn list_stories(
     content_rating_filter: Option<String>,
     state: State<'_, OllamaState>,
-) -> Result<Vec<StorySummary>, String> {
+) -> Result<Vec<StorySummary>, AppError> {
     let rating_clause = match content_rating_filter.as_deref() {
         Some("sfw") => " WHERE sp.content_rating = 'sfw'",
         _ => "",
@@ -504,6 +406,9 @@ pub async fn list_stories(
             COALESCE(
                 (SELECT COUNT(*) FROM messages m WHERE m.chat_id = sp.chat_id AND m.role = 'user'),
                 0
+            ) + COALESCE(
+                (SELECT b.shared_turns FROM story_branches b WHERE b.chat_id = sp.chat_id),
+                0
             ) AS turn_count
          FROM story_premises sp{}
          ORDER BY sp.last_played_at DESC",
@@ -512,7 +417,7 @@ pub async fn list_stories(
     let rows = sqlx::query(&sql)
     .fetch_all(&state.db)
     .await
-    .map_err(|e| format!("Failed to list stories: {}", e))?;
+    .map_err(|e| AppError::database(format!("Failed to list stories: {}", e)))?;
 
     let summaries: Vec<StorySummary> = rows
         .iter()
@@ -544,30 +449,191 @@ pub async fn update_story_rating(
     story_id: i64,
     content_rating: String,
     state: State<'_, OllamaState>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     sqlx::query("UPDATE story_premises SET content_rating = ? WHERE id = ?")
         .bind(&content_rating)
         .bind(story_id)
         .execute(&state.db)
         .await
-        .map_err(|e| format!("Failed to update story rating: {}", e))?;
+        .map_err(|e| AppError::database(format!("Failed to update story rating: {}", e)))?;
     Ok(())
 }
 
-/// Look up the story linked to a given chat_id (reverse lookup).
-/// Returns None if no story uses that chat.
+/// Load a story's model / sampler overrides. Missing story or NULL columns
+/// yield `None` fields, meaning "use the app setting".
+pub async fn load_generation_overrides(
+    pool: &sqlx::SqlitePool,
+    story_id: Option<i64>,
+) -> Result<GenerationOverrides, String> {
+    let story_id = match story_id {
+        Some(id) => id,
+        None => return Ok(GenerationOverrides::default()),
+    };
+    let row = sqlx::query(
+        "SELECT model, temperature, top_p, top_k, repeat_penalty, num_ctx, num_predict \
+         FROM story_premises WHERE id = ?"
+    )
+    .bind(story_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| format!("Failed to load generation overrides: {}", e))?;
+
+    Ok(row.map(|r| GenerationOverrides {
+        model: r.get("model"),
+        temperature: r.get("temperature"),
+        top_p: r.get("top_p"),
+        top_k: r.get::<Option<i64>, _>("top_k").map(|v| v as u32),
+        repeat_penalty: r.get("repeat_penalty"),
+        num_ctx: r.get::<Option<i64>, _>("num_ctx").map(|v| v as u32),
+        num_predict: r.get::<Option<i64>, _>("num_predict").map(|v| v as u32),
+    }).unwrap_or_default())
+}
+
+/// Get a story's model / sampler overrides.
+#[tauri::command]
+pub async fn get_story_generation_settings(
+    story_id: i64,
+    state: State<'_, OllamaState>,
+) -> Result<GenerationOverrides, AppError> {
+    Ok(load_generation_overrides(&state.db, Some(story_id)).await?)
+}
+
+/// Replace a story's model / sampler overrides. Pass `null` for any field to
+/// fall back to the app-wide setting.
+#[tauri::command]
+pub async fn update_story_generation_settings(
+    story_id: i64,
+    overrides: GenerationOverrides,
+    state: State<'_, OllamaState>,
+) -> Result<(), AppError> {
+    let model = overrides.model.as_ref()
+        .map(|m| m.trim().to_string())
+        .filter(|m| !m.is_empty());
+    if let Some(n) = overrides.num_ctx {
+        if n < MIN_NUM_CTX {
+            return Err(AppError::invalid_input(format!("num_ctx must be at least {}", MIN_NUM_CTX)));
+        }
+    }
+
+    sqlx::query(
+        "UPDATE story_premises SET model = ?, temperature = ?, top_p = ?, top_k = ?, \
+         repeat_penalty = ?, num_ctx = ?, num_predict = ? WHERE id = ?"
+    )
+    .bind(model)
+    .bind(overrides.temperature)
+    .bind(overrides.top_p)
+    .bind(overrides.top_k.map(|v| v as i64))
+    .bind(overrides.repeat_penalty)
+    .bind(overrides.num_ctx.map(|v| v as i64))
+    .bind(overrides.num_predict.map(|v| v as i64))
+    .bind(story_id)
+    .execute(&state.db)
+    .await
+    .map_err(|e| AppError::database(format!("Failed to update generation settings: {}", e)))?;
+
+    println!("[StoryManager] Updated generation overrides for story {}", story_id);
+    Ok(())
+}
+
+/// Load a story's bible (title, premise, genre, tone, setting, themes, rules).
+/// Returns None if the story doesn't exist.
+pub async fn load_story_bible(
+    pool: &sqlx::SqlitePool,
+    story_id: i64,
+) -> Result<Option<StoryBible>, String> {
+    let row = sqlx::query(
+        "SELECT title, description, genre, tone, setting, themes, hard_rules \
+         FROM story_premises WHERE id = ?"
+    )
+    .bind(story_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| format!("Failed to load story bible: {}", e))?;
+
+    let list = |json: Option<String>| -> Vec<String> {
+        json.and_then(|j| serde_json::from_str(&j).ok()).unwrap_or_default()
+    };
+    Ok(row.map(|r| StoryBible {
+        title: r.get("title"),
+        premise: r.get("description"),
+        genre: r.get("genre"),
+        tone: r.get("tone"),
+        setting: r.get("setting"),
+        themes: list(r.get("themes")),
+        hard_rules: list(r.get("hard_rules")),
+    }))
+}
+
+/// Get a story's bible.
+#[tauri::command]
+pub async fn get_story_bible(
+    story_id: i64,
+    state: State<'_, OllamaState>,
+) -> Result<StoryBible, AppError> {
+    load_story_bible(&state.db, story_id)
+        .await?
+        .ok_or_else(|| AppError::not_found(format!("Story {} not found", story_id)))
+}
+
+/// Replace a story's bible. The title and premise are the story's own
+/// title/description; blank optional fields are stored as NULL and blank
+/// list items are dropped.
+#[tauri::command]
+pub async fn update_story_bible(
+    story_id: i64,
+    bible: StoryBible,
+    state: State<'_, OllamaState>,
+) -> Result<(), AppError> {
+    let title = bible.title.trim();
+    if title.is_empty() {
+        return Err(AppError::invalid_input("Story title cannot be empty"));
+    }
+    if bible.premise.trim().is_empty() {
+        return Err(AppError::invalid_input("Story premise cannot be empty"));
+    }
+    let text = |v: &Option<String>| v.as_deref().map(str::trim).filter(|v| !v.is_empty()).map(String::from);
+    let list = |items: &[String]| -> Result<String, String> {
+        let items: Vec<&str> = items.iter().map(|i| i.trim()).filter(|i| !i.is_empty()).collect();
+        serde_json::to_string(&items).map_err(|e| e.to_string())
+    };
+
+    let result = sqlx::query(
+        "UPDATE story_premises SET title = ?, description = ?, genre = ?, tone = ?, setting = ?, \
+         themes = ?, hard_rules = ? WHERE id = ?"
+    )
+    .bind(title)
+    .bind(bible.premise.trim())
+    .bind(text(&bible.genre))
+    .bind(text(&bible.tone))
+    .bind(text(&bible.setting))
+    .bind(list(&bible.themes)?)
+    .bind(list(&bible.hard_rules)?)
+    .bind(story_id)
+    .execute(&state.db)
+    .await
+    .map_err(|e| AppError::database(format!("Failed to update story bible: {}", e)))?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::not_found(format!("Story {} not found", story_id)));
+    }
+    println!("[StoryManager] Updated story bible for story {}", story_id);
+    Ok(())
+}
+
+/// Look up the story linked to a given chat_id (reverse lookup), including
+/// inactive branch timelines. Returns None if no story uses that chat.
 #[tauri::command]
 pub async fn get_story_for_chat(
     chat_id: i64,
     state: State<'_, OllamaState>,
-) -> Result<Option<crate::models::StoryPremise>, String> {
+) -> Result<Option<crate::models::StoryPremise>, AppError> {
     let row = sqlx::query(
-        "SELECT id, title, description FROM story_premises WHERE chat_id = ?"
+        "SELECT id, title, description FROM story_premises
+         WHERE chat_id = ?1 OR id = (SELECT story_id FROM story_branches WHERE chat_id = ?1)"
     )
     .bind(chat_id)
     .fetch_optional(&state.db)
-    .await
-    .map_err(|e| e.to_string())?;
+    .await?;
 
     Ok(row.map(|r| crate::models::StoryPremise {
         id: r.get::<i64, _>("id"),
@@ -583,7 +649,7 @@ pub async fn delete_story(
     story_id: i64,
     state: State<'_, OllamaState>,
     app: AppHandle,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     // 1. Get the chat_id before deleting
     let story_row = sqlx::query(
         "SELECT chat_id, thumbnail_path FROM story_premises WHERE id = ?"
@@ -591,23 +657,38 @@ pub async fn delete_story(
     .bind(story_id)
     .fetch_optional(&state.db)
     .await
-    .map_err(|e| format!("DB error: {}", e))?;
+    .map_err(|e| AppError::database(format!("DB error: {}", e)))?;
 
     if let Some(row) = story_row {
         let chat_id: Option<i64> = row.get("chat_id");
         let thumbnail: Option<String> = row.get("thumbnail_path");
 
+        // Every timeline of the story: the active chat plus all branch chats
+        let mut chat_ids: Vec<i64> = sqlx::query("SELECT chat_id FROM story_branches WHERE story_id = ?")
+            .bind(story_id)
+            .fetch_all(&state.db)
+            .await
+            .map_err(|e| AppError::database(format!("Failed to query story branches: {}", e)))?
+            .iter()
+            .map(|r| r.get("chat_id"))
+            .collect();
+        if let Some(cid) = chat_id {
+            if !chat_ids.contains(&cid) {
+                chat_ids.push(cid);
+            }
+        }
+
         // 2. Collect image file paths to clean up from disk
         let mut image_paths: Vec<String> = Vec::new();
 
-        if let Some(cid) = chat_id {
+        for &cid in &chat_ids {
             let img_rows = sqlx::query(
                 "SELECT file_path FROM images WHERE chat_id = ?"
             )
             .bind(cid)
             .fetch_all(&state.db)
             .await
-            .map_err(|e| format!("Failed to query images: {}", e))?;
+            .map_err(|e| AppError::database(format!("Failed to query images: {}", e)))?;
 
             for img_row in &img_rows {
                 let path: String = img_row.get("file_path");
@@ -626,7 +707,7 @@ pub async fn delete_story(
         .bind(story_id)
         .fetch_all(&state.db)
         .await
-        .map_err(|e| format!("Failed to query character images: {}", e))?;
+        .map_err(|e| AppError::database(format!("Failed to query character images: {}", e)))?;
 
         for row in &char_img_rows {
             let path: String = row.get("master_image_path");
@@ -639,13 +720,31 @@ pub async fn delete_story(
 
         // 3. Delete database records (order matters for foreign keys)
         // Delete images first
-        if let Some(cid) = chat_id {
+        for &cid in &chat_ids {
             sqlx::query("DELETE FROM images WHERE chat_id = ?")
                 .bind(cid)
                 .execute(&state.db)
                 .await
                 .ok();
 
+            sqlx::query("DELETE FROM context_summaries WHERE chat_id = ?")
+                .bind(cid)
+                .execute(&state.db)
+                .await
+                .ok();
+
+            sqlx::query("DELETE FROM story_memory WHERE chat_id = ?")
+                .bind(cid)
+                .execute(&state.db)
+                .await
+                .ok();
+
+            sqlx::query("DELETE FROM turn_embeddings WHERE chat_id = ?")
+                .bind(cid)
+                .execute(&state.db)
+                .await
+                .ok();
+
             sqlx::query("DELETE FROM messages WHERE chat_id = ?")
                 .bind(cid)
                 .execute(&state.db)
@@ -659,15 +758,16 @@ pub async fn delete_story(
                 .ok();
         }
 
-        // Note: story_characters junction rows are removed by ON DELETE CASCADE
-        // when the story_premise is deleted. Characters themselves are NOT deleted.
+        // Note: story_characters junction rows and story-scoped lore_entries are
+        // removed by ON DELETE CASCADE when the story_premise is deleted.
+        // Characters and global lore entries are NOT deleted.
 
         // Delete the story premise itself
         sqlx::query("DELETE FROM story_premises WHERE id = ?")
             .bind(story_id)
             .execute(&state.db)
             .await
-            .map_err(|e| format!("Failed to delete story: {}", e))?;
+            .map_err(|e| AppError::database(format!("Failed to delete story: {}", e)))?;
 
         // 4. Clean up image files from disk (best-effort)
         for path in &image_paths {
@@ -680,7 +780,7 @@ pub async fn delete_story(
             image_paths.len()
         );
     } else {
-        return Err(format!("Story {} not found", story_id));
+        return Err(AppError::not_found(format!("Story {} not found", story_id)));
     }
 
     Ok(())
@@ -693,8 +793,9 @@ pub async fn export_story(
     format: ExportFormat,
     state: State<'_, OllamaState>,
     app: AppHandle,
-) -> Result<String, String> {
+) -> Result<String, AppError> {
     let session = load_story_internal(&state.db, story_id).await?;
+    let bible = load_story_bible(&state.db, story_id).await?.unwrap_or_default();
 
     let now = {
         let duration = std::time::SystemTime::now()
@@ -716,6 +817,7 @@ pub async fn export_story(
                     current_location: session.current_location.clone(),
                     exported_at: now.clone(),
                 },
+                bible: bible.clone(),
                 characters: session.characters.clone(),
                 compressed_history: session.compressed_history.clone(),
                 turns: session
@@ -725,7 +827,7 @@ pub async fn export_story(
                     .map(|(i, t)| ExportedTurn {
                         turn_number: i + 1,
                         user_input: t.user_input.clone(),
-                        story_text: t.assistant_response.clone(),
+                        story_text: t.story_text.clone(),
                         summary_hint: t.summary_hint.clone(),
                         timestamp: String::new(),
                         image_path: None,
@@ -782,6 +884,21 @@ pub async fn export_story(
                 session.description, session.total_turns, now
             ));
 
+            // Story bible (only the fields that are set)
+            let mut bible_items: Vec<String> = [("Genre", &bible.genre), ("Tone", &bible.tone), ("Setting", &bible.setting)]
+                .iter()
+                .filter_map(|(label, value)| value.as_ref().map(|v| format!("<li><b>{}:</b> {}</li>", label, v)))
+                .collect();
+            if !bible.themes.is_empty() {
+                bible_items.push(format!("<li><b>Themes:</b> {}</li>", bible.themes.join(", ")));
+            }
+            for rule in &bible.hard_rules {
+                bible_items.push(format!("<li><b>Rule:</b> {}</li>", rule));
+            }
+            if !bible_items.is_empty() {
+                html.push_str(&format!("<ul class='meta'>{}</ul>", bible_items.join("")));
+            }
+
             for (i, turn) in session.recent_turns.iter().enumerate() {
                 html.push_str("<div class='turn'>");
                 html.push_str(&format!(
@@ -791,7 +908,7 @@ pub async fn export_story(
                 ));
                 html.push_str(&format!(
                     "<div class='story-text'>{}</div>",
-                    turn.assistant_response
+                    turn.story_text
                 ));
                 html.push_str("</div>");
             }
@@ -837,7 +954,7 @@ pub async fn get_story_images(
     story_id: i64,
     chat_id: Option<i64>,
     state: State<'_, OllamaState>,
-) -> Result<Vec<StoryImage>, String> {
+) -> Result<Vec<StoryImage>, AppError> {
     println!("[StoryManager] get_story_images: story_id={}, chat_id={:?}", story_id, chat_id);
 
     // Resolve the effective chat_id: prefer the story's linked chat, fall back to provided
@@ -847,7 +964,7 @@ pub async fn get_story_images(
     .bind(story_id)
     .fetch_optional(&state.db)
     .await
-    .map_err(|e| format!("DB error: {}", e))?
+    .map_err(|e| AppError::database(format!("DB error: {}", e)))?
     .and_then(|row| row.get("chat_id"));
 
     let effective_chat_id = story_chat_id.or(chat_id);
@@ -859,23 +976,27 @@ pub async fn get_story_images(
 
     println!("[StoryManager] Querying images for chat_id={}", cid);
 
-    let rows = sqlx::query(
-        "SELECT i.id, i.file_path, i.message_id, m.content, m.timestamp
+    let rows = sqlx::query(&format!(
+        "{}SELECT i.id, i.file_path, i.message_id, m.timestamp, t.summary_hint, t.story_text
          FROM images i
-         INNER JOIN messages m ON m.id = i.message_id
-         WHERE i.chat_id = ?
-         ORDER BY m.timestamp ASC"
-    )
+         INNER JOIN messages m ON m.id = i.message_id {}
+         LEFT JOIN turns t ON t.assistant_message_id = m.id
+         WHERE m.is_selected = 1
+         ORDER BY m.id ASC",
+        LINEAGE_CTE, IN_LINEAGE
+    ))
     .bind(cid)
     .fetch_all(&state.db)
     .await
-    .map_err(|e| format!("Failed to fetch story images: {}", e))?;
+    .map_err(|e| AppError::database(format!("Failed to fetch story images: {}", e)))?;
 
     let images: Vec<StoryImage> = rows
         .iter()
         .map(|r| {
-            let content: String = r.get("content");
-            let caption = extract_caption_from_content(&content);
+            let caption = caption_for_turn(
+                &r.get::<Option<String>, _>("summary_hint").unwrap_or_default(),
+                &r.get::<Option<String>, _>("story_text").unwrap_or_default(),
+            );
             StoryImage {
                 id: r.get("id"),
                 file_path: r.get("file_path"),
@@ -890,28 +1011,14 @@ pub async fn get_story_images(
     Ok(images)
 }
 
-/// Extract a short caption from the raw assistant JSON content.
-fn extract_caption_from_content(content: &str) -> String {
-    if let Ok(v) = serde_json::from_str::<serde_json::Value>(content) {
-        if let Some(hint) = v.get("story_json")
-            .and_then(|sj| sj.get("summary_hint"))
-            .and_then(|h| h.as_str())
-        {
-            if !hint.is_empty() {
-                return hint.to_string();
-            }
-        }
-        if let Some(text) = v.get("story_json")
-            .and_then(|sj| sj.get("response"))
-            .and_then(|r| r.as_str())
-        {
-            if !text.is_empty() {
-                let truncated: String = text.chars().take(80).collect();
-                return if text.len() > 80 { format!("{}...", truncated) } else { truncated };
-            }
-        }
+/// A short caption for a turn's image: its summary hint, else the start of
+/// its story text.
+fn caption_for_turn(summary_hint: &str, story_text: &str) -> String {
+    if !summary_hint.is_empty() {
+        return summary_hint.to_string();
     }
-    String::new()
+    let truncated: String = story_text.chars().take(80).collect();
+    if story_text.len() > 80 { format!("{}...", truncated) } else { truncated }
 }
 
 // ============================================================================
@@ -932,7 +1039,7 @@ async fn load_story_internal(
     .fetch_optional(db)
     .await
     .map_err(|e| format!("DB error: {}", e))?
-    .ok_or_else(|| format!("Story {} not found", story_id))?;
+    .ok_or_else(|| AppError::not_found(format!("Story {} not found", story_id)))?;
 
     let title: String = story_row.get("title");
     let description: String = story_row.get("description");
@@ -943,7 +1050,7 @@ async fn load_story_internal(
     let chat_id: Option<i64> = story_row.get("chat_id");
     let content_rating: String = story_row.get::<Option<String>, _>("content_rating").unwrap_or_else(|| "sfw".to_string());
 
-    let compressed_history: CompressedHistory = compressed_json
+    let mut compressed_history: CompressedHistory = compressed_json
         .and_then(|json_str| serde_json::from_str(&json_str).ok())
         .unwrap_or_default();
 
@@ -994,51 +1101,13 @@ async fn load_story_internal(
     let mut total_turns: usize = compressed_history.compressed_turn_ids.len();
 
     if let Some(cid) = chat_id {
-        let msg_rows = sqlx::query(
-            "SELECT m.id, m.role, m.content, i.file_path as image_path
-             FROM messages m
-             LEFT JOIN images i ON i.message_id = m.id
-             WHERE m.chat_id = ?
-             ORDER BY m.timestamp ASC"
-        )
-        .bind(cid)
-        .fetch_all(db)
-        .await
-        .map_err(|e| format!("Failed to load messages: {}", e))?;
-
-        let rows: Vec<(String, String)> = msg_rows
-            .iter()
-            .map(|r| (r.get::<String, _>("role"), r.get::<String, _>("content")))
-            .collect();
-
-        let conversation = ConversationContext::from_db_rows(&rows);
-        recent_turns = conversation.turns;
+        recent_turns = load_story_turns(db, cid).await?;
         total_turns += recent_turns.len();
 
-        let mut turn_extras: Vec<(i64, Option<String>)> = Vec::new();
-        let mut i = 0;
-        while i < msg_rows.len() {
-            let role: String = msg_rows[i].get("role");
-            if role == "user" {
-                let next = i + 1;
-                if next < msg_rows.len() {
-                    let next_role: String = msg_rows[next].get("role");
-                    if next_role == "assistant" {
-                        let msg_id: i64 = msg_rows[next].get("id");
-                        let image_path: Option<String> = msg_rows[next].get("image_path");
-                        turn_extras.push((msg_id, image_path));
-                        i += 2;
-                        continue;
-                    }
-                }
-            }
-            i += 1;
-        }
-
-        for (idx, turn) in recent_turns.iter_mut().enumerate() {
-            if let Some((msg_id, image_path)) = turn_extras.get(idx) {
-                turn.message_id = Some(*msg_id);
-                turn.image_path = image_path.clone();
+        if let Some(summary) = load_latest_summary(db, cid).await? {
+            if summary.first_turn > 0 {
+                compressed_history = summary.to_compressed_history();
+                total_turns = recent_turns.len();
             }
         }
     }
@@ -1069,33 +1138,30 @@ pub async fn save_story_premise(
     description: String,
     id: Option<i64>,
     state: State<'_, OllamaState>,
-) -> Result<i64, String> {
+) -> Result<i64, AppError> {
     if let Some(existing_id) = id {
         sqlx::query("UPDATE story_premises SET title = ?, description = ? WHERE id = ?")
             .bind(&title)
             .bind(&description)
             .bind(existing_id)
             .execute(&state.db)
-            .await
-            .map_err(|e| e.to_string())?;
+            .await?;
         Ok(existing_id)
     } else {
         let result = sqlx::query("INSERT INTO story_premises (title, description) VALUES (?, ?)")
             .bind(&title)
             .bind(&description)
             .execute(&state.db)
-            .await
-            .map_err(|e| e.to_string())?;
+            .await?;
         Ok(result.last_insert_rowid())
     }
 }
 
 #[tauri::command]
-pub async fn get_story_list(state: State<'_, OllamaState>) -> Result<Vec<StoryPremise>, String> {
+pub async fn get_story_list(state: State<'_, OllamaState>) -> Result<Vec<StoryPremise>, AppError> {
     let rows = sqlx::query("SELECT id, title, description FROM story_premises ORDER BY title ASC")
         .fetch_all(&state.db)
-        .await
-        .map_err(|e| e.to_string())?;
+        .await?;
 
     let stories: Vec<StoryPremise> = rows
         .iter()
@@ -1110,13 +1176,12 @@ pub async fn get_story_list(state: State<'_, OllamaState>) -> Result<Vec<StoryPr
 }
 
 #[tauri::command]
-pub async fn delete_stories(ids: Vec<i64>, state: State<'_, OllamaState>) -> Result<(), String> {
+pub async fn delete_stories(ids: Vec<i64>, state: State<'_, OllamaState>) -> Result<(), AppError> {
     for id in ids {
         sqlx::query("DELETE FROM story_premises WHERE id = ?")
             .bind(id)
             .execute(&state.db)
-            .await
-            .map_err(|e| e.to_string())?;
+            .await?;
     }
     Ok(())
 }
\ No newline at end of file
@@ -1,8 +1,11 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
 use tauri::{AppHandle, Manager};
 
+use crate::text_gen::template::{PromptTemplate, TemplateFormat};
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct AppConfig {
     /// Path to the stable-diffusion-webui folder
@@ -47,6 +50,110 @@ pub struct AppConfig {
     /// Higher values = better turn-to-turn latency. Lower values = ComfyUI gets VRAM faster.
     #[serde(default = "default_keep_alive")]
     pub keep_alive: String,
+
+    /// Stream story prose to the frontend (`story-token` events) while Ollama
+    /// is still generating. Disable if a proxy in front of Ollama buffers NDJSON.
+    #[serde(default = "default_true")]
+    pub stream_responses: bool,
+
+    /// Text generation backend: "ollama" (native API) or "openai" for any
+    /// OpenAI-compatible server (llama.cpp server, vLLM, LM Studio, koboldcpp).
+    #[serde(default = "default_text_backend")]
+    pub text_backend: String,
+
+    /// Base URL of the OpenAI-compatible server (used when text_backend = "openai").
+    #[serde(default = "default_openai_api_url")]
+    pub openai_api_url: String,
+
+    /// Optional bearer token for the OpenAI-compatible server.
+    #[serde(default)]
+    pub openai_api_key: String,
+
+    /// Model used for story turns and summaries. Stories can override this.
+    #[serde(default = "default_story_model")]
+    pub story_model: String,
+
+    /// Sampling temperature for story turns.
+    #[serde(default = "default_temperature")]
+    pub temperature: f64,
+
+    /// Nucleus sampling cutoff. None = backend default.
+    #[serde(default)]
+    pub top_p: Option<f64>,
+
+    /// Top-k sampling cutoff. None = backend default.
+    #[serde(default)]
+    pub top_k: Option<u32>,
+
+    /// Repetition penalty. None = backend default.
+    #[serde(default)]
+    pub repeat_penalty: Option<f64>,
+
+    /// Context window in tokens. Context compression budgets scale with this.
+    #[serde(default = "default_num_ctx")]
+    pub num_ctx: u32,
+
+    /// Max tokens per response. None = derived from response_length.
+    #[serde(default)]
+    pub num_predict: Option<u32>,
+
+    /// Send the story-turn JSON schema so the backend constrains its output
+    /// (Ollama `format`, OpenAI `response_format`). Disable for servers that
+    /// reject schemas.
+    #[serde(default = "default_true")]
+    pub structured_output: bool,
+
+    /// Recall relevant past turns via embeddings (text_gen::recall).
+    #[serde(default = "default_true")]
+    pub recall_enabled: bool,
+
+    /// Ollama-compatible embedding server. Empty = use `ollama_url`.
+    #[serde(default)]
+    pub embedding_url: String,
+
+    #[serde(default = "default_embedding_model")]
+    pub embedding_model: String,
+
+    /// Past turns injected as "RELEVANT MEMORIES" per story turn.
+    #[serde(default = "default_recall_top_k")]
+    pub recall_top_k: usize,
+
+    /// Token budget for triggered lorebook entries per story turn.
+    #[serde(default = "default_lore_token_budget")]
+    pub lore_token_budget: usize,
+
+    /// Turn traces kept for debugging (text_gen::traces), newest first.
+    /// 0 disables tracing.
+    #[serde(default = "default_trace_retention")]
+    pub trace_retention: usize,
+
+    /// Hours between automatic database backups (backup.rs). 0 disables them.
+    #[serde(default = "default_backup_interval_hours")]
+    pub backup_interval_hours: u32,
+
+    /// Scheduled backups kept in app_data/backups; older ones are deleted.
+    /// Manual and safety backups are always kept. 0 keeps all.
+    #[serde(default = "default_backup_retention")]
+    pub backup_retention: usize,
+
+    /// Bundle generated images and portraits into backups (.zip).
+    #[serde(default)]
+    pub backup_include_media: bool,
+
+    /// Characters rendered with their own face reference and mask in a scene
+    /// image (1-9). Further characters are left out of the image.
+    #[serde(default = "default_max_scene_characters")]
+    pub max_scene_characters: usize,
+
+    /// Chat template per story model for raw prompts ("chatml", "llama3",
+    /// "mistral", "gemma", "alpaca", "custom"). Unlisted models are detected
+    /// from their name (text_gen::template).
+    #[serde(default)]
+    pub model_templates: HashMap<String, TemplateFormat>,
+
+    /// Template used by models mapped to "custom".
+    #[serde(default)]
+    pub custom_template: PromptTemplate,
 }
 
 fn default_content_rating() -> String {
@@ -69,6 +176,54 @@ fn default_keep_alive() -> String {
     "30m".to_string()
 }
 
+fn default_text_backend() -> String {
+    "ollama".to_string()
+}
+
+fn default_openai_api_url() -> String {
+    "http://127.0.0.1:8080".to_string()
+}
+
+fn default_story_model() -> String {
+    crate::text_gen::prompts::STORY_MODEL.to_string()
+}
+
+fn default_temperature() -> f64 {
+    crate::text_gen::prompts::DEFAULT_TEMPERATURE
+}
+
+fn default_num_ctx() -> u32 {
+    crate::text_gen::prompts::NUM_CTX
+}
+
+fn default_embedding_model() -> String {
+    "nomic-embed-text".to_string()
+}
+
+fn default_recall_top_k() -> usize {
+    4
+}
+
+fn default_lore_token_budget() -> usize {
+    600
+}
+
+fn default_trace_retention() -> usize {
+    200
+}
+
+fn default_backup_interval_hours() -> u32 {
+    24
+}
+
+fn default_backup_retention() -> usize {
+    10
+}
+
+fn default_max_scene_characters() -> usize {
+    4
+}
+
 impl Default for AppConfig {
     fn default() -> Self {
         Self {
@@ -84,6 +239,30 @@ impl Default for AppConfig {
             controlnet_pose_strength: 0.85,
             controlnet_pose_enabled: true,
             keep_alive: default_keep_alive(),
+            stream_responses: true,
+            text_backend: default_text_backend(),
+            openai_api_url: default_openai_api_url(),
+            openai_api_key: String::new(),
+            story_model: default_story_model(),
+            temperature: default_temperature(),
+            top_p: None,
+            top_k: None,
+            repeat_penalty: None,
+            num_ctx: default_num_ctx(),
+            num_predict: None,
+            structured_output: true,
+            recall_enabled: true,
+            embedding_url: String::new(),
+            embedding_model: default_embedding_model(),
+            recall_top_k: default_recall_top_k(),
+            lore_token_budget: default_lore_token_budget(),
+            trace_retention: default_trace_retention(),
+            backup_interval_hours: default_backup_interval_hours(),
+            backup_retention: default_backup_retention(),
+            backup_include_media: false,
+            max_scene_characters: default_max_scene_characters(),
+            model_templates: HashMap::new(),
+            custom_template: PromptTemplate::default(),
         }
     }
 }
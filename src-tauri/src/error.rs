@@ -0,0 +1,296 @@
+// src-tauri/src/error.rs
+//
+// Typed Command Errors
+// ====================
+// `AppError` is what the story, image, portrait, setup and database commands
+// return instead of a bare String. It serializes to
+//
+//   { kind: "model_not_found", message: "...", retryable: false,
+//     remediation: "Pull the model with `ollama pull ...`" }
+//
+// so the frontend can tell "Ollama not running" from "model not pulled" and
+// offer a fix instead of showing the raw message.
+//
+// Internal helpers keep returning `Result<_, String>`: `?` converts a String
+// into `ErrorKind::Other` (or `Cancelled` for `CANCELLED_ERROR`), and an
+// `AppError` back into its message. Errors are typed where the cause is
+// known — the text backends, the ComfyUI client, workflow selection and
+// database queries.
+
+use serde::{Deserialize, Serialize};
+
+use crate::cancellation::CANCELLED_ERROR;
+use crate::image_gen::comfyui::{ComfyError, MissingRequirement};
+
+// ============================================================================
+// TYPES
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// The text backend (Ollama or OpenAI-compatible server) can't be reached
+    BackendUnavailable,
+    /// The configured model isn't installed on the text backend
+    ModelNotFound,
+    /// The prompt doesn't fit the model's context window
+    ContextOverflow,
+    /// The text backend returned an error or unusable output
+    GenerationFailed,
+    ComfyUnavailable,
+    /// The workflow needs a custom node ComfyUI doesn't have (e.g. IPAdapter)
+    ComfyMissingNode,
+    WorkflowNotFound,
+    InvalidWorkflow,
+    ImageGenerationFailed,
+    Timeout,
+    /// Aborted by the user (see crate::cancellation)
+    Cancelled,
+    Database,
+    Io,
+    NotFound,
+    InvalidInput,
+    /// Downloading or installing a service failed (see services::setup)
+    SetupFailed,
+    Other,
+}
+
+impl ErrorKind {
+    /// Whether trying the same request again may succeed.
+    pub fn retryable(self) -> bool {
+        matches!(
+            self,
+            Self::BackendUnavailable
+                | Self::GenerationFailed
+                | Self::ComfyUnavailable
+                | Self::ImageGenerationFailed
+                | Self::Timeout
+                | Self::SetupFailed
+        )
+    }
+
+    /// Default fix shown to the user. `None` when there's nothing to suggest.
+    pub fn remediation(self) -> Option<&'static str> {
+        match self {
+            Self::BackendUnavailable => Some("Start Ollama (or your LLM server) and check its URL in Settings."),
+            Self::ModelNotFound => Some("Pull the model in Ollama or pick an installed model in Settings."),
+            Self::ContextOverflow => Some("Lower the response length or raise the context size (num_ctx) in Settings."),
+            Self::GenerationFailed => Some("Try again. If it keeps failing, check the LLM server's log."),
+            Self::ComfyUnavailable => Some("Start ComfyUI from the Services panel or check the ComfyUI URL in Settings."),
+            Self::ComfyMissingNode => Some("Install the missing custom node in ComfyUI (e.g. with ComfyUI-Manager) and restart it."),
+            Self::WorkflowNotFound => Some("Check the custom workflow's path, or remove it from the workflows folder to use the built-in one."),
+            Self::InvalidWorkflow => Some("Re-export the workflow from ComfyUI in API format."),
+            Self::ImageGenerationFailed => Some("Try again. If it keeps failing, check the ComfyUI console."),
+            Self::Timeout => Some("Try again — the first run after a model load is often slow."),
+            Self::SetupFailed => Some("Check your internet connection and free disk space, then retry setup."),
+            Self::Cancelled
+            | Self::Database
+            | Self::Io
+            | Self::NotFound
+            | Self::InvalidInput
+            | Self::Other => None,
+        }
+    }
+}
+
+/// A command error: what went wrong, whether retrying can help and how to
+/// fix it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AppError {
+    pub kind: ErrorKind,
+    pub message: String,
+    pub retryable: bool,
+    pub remediation: Option<String>,
+}
+
+impl AppError {
+    /// An error with the kind's default retry flag and remediation.
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            retryable: kind.retryable(),
+            remediation: kind.remediation().map(String::from),
+        }
+    }
+
+    pub fn with_remediation(mut self, remediation: impl Into<String>) -> Self {
+        self.remediation = Some(remediation.into());
+        self
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::NotFound, message)
+    }
+
+    pub fn invalid_input(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::InvalidInput, message)
+    }
+
+    pub fn database(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Database, message)
+    }
+}
+
+impl std::fmt::Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for AppError {}
+
+// ============================================================================
+// CONVERSIONS
+// ============================================================================
+
+impl From<String> for AppError {
+    fn from(message: String) -> Self {
+        if message == CANCELLED_ERROR {
+            Self::new(ErrorKind::Cancelled, message)
+        } else {
+            Self::new(ErrorKind::Other, message)
+        }
+    }
+}
+
+impl From<&str> for AppError {
+    fn from(message: &str) -> Self {
+        Self::from(message.to_string())
+    }
+}
+
+impl From<AppError> for String {
+    fn from(e: AppError) -> String {
+        e.message
+    }
+}
+
+impl From<sqlx::Error> for AppError {
+    fn from(e: sqlx::Error) -> Self {
+        match e {
+            sqlx::Error::RowNotFound => Self::not_found("Record not found"),
+            e => Self::database(format!("Database error: {}", e)),
+        }
+    }
+}
+
+impl From<ComfyError> for AppError {
+    fn from(e: ComfyError) -> Self {
+        if let ComfyError::MissingRequirements(missing) = &e {
+            return missing_requirements_error(missing, e.to_string());
+        }
+        let kind = match &e {
+            ComfyError::NotRunning(_) => ErrorKind::ComfyUnavailable,
+            ComfyError::MissingNode(_) | ComfyError::MissingRequirements(_) => ErrorKind::ComfyMissingNode,
+            ComfyError::WorkflowNotFound(_) => ErrorKind::WorkflowNotFound,
+            ComfyError::WorkflowLoadFailed(_) => ErrorKind::InvalidWorkflow,
+            ComfyError::Timeout(_) => ErrorKind::Timeout,
+            ComfyError::IoError(_) => ErrorKind::Io,
+            ComfyError::Cancelled => ErrorKind::Cancelled,
+            ComfyError::UploadFailed(_)
+            | ComfyError::QueueFailed(_)
+            | ComfyError::PollFailed(_)
+            | ComfyError::GenerationFailed(_)
+            | ComfyError::DownloadFailed(_) => ErrorKind::ImageGenerationFailed,
+        };
+        Self::new(kind, e.to_string())
+    }
+}
+
+/// Point the user at the setup installers for missing node packs and models,
+/// falling back to the default remediation for ones setup doesn't install.
+fn missing_requirements_error(missing: &[MissingRequirement], message: String) -> AppError {
+    let error = AppError::new(ErrorKind::ComfyMissingNode, message);
+    if missing.iter().any(|m| m.installer.is_none()) {
+        return error;
+    }
+    let mut installers: Vec<&str> = missing.iter().filter_map(|m| m.installer.as_deref()).collect();
+    installers.sort_unstable();
+    installers.dedup();
+    error.with_remediation(format!(
+        "Install them from Setup ({}), then restart ComfyUI.",
+        installers.join(", ")
+    ))
+}
+
+/// Classify a text backend's HTTP error response. `server` names the backend
+/// in the message ("Ollama", "LLM server").
+pub fn backend_http_error(server: &str, status: reqwest::StatusCode, body: &str) -> AppError {
+    let message = format!("{} returned {}: {}", server, status, body);
+    let lower = body.to_lowercase();
+
+    if lower.contains("context") && (lower.contains("exceed") || lower.contains("too long") || lower.contains("maximum")) {
+        AppError::new(ErrorKind::ContextOverflow, message)
+    } else if status == reqwest::StatusCode::NOT_FOUND
+        || lower.contains("model_not_found")
+        || (lower.contains("model") && (lower.contains("not found") || lower.contains("does not exist")))
+    {
+        AppError::new(ErrorKind::ModelNotFound, message)
+    } else {
+        AppError::new(ErrorKind::GenerationFailed, message)
+    }
+}
+
+/// Classify a failed request to the text backend (no HTTP response).
+pub fn backend_request_error(server: &str, e: &reqwest::Error) -> AppError {
+    let message = format!("{} request failed: {}", server, e);
+    if e.is_timeout() {
+        AppError::new(ErrorKind::Timeout, message)
+    } else if e.is_connect() {
+        AppError::new(ErrorKind::BackendUnavailable, message)
+    } else {
+        AppError::new(ErrorKind::GenerationFailed, message)
+    }
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_string_conversion() {
+        let err = AppError::from(CANCELLED_ERROR.to_string());
+        assert_eq!(err.kind, ErrorKind::Cancelled);
+        assert!(!err.retryable);
+
+        let err = AppError::from("Something broke");
+        assert_eq!(err.kind, ErrorKind::Other);
+        assert_eq!(String::from(err), "Something broke");
+    }
+
+    #[test]
+    fn test_backend_http_error_kinds() {
+        let not_pulled = backend_http_error(
+            "Ollama",
+            reqwest::StatusCode::NOT_FOUND,
+            r#"{"error":"model \"mistral-nemo\" not found, try pulling it first"}"#,
+        );
+        assert_eq!(not_pulled.kind, ErrorKind::ModelNotFound);
+        assert!(not_pulled.remediation.is_some());
+
+        let overflow = backend_http_error(
+            "LLM server",
+            reqwest::StatusCode::BAD_REQUEST,
+            "the request exceeds the available context size, try increasing it",
+        );
+        assert_eq!(overflow.kind, ErrorKind::ContextOverflow);
+        assert!(!overflow.retryable);
+
+        let other = backend_http_error("Ollama", reqwest::StatusCode::INTERNAL_SERVER_ERROR, "out of memory");
+        assert_eq!(other.kind, ErrorKind::GenerationFailed);
+        assert!(other.retryable);
+    }
+
+    #[test]
+    fn test_serialized_shape() {
+        let json = serde_json::to_value(AppError::new(ErrorKind::ComfyMissingNode, "missing IPAdapter")).unwrap();
+        assert_eq!(json["kind"], "comfy_missing_node");
+        assert_eq!(json["retryable"], false);
+        assert!(json["remediation"].is_string());
+    }
+}
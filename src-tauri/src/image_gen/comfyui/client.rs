@@ -6,14 +6,18 @@
 
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
 use std::time::Duration;
 
+use super::requirements::MissingRequirement;
+
 // ============================================================================
 // CONFIGURATION
 // ============================================================================
 
-pub(super) const DEFAULT_COMFYUI_URL: &str = "http://127.0.0.1:8188";
+pub const DEFAULT_COMFYUI_URL: &str = "http://127.0.0.1:8188";
 const HEALTH_CHECK_TIMEOUT_SECS: u64 = 3;
 const POLL_INTERVAL_MS: u64 = 1000;
 pub(super) const DEFAULT_GENERATION_TIMEOUT_SECS: u64 = 180;
@@ -34,15 +38,21 @@ pub struct ComfyUIStatus {
     pub system_stats: Option<Value>,
 }
 
-/// Progress info for a running job.
+/// Progress info for a running job, tracked from the WebSocket (progress.rs).
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JobProgress {
     pub prompt_id: String,
     pub status: JobStatus,
+    /// Fraction of the current sampler's steps done (0.0 - 1.0)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub progress: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub current_node: Option<String>,
+    /// Sampler step, e.g. 12 of 30. Resets for the hires fix pass.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub step: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -77,12 +87,20 @@ pub enum ComfyError {
     NotRunning(String),
     UploadFailed(String),
     QueueFailed(String),
+    /// The workflow uses a node type ComfyUI doesn't have installed.
+    MissingNode(String),
+    /// Found before queueing: node packs and model files the workflow needs
+    /// that ComfyUI's /object_info doesn't list.
+    MissingRequirements(Vec<MissingRequirement>),
     PollFailed(String),
     GenerationFailed(String),
     Timeout(String),
     DownloadFailed(String),
+    WorkflowNotFound(String),
     WorkflowLoadFailed(String),
     IoError(String),
+    /// The job was cancelled by the user (see crate::cancellation).
+    Cancelled,
 }
 
 impl std::fmt::Display for ComfyError {
@@ -91,12 +109,19 @@ impl std::fmt::Display for ComfyError {
             Self::NotRunning(msg) => write!(f, "ComfyUI not running: {}", msg),
             Self::UploadFailed(msg) => write!(f, "Image upload failed: {}", msg),
             Self::QueueFailed(msg) => write!(f, "Failed to queue prompt: {}", msg),
+            Self::MissingNode(msg) => write!(f, "ComfyUI is missing a node: {}", msg),
+            Self::MissingRequirements(missing) => {
+                let names: Vec<String> = missing.iter().map(|m| m.to_string()).collect();
+                write!(f, "ComfyUI is missing {}", names.join(", "))
+            }
             Self::PollFailed(msg) => write!(f, "Failed to poll status: {}", msg),
             Self::GenerationFailed(msg) => write!(f, "Generation failed: {}", msg),
             Self::Timeout(msg) => write!(f, "Generation timed out: {}", msg),
             Self::DownloadFailed(msg) => write!(f, "Image download failed: {}", msg),
+            Self::WorkflowNotFound(msg) => write!(f, "Workflow file not found: {}", msg),
             Self::WorkflowLoadFailed(msg) => write!(f, "Workflow load failed: {}", msg),
             Self::IoError(msg) => write!(f, "IO error: {}", msg),
+            Self::Cancelled => write!(f, "{}", crate::cancellation::CANCELLED_ERROR),
         }
     }
 }
@@ -152,6 +177,51 @@ pub async fn check_comfyui_health(base_url: &str) -> ComfyUIStatus {
     }
 }
 
+/// /object_info responses by base URL. The node list only changes when
+/// ComfyUI restarts, and the response is several MB.
+fn object_info_cache() -> &'static Mutex<HashMap<String, Arc<Value>>> {
+    static CACHE: OnceLock<Mutex<HashMap<String, Arc<Value>>>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Fetch ComfyUI's node definitions from /object_info: every node class with
+/// its inputs, including the options of enumerated inputs such as the model
+/// files in `ckpt_name`. Cached per URL unless `refresh` is set.
+pub async fn fetch_object_info(base_url: &str, refresh: bool) -> Result<Arc<Value>, ComfyError> {
+    if !refresh {
+        if let Some(cached) = object_info_cache().lock().ok().and_then(|c| c.get(base_url).cloned()) {
+            return Ok(cached);
+        }
+    }
+
+    let client = comfy_client(30);
+    let url = format!("{}/object_info", base_url);
+    let resp = client.get(&url).send().await.map_err(|e| {
+        if e.is_connect() {
+            ComfyError::NotRunning(format!("Cannot connect to {}: {}", base_url, e))
+        } else {
+            ComfyError::QueueFailed(format!("GET /object_info failed: {}", e))
+        }
+    })?;
+
+    if !resp.status().is_success() {
+        return Err(ComfyError::QueueFailed(format!(
+            "/object_info returned status {}",
+            resp.status()
+        )));
+    }
+
+    let info: Value = resp
+        .json()
+        .await
+        .map_err(|e| ComfyError::QueueFailed(format!("Invalid /object_info response: {}", e)))?;
+    let info = Arc::new(info);
+    if let Ok(mut cache) = object_info_cache().lock() {
+        cache.insert(base_url.to_string(), info.clone());
+    }
+    Ok(info)
+}
+
 /// Upload an image file to ComfyUI's /upload/image endpoint.
 /// Returns the filename as stored by ComfyUI.
 pub async fn upload_image_to_comfyui(
@@ -202,25 +272,40 @@ pub async fn upload_image_to_comfyui(
 }
 
 /// Queue a workflow prompt for execution.
-/// Returns the prompt_id on success.
-pub async fn queue_prompt(base_url: &str, workflow: &Value) -> Result<String, ComfyError> {
+/// Returns the prompt_id on success. Progress events for the prompt go to the
+/// WebSocket connected with `client_id` (see progress.rs).
+pub async fn queue_prompt(base_url: &str, workflow: &Value, client_id: Option<&str>) -> Result<String, ComfyError> {
     let client = comfy_client(15);
     let url = format!("{}/prompt", base_url);
 
-    let payload = json!({
+    let mut payload = json!({
         "prompt": workflow
     });
+    if let Some(client_id) = client_id {
+        payload["client_id"] = json!(client_id);
+    }
 
     let resp = client
         .post(&url)
         .json(&payload)
         .send()
         .await
-        .map_err(|e| ComfyError::QueueFailed(format!("POST /prompt failed: {}", e)))?;
+        .map_err(|e| {
+            if e.is_connect() {
+                ComfyError::NotRunning(format!("Cannot connect to {}: {}", base_url, e))
+            } else {
+                ComfyError::QueueFailed(format!("POST /prompt failed: {}", e))
+            }
+        })?;
 
     if !resp.status().is_success() {
         let status = resp.status();
         let body = resp.text().await.unwrap_or_default();
+        // Validation errors name unknown node types, e.g. an IPAdapter
+        // workflow on an install without ComfyUI_IPAdapter_plus
+        if body.contains("missing_node_type") || body.contains("does not exist") {
+            return Err(ComfyError::MissingNode(body));
+        }
         return Err(ComfyError::QueueFailed(format!(
             "Queue returned {}: {}",
             status, body
@@ -332,6 +417,35 @@ pub async fn poll_for_completion(
     }
 }
 
+/// Stop a queued or running prompt.
+///
+/// Interrupts execution (newer ComfyUI builds scope /interrupt to the given
+/// prompt_id) and deletes the prompt from the pending queue. Best-effort:
+/// failures are logged, not returned.
+pub async fn cancel_prompt(base_url: &str, prompt_id: &str) {
+    let client = comfy_client(5);
+
+    match client
+        .post(format!("{}/interrupt", base_url))
+        .json(&json!({ "prompt_id": prompt_id }))
+        .send()
+        .await
+    {
+        Ok(_) => println!("[ComfyUI] Interrupted prompt {}", prompt_id),
+        Err(e) => println!("[ComfyUI] Interrupt failed for {}: {}", prompt_id, e),
+    }
+
+    match client
+        .post(format!("{}/queue", base_url))
+        .json(&json!({ "delete": [prompt_id] }))
+        .send()
+        .await
+    {
+        Ok(_) => println!("[ComfyUI] Removed prompt {} from queue", prompt_id),
+        Err(e) => println!("[ComfyUI] Queue delete failed for {}: {}", prompt_id, e),
+    }
+}
+
 /// Download a generated image from ComfyUI and save it to disk.
 /// Returns the local file path.
 pub async fn download_image(
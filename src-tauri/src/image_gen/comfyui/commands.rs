@@ -14,7 +14,12 @@ use super::client::{
     upload_image_to_comfyui, ComfyOutputImage, ComfyUIStatus, DEFAULT_COMFYUI_URL,
     DEFAULT_GENERATION_TIMEOUT_SECS,
 };
+use super::manifest::{load_manifest, resolve_roles, WorkflowValidation};
 use super::pipeline::{generate_scene_image, ImageGenRequest, ImageGenResult};
+use super::progress::image_progress_emitter;
+use super::requirements::{check_requirements, MissingRequirement};
+use super::workflow::{fit_character_branches, load_workflow_template};
+use crate::error::AppError;
 
 /// Check if ComfyUI is running and reachable.
 ///
@@ -33,12 +38,10 @@ pub async fn upload_to_comfyui(
     file_path: String,
     upload_name: String,
     url: Option<String>,
-) -> Result<String, String> {
+) -> Result<String, AppError> {
     let base_url = url.as_deref().unwrap_or(DEFAULT_COMFYUI_URL);
     let path = Path::new(&file_path);
-    upload_image_to_comfyui(base_url, path, &upload_name)
-        .await
-        .map_err(|e| e.to_string())
+    Ok(upload_image_to_comfyui(base_url, path, &upload_name).await?)
 }
 
 /// Queue a raw workflow JSON for execution.
@@ -48,11 +51,22 @@ pub async fn upload_to_comfyui(
 pub async fn queue_comfyui_prompt(
     workflow: Value,
     url: Option<String>,
-) -> Result<String, String> {
+) -> Result<String, AppError> {
     let base_url = url.as_deref().unwrap_or(DEFAULT_COMFYUI_URL);
-    queue_prompt(base_url, &workflow)
-        .await
-        .map_err(|e| e.to_string())
+    Ok(queue_prompt(base_url, &workflow, None).await?)
+}
+
+/// Check a workflow's node classes and model files against ComfyUI's
+/// /object_info. Returns what's missing, with the setup installer for each.
+///
+/// Frontend: `await invoke('check_comfyui_requirements', { workflow: {...} })`
+#[tauri::command]
+pub async fn check_comfyui_requirements(
+    workflow: Value,
+    url: Option<String>,
+) -> Result<Vec<MissingRequirement>, AppError> {
+    let base_url = url.as_deref().unwrap_or(DEFAULT_COMFYUI_URL);
+    Ok(check_requirements(base_url, &workflow).await?)
 }
 
 /// Poll for a prompt's completion and get the output image info.
@@ -63,18 +77,16 @@ pub async fn poll_comfyui_result(
     prompt_id: String,
     timeout_secs: Option<u64>,
     url: Option<String>,
-) -> Result<Vec<Value>, String> {
+) -> Result<Vec<Value>, AppError> {
     let base_url = url.as_deref().unwrap_or(DEFAULT_COMFYUI_URL);
     let timeout = timeout_secs.unwrap_or(DEFAULT_GENERATION_TIMEOUT_SECS);
 
-    let images = poll_for_completion(base_url, &prompt_id, timeout)
-        .await
-        .map_err(|e| e.to_string())?;
+    let images = poll_for_completion(base_url, &prompt_id, timeout).await?;
 
-    images
+    Ok(images
         .iter()
         .map(|img| serde_json::to_value(img).map_err(|e| e.to_string()))
-        .collect()
+        .collect::<Result<_, String>>()?)
 }
 
 /// Download a generated image from ComfyUI to local disk.
@@ -87,7 +99,7 @@ pub async fn download_comfyui_image(
     image_type: Option<String>,
     url: Option<String>,
     app: AppHandle,
-) -> Result<String, String> {
+) -> Result<String, AppError> {
     let base_url = url.as_deref().unwrap_or(DEFAULT_COMFYUI_URL);
 
     let img = ComfyOutputImage {
@@ -102,9 +114,7 @@ pub async fn download_comfyui_image(
         .map_err(|e| format!("Failed to get app data dir: {}", e))?;
     let output_dir = app_data.join("generated_images");
 
-    let path = download_image(base_url, &img, &output_dir)
-        .await
-        .map_err(|e| e.to_string())?;
+    let path = download_image(base_url, &img, &output_dir).await?;
 
     Ok(path.to_string_lossy().to_string())
 }
@@ -116,16 +126,56 @@ pub async fn download_comfyui_image(
 pub async fn generate_comfyui_scene(
     request: ImageGenRequest,
     app: AppHandle,
-) -> Result<ImageGenResult, String> {
+) -> Result<ImageGenResult, AppError> {
     let app_data = app
         .path()
         .app_data_dir()
         .map_err(|e| format!("Failed to get app data dir: {}", e))?;
     let output_dir = app_data.join("generated_images");
 
-    generate_scene_image(&request, &output_dir)
-        .await
-        .map_err(|e| e.to_string())
+    let mut on_progress = image_progress_emitter(&app, None);
+    Ok(generate_scene_image(&request, &output_dir, None, Some(&mut on_progress)).await?)
+}
+
+/// Check that a custom workflow template can be used for a scene with
+/// `characters` characters (default 2): which node each role maps to, and
+/// which roles have none. Relative paths are resolved against the app data dir.
+///
+/// Frontend: `await invoke('validate_workflow', { path: 'workflows/scene_workflow_2char.json', characters: 3 })`
+#[tauri::command]
+pub async fn validate_workflow(
+    path: String,
+    characters: Option<usize>,
+    app: AppHandle,
+) -> Result<WorkflowValidation, AppError> {
+    let characters = characters.unwrap_or(2);
+    let mut workflow_path = std::path::PathBuf::from(&path);
+    if workflow_path.is_relative() {
+        let app_data = app
+            .path()
+            .app_data_dir()
+            .map_err(|e| format!("Failed to get app data dir: {}", e))?;
+        workflow_path = app_data.join(workflow_path);
+    }
+
+    let mut workflow = load_workflow_template(&workflow_path)?;
+    let manifest = load_manifest(&workflow_path)?;
+    let mut roles = resolve_roles(&workflow, manifest.as_ref());
+
+    let mut problems = roles.unknown_nodes(&workflow);
+    if characters > 1 || roles.reference_images.len() > characters {
+        if let Err(e) = fit_character_branches(&mut workflow, &mut roles, characters) {
+            problems.push(e.to_string());
+        }
+    }
+
+    Ok(WorkflowValidation {
+        path: workflow_path.to_string_lossy().to_string(),
+        manifest_found: manifest.is_some(),
+        unmapped: roles.unmapped_roles(characters),
+        roles,
+        problems,
+    })
 }
 
 /// Read a file as raw bytes (used by frontend to upload images).
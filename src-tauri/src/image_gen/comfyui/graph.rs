@@ -0,0 +1,254 @@
+// src-tauri/src/image_gen/comfyui/graph.rs
+//
+// Typed ComfyUI graph builder
+// =============================
+// Builds workflows in ComfyUI's API format
+//
+//   { "<id>": { "class_type": "...", "inputs": { ... }, "_meta": { "title": "..." } } }
+//
+// from code. Nodes get auto-assigned ids and are wired with `Link`s (a node's
+// output slot), which serialize to the `["<id>", slot]` pairs ComfyUI expects.
+// A graph can also be loaded from an existing workflow JSON to add stages to
+// it.
+
+use serde::{Serialize, Serializer};
+use serde_json::{json, Map, Value};
+
+// ============================================================================
+// IDS AND LINKS
+// ============================================================================
+
+/// Id of a node in a `Graph`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NodeId(String);
+
+impl NodeId {
+    pub fn new(id: impl Into<String>) -> Self {
+        Self(id.into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Output `slot` of this node, for wiring into another node's input.
+    pub fn out(&self, slot: u32) -> Link {
+        Link { node: self.clone(), slot }
+    }
+}
+
+/// An input wired to a node's output slot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Link {
+    pub node: NodeId,
+    pub slot: u32,
+}
+
+impl Link {
+    /// Parse a `["<id>", slot]` input value.
+    pub fn from_value(value: &Value) -> Option<Self> {
+        let pair = value.as_array().filter(|a| a.len() == 2)?;
+        Some(Self {
+            node: NodeId::new(pair[0].as_str()?),
+            slot: pair[1].as_u64()? as u32,
+        })
+    }
+}
+
+impl Serialize for Link {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (self.node.as_str(), self.slot).serialize(serializer)
+    }
+}
+
+// ============================================================================
+// GRAPH
+// ============================================================================
+
+#[derive(Debug, Clone)]
+struct Node {
+    class_type: String,
+    inputs: Map<String, Value>,
+    title: Option<String>,
+}
+
+/// A ComfyUI workflow under construction.
+#[derive(Debug, Clone)]
+pub struct Graph {
+    /// Nodes in insertion order
+    nodes: Vec<(NodeId, Node)>,
+    next_id: u64,
+}
+
+impl Graph {
+    pub fn new() -> Self {
+        Self { nodes: Vec::new(), next_id: 1 }
+    }
+
+    /// Load a workflow in API format. New nodes get ids above the highest
+    /// numeric id in it.
+    pub fn from_json(workflow: &Value) -> Result<Self, String> {
+        let obj = workflow.as_object().ok_or("Workflow root is not an object")?;
+        let mut graph = Self::new();
+        for (id, node) in obj {
+            let class_type = node
+                .get("class_type")
+                .and_then(|c| c.as_str())
+                .ok_or_else(|| format!("Node '{}' has no class_type", id))?;
+            let inputs = node.get("inputs").and_then(|i| i.as_object()).cloned().unwrap_or_default();
+            let title = node.pointer("/_meta/title").and_then(|t| t.as_str()).map(String::from);
+            if let Ok(n) = id.parse::<u64>() {
+                graph.next_id = graph.next_id.max(n + 1);
+            }
+            graph.nodes.push((
+                NodeId::new(id.clone()),
+                Node { class_type: class_type.to_string(), inputs, title },
+            ));
+        }
+        Ok(graph)
+    }
+
+    /// Add a node. `inputs` is a JSON object of literal values and `Link`s.
+    pub fn add(&mut self, class_type: &str, inputs: Value) -> NodeId {
+        let id = NodeId::new(self.next_id.to_string());
+        self.next_id += 1;
+        let inputs = match inputs {
+            Value::Object(map) => map,
+            _ => Map::new(),
+        };
+        self.nodes.push((
+            id.clone(),
+            Node { class_type: class_type.to_string(), inputs, title: None },
+        ));
+        id
+    }
+
+    /// Add a copy of a node (inputs and title) under a fresh id.
+    pub fn duplicate(&mut self, id: &NodeId) -> Option<NodeId> {
+        let node = self.node(id)?.clone();
+        let copy = NodeId::new(self.next_id.to_string());
+        self.next_id += 1;
+        self.nodes.push((copy.clone(), node));
+        Some(copy)
+    }
+
+    /// Remove a node. Links into it are left for the caller to rewire.
+    pub fn remove(&mut self, id: &NodeId) {
+        self.nodes.retain(|(n, _)| n != id);
+    }
+
+    pub fn contains(&self, id: &NodeId) -> bool {
+        self.node(id).is_some()
+    }
+
+    /// Every (node, input, link) wired to an output of `source`.
+    pub fn links_from(&self, source: &NodeId) -> Vec<(NodeId, String, Link)> {
+        self.nodes
+            .iter()
+            .flat_map(|(id, node)| {
+                node.inputs.iter().filter_map(move |(key, value)| {
+                    Link::from_value(value)
+                        .filter(|link| &link.node == source)
+                        .map(|link| (id.clone(), key.clone(), link))
+                })
+            })
+            .collect()
+    }
+
+    fn node(&self, id: &NodeId) -> Option<&Node> {
+        self.nodes.iter().find(|(n, _)| n == id).map(|(_, node)| node)
+    }
+
+    fn node_mut(&mut self, id: &NodeId) -> Option<&mut Node> {
+        self.nodes.iter_mut().find(|(n, _)| n == id).map(|(_, node)| node)
+    }
+
+    /// The link wired into a node's input, if that input is a link.
+    pub fn input_link(&self, id: &NodeId, key: &str) -> Option<Link> {
+        self.node(id)?.inputs.get(key).and_then(Link::from_value)
+    }
+
+    /// Set a node's input. Returns false when the node doesn't exist.
+    pub fn set_input(&mut self, id: &NodeId, key: &str, value: impl Serialize) -> bool {
+        let value = serde_json::to_value(value).unwrap_or(Value::Null);
+        match self.node_mut(id) {
+            Some(node) => {
+                node.inputs.insert(key.to_string(), value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Serialize to ComfyUI's API format.
+    pub fn to_json(&self) -> Value {
+        let mut obj = Map::new();
+        for (id, node) in &self.nodes {
+            let mut entry = json!({
+                "class_type": node.class_type,
+                "inputs": node.inputs,
+            });
+            if let Some(title) = &node.title {
+                entry["_meta"] = json!({ "title": title });
+            }
+            obj.insert(id.as_str().to_string(), entry);
+        }
+        Value::Object(obj)
+    }
+}
+
+impl Default for Graph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builds_linked_api_json() {
+        let mut graph = Graph::new();
+        let ckpt = graph.add("CheckpointLoaderSimple", json!({ "ckpt_name": "model.safetensors" }));
+        let prompt = graph.add("CLIPTextEncode", json!({ "clip": ckpt.out(1), "text": "a tavern" }));
+
+        let workflow = graph.to_json();
+        assert_eq!(ckpt.as_str(), "1");
+        assert_eq!(workflow["2"]["inputs"]["clip"], json!(["1", 1]));
+        assert_eq!(graph.input_link(&prompt, "clip"), Some(ckpt.out(1)));
+    }
+
+    #[test]
+    fn test_loaded_graph_assigns_fresh_ids() {
+        let workflow = json!({
+            "35": { "class_type": "KSampler", "inputs": { "model": ["1", 0] } },
+            "1": { "class_type": "CheckpointLoaderSimple", "inputs": {} }
+        });
+        let mut graph = Graph::from_json(&workflow).unwrap();
+        let added = graph.add("LatentUpscaleBy", json!({ "samples": NodeId::new("35").out(0) }));
+        assert_eq!(added.as_str(), "36");
+
+        assert!(graph.set_input(&NodeId::new("35"), "seed", 7));
+        assert!(!graph.set_input(&NodeId::new("99"), "seed", 7));
+        assert_eq!(graph.to_json()["35"]["inputs"]["seed"], 7);
+
+        assert!(Graph::from_json(&json!({ "1": { "inputs": {} } })).is_err());
+
+        // Copies get fresh ids; removed nodes are gone from the output
+        let copy = graph.duplicate(&NodeId::new("35")).unwrap();
+        assert_eq!(copy.as_str(), "37");
+        assert_eq!(graph.links_from(&NodeId::new("1")).len(), 2);
+        graph.remove(&copy);
+        assert!(!graph.contains(&copy));
+        assert_eq!(graph.links_from(&NodeId::new("1")), vec![(NodeId::new("35"), "model".to_string(), NodeId::new("1").out(0))]);
+
+        // Titles survive the round trip
+        let titled = json!({ "1": { "class_type": "LoadImage", "inputs": {}, "_meta": { "title": "Reference A" } } });
+        assert_eq!(Graph::from_json(&titled).unwrap().to_json(), titled);
+    }
+}
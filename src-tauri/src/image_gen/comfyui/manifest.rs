@@ -0,0 +1,436 @@
+// src-tauri/src/image_gen/comfyui/manifest.rs
+//
+// Workflow manifests
+// ===================
+// A custom workflow template can ship with a manifest next to it
+// (`scene_workflow_2char.json` → `scene_workflow_2char.manifest.json`) that
+// maps the roles the pipeline fills to the template's node ids:
+//
+//   { "positive_prompt": "6", "negative_prompt": "7", "latent": "5",
+//     "sampler": "3", "decode": "8", "output": "9", "insightface": "12",
+//     "reference_images": ["20", "21"], "masks": ["40", "41"] }
+//
+// Roles the manifest leaves out are detected from the graph: by class_type
+// and wiring (the KSampler fed by an empty latent, the text encoders behind
+// its conditioning, the IPAdapter chain feeding its model), then by
+// `_meta.title` ("Positive", "Reference 1", "Mask 2").
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+use super::client::ComfyError;
+
+// ============================================================================
+// TYPES
+// ============================================================================
+
+/// Node ids of the roles in a workflow. Also the manifest file format.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct WorkflowManifest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub positive_prompt: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub negative_prompt: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latent: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sampler: Option<String>,
+    /// VAEDecode of the sampler's output; the hires fix pass goes in between.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decode: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub insightface: Option<String>,
+    /// Reference image loaders, one per character in order.
+    #[serde(default)]
+    pub reference_images: Vec<String>,
+    /// Per-character mask image loaders, in the same order.
+    #[serde(default)]
+    pub masks: Vec<String>,
+}
+
+/// Result of `validate_workflow`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkflowValidation {
+    pub path: String,
+    pub manifest_found: bool,
+    /// Roles after merging the manifest with detection.
+    pub roles: WorkflowManifest,
+    /// Roles no node could be found for, e.g. "sampler", "mask[2]".
+    pub unmapped: Vec<String>,
+    /// Other problems: manifest ids missing from the workflow, or a
+    /// template that can't grow to the requested number of characters.
+    pub problems: Vec<String>,
+}
+
+// ============================================================================
+// MANIFEST I/O
+// ============================================================================
+
+/// `foo.json` → `foo.manifest.json`
+pub fn manifest_path(workflow_path: &Path) -> PathBuf {
+    workflow_path.with_extension("manifest.json")
+}
+
+/// Load the manifest shipped next to a workflow, if there is one.
+pub(super) fn load_manifest(workflow_path: &Path) -> Result<Option<WorkflowManifest>, ComfyError> {
+    let path = manifest_path(workflow_path);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = std::fs::read_to_string(&path)
+        .map_err(|e| ComfyError::WorkflowLoadFailed(format!("Cannot read {}: {}", path.display(), e)))?;
+    serde_json::from_str(&content)
+        .map(Some)
+        .map_err(|e| ComfyError::WorkflowLoadFailed(format!("Invalid manifest {}: {}", path.display(), e)))
+}
+
+// ============================================================================
+// ROLE DETECTION
+// ============================================================================
+
+fn class_of(node: &Value) -> &str {
+    node.get("class_type").and_then(|c| c.as_str()).unwrap_or("")
+}
+
+fn title_of(node: &Value) -> String {
+    node.pointer("/_meta/title").and_then(|t| t.as_str()).unwrap_or("").to_lowercase()
+}
+
+/// The node id a link input (`["35", 0]`) points to.
+pub(super) fn link_source(node: &Value, input: &str) -> Option<String> {
+    node.pointer(&format!("/inputs/{}", input))?
+        .as_array()?
+        .first()?
+        .as_str()
+        .map(String::from)
+}
+
+/// Nodes ordered by numeric id, so "ids in order" matches graph order.
+fn sorted_nodes(workflow: &Value) -> Vec<(&String, &Value)> {
+    let mut nodes: Vec<_> = workflow.as_object().map(|o| o.iter().collect()).unwrap_or_default();
+    nodes.sort_by_key(|(id, _)| (id.parse::<u64>().unwrap_or(u64::MAX), id.to_string()));
+    nodes
+}
+
+fn first_of_class(workflow: &Value, matches: impl Fn(&str) -> bool) -> Option<String> {
+    sorted_nodes(workflow)
+        .into_iter()
+        .find(|(_, node)| matches(class_of(node)))
+        .map(|(id, _)| id.clone())
+}
+
+/// Nodes whose title contains `word`, ordered by title ("Reference 1", "Reference 2").
+fn titled(workflow: &Value, word: &str, class_matches: impl Fn(&str) -> bool) -> Vec<String> {
+    let mut nodes: Vec<_> = sorted_nodes(workflow)
+        .into_iter()
+        .filter(|(_, node)| class_matches(class_of(node)) && title_of(node).contains(word))
+        .map(|(id, node)| (title_of(node), id.clone()))
+        .collect();
+    nodes.sort();
+    nodes.into_iter().map(|(_, id)| id).collect()
+}
+
+fn is_sampler(class: &str) -> bool {
+    class.starts_with("KSampler")
+}
+
+fn is_text_encoder(class: &str) -> bool {
+    class.starts_with("CLIPTextEncode")
+}
+
+fn is_image_loader(class: &str) -> bool {
+    class.starts_with("LoadImage")
+}
+
+/// Follow a conditioning input upstream (through ControlNet applies and the
+/// like) to the text encoder it starts at.
+fn trace_text_encoder(workflow: &Value, sampler: &str, input: &str) -> Option<String> {
+    let mut id = link_source(&workflow[sampler], input)?;
+    for _ in 0..10 {
+        let node = workflow.get(&id)?;
+        if is_text_encoder(class_of(node)) {
+            return Some(id);
+        }
+        id = [input, "conditioning"].iter().find_map(|key| link_source(node, key))?;
+    }
+    None
+}
+
+/// IPAdapter nodes applied to the sampler's model, first applied first.
+fn adapter_chain(workflow: &Value, sampler: &str) -> Vec<String> {
+    let mut chain = Vec::new();
+    let mut id = link_source(&workflow[sampler], "model");
+    while let Some(current) = id {
+        let Some(node) = workflow.get(&current) else { break };
+        if chain.len() > 64 {
+            break;
+        }
+        if class_of(node).starts_with("IPAdapter") && link_source(node, "image").is_some() {
+            chain.push(current.clone());
+        }
+        id = link_source(node, "model");
+    }
+    chain.reverse();
+    chain
+}
+
+/// The image loader behind an adapter's attention mask (directly, or through
+/// one conversion node such as ImageToMask).
+fn mask_loader(workflow: &Value, adapter: &str) -> Option<String> {
+    let source = link_source(&workflow[adapter], "attn_mask")?;
+    let node = workflow.get(&source)?;
+    if is_image_loader(class_of(node)) {
+        return Some(source);
+    }
+    link_source(node, "image").filter(|id| workflow.get(id).is_some_and(|n| is_image_loader(class_of(n))))
+}
+
+/// Detect every role from the graph alone.
+pub fn detect_roles(workflow: &Value) -> WorkflowManifest {
+    let nodes = sorted_nodes(workflow);
+
+    // The sampler that starts from an empty latent (not a hires fix pass)
+    let sampler = nodes
+        .iter()
+        .find(|(_, node)| {
+            is_sampler(class_of(node))
+                && link_source(node, "latent_image")
+                    .and_then(|id| workflow.get(&id))
+                    .is_some_and(|latent| class_of(latent).contains("EmptyLatent"))
+        })
+        .map(|(id, _)| id.to_string())
+        .or_else(|| titled(workflow, "sampler", is_sampler).into_iter().next())
+        .or_else(|| first_of_class(workflow, is_sampler));
+
+    let latent = sampler
+        .as_deref()
+        .and_then(|s| link_source(&workflow[s], "latent_image"))
+        .filter(|id| workflow.get(id).is_some_and(|n| class_of(n).contains("EmptyLatent")))
+        .or_else(|| first_of_class(workflow, |c| c.contains("EmptyLatent")));
+
+    let prompt = |input: &str| {
+        sampler
+            .as_deref()
+            .and_then(|s| trace_text_encoder(workflow, s, input))
+            .or_else(|| titled(workflow, input, is_text_encoder).into_iter().next())
+    };
+    let positive_prompt = prompt("positive");
+    let negative_prompt = prompt("negative");
+
+    let decode = nodes
+        .iter()
+        .find(|(_, node)| class_of(node) == "VAEDecode" && link_source(node, "samples") == sampler)
+        .map(|(id, _)| id.to_string())
+        .or_else(|| first_of_class(workflow, |c| c == "VAEDecode"));
+    let output = first_of_class(workflow, |c| c == "SaveImage")
+        .or_else(|| first_of_class(workflow, |c| c == "PreviewImage"));
+    let insightface = first_of_class(workflow, |c| c == "IPAdapterInsightFaceLoader");
+
+    let adapters = sampler.as_deref().map(|s| adapter_chain(workflow, s)).unwrap_or_default();
+    let mut reference_images: Vec<String> = adapters
+        .iter()
+        .filter_map(|a| link_source(&workflow[a.as_str()], "image"))
+        .filter(|id| workflow.get(id).is_some_and(|n| is_image_loader(class_of(n))))
+        .collect();
+    if reference_images.is_empty() {
+        reference_images = titled(workflow, "reference", is_image_loader);
+    }
+    let mut masks: Vec<String> = adapters.iter().map_while(|a| mask_loader(workflow, a)).collect();
+    if masks.is_empty() {
+        masks = titled(workflow, "mask", is_image_loader);
+    }
+
+    WorkflowManifest {
+        positive_prompt,
+        negative_prompt,
+        latent,
+        sampler,
+        decode,
+        output,
+        insightface,
+        reference_images,
+        masks,
+    }
+}
+
+/// Roles for a workflow: the manifest's, with the rest detected.
+pub fn resolve_roles(workflow: &Value, manifest: Option<&WorkflowManifest>) -> WorkflowManifest {
+    let detected = detect_roles(workflow);
+    let Some(manifest) = manifest else {
+        return detected;
+    };
+    let pick = |m: &Option<String>, d: Option<String>| m.clone().or(d);
+    let pick_list = |m: &Vec<String>, d: Vec<String>| if m.is_empty() { d } else { m.clone() };
+    WorkflowManifest {
+        positive_prompt: pick(&manifest.positive_prompt, detected.positive_prompt),
+        negative_prompt: pick(&manifest.negative_prompt, detected.negative_prompt),
+        latent: pick(&manifest.latent, detected.latent),
+        sampler: pick(&manifest.sampler, detected.sampler),
+        decode: pick(&manifest.decode, detected.decode),
+        output: pick(&manifest.output, detected.output),
+        insightface: pick(&manifest.insightface, detected.insightface),
+        reference_images: pick_list(&manifest.reference_images, detected.reference_images),
+        masks: pick_list(&manifest.masks, detected.masks),
+    }
+}
+
+impl WorkflowManifest {
+    /// Roles a scene with `characters` characters needs but has no node for.
+    /// Masks are only needed with more than one character.
+    pub fn unmapped_roles(&self, characters: usize) -> Vec<String> {
+        let singles = [
+            ("positive_prompt", &self.positive_prompt),
+            ("negative_prompt", &self.negative_prompt),
+            ("latent", &self.latent),
+            ("sampler", &self.sampler),
+            ("decode", &self.decode),
+            ("output", &self.output),
+        ];
+        let mut unmapped: Vec<String> = singles
+            .iter()
+            .filter(|(_, id)| id.is_none())
+            .map(|(role, _)| role.to_string())
+            .collect();
+        for i in self.reference_images.len()..characters {
+            unmapped.push(format!("reference_image[{}]", i));
+        }
+        if characters > 1 {
+            for i in self.masks.len()..characters {
+                unmapped.push(format!("mask[{}]", i));
+            }
+        }
+        unmapped
+    }
+
+    /// Mapped roles whose node isn't in the workflow, as "role → id".
+    pub fn unknown_nodes(&self, workflow: &Value) -> Vec<String> {
+        let singles = [
+            ("positive_prompt", &self.positive_prompt),
+            ("negative_prompt", &self.negative_prompt),
+            ("latent", &self.latent),
+            ("sampler", &self.sampler),
+            ("decode", &self.decode),
+            ("output", &self.output),
+            ("insightface", &self.insightface),
+        ];
+        let lists = [("reference_image", &self.reference_images), ("mask", &self.masks)];
+
+        let mut mapped: Vec<(String, &String)> = singles
+            .iter()
+            .filter_map(|(role, id)| id.as_ref().map(|id| (role.to_string(), id)))
+            .collect();
+        for (role, ids) in lists {
+            mapped.extend(ids.iter().enumerate().map(|(i, id)| (format!("{}[{}]", role, i), id)));
+        }
+        mapped
+            .into_iter()
+            .filter(|(_, id)| workflow.get(id.as_str()).is_none())
+            .map(|(role, id)| format!("{} → node '{}' is not in the workflow", role, id))
+            .collect()
+    }
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// A two-character graph exported from ComfyUI with its own numbering.
+    fn exported_workflow() -> Value {
+        json!({
+            "3": { "class_type": "KSampler", "inputs": {
+                "model": ["15", 0], "positive": ["18", 0], "negative": ["7", 0], "latent_image": ["5", 0] } },
+            "4": { "class_type": "CheckpointLoaderSimple", "inputs": { "ckpt_name": "model.safetensors" } },
+            "5": { "class_type": "EmptyLatentImage", "inputs": { "width": 1152, "height": 896, "batch_size": 1 } },
+            "6": { "class_type": "CLIPTextEncode", "inputs": { "clip": ["4", 1], "text": "" } },
+            "7": { "class_type": "CLIPTextEncode", "inputs": { "clip": ["4", 1], "text": "" } },
+            "8": { "class_type": "VAEDecode", "inputs": { "samples": ["3", 0], "vae": ["4", 2] } },
+            "9": { "class_type": "SaveImage", "inputs": { "images": ["8", 0] } },
+            "10": { "class_type": "IPAdapterUnifiedLoaderFaceID", "inputs": { "model": ["4", 0] } },
+            "11": { "class_type": "IPAdapterInsightFaceLoader", "inputs": { "provider": "CUDA" } },
+            "13": { "class_type": "LoadImage", "inputs": { "image": "b.png" } },
+            "12": { "class_type": "LoadImage", "inputs": { "image": "a.png" } },
+            "16": { "class_type": "LoadImage", "inputs": { "image": "mask_a.png" } },
+            "17": { "class_type": "LoadImage", "inputs": { "image": "mask_b.png" } },
+            "19": { "class_type": "ImageToMask", "inputs": { "image": ["17", 0], "channel": "red" } },
+            "14": { "class_type": "IPAdapterFaceID", "inputs": {
+                "model": ["10", 0], "image": ["12", 0], "attn_mask": ["16", 1] } },
+            "15": { "class_type": "IPAdapterFaceID", "inputs": {
+                "model": ["14", 0], "image": ["13", 0], "attn_mask": ["19", 0] } },
+            "20": { "class_type": "ControlNetLoader", "inputs": {} },
+            "18": { "class_type": "ControlNetApplyAdvanced", "inputs": {
+                "positive": ["6", 0], "negative": ["7", 0], "control_net": ["20", 0] } }
+        })
+    }
+
+    #[test]
+    fn test_detect_roles_from_wiring() {
+        let roles = detect_roles(&exported_workflow());
+        assert_eq!(roles.sampler.as_deref(), Some("3"));
+        assert_eq!(roles.latent.as_deref(), Some("5"));
+        // Traced through the ControlNet apply node
+        assert_eq!(roles.positive_prompt.as_deref(), Some("6"));
+        assert_eq!(roles.negative_prompt.as_deref(), Some("7"));
+        assert_eq!(roles.decode.as_deref(), Some("8"));
+        assert_eq!(roles.output.as_deref(), Some("9"));
+        assert_eq!(roles.insightface.as_deref(), Some("11"));
+        // In the order the adapters are applied
+        assert_eq!(roles.reference_images, vec!["12", "13"]);
+        assert_eq!(roles.masks, vec!["16", "17"]);
+        assert!(roles.unmapped_roles(2).is_empty());
+    }
+
+    #[test]
+    fn test_detect_roles_by_title() {
+        let workflow = json!({
+            "1": { "class_type": "CLIPTextEncode", "inputs": {}, "_meta": { "title": "Negative" } },
+            "2": { "class_type": "CLIPTextEncode", "inputs": {}, "_meta": { "title": "Positive prompt" } },
+            "5": { "class_type": "LoadImage", "inputs": {}, "_meta": { "title": "Reference 2" } },
+            "6": { "class_type": "LoadImage", "inputs": {}, "_meta": { "title": "Reference 1" } },
+            "7": { "class_type": "LoadImage", "inputs": {}, "_meta": { "title": "Background" } }
+        });
+        let roles = detect_roles(&workflow);
+        assert_eq!(roles.positive_prompt.as_deref(), Some("2"));
+        assert_eq!(roles.negative_prompt.as_deref(), Some("1"));
+        assert_eq!(roles.reference_images, vec!["6", "5"]);
+        assert_eq!(
+            roles.unmapped_roles(2),
+            vec!["latent", "sampler", "decode", "output", "mask[0]", "mask[1]"]
+        );
+    }
+
+    #[test]
+    fn test_manifest_overrides_detection() {
+        let workflow = exported_workflow();
+        let manifest: WorkflowManifest = serde_json::from_value(json!({
+            "positive_prompt": "7",
+            "negative_prompt": "6",
+            "reference_images": ["13", "12"],
+            "output": "99"
+        }))
+        .unwrap();
+
+        let roles = resolve_roles(&workflow, Some(&manifest));
+        assert_eq!(roles.positive_prompt.as_deref(), Some("7"));
+        assert_eq!(roles.reference_images, vec!["13", "12"]);
+        // Left out of the manifest → detected
+        assert_eq!(roles.sampler.as_deref(), Some("3"));
+        assert_eq!(roles.masks, vec!["16", "17"]);
+        assert_eq!(roles.unknown_nodes(&workflow), vec!["output → node '99' is not in the workflow"]);
+    }
+
+    #[test]
+    fn test_manifest_path() {
+        assert_eq!(
+            manifest_path(Path::new("/data/workflows/scene_workflow_2char.json")),
+            PathBuf::from("/data/workflows/scene_workflow_2char.manifest.json")
+        );
+    }
+}
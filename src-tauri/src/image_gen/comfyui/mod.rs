@@ -5,23 +5,40 @@
 // Rust-side client for the ComfyUI local API. Handles:
 //   - Health checking (is ComfyUI reachable?)
 //   - Uploading reference images and masks to ComfyUI's /input directory
-//   - Building workflow JSON from a template with dynamic node modifications
+//   - Building the scene workflow in code, or from a custom template whose
+//     nodes are mapped by a manifest (or detected from the graph)
+//   - Checking the workflow's node classes and models against /object_info
 //   - Queuing prompts via POST /prompt
-//   - Polling GET /history/{prompt_id} for completion
+//   - Tracking progress and step previews over the /ws WebSocket, with
+//     GET /history/{prompt_id} polling as the fallback
 //   - Downloading generated images via GET /view
 //
 // Sub-modules:
 //   client   — low-level HTTP types and operations
-//   workflow — workflow template loading and modification
+//   graph    — typed builder for ComfyUI API-format graphs
+//   manifest — role mapping and validation for custom workflow templates
+//   requirements — checks a workflow's nodes and models against /object_info
+//   workflow — scene graph construction and template modification
+//   progress — WebSocket progress tracking and `image-progress` events
 //   pipeline — request/result types and the full generation pipeline
 //   commands — #[tauri::command] wrappers for the Svelte frontend
 
 mod client;
 mod commands;
+mod graph;
+mod manifest;
 mod pipeline;
+mod progress;
+mod requirements;
 mod workflow;
 
 // Re-export types that other modules (orchestrator, etc.) need
-pub use client::{ComfyError, ComfyOutputImage, ComfyUIStatus};
+pub use client::{
+    cancel_prompt, download_image, queue_prompt, ComfyError, ComfyOutputImage, ComfyUIStatus, DEFAULT_COMFYUI_URL,
+};
+pub use graph::Graph;
 pub use pipeline::{generate_scene_image, CharacterInput, ImageGenRequest, ImageGenResult};
+pub use progress::{image_progress_emitter, wait_for_completion, ProgressSocket};
+pub use requirements::{check_requirements, MissingRequirement};
+pub use workflow::MAX_SCENE_CHARACTERS;
 pub use commands::*;
@@ -4,14 +4,22 @@
 // ======================================
 // Request/result types and the full upload → queue → poll → download pipeline.
 
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 use std::path::Path;
 
 use super::client::{
-    check_comfyui_health, download_image, poll_for_completion, queue_prompt,
-    upload_image_to_comfyui, ComfyError, DEFAULT_COMFYUI_URL, DEFAULT_GENERATION_TIMEOUT_SECS,
+    cancel_prompt, check_comfyui_health, download_image, queue_prompt, upload_image_to_comfyui,
+    ComfyError, DEFAULT_COMFYUI_URL, DEFAULT_GENERATION_TIMEOUT_SECS,
+};
+use super::progress::{wait_for_completion, ProgressCallback, ProgressSocket};
+use crate::cancellation::GenerationJob;
+use super::manifest::{load_manifest, manifest_path, resolve_roles};
+use super::requirements::check_requirements;
+use super::workflow::{
+    add_template_stages, build_scene_workflow, build_workflow_modifications, fit_character_branches,
+    load_workflow_template, modify_workflow, DEFAULT_CONTROLNET_STRENGTH,
 };
-use super::workflow::{build_workflow_modifications, load_workflow_template, modify_workflow};
 
 // ============================================================================
 // REQUEST / RESULT TYPES
@@ -37,10 +45,13 @@ pub struct ImageGenRequest {
     /// Characters to render with IP-Adapter FaceID.
     pub characters: Vec<CharacterInput>,
     /// Per-character mask PNGs. Empty for 1-char workflow.
-    /// For 2-char: vec of 2 paths, one per character.
+    /// For multi-character scenes: one path per character, in order.
     pub mask_paths: Vec<String>,
-    /// Path to the base workflow JSON template.
-    pub workflow_template: String,
+    /// Optional: path to a custom workflow JSON template. When unset the
+    /// scene graph is built in code. The template's nodes are mapped by the
+    /// `.manifest.json` next to it, or detected from the graph.
+    #[serde(default)]
+    pub workflow_template: Option<String>,
     /// Optional: override the ComfyUI base URL.
     #[serde(default)]
     pub comfyui_url: Option<String>,
@@ -91,15 +102,26 @@ pub struct ImageGenResult {
 /// Run the full image generation pipeline:
 ///   1. Check ComfyUI health
 ///   2. Upload reference images + mask
-///   3. Load & modify workflow template
-///   4. Queue the prompt
-///   5. Poll for completion
+///   3. Build the workflow, or map and modify the custom template
+///   4. Check its nodes and models against /object_info, and queue it
+///   5. Wait for completion over the WebSocket, or by polling /history
 ///   6. Download output images
 ///
 /// This is the main function the orchestrator calls.
+///
+/// If `job` is given and gets cancelled, the queued prompt is interrupted and
+/// removed from the ComfyUI queue, and `ComfyError::Cancelled` is returned.
+///
+/// `on_progress` receives step progress and previews while the prompt runs
+/// (see `image_progress_emitter`).
+///
+/// Without `request.seed` a random seed is picked once here, so the sampler
+/// and the hires fix pass share it.
 pub async fn generate_scene_image(
     request: &ImageGenRequest,
     output_dir: &Path,
+    job: Option<&GenerationJob>,
+    on_progress: Option<ProgressCallback<'_>>,
 ) -> Result<ImageGenResult, ComfyError> {
     let base_url = request
         .comfyui_url
@@ -115,6 +137,10 @@ pub async fn generate_scene_image(
     }
     println!("[ComfyUI] Connected to {}", base_url);
 
+    let seed = request.seed.unwrap_or_else(|| rand::thread_rng().gen_range(0..i64::MAX));
+    let request = &ImageGenRequest { seed: Some(seed), ..request.clone() };
+    println!("[ComfyUI] Seed: {}", seed);
+
     // 2. Upload reference images
     let mut uploaded_refs: Vec<String> = Vec::new();
     for (i, character) in request.characters.iter().enumerate() {
@@ -156,24 +182,51 @@ pub async fn generate_scene_image(
         None
     };
 
-    // 3. Load and modify workflow
-    let template_path = Path::new(&request.workflow_template);
-    let mut workflow = load_workflow_template(template_path)?;
+    // 3. Build the workflow, or load and modify the custom template
+    let workflow = match &request.workflow_template {
+        None => {
+            let workflow = build_scene_workflow(
+                request,
+                &uploaded_refs,
+                &uploaded_masks,
+                controlnet_skeleton_name.as_deref(),
+            );
+            println!("[ComfyUI] Built scene workflow for {} character(s)", uploaded_refs.len());
+            workflow
+        }
+        Some(template) => {
+            let template_path = Path::new(template);
+            let mut workflow = load_workflow_template(template_path)?;
+            let manifest = load_manifest(template_path)?;
+            let mut roles = resolve_roles(&workflow, manifest.as_ref());
+            if !request.mask_paths.is_empty() || roles.reference_images.len() > request.characters.len() {
+                // Multi-character template: one IPAdapter branch per character
+                fit_character_branches(&mut workflow, &mut roles, request.characters.len())?;
+            }
 
-    let modifications = build_workflow_modifications(request, &uploaded_refs, &uploaded_masks);
-    modify_workflow(&mut workflow, &modifications)?;
-    println!("[ComfyUI] Workflow prepared with {} modifications", modifications.len());
+            let unmapped = roles.unmapped_roles(request.characters.len());
+            if !unmapped.is_empty() {
+                return Err(ComfyError::WorkflowLoadFailed(format!(
+                    "{}: no node found for {}. Add a {} mapping these roles to node ids.",
+                    template_path.display(),
+                    unmapped.join(", "),
+                    manifest_path(template_path).display()
+                )));
+            }
 
-    // Inject ControlNet if a skeleton was uploaded
-    if let Some(ref skeleton_name) = controlnet_skeleton_name {
-        let cn_strength = request.controlnet_strength.unwrap_or(0.85);
-        super::workflow::inject_controlnet(&mut workflow, skeleton_name, cn_strength)?;
-    }
+            let modifications = build_workflow_modifications(request, &uploaded_refs, &uploaded_masks, &roles);
+            modify_workflow(&mut workflow, &modifications)?;
+            println!("[ComfyUI] Workflow prepared with {} modifications", modifications.len());
 
-    // Inject hi-res fix for sharper facial detail (mimics A1111 Hires. fix)
-    // Uses conservative settings: 1.5x upscale, 0.45 denoise, 15 steps
-    // This keeps generation time reasonable while dramatically improving face/eye quality.
-    super::workflow::inject_hires_fix(&mut workflow, 1.5, 0.45, 15)?;
+            add_template_stages(
+                &workflow,
+                &roles,
+                controlnet_skeleton_name.as_deref(),
+                request.controlnet_strength.unwrap_or(DEFAULT_CONTROLNET_STRENGTH),
+                seed,
+            )?
+        }
+    };
 
     println!(
         "[ComfyUI][DEBUG] Final workflow JSON:\n{}",
@@ -181,13 +234,39 @@ pub async fn generate_scene_image(
             .unwrap_or_else(|_| "SERIALIZATION_FAILED".to_string())
     );
 
-    // 4. Queue
-    let prompt_id = queue_prompt(base_url, &workflow).await?;
+    // 4. Check the nodes and models it needs, then queue
+    match check_requirements(base_url, &workflow).await {
+        Ok(missing) if !missing.is_empty() => return Err(ComfyError::MissingRequirements(missing)),
+        Ok(_) => {}
+        Err(e) => println!("[ComfyUI] Warning: could not check workflow requirements, queueing anyway: {}", e),
+    }
+    if job.map_or(false, |j| j.is_cancelled()) {
+        return Err(ComfyError::Cancelled);
+    }
+    // Connect the progress socket first so none of the prompt's events are missed
+    let socket = match ProgressSocket::connect(base_url).await {
+        Ok(socket) => Some(socket),
+        Err(e) => {
+            println!("[ComfyUI] No progress socket, polling history instead: {}", e);
+            None
+        }
+    };
+    let client_id = socket.as_ref().map(|s| s.client_id().to_string());
+    let prompt_id = queue_prompt(base_url, &workflow, client_id.as_deref()).await?;
     println!("[ComfyUI] Queued prompt: {}", prompt_id);
 
-    // 5. Poll for completion
+    // 5. Wait for completion, reporting progress (aborting if the job is cancelled)
     let timeout = request.timeout_secs.unwrap_or(DEFAULT_GENERATION_TIMEOUT_SECS);
-    let output_images = poll_for_completion(base_url, &prompt_id, timeout).await?;
+    let output_images = match job {
+        Some(job) => tokio::select! {
+            res = wait_for_completion(base_url, &prompt_id, timeout, socket, on_progress) => res?,
+            _ = job.cancelled() => {
+                cancel_prompt(base_url, &prompt_id).await;
+                return Err(ComfyError::Cancelled);
+            }
+        },
+        None => wait_for_completion(base_url, &prompt_id, timeout, socket, on_progress).await?,
+    };
     println!(
         "[ComfyUI] Generation complete! {} image(s) produced",
         output_images.len()
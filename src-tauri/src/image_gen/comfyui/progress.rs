@@ -0,0 +1,373 @@
+// src-tauri/src/image_gen/comfyui/progress.rs
+//
+// Live generation progress over ComfyUI's WebSocket
+// ===================================================
+// ComfyUI pushes execution events to `/ws?clientId=<id>` for prompts queued
+// with the same client_id:
+//
+//   text   { "type": "executing", "data": { "node": "3", "prompt_id": "..." } }
+//          { "type": "progress",  "data": { "value": 12, "max": 30, ... } }
+//          { "type": "execution_error", "data": { "node_type": ..., "exception_message": ... } }
+//   binary [u32 event = 1][u32 format: 1 JPEG, 2 PNG][image bytes]   (step preview)
+//
+// `executing` with a null node marks the prompt as finished. The socket only
+// drives progress reporting; outputs are still read from /history, and when
+// the socket can't connect or drops mid-run `wait_for_completion` falls back
+// to polling history (client::poll_for_completion).
+//
+// Progress reaches the frontend as `image-progress` Tauri events.
+
+use base64::Engine;
+use futures_util::StreamExt;
+use serde::Serialize;
+use serde_json::Value;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+use super::client::{poll_for_completion, ComfyError, ComfyOutputImage, JobProgress, JobStatus};
+
+// ============================================================================
+// TYPES
+// ============================================================================
+
+/// Tauri event emitted for every progress update and step preview.
+pub const IMAGE_PROGRESS_EVENT: &str = "image-progress";
+
+/// Binary frame event type ComfyUI uses for step previews.
+const PREVIEW_IMAGE_EVENT: u32 = 1;
+
+const CONNECT_TIMEOUT_SECS: u64 = 3;
+
+/// A step preview decoded from a binary frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreviewImage {
+    pub mime: &'static str,
+    pub bytes: Vec<u8>,
+}
+
+/// Called with the job's progress after every update, plus the preview when
+/// the update is a new step preview.
+pub type ProgressCallback<'a> = &'a mut (dyn FnMut(&JobProgress, Option<&PreviewImage>) + Send);
+
+/// Payload of an `image-progress` event.
+#[derive(Debug, Clone, Serialize)]
+pub struct ImageProgressPayload {
+    /// Cancellation job id of the generation, when it has one
+    pub job_id: Option<String>,
+    #[serde(flatten)]
+    pub progress: JobProgress,
+    /// Latest step preview as a data URL (`data:image/jpeg;base64,...`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preview: Option<String>,
+}
+
+/// A progress callback that emits `image-progress` events.
+pub fn image_progress_emitter(
+    app: &AppHandle,
+    job_id: Option<String>,
+) -> impl FnMut(&JobProgress, Option<&PreviewImage>) + Send + '_ {
+    move |progress, preview| {
+        let preview = preview.map(|p| {
+            format!(
+                "data:{};base64,{}",
+                p.mime,
+                base64::engine::general_purpose::STANDARD.encode(&p.bytes)
+            )
+        });
+        let _ = app.emit(
+            IMAGE_PROGRESS_EVENT,
+            ImageProgressPayload { job_id: job_id.clone(), progress: progress.clone(), preview },
+        );
+    }
+}
+
+// ============================================================================
+// MESSAGE PARSING
+// ============================================================================
+
+/// What a socket message means for the tracked prompt.
+#[derive(Debug, PartialEq)]
+enum SocketEvent {
+    /// `JobProgress` changed
+    Updated,
+    /// The prompt finished executing
+    Done,
+    /// The prompt failed
+    Failed(String),
+    /// Not about this prompt, or nothing to report
+    Ignored,
+}
+
+/// Apply a text message to `progress`.
+fn apply_message(text: &str, progress: &mut JobProgress) -> SocketEvent {
+    let Ok(message) = serde_json::from_str::<Value>(text) else {
+        return SocketEvent::Ignored;
+    };
+    let data = &message["data"];
+    // Messages without a prompt_id (e.g. older `progress` payloads) are for
+    // whatever is running, which is ours once it has started
+    if let Some(id) = data.get("prompt_id").and_then(|p| p.as_str()) {
+        if id != progress.prompt_id {
+            return SocketEvent::Ignored;
+        }
+    } else if progress.status == JobStatus::Queued {
+        return SocketEvent::Ignored;
+    }
+
+    match message["type"].as_str().unwrap_or("") {
+        "execution_start" => {
+            progress.status = JobStatus::Running;
+            SocketEvent::Updated
+        }
+        "executing" => match data.get("node").and_then(|n| n.as_str()) {
+            Some(node) => {
+                progress.status = JobStatus::Running;
+                progress.current_node = Some(node.to_string());
+                SocketEvent::Updated
+            }
+            None => {
+                progress.status = JobStatus::Completed;
+                progress.current_node = None;
+                SocketEvent::Done
+            }
+        },
+        "execution_success" => {
+            progress.status = JobStatus::Completed;
+            SocketEvent::Done
+        }
+        "progress" => {
+            let (Some(value), Some(max)) = (data["value"].as_u64(), data["max"].as_u64()) else {
+                return SocketEvent::Ignored;
+            };
+            progress.status = JobStatus::Running;
+            progress.step = Some(value);
+            progress.total = Some(max);
+            progress.progress = Some(value as f64 / max.max(1) as f64);
+            if let Some(node) = data.get("node").and_then(|n| n.as_str()) {
+                progress.current_node = Some(node.to_string());
+            }
+            SocketEvent::Updated
+        }
+        "execution_error" => {
+            progress.status = JobStatus::Failed;
+            SocketEvent::Failed(format!(
+                "ComfyUI execution error in {} (node {}): {}",
+                data["node_type"].as_str().unwrap_or("unknown node"),
+                data["node_id"].as_str().unwrap_or("?"),
+                data["exception_message"].as_str().unwrap_or("unknown error").trim()
+            ))
+        }
+        "execution_interrupted" => {
+            progress.status = JobStatus::Failed;
+            SocketEvent::Failed("Execution was interrupted in ComfyUI".to_string())
+        }
+        _ => SocketEvent::Ignored,
+    }
+}
+
+/// Decode a binary preview frame.
+fn parse_preview(frame: &[u8]) -> Option<PreviewImage> {
+    let word = |at: usize| frame.get(at..at + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]));
+    if word(0)? != PREVIEW_IMAGE_EVENT {
+        return None;
+    }
+    let mime = match word(4)? {
+        1 => "image/jpeg",
+        2 => "image/png",
+        _ => return None,
+    };
+    Some(PreviewImage { mime, bytes: frame[8..].to_vec() })
+}
+
+// ============================================================================
+// SOCKET
+// ============================================================================
+
+/// A connection to ComfyUI's `/ws` endpoint. Connect before queueing and
+/// queue with `client_id()`, so the prompt's events are sent here.
+pub struct ProgressSocket {
+    stream: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    client_id: String,
+}
+
+impl ProgressSocket {
+    pub async fn connect(base_url: &str) -> Result<Self, ComfyError> {
+        let client_id = uuid::Uuid::new_v4().to_string();
+        let ws_base = base_url
+            .replacen("https://", "wss://", 1)
+            .replacen("http://", "ws://", 1);
+        let url = format!("{}/ws?clientId={}", ws_base.trim_end_matches('/'), client_id);
+
+        let (stream, _) = tokio::time::timeout(Duration::from_secs(CONNECT_TIMEOUT_SECS), connect_async(url.as_str()))
+            .await
+            .map_err(|_| ComfyError::PollFailed(format!("Timed out connecting to {}", url)))?
+            .map_err(|e| ComfyError::PollFailed(format!("WebSocket connect to {} failed: {}", url, e)))?;
+
+        Ok(Self { stream, client_id })
+    }
+
+    pub fn client_id(&self) -> &str {
+        &self.client_id
+    }
+
+    /// Read events until the prompt finishes. Returns `PollFailed` when the
+    /// socket closes first, `GenerationFailed` on an execution error.
+    async fn track(&mut self, progress: &mut JobProgress, on_progress: &mut Option<ProgressCallback<'_>>) -> Result<(), ComfyError> {
+        while let Some(message) = self.stream.next().await {
+            let message = message.map_err(|e| ComfyError::PollFailed(format!("WebSocket error: {}", e)))?;
+            let (event, preview) = match message {
+                Message::Text(text) => (apply_message(&text, progress), None),
+                Message::Binary(frame) => match parse_preview(&frame) {
+                    // Previews only come from our prompt's sampler
+                    Some(preview) if progress.status == JobStatus::Running => (SocketEvent::Updated, Some(preview)),
+                    _ => (SocketEvent::Ignored, None),
+                },
+                Message::Close(_) => break,
+                _ => (SocketEvent::Ignored, None),
+            };
+
+            match event {
+                SocketEvent::Ignored => continue,
+                SocketEvent::Failed(msg) => return Err(ComfyError::GenerationFailed(msg)),
+                SocketEvent::Updated | SocketEvent::Done => {
+                    if let Some(callback) = on_progress.as_mut() {
+                        callback(progress, preview.as_ref());
+                    }
+                    if event == SocketEvent::Done {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+        Err(ComfyError::PollFailed("WebSocket closed before the prompt finished".into()))
+    }
+}
+
+// ============================================================================
+// WAITING
+// ============================================================================
+
+/// Wait for a queued prompt and return its output images.
+///
+/// With a socket, progress and previews are reported through `on_progress`
+/// as they arrive and the outputs are read from history once it's done. If
+/// the socket is missing or drops, /history is polled for the rest of the
+/// timeout.
+pub async fn wait_for_completion(
+    base_url: &str,
+    prompt_id: &str,
+    timeout_secs: u64,
+    socket: Option<ProgressSocket>,
+    mut on_progress: Option<ProgressCallback<'_>>,
+) -> Result<Vec<ComfyOutputImage>, ComfyError> {
+    let start = Instant::now();
+    let timeout = Duration::from_secs(timeout_secs);
+
+    if let Some(mut socket) = socket {
+        let mut progress = JobProgress {
+            prompt_id: prompt_id.to_string(),
+            status: JobStatus::Queued,
+            progress: None,
+            current_node: None,
+            step: None,
+            total: None,
+        };
+        let tracked = tokio::time::timeout(timeout, socket.track(&mut progress, &mut on_progress)).await;
+        match tracked {
+            Ok(Ok(())) => {}
+            Ok(Err(ComfyError::PollFailed(e))) => {
+                println!("[ComfyUI] Progress socket lost ({}), falling back to polling", e);
+            }
+            Ok(Err(e)) => return Err(e),
+            Err(_) => {
+                if let Some(callback) = on_progress.as_mut() {
+                    progress.status = JobStatus::TimedOut;
+                    callback(&progress, None);
+                }
+                return Err(ComfyError::Timeout(format!(
+                    "Generation did not complete within {} seconds for prompt {}",
+                    timeout_secs, prompt_id
+                )));
+            }
+        }
+    }
+
+    let remaining = timeout.saturating_sub(start.elapsed()).as_secs().max(1);
+    poll_for_completion(base_url, prompt_id, remaining).await
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queued(prompt_id: &str) -> JobProgress {
+        JobProgress {
+            prompt_id: prompt_id.to_string(),
+            status: JobStatus::Queued,
+            progress: None,
+            current_node: None,
+            step: None,
+            total: None,
+        }
+    }
+
+    #[test]
+    fn test_apply_message_tracks_our_prompt() {
+        let mut progress = queued("abc");
+
+        // Another client's prompt
+        let other = r#"{"type":"executing","data":{"node":"3","prompt_id":"xyz"}}"#;
+        assert_eq!(apply_message(other, &mut progress), SocketEvent::Ignored);
+        // Progress without a prompt_id before ours has started
+        let anonymous = r#"{"type":"progress","data":{"value":1,"max":30}}"#;
+        assert_eq!(apply_message(anonymous, &mut progress), SocketEvent::Ignored);
+
+        let executing = r#"{"type":"executing","data":{"node":"3","prompt_id":"abc"}}"#;
+        assert_eq!(apply_message(executing, &mut progress), SocketEvent::Updated);
+        assert_eq!(progress.status, JobStatus::Running);
+        assert_eq!(progress.current_node.as_deref(), Some("3"));
+
+        assert_eq!(apply_message(r#"{"type":"progress","data":{"value":12,"max":30}}"#, &mut progress), SocketEvent::Updated);
+        assert_eq!((progress.step, progress.total), (Some(12), Some(30)));
+        assert_eq!(progress.progress, Some(0.4));
+
+        let done = r#"{"type":"executing","data":{"node":null,"prompt_id":"abc"}}"#;
+        assert_eq!(apply_message(done, &mut progress), SocketEvent::Done);
+        assert_eq!(progress.status, JobStatus::Completed);
+    }
+
+    #[test]
+    fn test_apply_message_execution_error() {
+        let mut progress = queued("abc");
+        let error = r#"{"type":"execution_error","data":{"prompt_id":"abc","node_id":"12",
+            "node_type":"IPAdapterFaceID","exception_message":"No face detected\n"}}"#;
+        match apply_message(error, &mut progress) {
+            SocketEvent::Failed(msg) => {
+                assert!(msg.contains("IPAdapterFaceID"));
+                assert!(msg.ends_with("No face detected"));
+            }
+            other => panic!("expected failure, got {:?}", other),
+        }
+        assert_eq!(progress.status, JobStatus::Failed);
+    }
+
+    #[test]
+    fn test_parse_preview() {
+        let mut frame = vec![0, 0, 0, 1, 0, 0, 0, 1];
+        frame.extend_from_slice(&[0xFF, 0xD8, 0xFF]);
+        let preview = parse_preview(&frame).unwrap();
+        assert_eq!(preview.mime, "image/jpeg");
+        assert_eq!(preview.bytes, vec![0xFF, 0xD8, 0xFF]);
+
+        // Other binary events and truncated frames
+        assert_eq!(parse_preview(&[0, 0, 0, 2, 0, 0, 0, 1, 9]), None);
+        assert_eq!(parse_preview(&[0, 0, 0, 1]), None);
+    }
+}
@@ -0,0 +1,276 @@
+// src-tauri/src/image_gen/comfyui/requirements.rs
+//
+// Workflow requirement checks
+// ============================
+// Before a workflow is queued, every node class and every enumerated model
+// input in it (ckpt_name, control_net_name, model_name, ...) is checked
+// against ComfyUI's /object_info. Without this a missing custom node or
+// model file only shows up as an execution error after the job has run.
+//
+// Missing items are grouped into node packs and model files, and mapped to
+// the `services::setup` dependency that installs them (the name passed to
+// `install_dependency`), so the frontend can offer a one-click fix.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::client::{fetch_object_info, ComfyError};
+
+// ============================================================================
+// TYPES
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RequirementKind {
+    /// A custom node pack providing node classes ComfyUI doesn't have
+    NodePack,
+    /// A model file missing from an enumerated input's options
+    Model,
+}
+
+/// A node pack or model file the workflow needs and ComfyUI doesn't have.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MissingRequirement {
+    pub kind: RequirementKind,
+    /// Node pack ("ComfyUI_IPAdapter_plus"), or the node class when the pack
+    /// isn't known; model file name ("OpenPoseXL2.safetensors")
+    pub name: String,
+    /// `services::setup` dependency that installs it, for `install_dependency`
+    pub installer: Option<String>,
+    /// What needs it: node classes for a pack, "Class.input" for a model
+    pub required_by: Vec<String>,
+}
+
+impl std::fmt::Display for MissingRequirement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            RequirementKind::NodePack => write!(f, "node pack {} ({})", self.name, self.required_by.join(", ")),
+            RequirementKind::Model => write!(f, "model {}", self.name),
+        }
+    }
+}
+
+// ============================================================================
+// INSTALLERS
+// ============================================================================
+
+/// Node pack and installer providing a node class, for the packs setup installs.
+fn node_pack(class_type: &str) -> Option<(&'static str, &'static str)> {
+    if class_type.starts_with("IPAdapter") {
+        Some(("ComfyUI_IPAdapter_plus", "custom_node_ipadapter"))
+    } else if class_type.ends_with("Preprocessor") {
+        Some(("comfyui_controlnet_aux", "custom_node_controlnet_aux"))
+    } else {
+        None
+    }
+}
+
+/// Model files `services::setup` downloads, by the installer that does it.
+const MODEL_INSTALLERS: &[(&str, &str)] = &[
+    ("juggernautXL_ragnarokBy.safetensors", "checkpoint_juggernaut"),
+    ("animagine-xl-3.1.safetensors", "checkpoint_animagine"),
+    ("OpenPoseXL2.safetensors", "controlnet_openpose_model"),
+    ("ip-adapter-faceid-plusv2_sdxl.bin", "ipadapter_faceid_model"),
+    ("ip-adapter-faceid-plusv2_sdxl_lora.safetensors", "ipadapter_faceid_lora"),
+    ("CLIP-ViT-H-14-laion2B-s32B-b79K.safetensors", "clip_vision"),
+    ("buffalo_l", "insightface_buffalo_l"),
+];
+
+fn model_installer(file: &str) -> Option<&'static str> {
+    MODEL_INSTALLERS
+        .iter()
+        .find(|(name, _)| file.eq_ignore_ascii_case(name) || file.ends_with(&format!("/{}", name)))
+        .map(|(_, installer)| *installer)
+}
+
+/// Inputs that pick a model file. Other enumerated inputs (sampler_name,
+/// scheduler, LoadImage's file list) aren't checked.
+fn is_model_input(key: &str) -> bool {
+    key != "sampler_name" && (key.ends_with("_name") || key.ends_with("_file"))
+}
+
+// ============================================================================
+// CHECK
+// ============================================================================
+
+/// Options of an enumerated input spec: `[["a", "b"], {...}]`, or the newer
+/// `["COMBO", { "options": ["a", "b"] }]`.
+fn enum_options(spec: &Value) -> Option<&Vec<Value>> {
+    match spec.get(0)? {
+        Value::Array(options) => Some(options),
+        Value::String(kind) if kind == "COMBO" => spec.pointer("/1/options")?.as_array(),
+        _ => None,
+    }
+}
+
+fn input_spec<'a>(class_info: &'a Value, key: &str) -> Option<&'a Value> {
+    ["required", "optional"]
+        .iter()
+        .find_map(|group| class_info.pointer(&format!("/input/{}/{}", group, key)))
+}
+
+/// Add `required_by` to the entry for `name`, creating it if needed.
+fn record(
+    missing: &mut Vec<MissingRequirement>,
+    kind: RequirementKind,
+    name: &str,
+    installer: Option<&str>,
+    required_by: String,
+) {
+    match missing.iter_mut().find(|m| m.kind == kind && m.name == name) {
+        Some(entry) => {
+            if !entry.required_by.contains(&required_by) {
+                entry.required_by.push(required_by);
+            }
+        }
+        None => missing.push(MissingRequirement {
+            kind,
+            name: name.to_string(),
+            installer: installer.map(String::from),
+            required_by: vec![required_by],
+        }),
+    }
+}
+
+/// Check a workflow in API format against an /object_info response.
+pub fn check_workflow(workflow: &Value, object_info: &Value) -> Vec<MissingRequirement> {
+    let mut missing = Vec::new();
+    let Some(nodes) = workflow.as_object() else {
+        return missing;
+    };
+
+    for node in nodes.values() {
+        let Some(class_type) = node.get("class_type").and_then(|c| c.as_str()) else {
+            continue;
+        };
+        let Some(class_info) = object_info.get(class_type) else {
+            match node_pack(class_type) {
+                Some((pack, installer)) => {
+                    record(&mut missing, RequirementKind::NodePack, pack, Some(installer), class_type.to_string())
+                }
+                None => record(&mut missing, RequirementKind::NodePack, class_type, None, class_type.to_string()),
+            }
+            continue;
+        };
+
+        let Some(inputs) = node.get("inputs").and_then(|i| i.as_object()) else {
+            continue;
+        };
+        for (key, value) in inputs {
+            let Some(file) = value.as_str().filter(|_| is_model_input(key)) else {
+                continue;
+            };
+            let Some(options) = input_spec(class_info, key).and_then(enum_options) else {
+                continue;
+            };
+            if !options.iter().any(|o| o.as_str() == Some(file)) {
+                record(
+                    &mut missing,
+                    RequirementKind::Model,
+                    file,
+                    model_installer(file),
+                    format!("{}.{}", class_type, key),
+                );
+            }
+        }
+    }
+
+    missing
+}
+
+/// Check a workflow against the ComfyUI at `base_url`. Uses the cached
+/// /object_info, refetching once when something looks missing in case it
+/// was installed since.
+pub async fn check_requirements(base_url: &str, workflow: &Value) -> Result<Vec<MissingRequirement>, ComfyError> {
+    let info = fetch_object_info(base_url, false).await?;
+    let missing = check_workflow(workflow, &info);
+    if missing.is_empty() {
+        return Ok(missing);
+    }
+    let info = fetch_object_info(base_url, true).await?;
+    Ok(check_workflow(workflow, &info))
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{AppError, ErrorKind};
+    use serde_json::json;
+
+    fn object_info() -> Value {
+        json!({
+            "CheckpointLoaderSimple": { "input": { "required": {
+                "ckpt_name": [["juggernautXL_ragnarokBy.safetensors"], {}] } } },
+            "ControlNetLoader": { "input": { "required": {
+                "control_net_name": ["COMBO", { "options": ["control_v11p_sd15_openpose.pth"] }] } } },
+            "KSampler": { "input": { "required": {
+                "sampler_name": [["euler"], {}], "seed": ["INT", { "default": 0 }] } } },
+            "LoadImage": { "input": { "required": { "image": [["example.png"], {}] } } }
+        })
+    }
+
+    #[test]
+    fn test_check_workflow_finds_missing_packs_and_models() {
+        let workflow = json!({
+            "1": { "class_type": "CheckpointLoaderSimple", "inputs": { "ckpt_name": "juggernautXL_ragnarokBy.safetensors" } },
+            "2": { "class_type": "ControlNetLoader", "inputs": { "control_net_name": "OpenPoseXL2.safetensors" } },
+            "3": { "class_type": "IPAdapterUnifiedLoaderFaceID", "inputs": { "model": ["1", 0] } },
+            "4": { "class_type": "IPAdapterFaceID", "inputs": { "model": ["3", 0] } },
+            "5": { "class_type": "IPAdapterFaceID", "inputs": { "model": ["4", 0] } },
+            "6": { "class_type": "MysteryNode", "inputs": {} }
+        });
+
+        let mut missing = check_workflow(&workflow, &object_info());
+        missing.sort_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!(missing.len(), 3);
+
+        let pack = missing.iter().find(|m| m.name == "ComfyUI_IPAdapter_plus").unwrap();
+        assert_eq!(pack.kind, RequirementKind::NodePack);
+        assert_eq!(pack.installer.as_deref(), Some("custom_node_ipadapter"));
+        assert_eq!(pack.required_by.len(), 2);
+
+        let model = missing.iter().find(|m| m.name == "OpenPoseXL2.safetensors").unwrap();
+        assert_eq!(model.kind, RequirementKind::Model);
+        assert_eq!(model.installer.as_deref(), Some("controlnet_openpose_model"));
+        assert_eq!(model.required_by, vec!["ControlNetLoader.control_net_name"]);
+
+        let unknown = missing.iter().find(|m| m.name == "MysteryNode").unwrap();
+        assert_eq!(unknown.installer, None);
+    }
+
+    #[test]
+    fn test_check_workflow_ignores_non_model_inputs() {
+        let workflow = json!({
+            "1": { "class_type": "KSampler", "inputs": { "sampler_name": "dpmpp_2m_sde", "seed": 7 } },
+            "2": { "class_type": "LoadImage", "inputs": { "image": "ref_alice_0.png" } }
+        });
+        assert!(check_workflow(&workflow, &object_info()).is_empty());
+    }
+
+    #[test]
+    fn test_missing_requirements_error_names_installers() {
+        let requirement = |name: &str, installer: Option<&str>| MissingRequirement {
+            kind: RequirementKind::Model,
+            name: name.to_string(),
+            installer: installer.map(String::from),
+            required_by: vec![],
+        };
+
+        let err = AppError::from(ComfyError::MissingRequirements(vec![requirement(
+            "OpenPoseXL2.safetensors",
+            Some("controlnet_openpose_model"),
+        )]));
+        assert_eq!(err.kind, ErrorKind::ComfyMissingNode);
+        assert!(err.message.contains("OpenPoseXL2.safetensors"));
+        assert!(err.remediation.unwrap().contains("controlnet_openpose_model"));
+
+        // Setup can't install everything: keep the generic advice
+        let err = AppError::from(ComfyError::MissingRequirements(vec![requirement("custom.safetensors", None)]));
+        assert_eq!(err.remediation.as_deref(), ErrorKind::ComfyMissingNode.remediation());
+    }
+}
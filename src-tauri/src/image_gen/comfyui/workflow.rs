@@ -1,24 +1,80 @@
 // src-tauri/src/image_gen/comfyui/workflow.rs
 //
-// Workflow template operations
-// ==============================
-// Load, inspect, and modify ComfyUI workflow JSON templates.
-
-use serde_json::Value;
+// Workflow construction
+// ======================
+// Builds the scene graph in code (see graph.rs): checkpoint, one IPAdapter
+// FaceID branch per character, prompts, optional ControlNet pose guidance,
+// sampler, hires fix and output. ControlNet and hires fix are builder stages
+// that take and return links, so they also apply to custom workflow
+// templates, whose nodes are found through their manifest (manifest.rs).
+
+use serde_json::{json, Value};
 use std::collections::HashMap;
 use std::path::Path;
 
 use super::client::ComfyError;
+use super::graph::{Graph, Link, NodeId};
+use super::manifest::WorkflowManifest;
 use super::pipeline::ImageGenRequest;
 
+/// Most characters a scene workflow holds — one per named scene region
+/// (see masks::region_to_rect).
+pub const MAX_SCENE_CHARACTERS: usize = 9;
+
+/// Negative prompt used when the request doesn't set one.
+const DEFAULT_NEGATIVE_PROMPT: &str = "(cropped head:1.5), (head out of frame:1.5), (cut off head:1.5), (headless:1.5), decapitated, \
+     (worst quality, low quality:1.4), (bad anatomy:1.3), (bad hands:1.4), \
+     (missing fingers:1.3), (extra fingers:1.3), (too many fingers:1.4), \
+     (fused fingers:1.3), (poorly drawn hands:1.4), (floating limbs:1.3), \
+     (disconnected limbs:1.3), (extra limbs:1.3), (missing arms:1.2), \
+     (extra arms:1.2), (deformed:1.3), (mutated:1.2), (disfigured:1.2), \
+     (malformed:1.2), blurry, lowres, watermark, text, signature, cropped, \
+     out of frame, ugly, duplicate, cloned face, poorly drawn face, \
+     (floating head:1.4), (detached head:1.4), (severed head:1.3), \
+     bad proportions, gross proportions, long neck, (mutation:1.2), \
+     (asymmetric eyes:1.4), (crossed eyes:1.4), (lazy eye:1.3), \
+     (uneven eyes:1.4), (different sized eyes:1.4), (misaligned eyes:1.4), \
+     (bad eyes:1.3), (poorly drawn eyes:1.3), (extra eyes:1.3), \
+     (bad iris:1.3), (bad pupils:1.3), (distorted pupils:1.3), \
+     (dead eyes:1.2), (empty eyes:1.2), \
+     (bad face:1.3), (asymmetric face:1.3), (distorted face:1.3), \
+     (cross-eyed:1.4), (wall-eyed:1.3), (strabismus:1.4), \
+     (unfocused eyes:1.3), (different colored eyes:1.3), (heterochromia:1.2), \
+     (wonky eyes:1.3), (derpy eyes:1.3), \
+     close up, closeup, headshot, upper body only, face only, \
+     portrait crop, zoomed in, \
+     masculine features, strong jawline, cleft chin, square jaw, \
+     angular face, manly, \
+     standing when should be sitting, standing when should be lying down, \
+     stiff pose, t-pose, a-pose, mannequin pose, \
+     (blurry eyes:1.4), (unfocused eyes:1.3), (glossy eyes:1.2), \
+     (plastic skin:1.3), (waxy skin:1.2), (airbrushed:1.3), (smooth skin:1.2)";
+
+/// Checkpoint the scene graph renders with.
+const SCENE_CHECKPOINT: &str = "juggernautXL_ragnarokBy.safetensors";
+
+/// dpmpp_2m_sde + karras produces the sharpest facial detail with JuggernautXL.
+const SCENE_SAMPLER: &str = "dpmpp_2m_sde";
+const SCENE_SCHEDULER: &str = "karras";
+const DEFAULT_STEPS: u32 = 30;
+const DEFAULT_CFG: f64 = 5.5;
+pub(super) const DEFAULT_CONTROLNET_STRENGTH: f64 = 0.85;
+
+/// Hires fix: conservative 1.5x upscale, 0.45 denoise, 15 steps. Keeps
+/// generation time reasonable while dramatically improving face/eye quality.
+const HIRES_UPSCALE: f64 = 1.5;
+const HIRES_DENOISE: f64 = 0.45;
+const HIRES_STEPS: u32 = 15;
+
 // ============================================================================
 // TEMPLATE I/O
 // ============================================================================
 
 /// Load a workflow JSON template from disk.
 pub(super) fn load_workflow_template(path: &Path) -> Result<Value, ComfyError> {
-    let content = std::fs::read_to_string(path).map_err(|e| {
-        ComfyError::WorkflowLoadFailed(format!("Cannot read {}: {}", path.display(), e))
+    let content = std::fs::read_to_string(path).map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => ComfyError::WorkflowNotFound(path.display().to_string()),
+        _ => ComfyError::WorkflowLoadFailed(format!("Cannot read {}: {}", path.display(), e)),
     })?;
 
     serde_json::from_str(&content).map_err(|e| {
@@ -65,285 +121,439 @@ pub(super) fn modify_workflow(
 /// Build node modifications for the workflow template based on the request.
 ///
 /// Creates a mapping of node_id → { input_key: value } that will be injected
-/// into the workflow JSON. Node IDs must match the template.
-///
-/// Node ID conventions (matching workflow JSON):
-///   - "2" = positive prompt, "3" = negative prompt, "4" = empty latent
-///   - "20","21" = character reference image loaders
-///   - "40","41" = per-character mask image loaders
-///   - "35" = KSampler (seed, steps, cfg)
+/// into the workflow JSON. Node ids come from the template's `roles`:
+///   - positive_prompt / negative_prompt → "text"
+///   - latent → width, height
+///   - sampler → seed, steps, cfg, sampler_name, scheduler
+///   - insightface → model_name
+///   - reference_images[i] / masks[i] → the uploaded image of character i
 pub(super) fn build_workflow_modifications(
     request: &ImageGenRequest,
     uploaded_refs: &[String],
     mask_filenames: &[String],
+    roles: &WorkflowManifest,
 ) -> HashMap<String, HashMap<String, Value>> {
     let mut mods: HashMap<String, HashMap<String, Value>> = HashMap::new();
+    let mut set = |node_id: Option<&String>, key: &str, value: Value| {
+        if let Some(node_id) = node_id {
+            mods.entry(node_id.clone()).or_default().insert(key.to_string(), value);
+        }
+    };
 
-    // --- Positive prompt ---
-    let mut positive_inputs = HashMap::new();
-    positive_inputs.insert("text".to_string(), Value::String(request.scene_prompt.clone()));
-    mods.insert("2".to_string(), positive_inputs);
-
-    // --- Negative prompt ---
-    let neg_text = request.negative_prompt.as_deref().unwrap_or(
-        "(cropped head:1.5), (head out of frame:1.5), (cut off head:1.5), (headless:1.5), decapitated, \
-         (worst quality, low quality:1.4), (bad anatomy:1.3), (bad hands:1.4), \
-         (missing fingers:1.3), (extra fingers:1.3), (too many fingers:1.4), \
-         (fused fingers:1.3), (poorly drawn hands:1.4), (floating limbs:1.3), \
-         (disconnected limbs:1.3), (extra limbs:1.3), (missing arms:1.2), \
-         (extra arms:1.2), (deformed:1.3), (mutated:1.2), (disfigured:1.2), \
-         (malformed:1.2), blurry, lowres, watermark, text, signature, cropped, \
-         out of frame, ugly, duplicate, cloned face, poorly drawn face, \
-         (floating head:1.4), (detached head:1.4), (severed head:1.3), \
-         bad proportions, gross proportions, long neck, (mutation:1.2), \
-         (asymmetric eyes:1.4), (crossed eyes:1.4), (lazy eye:1.3), \
-         (uneven eyes:1.4), (different sized eyes:1.4), (misaligned eyes:1.4), \
-         (bad eyes:1.3), (poorly drawn eyes:1.3), (extra eyes:1.3), \
-         (bad iris:1.3), (bad pupils:1.3), (distorted pupils:1.3), \
-         (dead eyes:1.2), (empty eyes:1.2), \
-         (bad face:1.3), (asymmetric face:1.3), (distorted face:1.3), \
-         (cross-eyed:1.4), (wall-eyed:1.3), (strabismus:1.4), \
-         (unfocused eyes:1.3), (different colored eyes:1.3), (heterochromia:1.2), \
-         (wonky eyes:1.3), (derpy eyes:1.3), \
-         close up, closeup, headshot, upper body only, face only, \
-         portrait crop, zoomed in, \
-         masculine features, strong jawline, cleft chin, square jaw, \
-         angular face, manly, \
-         standing when should be sitting, standing when should be lying down, \
-         stiff pose, t-pose, a-pose, mannequin pose, \
-         (blurry eyes:1.4), (unfocused eyes:1.3), (glossy eyes:1.2), \
-         (plastic skin:1.3), (waxy skin:1.2), (airbrushed:1.3), (smooth skin:1.2)"
-    );
-    let mut neg_inputs = HashMap::new();
-    neg_inputs.insert("text".to_string(), Value::String(neg_text.to_string()));
-    mods.insert("3".to_string(), neg_inputs);
+    // --- Prompts ---
+    set(roles.positive_prompt.as_ref(), "text", Value::String(request.scene_prompt.clone()));
+    let neg_text = request.negative_prompt.as_deref().unwrap_or(DEFAULT_NEGATIVE_PROMPT);
+    set(roles.negative_prompt.as_ref(), "text", Value::String(neg_text.to_string()));
 
     // --- Empty latent dimensions ---
-    if request.width.is_some() || request.height.is_some() {
-        let mut latent_inputs = HashMap::new();
-        if let Some(w) = request.width {
-            latent_inputs.insert("width".to_string(), Value::Number(w.into()));
-        }
-        if let Some(h) = request.height {
-            latent_inputs.insert("height".to_string(), Value::Number(h.into()));
-        }
-        mods.insert("4".to_string(), latent_inputs);
+    if let Some(w) = request.width {
+        set(roles.latent.as_ref(), "width", Value::Number(w.into()));
+    }
+    if let Some(h) = request.height {
+        set(roles.latent.as_ref(), "height", Value::Number(h.into()));
     }
 
     // --- KSampler ---
-    let mut ksampler_inputs = HashMap::new();
+    let sampler = roles.sampler.as_ref();
     if let Some(seed) = request.seed {
-        ksampler_inputs.insert(
-            "seed".to_string(),
-            Value::Number(serde_json::Number::from(seed)),
-        );
+        set(sampler, "seed", Value::Number(serde_json::Number::from(seed)));
     }
     if let Some(steps) = request.steps {
-        ksampler_inputs.insert("steps".to_string(), Value::Number(steps.into()));
+        set(sampler, "steps", Value::Number(steps.into()));
     }
-    if let Some(cfg) = request.cfg {
-        if let Some(n) = serde_json::Number::from_f64(cfg) {
-            ksampler_inputs.insert("cfg".to_string(), Value::Number(n));
-        }
+    if let Some(n) = request.cfg.and_then(serde_json::Number::from_f64) {
+        set(sampler, "cfg", Value::Number(n));
     }
     // Always set sampler and scheduler for consistent quality.
-    // dpmpp_2m_sde + karras produces the sharpest facial detail with JuggernautXL.
-    ksampler_inputs.insert("sampler_name".to_string(), Value::String("dpmpp_2m_sde".to_string()));
-    ksampler_inputs.insert("scheduler".to_string(), Value::String("karras".to_string()));
-    if !ksampler_inputs.is_empty() {
-        mods.insert("35".to_string(), ksampler_inputs);
-    }
+    set(sampler, "sampler_name", Value::String(SCENE_SAMPLER.to_string()));
+    set(sampler, "scheduler", Value::String(SCENE_SCHEDULER.to_string()));
 
-    // --- InsightFace model name (node 12: IPAdapterInsightFaceLoader) ---
-    {
-        let mut inputs = HashMap::new();
-        inputs.insert("model_name".to_string(), Value::String("buffalo_l".to_string()));
-        mods.insert("12".to_string(), inputs);
-    }
+    // --- InsightFace model name (IPAdapterInsightFaceLoader) ---
+    set(roles.insightface.as_ref(), "model_name", Value::String("buffalo_l".to_string()));
 
-    // --- Character reference images (nodes 20, 21) ---
-    let ref_node_ids = ["20", "21"];
-    for (i, ref_filename) in uploaded_refs.iter().enumerate() {
-        if i >= ref_node_ids.len() {
-            break;
-        }
-        let mut inputs = HashMap::new();
-        inputs.insert("image".to_string(), Value::String(ref_filename.clone()));
-        mods.insert(ref_node_ids[i].to_string(), inputs);
+    // --- Character reference images and masks ---
+    for (node_id, ref_filename) in roles.reference_images.iter().zip(uploaded_refs) {
+        set(Some(node_id), "image", Value::String(ref_filename.clone()));
     }
-
-    // --- Per-character mask images (nodes 40, 41) ---
-    let mask_node_ids = ["40", "41"];
-    for (i, mask_name) in mask_filenames.iter().enumerate() {
-        if i >= mask_node_ids.len() {
-            break;
-        }
-        let mut inputs = HashMap::new();
-        inputs.insert("image".to_string(), Value::String(mask_name.clone()));
-        mods.insert(mask_node_ids[i].to_string(), inputs);
+    for (node_id, mask_name) in roles.masks.iter().zip(mask_filenames) {
+        set(Some(node_id), "image", Value::String(mask_name.clone()));
     }
 
     mods
 }
 
 // ============================================================================
-// CONTROLNET INJECTION
+// PER-CHARACTER BRANCHES
 // ============================================================================
 
-/// Inject ControlNet nodes into the workflow for pose guidance.
+/// Point a node's link input at another node, keeping the output slot.
+fn relink(graph: &mut Graph, id: &NodeId, input: &str, source: &NodeId) {
+    if let Some(link) = graph.input_link(id, input) {
+        graph.set_input(id, input, source.out(link.slot));
+    }
+}
+
+/// Fit a multi-character template to the scene: one IPAdapter FaceID branch
+/// per character.
 ///
-/// Adds 3 nodes:
-///   Node "60" — ControlNetLoader: loads the OpenPose SDXL model
-///   Node "61" — LoadImage: loads the skeleton image (already uploaded to ComfyUI)
-///   Node "62" — ControlNetApplyAdvanced: applies conditioning
+/// Extra branches are bypassed — whatever took an unused IPAdapter node's
+/// model takes that node's model input instead — and removed along with
+/// their reference and mask loaders, so no placeholder face is applied.
 ///
-/// Rewires KSampler (node "35") so positive/negative come from the ControlNet
-/// apply node instead of directly from the CLIP encoders.
-pub(super) fn inject_controlnet(
+/// Missing branches are cloned from the template's last one — the IPAdapter
+/// node feeding the sampler's model, its reference loader, and its attention
+/// mask chain (mask loader, plus e.g. an ImageToMask in between). The new
+/// loaders are appended to `roles` so `build_workflow_modifications` fills
+/// them. Each new IPAdapter node takes the previous one's model, and the
+/// sampler is rewired to the last.
+pub(super) fn fit_character_branches(
     workflow: &mut Value,
-    skeleton_filename: &str,
-    strength: f64,
+    roles: &mut WorkflowManifest,
+    num_chars: usize,
 ) -> Result<(), ComfyError> {
-    let obj = workflow
-        .as_object_mut()
-        .ok_or_else(|| ComfyError::WorkflowLoadFailed("Workflow root is not an object".into()))?;
+    if num_chars > MAX_SCENE_CHARACTERS {
+        return Err(ComfyError::WorkflowLoadFailed(format!(
+            "Scene workflows support at most {} characters, got {}",
+            MAX_SCENE_CHARACTERS, num_chars
+        )));
+    }
+    if roles.reference_images.len() > num_chars {
+        let mut graph = Graph::from_json(workflow).map_err(ComfyError::WorkflowLoadFailed)?;
+        drop_character_branches(&mut graph, roles, num_chars)?;
+        *workflow = graph.to_json();
+        return Ok(());
+    }
+    let existing = roles.reference_images.len().min(roles.masks.len());
+    if existing >= num_chars {
+        return Ok(());
+    }
+    let mut graph = Graph::from_json(workflow).map_err(ComfyError::WorkflowLoadFailed)?;
 
-    // Node 60: ControlNetLoader
-    obj.insert("60".to_string(), serde_json::json!({
-        "class_type": "ControlNetLoader",
-        "inputs": {
-            "control_net_name": "OpenPoseXL2.safetensors"
-        }
-    }));
+    let not_extendable = |why: &str| {
+        ComfyError::WorkflowLoadFailed(format!(
+            "Workflow has {} character branch(es) and can't be extended to {}: {}",
+            existing, num_chars, why
+        ))
+    };
 
-    // Node 61: LoadImage — the uploaded skeleton PNG
-    obj.insert("61".to_string(), serde_json::json!({
-        "class_type": "LoadImage",
-        "inputs": {
-            "image": skeleton_filename,
-            "upload": "image"
-        }
-    }));
+    // Locate the template's last branch
+    let sampler = roles.sampler.clone().map(NodeId::new).ok_or_else(|| not_extendable("no sampler node"))?;
+    let adapter = graph
+        .input_link(&sampler, "model")
+        .ok_or_else(|| not_extendable("the sampler has no model input"))?
+        .node;
+    if !graph.contains(&adapter) {
+        return Err(not_extendable("the sampler's model node is missing"));
+    }
+    let linked = |input: &str| graph.input_link(&adapter, input).map(|l| l.node).filter(|id| graph.contains(id));
+    let ref_loader = linked("image")
+        .ok_or_else(|| not_extendable("the sampler's model is not an IPAdapter node with a reference image"))?;
+    let mask_source = linked("attn_mask").ok_or_else(|| not_extendable("the last IPAdapter node has no attention mask"))?;
+
+    // The mask either comes straight from a loader or through one conversion node
+    let mask_loader = match graph.input_link(&mask_source, "image") {
+        Some(link) => Some(link.node).filter(|id| graph.contains(id)),
+        None => Some(mask_source.clone()),
+    }
+    .ok_or_else(|| not_extendable("the attention mask has no image loader"))?;
+    let mask_converter = (mask_loader != mask_source).then_some(mask_source);
+
+    let copy = |graph: &mut Graph, id: &NodeId| graph.duplicate(id).ok_or_else(|| not_extendable("a branch node is missing"));
+
+    roles.reference_images.truncate(existing);
+    roles.masks.truncate(existing);
+    let mut model_source = adapter.clone();
+    for _ in existing..num_chars {
+        let ref_id = copy(&mut graph, &ref_loader)?;
+        let mask_id = copy(&mut graph, &mask_loader)?;
+
+        let mask_link = match &mask_converter {
+            Some(converter) => {
+                let converter_id = copy(&mut graph, converter)?;
+                relink(&mut graph, &converter_id, "image", &mask_id);
+                converter_id
+            }
+            None => mask_id.clone(),
+        };
 
-    // Node 62: ControlNetApplyAdvanced
-    obj.insert("62".to_string(), serde_json::json!({
-        "class_type": "ControlNetApplyAdvanced",
-        "inputs": {
-            "positive":    ["2",  0],
-            "negative":    ["3",  0],
-            "control_net": ["60", 0],
-            "image":       ["61", 0],
-            "strength":    strength,
-            "start_percent": 0.0,
-            "end_percent":   0.8
-        }
-    }));
+        let new_adapter = copy(&mut graph, &adapter)?;
+        relink(&mut graph, &new_adapter, "model", &model_source);
+        relink(&mut graph, &new_adapter, "image", &ref_id);
+        relink(&mut graph, &new_adapter, "attn_mask", &mask_link);
+        model_source = new_adapter;
 
-    // Rewire KSampler (node "35"): positive → ["62", 0], negative → ["62", 1]
-    if let Some(node_35) = obj.get_mut("35") {
-        if let Some(inputs) = node_35.get_mut("inputs") {
-            if let Some(pos) = inputs.get_mut("positive") {
-                if pos.as_array()
-                    .and_then(|a| a.first())
-                    .and_then(|v| v.as_str())
-                    == Some("2")
-                {
-                    *pos = serde_json::json!(["62", 0]);
+        roles.reference_images.push(ref_id.as_str().to_string());
+        roles.masks.push(mask_id.as_str().to_string());
+    }
+    relink(&mut graph, &sampler, "model", &model_source);
+    *workflow = graph.to_json();
+
+    println!(
+        "[ComfyUI] Extended workflow from {} to {} character branches",
+        existing, num_chars
+    );
+    Ok(())
+}
+
+/// Bypass and remove the branches of characters `keep..`, last first.
+fn drop_character_branches(graph: &mut Graph, roles: &mut WorkflowManifest, keep: usize) -> Result<(), ComfyError> {
+    let total = roles.reference_images.len();
+    for i in (keep..total).rev() {
+        let ref_loader = NodeId::new(roles.reference_images[i].clone());
+        let adapter = graph
+            .links_from(&ref_loader)
+            .into_iter()
+            .find(|(id, input, _)| input == "image" && graph.input_link(id, "model").is_some())
+            .map(|(id, _, _)| id)
+            .ok_or_else(|| {
+                ComfyError::WorkflowLoadFailed(format!(
+                    "Workflow has {} character branches and can't be cut down to {}: reference {} feeds no IPAdapter node",
+                    total, keep, ref_loader.as_str()
+                ))
+            })?;
+
+        // Whatever took the adapter's model takes the adapter's own model input
+        if let Some(model) = graph.input_link(&adapter, "model") {
+            for (id, input, link) in graph.links_from(&adapter) {
+                if link.slot == 0 {
+                    graph.set_input(&id, &input, &model);
                 }
             }
-            if let Some(neg) = inputs.get_mut("negative") {
-                if neg.as_array()
-                    .and_then(|a| a.first())
-                    .and_then(|v| v.as_str())
-                    == Some("3")
-                {
-                    *neg = serde_json::json!(["62", 1]);
-                }
+        }
+        let mask_source = graph.input_link(&adapter, "attn_mask").map(|l| l.node);
+        graph.remove(&adapter);
+
+        // Then the branch's loaders (and mask converter) nothing else uses
+        let mask_loader = roles.masks.get(i).cloned().map(NodeId::new);
+        for id in [Some(ref_loader), mask_source, mask_loader].into_iter().flatten() {
+            if graph.links_from(&id).is_empty() {
+                graph.remove(&id);
             }
         }
     }
 
-    println!(
-        "[ComfyUI] Injected ControlNet nodes (60,61,62): skeleton={}, strength={}",
-        skeleton_filename, strength
-    );
+    roles.reference_images.truncate(keep);
+    roles.masks.truncate(keep);
+    println!("[ComfyUI] Dropped {} unused character branch(es) from workflow", total - keep);
     Ok(())
 }
 
 // ============================================================================
-// HIRES FIX INJECTION
+// BUILDER STAGES
 // ============================================================================
 
-/// Inject a hi-res fix (latent upscale + second sampling pass) into the workflow.
-///
-/// This mimics A1111's "Hires. fix" feature:
-///   Node "70" — LatentUpscaleBy: upscales the first-pass latent by `upscale_factor`
-///   Node "71" — KSampler: re-denoises the upscaled latent at low denoise strength
-///
-/// Rewires the VAEDecode (node "6") to read from the second KSampler instead of the first.
-/// The second pass inherits the same model and conditioning sources as the first KSampler.
-pub(super) fn inject_hires_fix(
-    workflow: &mut Value,
-    upscale_factor: f64,
-    denoise_strength: f64,
-    hires_steps: u32,
-) -> Result<(), ComfyError> {
-    let obj = workflow
-        .as_object_mut()
-        .ok_or_else(|| ComfyError::WorkflowLoadFailed("Workflow root is not an object".into()))?;
+/// Positive and negative conditioning feeding the samplers.
+#[derive(Debug, Clone)]
+pub(super) struct Conditioning {
+    pub positive: Link,
+    pub negative: Link,
+}
 
-    // Determine what the first KSampler's positive/negative sources are.
-    // If ControlNet was injected, KSampler "35" reads from ["62", 0/1].
-    // Otherwise it reads from ["2", 0] and ["3", 0].
-    let (pos_source, neg_source) = if let Some(node_35) = obj.get("35") {
-        let pos = node_35.pointer("/inputs/positive").cloned().unwrap_or(serde_json::json!(["2", 0]));
-        let neg = node_35.pointer("/inputs/negative").cloned().unwrap_or(serde_json::json!(["3", 0]));
-        (pos, neg)
-    } else {
-        (serde_json::json!(["2", 0]), serde_json::json!(["3", 0]))
-    };
+/// Add one IPAdapter FaceID node per uploaded reference, chained on `model`.
+/// With masks, each face is confined to its character's mask (red channel).
+/// Returns the patched model.
+fn add_face_references(graph: &mut Graph, model: Link, uploaded_refs: &[String], mask_filenames: &[String]) -> Link {
+    let loader = graph.add("IPAdapterUnifiedLoaderFaceID", json!({
+        "model": model,
+        "preset": "FACEID PLUS V2",
+        "lora_strength": 0.6,
+        "provider": "CUDA"
+    }));
+    let insightface = graph.add("IPAdapterInsightFaceLoader", json!({
+        "provider": "CUDA",
+        "model_name": "buffalo_l"
+    }));
 
-    // Node 70: LatentUpscaleBy — upscale the first-pass latent output
-    obj.insert("70".to_string(), serde_json::json!({
-        "class_type": "LatentUpscaleBy",
-        "inputs": {
-            "samples": ["35", 0],
-            "upscale_method": "bilinear",
-            "scale_by": upscale_factor
+    let mut model = loader.out(0);
+    for (i, ref_filename) in uploaded_refs.iter().enumerate() {
+        let reference = graph.add("LoadImage", json!({ "image": ref_filename, "upload": "image" }));
+        let mut inputs = json!({
+            "model": model,
+            "ipadapter": loader.out(1),
+            "image": reference.out(0),
+            "insightface": insightface.out(0),
+            "weight": 0.85,
+            "weight_faceidv2": 1.0,
+            "weight_type": "linear",
+            "combine_embeds": "concat",
+            "start_at": 0.0,
+            "end_at": 1.0,
+            "embeds_scaling": "V only"
+        });
+        if let Some(mask_filename) = mask_filenames.get(i) {
+            let mask_image = graph.add("LoadImage", json!({ "image": mask_filename, "upload": "image" }));
+            let mask = graph.add("ImageToMask", json!({ "image": mask_image.out(0), "channel": "red" }));
+            inputs["attn_mask"] = json!(mask.out(0));
         }
+        model = graph.add("IPAdapterFaceID", inputs).out(0);
+    }
+    model
+}
+
+/// ControlNet pose guidance: applies the OpenPose SDXL model with the
+/// uploaded skeleton to both conditionings.
+pub(super) fn add_controlnet(
+    graph: &mut Graph,
+    conditioning: &Conditioning,
+    skeleton_filename: &str,
+    strength: f64,
+) -> Conditioning {
+    let control_net = graph.add("ControlNetLoader", json!({ "control_net_name": "OpenPoseXL2.safetensors" }));
+    let skeleton = graph.add("LoadImage", json!({ "image": skeleton_filename, "upload": "image" }));
+    let apply = graph.add("ControlNetApplyAdvanced", json!({
+        "positive": conditioning.positive,
+        "negative": conditioning.negative,
+        "control_net": control_net.out(0),
+        "image": skeleton.out(0),
+        "strength": strength,
+        "start_percent": 0.0,
+        "end_percent": 0.8
     }));
 
-    // Node 71: Second KSampler — re-denoise the upscaled latent
-    obj.insert("71".to_string(), serde_json::json!({
-        "class_type": "KSampler",
-        "inputs": {
-            "model": ["1", 0],
-            "positive": pos_source,
-            "negative": neg_source,
-            "latent_image": ["70", 0],
-            "seed": -1,
-            "steps": hires_steps,
-            "cfg": 5.5,
-            "sampler_name": "dpmpp_2m_sde",
-            "scheduler": "karras",
-            "denoise": denoise_strength
-        }
+    println!(
+        "[ComfyUI] Added ControlNet: skeleton={}, strength={}",
+        skeleton_filename, strength
+    );
+    Conditioning { positive: apply.out(0), negative: apply.out(1) }
+}
+
+/// Hi-res fix (mimics A1111's "Hires. fix"): upscale the first-pass latent
+/// and re-denoise it at low strength with the same model and conditioning.
+/// Returns the refined latent.
+pub(super) fn add_hires_fix(
+    graph: &mut Graph,
+    model: &Link,
+    conditioning: &Conditioning,
+    samples: Link,
+    seed: i64,
+) -> Link {
+    let upscaled = graph.add("LatentUpscaleBy", json!({
+        "samples": samples,
+        "upscale_method": "bilinear",
+        "scale_by": HIRES_UPSCALE
+    }));
+    let refined = graph.add("KSampler", json!({
+        "model": model,
+        "positive": conditioning.positive,
+        "negative": conditioning.negative,
+        "latent_image": upscaled.out(0),
+        "seed": seed,
+        "steps": HIRES_STEPS,
+        "cfg": DEFAULT_CFG,
+        "sampler_name": SCENE_SAMPLER,
+        "scheduler": SCENE_SCHEDULER,
+        "denoise": HIRES_DENOISE
     }));
 
-    // Rewire VAEDecode (node "6") to read from the second KSampler
-    if let Some(node_6) = obj.get_mut("6") {
-        if let Some(inputs) = node_6.get_mut("inputs") {
-            if let Some(samples) = inputs.get_mut("samples") {
-                *samples = serde_json::json!(["71", 0]);
-            }
+    println!(
+        "[ComfyUI] Added hires fix: scale={}x, denoise={}, steps={}",
+        HIRES_UPSCALE, HIRES_DENOISE, HIRES_STEPS
+    );
+    refined.out(0)
+}
+
+// ============================================================================
+// SCENE GRAPH
+// ============================================================================
+
+/// Build the scene workflow: one IPAdapter FaceID branch per uploaded
+/// reference (masked when `mask_filenames` are given), ControlNet when a
+/// skeleton was uploaded, and a hires fix pass.
+pub(super) fn build_scene_workflow(
+    request: &ImageGenRequest,
+    uploaded_refs: &[String],
+    mask_filenames: &[String],
+    skeleton_filename: Option<&str>,
+) -> Value {
+    let mut graph = Graph::new();
+    let seed = request.seed.unwrap_or(0);
+
+    let checkpoint = graph.add("CheckpointLoaderSimple", json!({ "ckpt_name": SCENE_CHECKPOINT }));
+    let mut model = checkpoint.out(0);
+    if !uploaded_refs.is_empty() {
+        let refs = &uploaded_refs[..uploaded_refs.len().min(MAX_SCENE_CHARACTERS)];
+        model = add_face_references(&mut graph, model, refs, mask_filenames);
+    }
+
+    let positive = graph.add("CLIPTextEncode", json!({ "clip": checkpoint.out(1), "text": request.scene_prompt }));
+    let negative = graph.add("CLIPTextEncode", json!({
+        "clip": checkpoint.out(1),
+        "text": request.negative_prompt.as_deref().unwrap_or(DEFAULT_NEGATIVE_PROMPT)
+    }));
+    let mut conditioning = Conditioning { positive: positive.out(0), negative: negative.out(0) };
+    if let Some(skeleton) = skeleton_filename {
+        let strength = request.controlnet_strength.unwrap_or(DEFAULT_CONTROLNET_STRENGTH);
+        conditioning = add_controlnet(&mut graph, &conditioning, skeleton, strength);
+    }
+
+    let latent = graph.add("EmptyLatentImage", json!({
+        "width": request.width.unwrap_or(1152),
+        "height": request.height.unwrap_or(896),
+        "batch_size": 1
+    }));
+    let sampler = graph.add("KSampler", json!({
+        "model": model,
+        "positive": conditioning.positive,
+        "negative": conditioning.negative,
+        "latent_image": latent.out(0),
+        "seed": seed,
+        "steps": request.steps.unwrap_or(DEFAULT_STEPS),
+        "cfg": request.cfg.unwrap_or(DEFAULT_CFG),
+        "sampler_name": SCENE_SAMPLER,
+        "scheduler": SCENE_SCHEDULER,
+        "denoise": 1.0
+    }));
+    let samples = add_hires_fix(&mut graph, &model, &conditioning, sampler.out(0), seed);
+
+    let image = graph.add("VAEDecode", json!({ "samples": samples, "vae": checkpoint.out(2) }));
+    graph.add("SaveImage", json!({ "images": image.out(0), "filename_prefix": "StoryEngine/scene" }));
+
+    graph.to_json()
+}
+
+/// Add the ControlNet and hires fix stages to a custom template, between its
+/// sampler and decode roles. Without a decode node the hires fix is skipped.
+pub(super) fn add_template_stages(
+    workflow: &Value,
+    roles: &WorkflowManifest,
+    skeleton_filename: Option<&str>,
+    controlnet_strength: f64,
+    seed: i64,
+) -> Result<Value, ComfyError> {
+    let mut graph = Graph::from_json(workflow).map_err(ComfyError::WorkflowLoadFailed)?;
+    let sampler = roles
+        .sampler
+        .as_deref()
+        .map(NodeId::new)
+        .ok_or_else(|| ComfyError::WorkflowLoadFailed("Workflow has no sampler node".into()))?;
+
+    let prompt_link = |input: &str, role: &Option<String>| {
+        graph
+            .input_link(&sampler, input)
+            .or_else(|| role.as_deref().map(|id| NodeId::new(id).out(0)))
+    };
+    let (Some(positive), Some(negative)) = (
+        prompt_link("positive", &roles.positive_prompt),
+        prompt_link("negative", &roles.negative_prompt),
+    ) else {
+        return Err(ComfyError::WorkflowLoadFailed("The sampler has no positive/negative conditioning".into()));
+    };
+    let mut conditioning = Conditioning { positive, negative };
+    if let Some(skeleton) = skeleton_filename {
+        conditioning = add_controlnet(&mut graph, &conditioning, skeleton, controlnet_strength);
+        graph.set_input(&sampler, "positive", &conditioning.positive);
+        graph.set_input(&sampler, "negative", &conditioning.negative);
+    }
+
+    match (roles.decode.as_deref(), graph.input_link(&sampler, "model")) {
+        (Some(decode), Some(model)) => {
+            let samples = add_hires_fix(&mut graph, &model, &conditioning, sampler.out(0), seed);
+            graph.set_input(&NodeId::new(decode), "samples", &samples);
         }
+        _ => println!("[ComfyUI] Warning: workflow has no decode node or sampler model, skipping hires fix"),
     }
 
-    println!(
-        "[ComfyUI] Injected hires fix: scale={}x, denoise={}, steps={}",
-        upscale_factor, denoise_strength, hires_steps
-    );
-    Ok(())
+    Ok(graph.to_json())
 }
 
 // ============================================================================
@@ -353,9 +563,25 @@ pub(super) fn inject_hires_fix(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::image_gen::comfyui::manifest::resolve_roles;
     use crate::image_gen::comfyui::pipeline::{CharacterInput, ImageGenRequest};
     use serde_json::json;
 
+    /// Roles of a template numbered the way the original scene templates were.
+    fn numbered_roles(characters: usize) -> WorkflowManifest {
+        WorkflowManifest {
+            positive_prompt: Some("2".to_string()),
+            negative_prompt: Some("3".to_string()),
+            latent: Some("4".to_string()),
+            sampler: Some("35".to_string()),
+            decode: Some("6".to_string()),
+            output: Some("7".to_string()),
+            insightface: Some("12".to_string()),
+            reference_images: (0..characters).map(|i| (20 + i).to_string()).collect(),
+            masks: (0..characters).map(|i| (40 + i).to_string()).collect(),
+        }
+    }
+
     #[test]
     fn test_modify_workflow_sets_inputs() {
         let mut workflow = json!({
@@ -426,7 +652,7 @@ mod tests {
                 prompt: "smiling".to_string(),
             }],
             mask_paths: vec!["/path/to/mask.png".to_string()],
-            workflow_template: "template.json".to_string(),
+            workflow_template: Some("template.json".to_string()),
             comfyui_url: None,
             seed: Some(123),
             steps: Some(25),
@@ -442,7 +668,7 @@ mod tests {
         let uploaded_refs = vec!["ref_alice_0.png".to_string()];
         let mask_filenames = vec!["scene_mask.png".to_string()];
 
-        let mods = build_workflow_modifications(&request, &uploaded_refs, &mask_filenames);
+        let mods = build_workflow_modifications(&request, &uploaded_refs, &mask_filenames, &numbered_roles(1));
 
         // Positive prompt set
         assert_eq!(mods["2"]["text"], json!("test scene"));
@@ -455,5 +681,258 @@ mod tests {
         // Ref image
         assert_eq!(mods["20"]["image"], json!("ref_alice_0.png"));
         assert_eq!(mods["40"]["image"], json!("scene_mask.png"));
+        assert_eq!(mods["12"]["model_name"], json!("buffalo_l"));
+
+        // Unmapped roles are skipped
+        let roles = WorkflowManifest { latent: None, ..numbered_roles(1) };
+        let mods = build_workflow_modifications(&request, &uploaded_refs, &mask_filenames, &roles);
+        assert!(!mods.contains_key("4"));
+    }
+
+    /// Minimal two-character template: FaceID branches 30/31 chained into
+    /// KSampler 35, masks going through ImageToMask 50/51.
+    fn two_char_template() -> Value {
+        json!({
+            "1": { "class_type": "CheckpointLoaderSimple", "inputs": { "ckpt_name": "model.safetensors" } },
+            "10": { "class_type": "IPAdapterUnifiedLoaderFaceID", "inputs": { "model": ["1", 0], "preset": "FACEID PLUS V2" } },
+            "12": { "class_type": "IPAdapterInsightFaceLoader", "inputs": { "provider": "CUDA" } },
+            "20": { "class_type": "LoadImage", "inputs": { "image": "ref_a.png" } },
+            "21": { "class_type": "LoadImage", "inputs": { "image": "ref_b.png" } },
+            "40": { "class_type": "LoadImage", "inputs": { "image": "mask_a.png" } },
+            "41": { "class_type": "LoadImage", "inputs": { "image": "mask_b.png" } },
+            "50": { "class_type": "ImageToMask", "inputs": { "image": ["40", 0], "channel": "red" } },
+            "51": { "class_type": "ImageToMask", "inputs": { "image": ["41", 0], "channel": "red" } },
+            "30": { "class_type": "IPAdapterFaceID", "inputs": {
+                "model": ["10", 0], "ipadapter": ["10", 1], "image": ["20", 0],
+                "attn_mask": ["50", 0], "insightface": ["12", 0], "weight": 0.85 } },
+            "31": { "class_type": "IPAdapterFaceID", "inputs": {
+                "model": ["30", 0], "ipadapter": ["10", 1], "image": ["21", 0],
+                "attn_mask": ["51", 0], "insightface": ["12", 0], "weight": 0.85 } },
+            "35": { "class_type": "KSampler", "inputs": { "model": ["31", 0], "seed": 0 } }
+        })
+    }
+
+    #[test]
+    fn test_fit_character_branches_chains_new_adapters() {
+        let mut workflow = two_char_template();
+        let mut roles = resolve_roles(&workflow, None);
+        fit_character_branches(&mut workflow, &mut roles, 4).unwrap();
+
+        // New branches get ids above the template's highest (51)
+        assert_eq!(roles.reference_images, vec!["20", "21", "52", "56"]);
+        assert_eq!(roles.masks, vec!["40", "41", "53", "57"]);
+        for id in roles.reference_images.iter().chain(&roles.masks) {
+            assert_eq!(workflow[id.as_str()]["class_type"], "LoadImage", "loader {}", id);
+        }
+
+        // Walk the model chain back from the KSampler: 4 FaceID nodes
+        let mut node_id = workflow["35"]["inputs"]["model"][0].as_str().unwrap().to_string();
+        let mut refs = Vec::new();
+        while workflow[&node_id]["class_type"] == "IPAdapterFaceID" {
+            let node = &workflow[&node_id];
+            refs.push(node["inputs"]["image"][0].as_str().unwrap().to_string());
+            let mask_node = node["inputs"]["attn_mask"][0].as_str().unwrap();
+            assert_eq!(workflow[mask_node]["class_type"], "ImageToMask");
+            assert_eq!(node["inputs"]["ipadapter"], json!(["10", 1]));
+            node_id = node["inputs"]["model"][0].as_str().unwrap().to_string();
+        }
+        assert_eq!(node_id, "10");
+        assert_eq!(refs, vec!["56", "52", "21", "20"]);
+
+        // The new masks go through their own converter
+        let last = workflow["35"]["inputs"]["model"][0].as_str().unwrap();
+        let converter = workflow[last]["inputs"]["attn_mask"][0].as_str().unwrap();
+        assert_eq!(workflow[converter]["inputs"]["image"], json!(["57", 0]));
+    }
+
+    #[test]
+    fn test_fit_character_branches_drops_unused_branches() {
+        let mut workflow = two_char_template();
+        let mut roles = resolve_roles(&workflow, None);
+        fit_character_branches(&mut workflow, &mut roles, 1).unwrap();
+
+        // The second branch is bypassed and its nodes removed
+        assert_eq!(workflow["35"]["inputs"]["model"], json!(["30", 0]));
+        for id in ["31", "21", "41", "51"] {
+            assert!(workflow.get(id).is_none(), "node {} kept", id);
+        }
+        assert_eq!(roles.reference_images, vec!["20"]);
+        assert_eq!(roles.masks, vec!["40"]);
+
+        // With no characters the sampler gets the unpatched model
+        fit_character_branches(&mut workflow, &mut roles, 0).unwrap();
+        assert_eq!(workflow["35"]["inputs"]["model"], json!(["10", 0]));
+        assert!(workflow.get("30").is_none() && workflow.get("20").is_none());
+        assert!(roles.reference_images.is_empty());
+    }
+
+    #[test]
+    fn test_fit_character_branches_noop_and_limits() {
+        let mut workflow = two_char_template();
+        let mut roles = resolve_roles(&workflow, None);
+        fit_character_branches(&mut workflow, &mut roles, 2).unwrap();
+        assert_eq!(workflow, two_char_template());
+        assert_eq!(roles.reference_images.len(), 2);
+
+        assert!(fit_character_branches(&mut workflow, &mut roles, MAX_SCENE_CHARACTERS + 1).is_err());
+
+        // A template without per-character masks can't be extended
+        let mut single = json!({
+            "20": { "class_type": "LoadImage", "inputs": { "image": "ref.png" } },
+            "30": { "class_type": "IPAdapterFaceID", "inputs": { "model": ["1", 0], "image": ["20", 0] } },
+            "35": { "class_type": "KSampler", "inputs": { "model": ["30", 0] } }
+        });
+        let mut roles = resolve_roles(&single, None);
+        assert!(fit_character_branches(&mut single, &mut roles, 3).is_err());
+    }
+
+    #[test]
+    fn test_build_workflow_modifications_many_characters() {
+        let character = |name: &str, region: &str| CharacterInput {
+            name: name.to_string(),
+            reference_image_path: format!("/refs/{}.png", name),
+            region: region.to_string(),
+            prompt: String::new(),
+        };
+        let request = ImageGenRequest {
+            scene_prompt: "a tavern".to_string(),
+            characters: vec![
+                character("a", "left"),
+                character("b", "center"),
+                character("c", "right"),
+                character("d", "left-background"),
+            ],
+            mask_paths: vec![],
+            workflow_template: Some("template.json".to_string()),
+            comfyui_url: None,
+            seed: None,
+            steps: None,
+            cfg: None,
+            width: None,
+            height: None,
+            negative_prompt: None,
+            timeout_secs: None,
+            controlnet_image_path: None,
+            controlnet_strength: None,
+        };
+
+        let refs: Vec<String> = (0..4).map(|i| format!("ref_{}.png", i)).collect();
+        let masks: Vec<String> = (0..4).map(|i| format!("scene_mask_char{}.png", i)).collect();
+        let mods = build_workflow_modifications(&request, &refs, &masks, &numbered_roles(4));
+
+        assert_eq!(mods["22"]["image"], json!("ref_2.png"));
+        assert_eq!(mods["23"]["image"], json!("ref_3.png"));
+        assert_eq!(mods["43"]["image"], json!("scene_mask_char3.png"));
+    }
+
+    fn scene_request(characters: usize) -> ImageGenRequest {
+        ImageGenRequest {
+            scene_prompt: "a tavern at night".to_string(),
+            characters: (0..characters)
+                .map(|i| CharacterInput {
+                    name: format!("char{}", i),
+                    reference_image_path: format!("/refs/{}.png", i),
+                    region: "left".to_string(),
+                    prompt: String::new(),
+                })
+                .collect(),
+            mask_paths: vec![],
+            workflow_template: None,
+            comfyui_url: None,
+            seed: Some(42),
+            steps: None,
+            cfg: None,
+            width: Some(1152),
+            height: Some(896),
+            negative_prompt: None,
+            timeout_secs: None,
+            controlnet_image_path: None,
+            controlnet_strength: Some(0.7),
+        }
+    }
+
+    fn nodes_of<'a>(workflow: &'a Value, class_type: &str) -> Vec<(&'a String, &'a Value)> {
+        workflow
+            .as_object()
+            .unwrap()
+            .iter()
+            .filter(|(_, node)| node["class_type"] == class_type)
+            .collect()
+    }
+
+    /// Follow a `["id", slot]` link to the node it points to.
+    fn linked<'a>(workflow: &'a Value, link: &Value) -> &'a Value {
+        &workflow[link[0].as_str().unwrap()]
+    }
+
+    #[test]
+    fn test_build_scene_workflow_one_branch_per_character() {
+        let refs: Vec<String> = (0..3).map(|i| format!("ref_{}.png", i)).collect();
+        let masks: Vec<String> = (0..3).map(|i| format!("scene_mask_char{}.png", i)).collect();
+        let workflow = build_scene_workflow(&scene_request(3), &refs, &masks, Some("pose_skeleton.png"));
+
+        let adapters = nodes_of(&workflow, "IPAdapterFaceID");
+        assert_eq!(adapters.len(), 3);
+        for (_, adapter) in &adapters {
+            assert_eq!(linked(&workflow, &adapter["inputs"]["attn_mask"])["class_type"], "ImageToMask");
+        }
+
+        // The first sampler uses the last FaceID node and ControlNet conditioning
+        let samplers = nodes_of(&workflow, "KSampler");
+        assert_eq!(samplers.len(), 2);
+        let first = samplers.iter().find(|(_, s)| s["inputs"]["denoise"] == 1.0).unwrap().1;
+        let model = linked(&workflow, &first["inputs"]["model"]);
+        assert_eq!(model["class_type"], "IPAdapterFaceID");
+        assert_eq!(linked(&workflow, &model["inputs"]["image"])["inputs"]["image"], "ref_2.png");
+        assert_eq!(linked(&workflow, &first["inputs"]["positive"])["class_type"], "ControlNetApplyAdvanced");
+        assert_eq!(nodes_of(&workflow, "ControlNetApplyAdvanced")[0].1["inputs"]["strength"], 0.7);
+
+        // Output goes through the hires fix pass
+        let decode = nodes_of(&workflow, "VAEDecode")[0].1;
+        assert_eq!(linked(&workflow, &decode["inputs"]["samples"])["inputs"]["denoise"], HIRES_DENOISE);
+        assert_eq!(nodes_of(&workflow, "SaveImage").len(), 1);
+    }
+
+    #[test]
+    fn test_build_scene_workflow_single_character_without_masks() {
+        let workflow = build_scene_workflow(&scene_request(1), &["ref_0.png".to_string()], &[], None);
+
+        let adapters = nodes_of(&workflow, "IPAdapterFaceID");
+        assert_eq!(adapters.len(), 1);
+        assert!(adapters[0].1["inputs"].get("attn_mask").is_none());
+        assert!(nodes_of(&workflow, "ControlNetLoader").is_empty());
+
+        let latent = nodes_of(&workflow, "EmptyLatentImage")[0].1;
+        assert_eq!(latent["inputs"]["width"], 1152);
+        assert_eq!(latent["inputs"]["height"], 896);
+    }
+
+    #[test]
+    fn test_add_template_stages_rewires_sampler_and_decode() {
+        let template = json!({
+            "1": { "class_type": "CheckpointLoaderSimple", "inputs": { "ckpt_name": "model.safetensors" } },
+            "2": { "class_type": "CLIPTextEncode", "inputs": { "clip": ["1", 1], "text": "" } },
+            "3": { "class_type": "CLIPTextEncode", "inputs": { "clip": ["1", 1], "text": "" } },
+            "35": { "class_type": "KSampler", "inputs": {
+                "model": ["1", 0], "positive": ["2", 0], "negative": ["3", 0], "latent_image": ["4", 0] } },
+            "6": { "class_type": "VAEDecode", "inputs": { "samples": ["35", 0], "vae": ["1", 2] } }
+        });
+
+        let workflow = add_template_stages(&template, &numbered_roles(0), Some("pose_skeleton.png"), 0.85, 7).unwrap();
+
+        let apply = linked(&workflow, &workflow["35"]["inputs"]["positive"]);
+        assert_eq!(apply["class_type"], "ControlNetApplyAdvanced");
+        assert_eq!(apply["inputs"]["positive"], json!(["2", 0]));
+        assert_eq!(workflow["35"]["inputs"]["negative"][1], 1);
+
+        let refined = linked(&workflow, &workflow["6"]["inputs"]["samples"]);
+        assert_eq!(refined["class_type"], "KSampler");
+        assert_eq!(refined["inputs"]["seed"], 7);
+        assert_eq!(linked(&workflow, &refined["inputs"]["latent_image"])["inputs"]["samples"], json!(["35", 0]));
+
+        // Without a decode role the hires fix is skipped
+        let roles = WorkflowManifest { decode: None, ..numbered_roles(0) };
+        let workflow = add_template_stages(&template, &roles, None, 0.85, 7).unwrap();
+        assert_eq!(workflow, template);
     }
 }
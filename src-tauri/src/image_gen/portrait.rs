@@ -6,15 +6,14 @@
 // These "master images" are used by IP-Adapter FaceID to maintain
 // character consistency across all future scene generations.
 //
-// ARCHITECTURE NOTE:
-// This module does NOT import private internals from comfyui_api.rs.
-// Instead it makes its own HTTP calls to ComfyUI directly, keeping the
-// module fully self-contained.  This avoids the `ComfyOutputImage is private`
-// compiler error while reusing the same ComfyUI REST API.
+// Queueing, waiting and downloading go through the shared ComfyUI client
+// (image_gen::comfyui), so portraits report `image-progress` events and step
+// previews over the WebSocket like scene images do.
 //
 // Pipeline:
 //   1. Build a portrait-optimized prompt from character details
-//   2. Send to ComfyUI as a batch of 4 (user picks the best)
+//   2. Check the workflow's nodes and checkpoint (comfyui::check_requirements),
+//      then send it to ComfyUI as a batch of 4 (user picks the best)
 //   3. Save the selected image to disk as the master reference
 //   4. Update the character database with the master_image_path
 
@@ -22,12 +21,19 @@ use base64::Engine;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use std::path::{Path, PathBuf};
+use std::path::Path;
 use std::time::Duration;
 use tauri::{AppHandle, Manager, State};
 
+use crate::cancellation::{CancelRegistry, CANCELLED_ERROR};
 use crate::config::ConfigState;
+use crate::error::{AppError, ErrorKind};
+use crate::image_gen::comfyui::{
+    cancel_prompt, check_requirements, download_image, image_progress_emitter, queue_prompt, wait_for_completion,
+    ComfyError, Graph, ProgressSocket,
+};
 use crate::state::OllamaState;
+use crate::text_gen::orchestrator::unload_story_model;
 
 // ============================================================================
 // CONFIGURATION
@@ -45,7 +51,6 @@ const PORTRAIT_BATCH_SIZE: u32 = 4;
 const PORTRAIT_SAMPLER: &str = "euler_ancestral";
 const PORTRAIT_SCHEDULER: &str = "normal";
 const PORTRAIT_TIMEOUT_SECS: u64 = 300;
-const POLL_INTERVAL_MS: u64 = 1000;
 
 // ============================================================================
 // TYPES
@@ -148,20 +153,6 @@ pub struct SaveMasterPortraitRequest {
     pub character_name: Option<String>,
 }
 
-/// Internal: image info parsed from ComfyUI /history response.
-#[derive(Debug, Clone, Deserialize)]
-struct OutputImage {
-    filename: String,
-    #[serde(default)]
-    subfolder: String,
-    #[serde(default = "default_output_type")]
-    r#type: String,
-}
-
-fn default_output_type() -> String {
-    "output".to_string()
-}
-
 // ============================================================================
 // PROMPT BUILDER
 // ============================================================================
@@ -354,163 +345,6 @@ async fn check_health(base_url: &str) -> Result<(), String> {
     }
 }
 
-/// POST the workflow JSON to /prompt and return the prompt_id.
-async fn queue_workflow(base_url: &str, workflow: &Value) -> Result<String, String> {
-    let client = http_client(30);
-    let url = format!("{}/prompt", base_url);
-
-    let body = json!({ "prompt": workflow });
-
-    let resp = client
-        .post(&url)
-        .json(&body)
-        .send()
-        .await
-        .map_err(|e| format!("POST /prompt failed: {}", e))?;
-
-    if !resp.status().is_success() {
-        let status = resp.status();
-        let text = resp.text().await.unwrap_or_default();
-        return Err(format!("Queue returned {}: {}", status, text));
-    }
-
-    let result: Value = resp
-        .json()
-        .await
-        .map_err(|e| format!("Invalid queue response: {}", e))?;
-
-    result["prompt_id"]
-        .as_str()
-        .map(|s| s.to_string())
-        .ok_or_else(|| "No prompt_id in queue response".to_string())
-}
-
-/// Poll GET /history/{prompt_id} until the job completes or times out.
-async fn poll_until_complete(
-    base_url: &str,
-    prompt_id: &str,
-    timeout_secs: u64,
-) -> Result<Vec<OutputImage>, String> {
-    let client = http_client(30);
-    let url = format!("{}/history/{}", base_url, prompt_id);
-    let deadline = std::time::Instant::now() + Duration::from_secs(timeout_secs);
-
-    loop {
-        if std::time::Instant::now() > deadline {
-            return Err(format!(
-                "Timed out after {}s waiting for ComfyUI generation",
-                timeout_secs
-            ));
-        }
-
-        tokio::time::sleep(Duration::from_millis(POLL_INTERVAL_MS)).await;
-
-        let resp = client
-            .get(&url)
-            .send()
-            .await
-            .map_err(|e| format!("GET /history failed: {}", e))?;
-
-        if !resp.status().is_success() {
-            continue;
-        }
-
-        let history: Value = resp
-            .json()
-            .await
-            .map_err(|e| format!("Invalid history response: {}", e))?;
-
-        let entry = match history.get(prompt_id) {
-            Some(e) => e,
-            None => continue, // Not ready yet
-        };
-
-        // Check for errors
-        if let Some(status) = entry.get("status") {
-            if let Some(msgs) = status.get("messages") {
-                if let Some(arr) = msgs.as_array() {
-                    for msg in arr {
-                        if msg.get(0).and_then(|v| v.as_str()) == Some("execution_error") {
-                            let detail = msg.get(1).cloned().unwrap_or(Value::Null);
-                            return Err(format!("ComfyUI execution error: {}", detail));
-                        }
-                    }
-                }
-            }
-        }
-
-        // Look for outputs
-        if let Some(outputs) = entry.get("outputs") {
-            let mut images = Vec::new();
-
-            if let Some(outputs_obj) = outputs.as_object() {
-                for (_node_id, node_output) in outputs_obj {
-                    if let Some(img_list) = node_output.get("images") {
-                        if let Some(arr) = img_list.as_array() {
-                            for img_val in arr {
-                                if let Ok(img) =
-                                    serde_json::from_value::<OutputImage>(img_val.clone())
-                                {
-                                    images.push(img);
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-
-            if !images.is_empty() {
-                return Ok(images);
-            }
-
-            // Outputs exist but no images found
-            if outputs.as_object().map_or(false, |o| !o.is_empty()) {
-                return Err("Workflow completed but no images were found in outputs".to_string());
-            }
-        }
-    }
-}
-
-/// Download a single output image from ComfyUI to local disk.
-async fn download_output_image(
-    base_url: &str,
-    image: &OutputImage,
-    output_dir: &Path,
-) -> Result<PathBuf, String> {
-    let client = http_client(30);
-
-    let url = format!(
-        "{}/view?filename={}&subfolder={}&type={}",
-        base_url,
-        urlencoding::encode(&image.filename),
-        urlencoding::encode(&image.subfolder),
-        urlencoding::encode(&image.r#type),
-    );
-
-    let resp = client
-        .get(&url)
-        .send()
-        .await
-        .map_err(|e| format!("GET /view failed: {}", e))?;
-
-    if !resp.status().is_success() {
-        return Err(format!("Download returned status {}", resp.status()));
-    }
-
-    let bytes = resp
-        .bytes()
-        .await
-        .map_err(|e| format!("Failed to read image bytes: {}", e))?;
-
-    std::fs::create_dir_all(output_dir).map_err(|e| format!("Cannot create output dir: {}", e))?;
-
-    let output_path = output_dir.join(&image.filename);
-    std::fs::write(&output_path, &bytes)
-        .map_err(|e| format!("Cannot write {}: {}", output_path.display(), e))?;
-
-    Ok(output_path)
-}
-
 // ============================================================================
 // WORKFLOW BUILDER
 // ============================================================================
@@ -532,65 +366,34 @@ fn build_portrait_workflow(
         }
     };
 
-    json!({
-        "1": {
-            "class_type": "CheckpointLoaderSimple",
-            "inputs": {
-                "ckpt_name": ckpt_name
-            }
-        },
-        "2": {
-            "class_type": "CLIPTextEncode",
-            "inputs": {
-                "clip": ["1", 1],
-                "text": prompt
-            }
-        },
-        "3": {
-            "class_type": "CLIPTextEncode",
-            "inputs": {
-                "clip": ["1", 1],
-                "text": negative_prompt
-            }
-        },
-        "4": {
-            "class_type": "EmptyLatentImage",
-            "inputs": {
-                "width": PORTRAIT_WIDTH,
-                "height": PORTRAIT_HEIGHT,
-                "batch_size": PORTRAIT_BATCH_SIZE
-            }
-        },
-        "5": {
-            "class_type": "KSampler",
-            "inputs": {
-                "model": ["1", 0],
-                "positive": ["2", 0],
-                "negative": ["3", 0],
-                "latent_image": ["4", 0],
-                "seed": seed,
-                "steps": PORTRAIT_STEPS,
-                "cfg": PORTRAIT_CFG,
-                "sampler_name": PORTRAIT_SAMPLER,
-                "scheduler": PORTRAIT_SCHEDULER,
-                "denoise": 1.0
-            }
-        },
-        "6": {
-            "class_type": "VAEDecode",
-            "inputs": {
-                "samples": ["5", 0],
-                "vae": ["1", 2]
-            }
-        },
-        "7": {
-            "class_type": "SaveImage",
-            "inputs": {
-                "images": ["6", 0],
-                "filename_prefix": "StoryEngine/master_portrait"
-            }
-        }
-    })
+    let mut graph = Graph::new();
+    let checkpoint = graph.add("CheckpointLoaderSimple", json!({ "ckpt_name": ckpt_name }));
+    let positive = graph.add("CLIPTextEncode", json!({ "clip": checkpoint.out(1), "text": prompt }));
+    let negative = graph.add("CLIPTextEncode", json!({ "clip": checkpoint.out(1), "text": negative_prompt }));
+    let latent = graph.add("EmptyLatentImage", json!({
+        "width": PORTRAIT_WIDTH,
+        "height": PORTRAIT_HEIGHT,
+        "batch_size": PORTRAIT_BATCH_SIZE
+    }));
+    let sampler = graph.add("KSampler", json!({
+        "model": checkpoint.out(0),
+        "positive": positive.out(0),
+        "negative": negative.out(0),
+        "latent_image": latent.out(0),
+        "seed": seed,
+        "steps": PORTRAIT_STEPS,
+        "cfg": PORTRAIT_CFG,
+        "sampler_name": PORTRAIT_SAMPLER,
+        "scheduler": PORTRAIT_SCHEDULER,
+        "denoise": 1.0
+    }));
+    let image = graph.add("VAEDecode", json!({ "samples": sampler.out(0), "vae": checkpoint.out(2) }));
+    graph.add("SaveImage", json!({
+        "images": image.out(0),
+        "filename_prefix": "StoryEngine/master_portrait"
+    }));
+
+    graph.to_json()
 }
 
 // ============================================================================
@@ -617,12 +420,20 @@ fn build_portrait_workflow(
 /// });
 /// // result.images_base64 = [img1, img2, img3, img4]
 /// ```
+///
+/// `story_id` is the story being played, whose model is unloaded first.
 #[tauri::command]
 pub async fn generate_master_portrait(
     request: MasterPortraitRequest,
+    story_id: Option<i64>,
+    job_id: Option<String>,
+    state: State<'_, OllamaState>,
     config_state: State<'_, ConfigState>,
+    cancel_registry: State<'_, CancelRegistry>,
     app: AppHandle,
-) -> Result<MasterPortraitResult, String> {
+) -> Result<MasterPortraitResult, AppError> {
+    let job = cancel_registry.register(job_id.unwrap_or_else(|| "portrait".to_string()));
+
     let base_url = request
         .comfyui_url
         .as_deref()
@@ -630,9 +441,9 @@ pub async fn generate_master_portrait(
 
     // 1. Health check
     check_health(base_url).await.map_err(|e| {
-        format!(
-            "ComfyUI is not running at {}. Please start ComfyUI first. ({})",
-            base_url, e
+        AppError::new(
+            ErrorKind::ComfyUnavailable,
+            format!("ComfyUI is not running at {} ({})", base_url, e),
         )
     })?;
     println!("[MasterPortrait] ComfyUI connected at {}", base_url);
@@ -671,35 +482,39 @@ pub async fn generate_master_portrait(
     let checkpoint_override = request.checkpoint_override.as_deref();
     let workflow = build_portrait_workflow(&prompt, &negative, seed, request.art_style.as_deref(), checkpoint_override);
 
-    // 4. Free VRAM: unload Ollama model before ComfyUI needs the GPU
-    {
-        let ollama_url = {
-            let config = config_state.0.lock().map_err(|e| e.to_string())?;
-            config.ollama_url.clone()
-        };
-        let client = reqwest::Client::new();
-        let unload_result = client
-            .post(format!("{}/api/generate", ollama_url))
-            .json(&serde_json::json!({
-                "model": crate::text_gen::prompts::STORY_MODEL,
-                "keep_alive": crate::text_gen::prompts::KEEP_ALIVE_UNLOAD
-            }))
-            .timeout(std::time::Duration::from_secs(10))
-            .send()
-            .await;
-        match unload_result {
-            Ok(_) => println!("[VRAM] Ollama model unloaded for portrait generation"),
-            Err(e) => println!("[VRAM] Ollama unload skipped ({})", e),
-        }
-        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    // Missing nodes or checkpoints fail here, before the story model is unloaded
+    match check_requirements(base_url, &workflow).await {
+        Ok(missing) if !missing.is_empty() => return Err(ComfyError::MissingRequirements(missing).into()),
+        Ok(_) => {}
+        Err(e) => println!("[MasterPortrait] Warning: could not check workflow requirements, queueing anyway: {}", e),
     }
 
-    // 5. Queue prompt
-    let prompt_id = queue_workflow(base_url, &workflow).await?;
+    // 4. Free VRAM: unload Ollama model before ComfyUI needs the GPU
+    unload_story_model(&state.db, &config_state, story_id).await?;
+
+    // 5. Queue prompt, with the progress socket connected first so none of
+    //    its events are missed
+    job.check()?;
+    let socket = match ProgressSocket::connect(base_url).await {
+        Ok(socket) => Some(socket),
+        Err(e) => {
+            println!("[MasterPortrait] No progress socket, polling history instead: {}", e);
+            None
+        }
+    };
+    let client_id = socket.as_ref().map(|s| s.client_id().to_string());
+    let prompt_id = queue_prompt(base_url, &workflow, client_id.as_deref()).await?;
     println!("[MasterPortrait] Queued prompt: {}", prompt_id);
 
-    // 6. Poll for completion
-    let output_images = poll_until_complete(base_url, &prompt_id, PORTRAIT_TIMEOUT_SECS).await?;
+    // 6. Wait for completion, emitting image-progress (aborting if the user cancels)
+    let mut on_progress = image_progress_emitter(&app, Some(job.key().to_string()));
+    let output_images = tokio::select! {
+        res = wait_for_completion(base_url, &prompt_id, PORTRAIT_TIMEOUT_SECS, socket, Some(&mut on_progress)) => res?,
+        _ = job.cancelled() => {
+            cancel_prompt(base_url, &prompt_id).await;
+            return Err(CANCELLED_ERROR.into());
+        }
+    };
     println!(
         "[MasterPortrait] Generation complete! {} images",
         output_images.len()
@@ -723,7 +538,7 @@ pub async fn generate_master_portrait(
     let mut images_base64: Vec<String> = Vec::new();
 
     for img in &output_images {
-        let local_path = download_output_image(base_url, img, &output_dir).await?;
+        let local_path = download_image(base_url, img, &output_dir).await?;
 
         let bytes = std::fs::read(&local_path)
             .map_err(|e| format!("Failed to read image {}: {}", local_path.display(), e))?;
@@ -767,21 +582,21 @@ pub async fn save_master_portrait(
     request: SaveMasterPortraitRequest,
     app: AppHandle,
     state: State<'_, OllamaState>,
-) -> Result<String, String> {
+) -> Result<String, AppError> {
     if request.selected_index >= request.image_paths.len() {
-        return Err(format!(
+        return Err(AppError::invalid_input(format!(
             "Invalid selection index {} (only {} images available)",
             request.selected_index,
             request.image_paths.len()
-        ));
+        )));
     }
 
     let source_path = Path::new(&request.image_paths[request.selected_index]);
     if !source_path.exists() {
-        return Err(format!(
+        return Err(AppError::not_found(format!(
             "Selected image not found at: {}",
             source_path.display()
-        ));
+        )));
     }
 
     // Create permanent storage directory
@@ -821,7 +636,7 @@ pub async fn save_master_portrait(
     .bind(request.character_id)
     .execute(&state.db)
     .await
-    .map_err(|e| format!("Failed to update character master image: {}", e))?;
+    .map_err(|e| AppError::database(format!("Failed to update character master image: {}", e)))?;
 
     println!(
         "[MasterPortrait] Saved master for character {} at: {}",
@@ -3,15 +3,20 @@
     windows_subsystem = "windows"
 )]
 
+mod backup;
+mod cancellation;
 mod commands;
 mod config;
 mod custom_assets;
+mod error;
 mod image_gen;
+mod migrations;
 mod models;
 mod services;
 mod state;
 mod text_gen;
 
+use cancellation::CancelRegistry;
 use config::{AppConfig, ConfigState};
 use services::ServiceManager;
 use state::{OllamaState, SceneHintState, ServicePidState};
@@ -78,15 +83,19 @@ fn main() {
                 }
             })
             .join()
-            .expect("Failed to initialize state");
+            .expect("Failed to initialize state")?; // migration errors stop startup
 
             app.manage(state);
             app.manage(SceneHintState(Mutex::new(HashMap::new())));
+            app.manage(CancelRegistry::default());
             app.manage(ServicePidState {
                 ollama_pid: Mutex::new(None),
                 comfyui_pid: Mutex::new(None),
             });
 
+            // Scheduled database backups (interval and rotation from config)
+            tauri::async_runtime::spawn(backup::run_scheduler(app.handle().clone()));
+
             // Pre-generate pose skeleton PNGs (fast no-op if already present)
             if let Ok(app_data_dir) = app.path().app_data_dir() {
                 if let Err(e) = image_gen::pose_skeletons::ensure_pose_skeletons(&app_data_dir) {
@@ -139,6 +148,17 @@ fn main() {
             commands::story::get_story_images,
             commands::story::get_story_for_chat,
             commands::story::update_story_rating,
+            commands::story::get_story_generation_settings,
+            commands::story::update_story_generation_settings,
+            commands::story::get_story_bible,
+            commands::story::update_story_bible,
+            // Story branch commands
+            commands::branch::fork_story_at,
+            commands::branch::list_story_branches,
+            commands::branch::switch_story_branch,
+            // Rewind / edit commands
+            commands::history::rewind_story_to,
+            commands::history::edit_story_message,
             // Character & Image commands
             image_gen::sd_webui::generate_image,
             image_gen::sd_webui::generate_image_variation,
@@ -164,9 +184,11 @@ fn main() {
             image_gen::comfyui::check_comfyui_status,
             image_gen::comfyui::upload_to_comfyui,
             image_gen::comfyui::queue_comfyui_prompt,
+            image_gen::comfyui::check_comfyui_requirements,
             image_gen::comfyui::poll_comfyui_result,
             image_gen::comfyui::download_comfyui_image,
             image_gen::comfyui::generate_comfyui_scene,
+            image_gen::comfyui::validate_workflow,
             image_gen::comfyui::read_file_bytes,
             image_gen::comfyui::read_file_base64,
             // Character commands
@@ -193,11 +215,29 @@ fn main() {
             text_gen::orchestrator::process_story_turn,
             text_gen::orchestrator::generate_scene_image_for_turn,
             text_gen::orchestrator::get_compression_diagnostics,
+            text_gen::orchestrator::get_context_summaries,
+            text_gen::memory::get_story_memory,
+            text_gen::recall::search_story_memories,
+            text_gen::parse_stats::get_parse_stats,
+            text_gen::turns::search_turns,
+            text_gen::traces::list_turn_traces,
+            text_gen::traces::get_turn_trace,
+            text_gen::traces::export_turn_traces,
             text_gen::orchestrator::regenerate_story,
             text_gen::orchestrator::regenerate_story_with_input,
+            text_gen::orchestrator::list_turn_candidates,
+            text_gen::orchestrator::select_turn_candidate,
             text_gen::orchestrator::free_vram,
             text_gen::orchestrator::preview_scene_prompt,
             text_gen::orchestrator::illustrate_scene_custom,
+            cancellation::cancel_generation,
+            text_gen::backend::get_text_backend_info,
+            // Lorebook commands
+            commands::lore::create_lore_entry,
+            commands::lore::update_lore_entry,
+            commands::lore::delete_lore_entry,
+            commands::lore::list_lore_entries,
+            commands::lore::get_lore_entry,
             // Scene commands
             commands::scene::create_scene,
             commands::scene::update_scene,
@@ -231,6 +271,11 @@ fn main() {
             custom_assets::delete_custom_pose,
             custom_assets::import_pose_file,
             custom_assets::import_checkpoint_file,
+            // Database backups
+            backup::create_backup,
+            backup::list_backups,
+            backup::restore_backup,
+            backup::delete_backup,
         ])
         .build(tauri::generate_context!())
         .expect("error building tauri application")
@@ -0,0 +1,831 @@
+// src-tauri/src/migrations.rs
+//
+// Versioned Database Migrations
+// =============================
+// The schema is built by an ordered list of migrations. The database's
+// `PRAGMA user_version` is the number of the last one applied; on startup
+// every newer migration runs in its own transaction and bumps the version
+// in that same transaction. A failing statement rolls the migration back and
+// stops startup with the error — nothing is swallowed.
+//
+// Before the first pending migration runs on an existing database, it is
+// backed up to `app_data/backups/` (see backup.rs).
+//
+// Databases created before versioning have user_version 0 and run the whole
+// list. Every step is therefore safe on a schema that is already (partly)
+// there: tables and indexes use IF NOT EXISTS, columns are added with
+// `Step::AddColumn`, which checks the table first instead of ignoring errors.
+//
+// To change the schema, append a migration with the next version. Never edit
+// or reorder one that has shipped.
+
+use futures_util::future::BoxFuture;
+use sqlx::sqlite::{SqliteConnection, SqlitePool};
+use sqlx::Row;
+use std::path::Path;
+
+// ============================================================================
+// TYPES
+// ============================================================================
+
+/// A data fix-up that needs more than SQL. Runs inside the migration's
+/// transaction.
+type StepFn = for<'c> fn(&'c mut SqliteConnection) -> BoxFuture<'c, Result<(), String>>;
+
+enum Step {
+    /// A statement that must succeed
+    Sql(&'static str),
+    /// `ALTER TABLE <table> ADD COLUMN <definition>` unless the column exists
+    /// (see add_column)
+    AddColumn(&'static str, &'static str),
+    Run(StepFn),
+}
+
+struct Migration {
+    version: i64,
+    name: &'static str,
+    steps: &'static [Step],
+}
+
+use Step::{AddColumn, Run, Sql};
+
+// ============================================================================
+// MIGRATIONS
+// ============================================================================
+
+static MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial_schema",
+        steps: &[
+            Sql("CREATE TABLE IF NOT EXISTS chats (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                title TEXT NOT NULL DEFAULT 'New Chat',
+                character_id INTEGER,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            )"),
+            Sql("CREATE TABLE IF NOT EXISTS messages (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                chat_id INTEGER NOT NULL,
+                role TEXT NOT NULL,
+                content TEXT NOT NULL,
+                timestamp DATETIME DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY(chat_id) REFERENCES chats(id) ON DELETE CASCADE
+            )"),
+            Sql("CREATE TABLE IF NOT EXISTS images (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                message_id INTEGER NOT NULL,
+                chat_id INTEGER NOT NULL,
+                file_path TEXT NOT NULL,
+                prompt TEXT,
+                FOREIGN KEY(message_id) REFERENCES messages(id) ON DELETE CASCADE,
+                FOREIGN KEY(chat_id) REFERENCES chats(id) ON DELETE CASCADE
+            )"),
+            Sql("CREATE TABLE IF NOT EXISTS story_premises (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                title TEXT NOT NULL,
+                description TEXT NOT NULL,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            )"),
+            Sql("CREATE TABLE IF NOT EXISTS characters (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                story_id INTEGER,
+                name TEXT NOT NULL,
+                age INTEGER,
+                gender TEXT,
+                skin_tone TEXT,
+                hair_style TEXT,
+                hair_color TEXT,
+                body_type TEXT,
+                personality TEXT,
+                additional_notes TEXT,
+                default_clothing TEXT,
+                sd_prompt TEXT,
+                image TEXT,
+                master_image_path TEXT,
+                seed INTEGER,
+                art_style TEXT DEFAULT 'Realistic',
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY(story_id) REFERENCES story_premises(id) ON DELETE CASCADE
+            )"),
+            // Columns added to characters after its first release
+            AddColumn("characters", "story_id INTEGER REFERENCES story_premises(id) ON DELETE CASCADE"),
+            AddColumn("characters", "default_clothing TEXT"),
+            AddColumn("characters", "master_image_path TEXT"),
+            AddColumn("characters", "created_at DATETIME DEFAULT CURRENT_TIMESTAMP"),
+            AddColumn("characters", "updated_at DATETIME DEFAULT CURRENT_TIMESTAMP"),
+            AddColumn("characters", "eye_color TEXT"),
+            AddColumn("characters", "height_scale INTEGER DEFAULT 3"),
+            AddColumn("characters", "weight_scale INTEGER DEFAULT 3"),
+            AddColumn("characters", "content_rating TEXT DEFAULT 'sfw'"),
+            AddColumn("characters", "is_pov INTEGER DEFAULT 0"),
+            // Many-to-many: character <-> story. Deleting a story removes its
+            // junction rows (not the characters themselves).
+            Sql("CREATE TABLE IF NOT EXISTS story_characters (
+                story_id     INTEGER NOT NULL,
+                character_id INTEGER NOT NULL,
+                added_at     DATETIME DEFAULT CURRENT_TIMESTAMP,
+                PRIMARY KEY (story_id, character_id),
+                FOREIGN KEY(story_id)     REFERENCES story_premises(id) ON DELETE CASCADE,
+                FOREIGN KEY(character_id) REFERENCES characters(id)     ON DELETE CASCADE
+            )"),
+            // Populate the junction table from the legacy characters.story_id
+            Sql("INSERT OR IGNORE INTO story_characters (story_id, character_id)
+                 SELECT story_id, id FROM characters WHERE story_id IS NOT NULL"),
+            Sql("CREATE INDEX IF NOT EXISTS idx_characters_name ON characters(name)"),
+            Sql("CREATE INDEX IF NOT EXISTS idx_characters_story ON characters(story_id)"),
+            Sql("CREATE INDEX IF NOT EXISTS idx_characters_story_name ON characters(story_id, name)"),
+            Sql("CREATE INDEX IF NOT EXISTS idx_sc_story ON story_characters(story_id)"),
+            Sql("CREATE INDEX IF NOT EXISTS idx_sc_character ON story_characters(character_id)"),
+            Sql("CREATE INDEX IF NOT EXISTS idx_messages_chat ON messages(chat_id)"),
+            Sql("CREATE INDEX IF NOT EXISTS idx_images_chat ON images(chat_id)"),
+        ],
+    },
+    Migration {
+        version: 2,
+        name: "scenes",
+        steps: &[
+            Sql("CREATE TABLE IF NOT EXISTS scenes (
+                id            INTEGER PRIMARY KEY AUTOINCREMENT,
+                name          TEXT NOT NULL,
+                description   TEXT,
+                location      TEXT,
+                location_type TEXT,
+                time_of_day   TEXT,
+                mood          TEXT,
+                created_at    DATETIME DEFAULT CURRENT_TIMESTAMP
+            )"),
+            // Many-to-many: scene <-> story
+            Sql("CREATE TABLE IF NOT EXISTS story_scenes (
+                story_id INTEGER NOT NULL,
+                scene_id INTEGER NOT NULL,
+                PRIMARY KEY (story_id, scene_id),
+                FOREIGN KEY(story_id) REFERENCES story_premises(id) ON DELETE CASCADE,
+                FOREIGN KEY(scene_id) REFERENCES scenes(id)          ON DELETE CASCADE
+            )"),
+            // Many-to-many: scene <-> character
+            Sql("CREATE TABLE IF NOT EXISTS scene_characters (
+                scene_id     INTEGER NOT NULL,
+                character_id INTEGER NOT NULL,
+                PRIMARY KEY (scene_id, character_id),
+                FOREIGN KEY(scene_id)     REFERENCES scenes(id)     ON DELETE CASCADE,
+                FOREIGN KEY(character_id) REFERENCES characters(id) ON DELETE CASCADE
+            )"),
+            Sql("CREATE INDEX IF NOT EXISTS idx_ss_story   ON story_scenes(story_id)"),
+            Sql("CREATE INDEX IF NOT EXISTS idx_ss_scene   ON story_scenes(scene_id)"),
+            Sql("CREATE INDEX IF NOT EXISTS idx_sch_scene  ON scene_characters(scene_id)"),
+            Sql("CREATE INDEX IF NOT EXISTS idx_sch_char   ON scene_characters(character_id)"),
+            AddColumn("story_premises", "active_scene_id INTEGER REFERENCES scenes(id) ON DELETE SET NULL"),
+        ],
+    },
+    Migration {
+        version: 3,
+        name: "story_manager",
+        steps: &[
+            AddColumn("story_premises", "created_at DATETIME DEFAULT CURRENT_TIMESTAMP"),
+            AddColumn("story_premises", "last_played_at DATETIME DEFAULT CURRENT_TIMESTAMP"),
+            AddColumn("story_premises", "current_location TEXT"),
+            AddColumn("story_premises", "compressed_history_json TEXT DEFAULT '{}'"),
+            AddColumn("story_premises", "chat_id INTEGER REFERENCES chats(id)"),
+            AddColumn("story_premises", "thumbnail_path TEXT"),
+            AddColumn("story_premises", "content_rating TEXT DEFAULT 'sfw'"),
+            Sql("CREATE INDEX IF NOT EXISTS idx_story_premises_last_played ON story_premises(last_played_at DESC)"),
+            Sql("CREATE INDEX IF NOT EXISTS idx_story_premises_chat ON story_premises(chat_id)"),
+            Run(link_orphan_stories),
+        ],
+    },
+    Migration {
+        version: 4,
+        name: "custom_assets",
+        steps: &[
+            Sql("CREATE TABLE IF NOT EXISTS custom_checkpoints (
+                id           INTEGER PRIMARY KEY AUTOINCREMENT,
+                display_name TEXT NOT NULL,
+                filename     TEXT NOT NULL,
+                created_at   DATETIME DEFAULT CURRENT_TIMESTAMP
+            )"),
+            Sql("CREATE TABLE IF NOT EXISTS custom_poses (
+                id           INTEGER PRIMARY KEY AUTOINCREMENT,
+                display_name TEXT NOT NULL,
+                filename     TEXT NOT NULL,
+                created_at   DATETIME DEFAULT CURRENT_TIMESTAMP
+            )"),
+        ],
+    },
+    Migration {
+        version: 5,
+        name: "emotional_states",
+        steps: &[
+            Sql("CREATE TABLE IF NOT EXISTS character_emotional_states (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                chat_id INTEGER NOT NULL,
+                character_name TEXT NOT NULL,
+                current_emotion TEXT NOT NULL DEFAULT 'neutral',
+                emotion_intensity TEXT NOT NULL DEFAULT 'low',
+                emotion_cause TEXT NOT NULL DEFAULT '',
+                lingering_emotions TEXT NOT NULL DEFAULT '[]',
+                updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+                UNIQUE(chat_id, character_name)
+            )"),
+            Sql("CREATE INDEX IF NOT EXISTS idx_ces_chat_id ON character_emotional_states(chat_id)"),
+        ],
+    },
+    Migration {
+        version: 6,
+        name: "story_generation_overrides",
+        // Per-story model / sampler overrides (NULL = use the app setting)
+        steps: &[
+            AddColumn("story_premises", "model TEXT"),
+            AddColumn("story_premises", "temperature REAL"),
+            AddColumn("story_premises", "top_p REAL"),
+            AddColumn("story_premises", "top_k INTEGER"),
+            AddColumn("story_premises", "repeat_penalty REAL"),
+            AddColumn("story_premises", "num_ctx INTEGER"),
+            AddColumn("story_premises", "num_predict INTEGER"),
+        ],
+    },
+    Migration {
+        version: 7,
+        name: "story_bible",
+        // themes / hard_rules are JSON string arrays
+        steps: &[
+            AddColumn("story_premises", "genre TEXT"),
+            AddColumn("story_premises", "tone TEXT"),
+            AddColumn("story_premises", "setting TEXT"),
+            AddColumn("story_premises", "themes TEXT DEFAULT '[]'"),
+            AddColumn("story_premises", "hard_rules TEXT DEFAULT '[]'"),
+        ],
+    },
+    Migration {
+        version: 8,
+        name: "parse_stats",
+        // See text_gen::parse_stats
+        steps: &[
+            Sql("CREATE TABLE IF NOT EXISTS parse_stats (
+                id            INTEGER PRIMARY KEY AUTOINCREMENT,
+                chat_id       INTEGER NOT NULL,
+                story_id      INTEGER,
+                status        TEXT NOT NULL,
+                warning_count INTEGER NOT NULL DEFAULT 0,
+                structured    INTEGER NOT NULL DEFAULT 0,
+                backend       TEXT NOT NULL DEFAULT '',
+                model         TEXT NOT NULL DEFAULT '',
+                created_at    DATETIME DEFAULT CURRENT_TIMESTAMP
+            )"),
+            Sql("CREATE INDEX IF NOT EXISTS idx_parse_stats_story ON parse_stats(story_id)"),
+        ],
+    },
+    Migration {
+        version: 9,
+        name: "context_summaries",
+        // See text_gen::context — compression never deletes messages, it
+        // records which turns a summary replaces
+        steps: &[
+            Sql("CREATE TABLE IF NOT EXISTS context_summaries (
+                id              INTEGER PRIMARY KEY AUTOINCREMENT,
+                chat_id         INTEGER NOT NULL,
+                first_turn      INTEGER NOT NULL DEFAULT 0,
+                last_turn       INTEGER NOT NULL DEFAULT 0,
+                last_message_id INTEGER NOT NULL DEFAULT 0,
+                summary         TEXT NOT NULL,
+                created_at      DATETIME DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY(chat_id) REFERENCES chats(id) ON DELETE CASCADE
+            )"),
+            Sql("CREATE INDEX IF NOT EXISTS idx_context_summaries_chat ON context_summaries(chat_id)"),
+            // Move legacy "[COMPRESSED SUMMARY]" system rows into the table.
+            // Their turns were deleted, so the summary covers everything
+            // before the oldest remaining message.
+            Sql("INSERT INTO context_summaries (chat_id, first_turn, last_turn, last_message_id, summary)
+                 SELECT m.chat_id, 0, 0,
+                        COALESCE((SELECT MIN(u.id) FROM messages u
+                                  WHERE u.chat_id = m.chat_id AND u.role IN ('user', 'assistant')), m.id) - 1,
+                        TRIM(SUBSTR(m.content, LENGTH('[COMPRESSED SUMMARY]') + 1))
+                 FROM messages m
+                 WHERE m.role = 'system' AND m.content LIKE '[COMPRESSED SUMMARY]%'"),
+            Sql("DELETE FROM messages WHERE role = 'system' AND content LIKE '[COMPRESSED SUMMARY]%'"),
+        ],
+    },
+    Migration {
+        version: 10,
+        name: "story_memory",
+        // See text_gen::memory
+        steps: &[
+            // Scene the assistant turn happened in — marks scene boundaries
+            AddColumn("messages", "scene_id INTEGER"),
+            Sql("CREATE TABLE IF NOT EXISTS story_memory (
+                id              INTEGER PRIMARY KEY AUTOINCREMENT,
+                chat_id         INTEGER NOT NULL,
+                level           TEXT NOT NULL,
+                scene_id        INTEGER,
+                first_turn      INTEGER NOT NULL,
+                last_turn       INTEGER NOT NULL,
+                last_message_id INTEGER NOT NULL,
+                summary         TEXT NOT NULL,
+                parent_id       INTEGER,
+                created_at      DATETIME DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY(chat_id) REFERENCES chats(id) ON DELETE CASCADE
+            )"),
+            Sql("CREATE INDEX IF NOT EXISTS idx_story_memory_chat ON story_memory(chat_id, level)"),
+        ],
+    },
+    Migration {
+        version: 11,
+        name: "turn_embeddings",
+        // See text_gen::recall — one embedding per turn, keyed by the
+        // assistant message
+        steps: &[
+            Sql("CREATE TABLE IF NOT EXISTS turn_embeddings (
+                message_id  INTEGER PRIMARY KEY,
+                chat_id     INTEGER NOT NULL,
+                turn_number INTEGER NOT NULL,
+                model       TEXT NOT NULL,
+                text        TEXT NOT NULL,
+                embedding   BLOB NOT NULL,
+                created_at  DATETIME DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY(message_id) REFERENCES messages(id) ON DELETE CASCADE
+            )"),
+            Sql("CREATE INDEX IF NOT EXISTS idx_turn_embeddings_chat ON turn_embeddings(chat_id, model)"),
+        ],
+    },
+    Migration {
+        version: 12,
+        name: "lorebook",
+        // See text_gen::lorebook — story_id NULL = global entry; keys is a
+        // JSON array of trigger words
+        steps: &[
+            Sql("CREATE TABLE IF NOT EXISTS lore_entries (
+                id         INTEGER PRIMARY KEY AUTOINCREMENT,
+                story_id   INTEGER,
+                name       TEXT NOT NULL,
+                keys       TEXT NOT NULL DEFAULT '[]',
+                content    TEXT NOT NULL,
+                priority   INTEGER NOT NULL DEFAULT 0,
+                position   TEXT NOT NULL DEFAULT 'before_input',
+                always_on  INTEGER NOT NULL DEFAULT 0,
+                enabled    INTEGER NOT NULL DEFAULT 1,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY(story_id) REFERENCES story_premises(id) ON DELETE CASCADE
+            )"),
+            Sql("CREATE INDEX IF NOT EXISTS idx_lore_entries_story ON lore_entries(story_id)"),
+        ],
+    },
+    Migration {
+        version: 13,
+        name: "swipes",
+        // Every assistant candidate answering the same user message shares
+        // reply_to; only the selected one is part of the story. The snapshot
+        // holds the chat's emotional states right after that candidate.
+        steps: &[
+            AddColumn("messages", "reply_to INTEGER"),
+            AddColumn("messages", "is_selected INTEGER NOT NULL DEFAULT 1"),
+            AddColumn("messages", "emotion_snapshot TEXT"),
+            Sql("CREATE INDEX IF NOT EXISTS idx_messages_reply_to ON messages(reply_to)"),
+        ],
+    },
+    Migration {
+        version: 14,
+        name: "turn_snapshots",
+        // Per-turn state snapshot (see commands::history): with scene_id (the
+        // active scene after the turn) and emotion_snapshot, the characters
+        // sync_scene_from_turn added to that scene's roster (JSON id array).
+        steps: &[AddColumn("messages", "roster_additions TEXT")],
+    },
+    Migration {
+        version: 15,
+        name: "story_branches",
+        // See commands::branch — each timeline of a story is a chat. A branch
+        // chat only stores its own messages; it shares its parent chat's
+        // messages up to shared_until_id. The root timeline has no parent.
+        // Scene state is parked here while another timeline is active.
+        steps: &[
+            Sql("CREATE TABLE IF NOT EXISTS story_branches (
+                id              INTEGER PRIMARY KEY AUTOINCREMENT,
+                story_id        INTEGER NOT NULL,
+                chat_id         INTEGER NOT NULL UNIQUE,
+                parent_chat_id  INTEGER,
+                shared_until_id INTEGER,
+                shared_turns    INTEGER NOT NULL DEFAULT 0,
+                name            TEXT NOT NULL,
+                active_scene_id INTEGER,
+                scene_roster    TEXT,
+                created_at      DATETIME DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY(story_id) REFERENCES story_premises(id) ON DELETE CASCADE,
+                FOREIGN KEY(chat_id) REFERENCES chats(id) ON DELETE CASCADE
+            )"),
+            Sql("CREATE INDEX IF NOT EXISTS idx_story_branches_story ON story_branches(story_id)"),
+            Sql("CREATE INDEX IF NOT EXISTS idx_story_branches_parent ON story_branches(parent_chat_id)"),
+        ],
+    },
+    Migration {
+        version: 16,
+        name: "turns",
+        // See text_gen::turns — one row per assistant message of a story with
+        // the parsed fields of the LLM output. messages.content keeps the raw
+        // response.
+        steps: &[
+            Sql("CREATE TABLE IF NOT EXISTS turns (
+                id                   INTEGER PRIMARY KEY AUTOINCREMENT,
+                chat_id              INTEGER NOT NULL,
+                user_message_id      INTEGER,
+                assistant_message_id INTEGER NOT NULL UNIQUE,
+                story_text           TEXT NOT NULL DEFAULT '',
+                summary_hint         TEXT NOT NULL DEFAULT '',
+                location             TEXT NOT NULL DEFAULT '',
+                scene_json           TEXT,
+                characters           TEXT NOT NULL DEFAULT '[]',
+                emotional_states     TEXT NOT NULL DEFAULT '[]',
+                generation_flags     TEXT,
+                scene_id             INTEGER,
+                image_id             INTEGER,
+                parse_status         TEXT NOT NULL DEFAULT 'legacy',
+                model                TEXT,
+                backend              TEXT,
+                elapsed_ms           INTEGER,
+                created_at           DATETIME DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY(user_message_id) REFERENCES messages(id) ON DELETE CASCADE,
+                FOREIGN KEY(assistant_message_id) REFERENCES messages(id) ON DELETE CASCADE,
+                FOREIGN KEY(image_id) REFERENCES images(id) ON DELETE SET NULL
+            )"),
+            Sql("CREATE INDEX IF NOT EXISTS idx_turns_chat ON turns(chat_id)"),
+            Sql("CREATE INDEX IF NOT EXISTS idx_turns_location ON turns(location)"),
+            Run(backfill_turns),
+        ],
+    },
+    Migration {
+        version: 17,
+        name: "turn_traces",
+        // See text_gen::traces
+        steps: &[
+            Sql("CREATE TABLE IF NOT EXISTS turn_traces (
+                id                   INTEGER PRIMARY KEY AUTOINCREMENT,
+                chat_id              INTEGER NOT NULL,
+                story_id             INTEGER,
+                assistant_message_id INTEGER,
+                user_input           TEXT NOT NULL DEFAULT '',
+                prompt_format        TEXT NOT NULL DEFAULT 'raw',
+                prompt               TEXT NOT NULL DEFAULT '',
+                token_breakdown      TEXT,
+                prompt_tokens        INTEGER NOT NULL DEFAULT 0,
+                raw_response         TEXT NOT NULL DEFAULT '',
+                parse_status         TEXT NOT NULL,
+                parse_warnings       TEXT NOT NULL DEFAULT '[]',
+                retries              TEXT NOT NULL DEFAULT '[]',
+                error                TEXT,
+                timings              TEXT,
+                backend              TEXT NOT NULL DEFAULT '',
+                model                TEXT NOT NULL DEFAULT '',
+                created_at           DATETIME DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY(chat_id) REFERENCES chats(id) ON DELETE CASCADE,
+                FOREIGN KEY(assistant_message_id) REFERENCES messages(id) ON DELETE SET NULL
+            )"),
+            Sql("CREATE INDEX IF NOT EXISTS idx_turn_traces_story ON turn_traces(story_id)"),
+            Sql("CREATE INDEX IF NOT EXISTS idx_turn_traces_chat ON turn_traces(chat_id)"),
+        ],
+    },
+    Migration {
+        version: 18,
+        name: "candidate_input",
+        // The user input an assistant candidate was written for when it was
+        // regenerated with edited input. NULL = the user message's content.
+        // Selecting a candidate copies its input back to the user message.
+        steps: &[AddColumn("messages", "user_input TEXT")],
+    },
+    Migration {
+        version: 19,
+        name: "parse_stats_cascade",
+        // parse_stats rows go with their chat. SQLite can't add a foreign key
+        // to a table, so it is rebuilt; rows of already deleted chats are
+        // dropped on the way.
+        steps: &[
+            Sql("CREATE TABLE parse_stats_new (
+                id            INTEGER PRIMARY KEY AUTOINCREMENT,
+                chat_id       INTEGER NOT NULL,
+                story_id      INTEGER,
+                status        TEXT NOT NULL,
+                warning_count INTEGER NOT NULL DEFAULT 0,
+                structured    INTEGER NOT NULL DEFAULT 0,
+                backend       TEXT NOT NULL DEFAULT '',
+                model         TEXT NOT NULL DEFAULT '',
+                created_at    DATETIME DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY(chat_id) REFERENCES chats(id) ON DELETE CASCADE
+            )"),
+            Sql("INSERT INTO parse_stats_new \
+                 SELECT id, chat_id, story_id, status, warning_count, structured, backend, model, created_at \
+                 FROM parse_stats WHERE chat_id IN (SELECT id FROM chats)"),
+            Sql("DROP TABLE parse_stats"),
+            Sql("ALTER TABLE parse_stats_new RENAME TO parse_stats"),
+            Sql("CREATE INDEX IF NOT EXISTS idx_parse_stats_story ON parse_stats(story_id)"),
+            Sql("CREATE INDEX IF NOT EXISTS idx_parse_stats_chat ON parse_stats(chat_id)"),
+        ],
+    },
+];
+
+// ============================================================================
+// DATA FIX-UPS
+// ============================================================================
+
+/// Give every story created before stories required a chat its own chat.
+fn link_orphan_stories(conn: &mut SqliteConnection) -> BoxFuture<'_, Result<(), String>> {
+    Box::pin(async move {
+        let orphans = sqlx::query("SELECT id, title FROM story_premises WHERE chat_id IS NULL")
+            .fetch_all(&mut *conn)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        for story in &orphans {
+            let story_id: i64 = story.get("id");
+            let title: String = story.get("title");
+            let chat_id = sqlx::query("INSERT INTO chats (title) VALUES (?)")
+                .bind(&title)
+                .execute(&mut *conn)
+                .await
+                .map_err(|e| e.to_string())?
+                .last_insert_rowid();
+            sqlx::query("UPDATE story_premises SET chat_id = ? WHERE id = ?")
+                .bind(chat_id)
+                .bind(story_id)
+                .execute(&mut *conn)
+                .await
+                .map_err(|e| e.to_string())?;
+            println!("[DB] Created chat {} for orphan story {}", chat_id, story_id);
+        }
+        Ok(())
+    })
+}
+
+/// Create turn rows for story messages saved before the `turns` table existed.
+fn backfill_turns(conn: &mut SqliteConnection) -> BoxFuture<'_, Result<(), String>> {
+    Box::pin(async move {
+        let n = crate::text_gen::turns::backfill_turns(conn).await?;
+        if n > 0 {
+            println!("[DB] Backfilled {} turns from stored messages", n);
+        }
+        Ok(())
+    })
+}
+
+// ============================================================================
+// RUNNER
+// ============================================================================
+
+/// The schema version this build migrates to.
+pub fn latest_version() -> i64 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+async fn user_version(pool: &SqlitePool) -> Result<i64, String> {
+    sqlx::query("PRAGMA user_version")
+        .fetch_one(pool)
+        .await
+        .map(|row| row.get(0))
+        .map_err(|e| format!("Failed to read schema version: {}", e))
+}
+
+async fn column_exists(conn: &mut SqliteConnection, table: &str, column: &str) -> Result<bool, String> {
+    sqlx::query("SELECT COUNT(*) FROM pragma_table_info(?) WHERE name = ?")
+        .bind(table)
+        .bind(column)
+        .fetch_one(conn)
+        .await
+        .map(|row| row.get::<i64, _>(0) > 0)
+        .map_err(|e| e.to_string())
+}
+
+/// `ALTER TABLE ... ADD COLUMN` unless the column already exists.
+///
+/// SQLite refuses to add a column with a non-constant default such as
+/// CURRENT_TIMESTAMP to a table that has rows. Then the column is added
+/// without the default and existing rows get the default's value.
+async fn add_column(conn: &mut SqliteConnection, table: &str, definition: &str) -> Result<(), String> {
+    let column = definition.split_whitespace().next().unwrap_or_default();
+    if column_exists(&mut *conn, table, column).await? {
+        return Ok(());
+    }
+
+    let added = sqlx::query(&format!("ALTER TABLE {} ADD COLUMN {}", table, definition))
+        .execute(&mut *conn)
+        .await;
+    match added {
+        Ok(_) => Ok(()),
+        Err(e) if e.to_string().contains("non-constant default") => {
+            let (base, default) = definition
+                .split_once(" DEFAULT ")
+                .ok_or_else(|| format!("Adding {}.{}: {}", table, column, e))?;
+            sqlx::query(&format!("ALTER TABLE {} ADD COLUMN {}", table, base))
+                .execute(&mut *conn)
+                .await
+                .map_err(|e| format!("Adding {}.{}: {}", table, column, e))?;
+            sqlx::query(&format!("UPDATE {} SET {} = {} WHERE {} IS NULL", table, column, default, column))
+                .execute(&mut *conn)
+                .await
+                .map_err(|e| format!("Filling {}.{}: {}", table, column, e))?;
+            Ok(())
+        }
+        Err(e) => Err(format!("Adding {}.{}: {}", table, column, e)),
+    }
+}
+
+/// Apply one migration and record its version, all in one transaction.
+async fn apply(pool: &SqlitePool, migration: &Migration) -> Result<(), String> {
+    let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+
+    for step in migration.steps {
+        match step {
+            Step::Sql(sql) => {
+                sqlx::query(sql).execute(&mut *tx).await.map_err(|e| e.to_string())?;
+            }
+            Step::AddColumn(table, definition) => add_column(&mut *tx, table, definition).await?,
+            Step::Run(run) => run(&mut *tx).await?,
+        }
+    }
+
+    // PRAGMA doesn't take bind parameters; the version is our own constant
+    sqlx::query(&format!("PRAGMA user_version = {}", migration.version))
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    tx.commit().await.map_err(|e| e.to_string())
+}
+
+/// Bring the database up to `latest_version()`. Called from
+/// `OllamaState::setup_database`; an error must stop startup. Without a
+/// `backup_dir` (restoring a backup, see backup.rs) nothing is backed up.
+pub async fn migrate(pool: &SqlitePool, backup_dir: Option<&Path>) -> Result<(), String> {
+    let current = user_version(pool).await?;
+    let latest = latest_version();
+    if current > latest {
+        return Err(format!(
+            "Database schema version {} is newer than this version of StoryEngine supports ({}). \
+             Update StoryEngine to open it.",
+            current, latest
+        ));
+    }
+    let pending: Vec<&Migration> = MIGRATIONS.iter().filter(|m| m.version > current).collect();
+    if pending.is_empty() {
+        return Ok(());
+    }
+
+    // A brand-new database has nothing worth backing up
+    let has_tables: bool = sqlx::query("SELECT COUNT(*) FROM sqlite_master WHERE type = 'table'")
+        .fetch_one(pool)
+        .await
+        .map(|row| row.get::<i64, _>(0) > 0)
+        .map_err(|e| format!("Failed to inspect database: {}", e))?;
+    let backup = match backup_dir {
+        Some(dir) if has_tables => {
+            let kind = format!("pre_migration_v{}", current);
+            Some(crate::backup::snapshot(pool, dir, &kind, None).await?.path)
+        }
+        _ => None,
+    };
+
+    for migration in pending {
+        println!("[DB] Applying migration {} ({})", migration.version, migration.name);
+        if let Err(e) = apply(pool, migration).await {
+            let restore_hint = backup
+                .as_ref()
+                .map(|p| format!(" A backup from before migrating is at {}.", p))
+                .unwrap_or_default();
+            return Err(format!(
+                "Database migration {} ({}) failed and was rolled back: {}.{}",
+                migration.version, migration.name, e, restore_hint
+            ));
+        }
+    }
+    println!("[DB] Schema migrated from version {} to {}", current, latest);
+    Ok(())
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    async fn memory_pool() -> SqlitePool {
+        SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap()
+    }
+
+    #[test]
+    fn test_versions_are_sequential() {
+        for (i, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version, i as i64 + 1, "migration {}", migration.name);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fresh_database_migrates_once() {
+        let pool = memory_pool().await;
+        let backup_dir = std::env::temp_dir().join("storyengine_migration_test_unused");
+
+        migrate(&pool, Some(&backup_dir)).await.unwrap();
+        assert_eq!(user_version(&pool).await.unwrap(), latest_version());
+        assert!(!backup_dir.exists());
+
+        // Second launch: nothing pending
+        migrate(&pool, Some(&backup_dir)).await.unwrap();
+        assert_eq!(user_version(&pool).await.unwrap(), latest_version());
+    }
+
+    #[tokio::test]
+    async fn test_unversioned_database_gets_missing_columns() {
+        // File-backed: VACUUM INTO from an in-memory pool writes an empty file
+        let dir = std::env::temp_dir().join(format!("storyengine_migration_test_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect(&format!("sqlite:{}?mode=rwc", dir.join("storyteller.db").display()))
+            .await
+            .unwrap();
+        // A pre-versioning database whose is_pov ALTER never happened
+        sqlx::query("CREATE TABLE characters (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT NOT NULL)")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO characters (name) VALUES ('Mara')")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let backup_dir = dir.join("backups");
+        migrate(&pool, Some(&backup_dir)).await.unwrap();
+
+        let mut conn = pool.acquire().await.unwrap();
+        assert!(column_exists(&mut conn, "characters", "is_pov").await.unwrap());
+        drop(conn);
+        let backups = std::fs::read_dir(&backup_dir).unwrap().count();
+        assert_eq!(backups, 1);
+        pool.close().await;
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_parse_stats_go_with_their_chat() {
+        let pool = memory_pool().await;
+        // A version 18 database with a stats row of a deleted chat
+        for sql in [
+            "CREATE TABLE chats (id INTEGER PRIMARY KEY AUTOINCREMENT, title TEXT NOT NULL DEFAULT 'New Chat')",
+            "CREATE TABLE parse_stats (id INTEGER PRIMARY KEY AUTOINCREMENT, chat_id INTEGER NOT NULL, \
+             story_id INTEGER, status TEXT NOT NULL, warning_count INTEGER NOT NULL DEFAULT 0, \
+             structured INTEGER NOT NULL DEFAULT 0, backend TEXT NOT NULL DEFAULT '', \
+             model TEXT NOT NULL DEFAULT '', created_at DATETIME DEFAULT CURRENT_TIMESTAMP)",
+            "INSERT INTO chats (id) VALUES (1)",
+            "INSERT INTO parse_stats (chat_id, status) VALUES (1, 'ok'), (2, 'fallback')",
+            "PRAGMA user_version = 18",
+            "PRAGMA foreign_keys = ON",
+        ] {
+            sqlx::query(sql).execute(&pool).await.unwrap();
+        }
+        let count = |pool: SqlitePool| async move {
+            sqlx::query("SELECT COUNT(*) FROM parse_stats").fetch_one(&pool).await.unwrap().get::<i64, _>(0)
+        };
+
+        migrate(&pool, None).await.unwrap();
+        assert_eq!(count(pool.clone()).await, 1);
+
+        sqlx::query("DELETE FROM chats WHERE id = 1").execute(&pool).await.unwrap();
+        assert_eq!(count(pool.clone()).await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_timestamp_column_added_to_populated_table() {
+        let pool = memory_pool().await;
+        sqlx::query("CREATE TABLE story_premises (id INTEGER PRIMARY KEY AUTOINCREMENT, title TEXT NOT NULL, description TEXT NOT NULL)")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO story_premises (title, description) VALUES ('Old story', '')")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let mut conn = pool.acquire().await.unwrap();
+        add_column(&mut conn, "story_premises", "last_played_at DATETIME DEFAULT CURRENT_TIMESTAMP")
+            .await
+            .unwrap();
+        let filled: Option<String> = sqlx::query("SELECT last_played_at FROM story_premises")
+            .fetch_one(&mut *conn)
+            .await
+            .unwrap()
+            .get(0);
+        assert!(filled.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_newer_schema_is_rejected() {
+        let pool = memory_pool().await;
+        sqlx::query(&format!("PRAGMA user_version = {}", latest_version() + 1))
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert!(migrate(&pool, None).await.is_err());
+    }
+}
@@ -139,6 +139,62 @@ pub struct SceneWithCharacters {
     pub characters: Vec<CharacterProfile>,
 }
 
+/// Where a lorebook entry is inserted in the story prompt.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LorePosition {
+    /// System block, right after the story premise
+    AfterPremise,
+    /// System block, after the character database
+    AfterCharacters,
+    /// Final user message, just before the player's input (default)
+    #[default]
+    BeforeInput,
+}
+
+impl LorePosition {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::AfterPremise => "after_premise",
+            Self::AfterCharacters => "after_characters",
+            Self::BeforeInput => "before_input",
+        }
+    }
+
+    /// Unknown values fall back to `BeforeInput`.
+    pub fn parse(s: &str) -> Self {
+        match s {
+            "after_premise" => Self::AfterPremise,
+            "after_characters" => Self::AfterCharacters,
+            _ => Self::BeforeInput,
+        }
+    }
+}
+
+/// A lorebook / world-info entry. `story_id = None` makes it global.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LoreEntry {
+    pub id: i64,
+    #[serde(default)]
+    pub story_id: Option<i64>,
+    pub name: String,
+    /// Trigger keywords (case-insensitive, whole words)
+    #[serde(default)]
+    pub keys: Vec<String>,
+    pub content: String,
+    /// Higher priority entries are injected first when the budget is tight
+    #[serde(default)]
+    pub priority: i64,
+    #[serde(default)]
+    pub position: LorePosition,
+    /// Injected every turn regardless of keys
+    #[serde(default)]
+    pub always_on: bool,
+    pub enabled: bool,
+    #[serde(default)]
+    pub created_at: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct SdJson {
     pub name: String,
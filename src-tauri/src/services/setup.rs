@@ -11,6 +11,8 @@ use std::process::{Command, Stdio};
 use std::time::Duration;
 use tauri::{AppHandle, Emitter, Manager};
 
+use crate::error::{AppError, ErrorKind};
+
 // =============================================================================
 // Public types
 // =============================================================================
@@ -1274,7 +1276,7 @@ pub fn detect_gpu_info() -> GpuInfo {
 }
 
 #[tauri::command]
-pub async fn check_ollama_gpu_usage() -> Result<OllamaGpuStatus, String> {
+pub async fn check_ollama_gpu_usage() -> Result<OllamaGpuStatus, AppError> {
     let ollama = match find_ollama_binary() {
         Some(p) => p,
         None => return Ok(OllamaGpuStatus {
@@ -1331,7 +1333,7 @@ pub async fn check_ollama_gpu_usage() -> Result<OllamaGpuStatus, String> {
 }
 
 #[tauri::command]
-pub async fn check_setup_status(app: AppHandle) -> Result<SetupStatus, String> {
+pub async fn check_setup_status(app: AppHandle) -> Result<SetupStatus, AppError> {
     let ollama = check_ollama().await;
     let ollama_model = check_ollama_model().await;
     let comfyui = check_comfyui(&app).await;
@@ -1360,7 +1362,7 @@ pub async fn check_setup_status(app: AppHandle) -> Result<SetupStatus, String> {
 }
 
 #[tauri::command]
-pub async fn install_dependency(name: String, app: AppHandle) -> Result<(), String> {
+pub async fn install_dependency(name: String, app: AppHandle) -> Result<(), AppError> {
     let result = match name.as_str() {
         "ollama" => install_ollama(&app).await,
         "ollama_model" => install_ollama_model(&app).await,
@@ -1375,18 +1377,22 @@ pub async fn install_dependency(name: String, app: AppHandle) -> Result<(), Stri
         "clip_vision" => install_clip_vision(&app).await,
         "custom_node_controlnet_aux" => install_custom_node_controlnet_aux(&app).await,
         "controlnet_openpose_model" => install_controlnet_openpose_model(&app).await,
-        _ => Err(format!("Unknown dependency: {}", name)),
+        _ => {
+            let message = format!("Unknown dependency: {}", name);
+            emit_progress(&app, &name, 0.0, &message, true);
+            return Err(AppError::invalid_input(message));
+        }
     };
 
     if let Err(ref e) = result {
         emit_progress(&app, &name, 0.0, e, true);
     }
 
-    result
+    result.map_err(|e| AppError::new(ErrorKind::SetupFailed, e))
 }
 
 #[tauri::command]
-pub async fn install_all_dependencies(app: AppHandle) -> Result<(), String> {
+pub async fn install_all_dependencies(app: AppHandle) -> Result<(), AppError> {
     let all_steps: &[(&str, &str)] = &[
         ("ollama", "Ollama LLM runtime"),
         ("ollama_model", "Story_v27 model"),
@@ -1449,7 +1455,10 @@ pub async fn install_all_dependencies(app: AppHandle) -> Result<(), String> {
                 &format!("Failed: {} — {}", label, e),
                 true,
             );
-            return Err(format!("Setup failed at '{}': {}", label, e));
+            return Err(AppError {
+                message: format!("Setup failed at '{}': {}", label, e),
+                ..e
+            });
         }
     }
 
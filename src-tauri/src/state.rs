@@ -7,6 +7,7 @@ use sqlx::sqlite::{SqlitePool, SqlitePoolOptions};
 use std::collections::HashMap;
 use std::sync::Mutex;
 use std::fs;
+use std::path::Path;
 use tauri::{AppHandle, Manager};
 
 /// Pending scene hints keyed by story_id.
@@ -29,7 +30,9 @@ pub struct OllamaState {
 }
 
 impl OllamaState {
-    pub async fn new(app_handle: &AppHandle) -> Self {
+    /// Open `storyteller.db` and migrate it. An error here means the
+    /// database can't be used safely and startup must stop.
+    pub async fn new(app_handle: &AppHandle) -> Result<Self, String> {
         // Tauri 2.0 uses app_handle.path() instead of tauri::api::path
         let app_dir = app_handle
             .path()
@@ -47,11 +50,11 @@ impl OllamaState {
             .max_connections(1)
             .connect(&db_url)
             .await
-            .expect("Failed to connect to SQLite");
+            .map_err(|e| format!("Failed to open database {}: {}", db_path.display(), e))?;
 
-        Self::setup_database(&pool).await;
+        Self::setup_database(&pool, &crate::backup::backup_dir(&app_dir)).await?;
 
-        Self {
+        Ok(Self {
             db: pool,
             client: reqwest::Client::builder()
                 .timeout(std::time::Duration::from_secs(180))
@@ -59,10 +62,10 @@ impl OllamaState {
                 .unwrap(),
             base_url: "http://localhost:11434".to_string(),
             current_chat_id: Mutex::new(None),
-        }
+        })
     }
 
-    async fn setup_database(pool: &SqlitePool) {
+    async fn setup_database(pool: &SqlitePool, backup_dir: &Path) -> Result<(), String> {
         // WAL mode allows concurrent reads without blocking writes
         sqlx::query("PRAGMA journal_mode=WAL")
             .execute(pool)
@@ -80,305 +83,8 @@ impl OllamaState {
             .execute(pool)
             .await
             .ok();
-    
-        // Chats table
-        sqlx::query(
-            "CREATE TABLE IF NOT EXISTS chats (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                title TEXT NOT NULL DEFAULT 'New Chat',
-                character_id INTEGER,
-                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
-            )"
-        )
-        .execute(pool)
-        .await
-        .expect("Failed to create chats table");
-
-        // Messages table
-        sqlx::query(
-            "CREATE TABLE IF NOT EXISTS messages (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                chat_id INTEGER NOT NULL,
-                role TEXT NOT NULL,
-                content TEXT NOT NULL,
-                timestamp DATETIME DEFAULT CURRENT_TIMESTAMP,
-                FOREIGN KEY(chat_id) REFERENCES chats(id) ON DELETE CASCADE
-            )"
-        )
-        .execute(pool)
-        .await
-        .expect("Failed to create messages table");
-
-        // Images table
-        sqlx::query(
-            "CREATE TABLE IF NOT EXISTS images (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                message_id INTEGER NOT NULL,
-                chat_id INTEGER NOT NULL,
-                file_path TEXT NOT NULL,
-                prompt TEXT,
-                FOREIGN KEY(message_id) REFERENCES messages(id) ON DELETE CASCADE,
-                FOREIGN KEY(chat_id) REFERENCES chats(id) ON DELETE CASCADE
-            )"
-        )
-        .execute(pool)
-        .await
-        .expect("Failed to create images table");
-
-        // Story premises table
-        sqlx::query(
-            "CREATE TABLE IF NOT EXISTS story_premises (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                title TEXT NOT NULL,
-                description TEXT NOT NULL,
-                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
-            )"
-        )
-        .execute(pool)
-        .await
-        .expect("Failed to create story_premises table");
-
-        // =====================================================================
-        // CHARACTERS TABLE - Extended for multi-story support
-        // =====================================================================
-        // Note: We add new columns if they don't exist (migration-safe)
-        sqlx::query(
-            "CREATE TABLE IF NOT EXISTS characters (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                story_id INTEGER,
-                name TEXT NOT NULL,
-                age INTEGER,
-                gender TEXT,
-                skin_tone TEXT,
-                hair_style TEXT,
-                hair_color TEXT,
-                body_type TEXT,
-                personality TEXT,
-                additional_notes TEXT,
-                default_clothing TEXT,
-                sd_prompt TEXT,
-                image TEXT,
-                master_image_path TEXT,
-                seed INTEGER,
-                art_style TEXT DEFAULT 'Realistic',
-                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-                updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-                FOREIGN KEY(story_id) REFERENCES story_premises(id) ON DELETE CASCADE
-            )"
-        )
-        .execute(pool)
-        .await
-        .expect("Failed to create characters table");
-
-        // Migration: Add new columns if they don't exist (for existing databases)
-        // These will silently fail if columns already exist
-        sqlx::query("ALTER TABLE characters ADD COLUMN story_id INTEGER REFERENCES story_premises(id) ON DELETE CASCADE")
-            .execute(pool).await.ok();
-        sqlx::query("ALTER TABLE characters ADD COLUMN default_clothing TEXT")
-            .execute(pool).await.ok();
-        sqlx::query("ALTER TABLE characters ADD COLUMN master_image_path TEXT")
-            .execute(pool).await.ok();
-        sqlx::query("ALTER TABLE characters ADD COLUMN created_at DATETIME DEFAULT CURRENT_TIMESTAMP")
-            .execute(pool).await.ok();
-        sqlx::query("ALTER TABLE characters ADD COLUMN updated_at DATETIME DEFAULT CURRENT_TIMESTAMP")
-            .execute(pool).await.ok();
-        sqlx::query("ALTER TABLE characters ADD COLUMN eye_color TEXT")
-            .execute(pool).await.ok();
-        sqlx::query("ALTER TABLE characters ADD COLUMN height_scale INTEGER DEFAULT 3")
-            .execute(pool).await.ok();
-        sqlx::query("ALTER TABLE characters ADD COLUMN weight_scale INTEGER DEFAULT 3")
-            .execute(pool).await.ok();
-        sqlx::query("ALTER TABLE characters ADD COLUMN content_rating TEXT DEFAULT 'sfw'")
-            .execute(pool).await.ok();
-        sqlx::query("ALTER TABLE characters ADD COLUMN is_pov INTEGER DEFAULT 0")
-            .execute(pool).await.ok();
-
-        // =====================================================================
-        // STORY_CHARACTERS junction table (many-to-many: character <-> story)
-        // A character can belong to multiple stories; a story can have many characters.
-        // CASCADE on both FKs: deleting a story removes its junction rows (not the
-        // characters themselves). Deleting a character removes all its junction rows.
-        // =====================================================================
-        sqlx::query(
-            "CREATE TABLE IF NOT EXISTS story_characters (
-                story_id     INTEGER NOT NULL,
-                character_id INTEGER NOT NULL,
-                added_at     DATETIME DEFAULT CURRENT_TIMESTAMP,
-                PRIMARY KEY (story_id, character_id),
-                FOREIGN KEY(story_id)     REFERENCES story_premises(id) ON DELETE CASCADE,
-                FOREIGN KEY(character_id) REFERENCES characters(id)     ON DELETE CASCADE
-            )"
-        )
-        .execute(pool)
-        .await
-        .expect("Failed to create story_characters table");
-
-        // Migrate existing data: populate junction table from characters.story_id
-        // INSERT OR IGNORE is safe to run on every startup (PK prevents duplicates).
-        sqlx::query(
-            "INSERT OR IGNORE INTO story_characters (story_id, character_id)
-             SELECT story_id, id FROM characters WHERE story_id IS NOT NULL"
-        )
-        .execute(pool)
-        .await
-        .ok();
-
-        // =====================================================================
-        // INDEXES for fast lookups
-        // =====================================================================
-        
-        // Index for fast exact name lookups (critical for LLM integration)
-        sqlx::query("CREATE INDEX IF NOT EXISTS idx_characters_name ON characters(name)")
-            .execute(pool).await.ok();
-
-        // Index for story-based queries
-        sqlx::query("CREATE INDEX IF NOT EXISTS idx_characters_story ON characters(story_id)")
-            .execute(pool).await.ok();
-
-        // Composite index for name + story lookups
-        sqlx::query("CREATE INDEX IF NOT EXISTS idx_characters_story_name ON characters(story_id, name)")
-            .execute(pool).await.ok();
-
-        // Indexes for story_characters junction table
-        sqlx::query("CREATE INDEX IF NOT EXISTS idx_sc_story ON story_characters(story_id)")
-            .execute(pool).await.ok();
-        sqlx::query("CREATE INDEX IF NOT EXISTS idx_sc_character ON story_characters(character_id)")
-            .execute(pool).await.ok();
 
-        // Index for chat lookups
-        sqlx::query("CREATE INDEX IF NOT EXISTS idx_messages_chat ON messages(chat_id)")
-            .execute(pool).await.ok();
-
-        // Index for image lookups
-        sqlx::query("CREATE INDEX IF NOT EXISTS idx_images_chat ON images(chat_id)")
-            .execute(pool).await.ok();
-
-        // =====================================================================
-        // SCENES TABLE
-        // =====================================================================
-        sqlx::query(
-            "CREATE TABLE IF NOT EXISTS scenes (
-                id            INTEGER PRIMARY KEY AUTOINCREMENT,
-                name          TEXT NOT NULL,
-                description   TEXT,
-                location      TEXT,
-                location_type TEXT,
-                time_of_day   TEXT,
-                mood          TEXT,
-                created_at    DATETIME DEFAULT CURRENT_TIMESTAMP
-            )"
-        )
-        .execute(pool)
-        .await
-        .expect("Failed to create scenes table");
-
-        // Many-to-many: scene <-> story
-        // Deleting a story removes its scene links (not the scenes themselves).
-        // Deleting a scene removes all its story links.
-        sqlx::query(
-            "CREATE TABLE IF NOT EXISTS story_scenes (
-                story_id INTEGER NOT NULL,
-                scene_id INTEGER NOT NULL,
-                PRIMARY KEY (story_id, scene_id),
-                FOREIGN KEY(story_id) REFERENCES story_premises(id) ON DELETE CASCADE,
-                FOREIGN KEY(scene_id) REFERENCES scenes(id)          ON DELETE CASCADE
-            )"
-        )
-        .execute(pool)
-        .await
-        .expect("Failed to create story_scenes table");
-
-        // Many-to-many: scene <-> character
-        // Deleting a scene or character removes their link rows.
-        sqlx::query(
-            "CREATE TABLE IF NOT EXISTS scene_characters (
-                scene_id     INTEGER NOT NULL,
-                character_id INTEGER NOT NULL,
-                PRIMARY KEY (scene_id, character_id),
-                FOREIGN KEY(scene_id)     REFERENCES scenes(id)     ON DELETE CASCADE,
-                FOREIGN KEY(character_id) REFERENCES characters(id) ON DELETE CASCADE
-            )"
-        )
-        .execute(pool)
-        .await
-        .expect("Failed to create scene_characters table");
-
-        // Indexes for scene tables
-        sqlx::query("CREATE INDEX IF NOT EXISTS idx_ss_story   ON story_scenes(story_id)")
-            .execute(pool).await.ok();
-        sqlx::query("CREATE INDEX IF NOT EXISTS idx_ss_scene   ON story_scenes(scene_id)")
-            .execute(pool).await.ok();
-        sqlx::query("CREATE INDEX IF NOT EXISTS idx_sch_scene  ON scene_characters(scene_id)")
-            .execute(pool).await.ok();
-        sqlx::query("CREATE INDEX IF NOT EXISTS idx_sch_char   ON scene_characters(character_id)")
-            .execute(pool).await.ok();
-
-        // Migration: add active_scene_id to story_premises (safe to run repeatedly)
-        sqlx::query(
-            "ALTER TABLE story_premises ADD COLUMN active_scene_id INTEGER REFERENCES scenes(id) ON DELETE SET NULL"
-        )
-        .execute(pool)
-        .await
-        .ok();
-
-        // =====================================================================
-        // STORY MANAGER MIGRATIONS
-        // =====================================================================
-        // Adds session-tracking columns to story_premises (safe to run repeatedly)
-        crate::commands::story::run_migrations(pool).await;
-
-        // =====================================================================
-        // CUSTOM ASSETS TABLES
-        // =====================================================================
-        // =====================================================================
-        // EMOTIONAL STATE PERSISTENCE
-        // =====================================================================
-        sqlx::query(
-            "CREATE TABLE IF NOT EXISTS character_emotional_states (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                chat_id INTEGER NOT NULL,
-                character_name TEXT NOT NULL,
-                current_emotion TEXT NOT NULL DEFAULT 'neutral',
-                emotion_intensity TEXT NOT NULL DEFAULT 'low',
-                emotion_cause TEXT NOT NULL DEFAULT '',
-                lingering_emotions TEXT NOT NULL DEFAULT '[]',
-                updated_at TEXT NOT NULL DEFAULT (datetime('now')),
-                UNIQUE(chat_id, character_name)
-            )"
-        )
-        .execute(pool)
-        .await
-        .expect("Failed to create character_emotional_states table");
-
-        sqlx::query(
-            "CREATE INDEX IF NOT EXISTS idx_ces_chat_id ON character_emotional_states(chat_id)"
-        )
-        .execute(pool)
-        .await
-        .ok();
-
-        sqlx::query(
-            "CREATE TABLE IF NOT EXISTS custom_checkpoints (
-                id           INTEGER PRIMARY KEY AUTOINCREMENT,
-                display_name TEXT NOT NULL,
-                filename     TEXT NOT NULL,
-                created_at   DATETIME DEFAULT CURRENT_TIMESTAMP
-            )"
-        )
-        .execute(pool)
-        .await
-        .expect("Failed to create custom_checkpoints table");
-
-        sqlx::query(
-            "CREATE TABLE IF NOT EXISTS custom_poses (
-                id           INTEGER PRIMARY KEY AUTOINCREMENT,
-                display_name TEXT NOT NULL,
-                filename     TEXT NOT NULL,
-                created_at   DATETIME DEFAULT CURRENT_TIMESTAMP
-            )"
-        )
-        .execute(pool)
-        .await
-        .expect("Failed to create custom_poses table");
+        // Tables, columns and indexes (see migrations.rs)
+        crate::migrations::migrate(pool, Some(backup_dir)).await
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,614 @@
+// src-tauri/src/text_gen/backend.rs
+//
+// Pluggable Text Generation Backends
+// ====================================
+// Everything that talks to an LLM goes through the `TextBackend` trait so the
+// story pipeline does not care which server is running the model:
+//
+//   - OllamaBackend  — Ollama native API (`/api/generate`, `/api/chat`)
+//   - OpenAiBackend  — any OpenAI-compatible server (`/v1/chat/completions`):
+//                      llama.cpp server, vLLM, LM Studio, koboldcpp, ...
+//
+// Each backend describes what it can do via `BackendCapabilities`. The
+// orchestrator uses that to decide whether to send the pre-templated raw
+// prompt (KV-cache friendly, see context::assemble_prompt_string) or a chat
+// message list (see context::build_compressed_chat_messages).
+
+use futures_util::future::BoxFuture;
+use futures_util::FutureExt;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::time::Duration;
+use tauri::State;
+
+use crate::config::{AppConfig, ConfigState};
+use crate::error::{backend_http_error, backend_request_error, AppError, ErrorKind};
+use crate::state::OllamaState;
+use crate::text_gen::streaming::{read_ollama_stream, read_openai_sse_stream};
+
+// ============================================================================
+// REQUEST / RESPONSE TYPES
+// ============================================================================
+
+/// What a backend supports. Serialized for the settings UI.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackendCapabilities {
+    /// Accepts a fully templated prompt string sent verbatim (no chat template).
+    pub raw_prompt: bool,
+    /// Accepts a role-tagged chat message list.
+    pub chat: bool,
+    /// Can constrain output to valid JSON.
+    pub json_mode: bool,
+    /// Can constrain output to a caller-supplied JSON schema.
+    pub json_schema: bool,
+    /// Reports prompt/completion token counts in its responses.
+    pub reports_token_usage: bool,
+    /// Can stream partial output while generating.
+    pub streaming: bool,
+}
+
+/// A single role-tagged chat message.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: String,
+    pub content: String,
+}
+
+/// The prompt to send, in the form the caller has prepared.
+#[derive(Debug, Clone)]
+pub enum PromptInput {
+    /// A fully templated prompt (e.g. ChatML) sent verbatim.
+    Raw(String),
+    /// A single instruction; the server wraps it in the model's chat template.
+    Instruction(String),
+    /// A chat message list; the server applies the model's chat template.
+    Chat(Vec<ChatMessage>),
+}
+
+/// Sampling options. `None` leaves the server's default in place.
+#[derive(Debug, Clone, Default)]
+pub struct SamplingOptions {
+    pub num_ctx: Option<u32>,
+    pub num_predict: Option<u32>,
+    pub temperature: Option<f64>,
+    pub top_p: Option<f64>,
+    pub top_k: Option<u32>,
+    pub repeat_penalty: Option<f64>,
+    /// Extra stop sequences (the chat template's end-of-turn markers for raw prompts).
+    pub stop: Vec<String>,
+}
+
+/// A backend-agnostic generation request.
+#[derive(Debug, Clone)]
+pub struct GenerateRequest {
+    pub model: String,
+    pub prompt: PromptInput,
+    pub options: SamplingOptions,
+    /// Ollama keep_alive duration string; ignored by other backends.
+    pub keep_alive: Option<String>,
+    /// Ask the server to constrain output to JSON (if supported).
+    pub json_mode: bool,
+    /// Constrain output to this JSON schema (takes precedence over `json_mode`).
+    pub json_schema: Option<Value>,
+    /// Stream output and report fragments through the text callback.
+    pub stream: bool,
+    pub timeout_secs: u64,
+}
+
+impl GenerateRequest {
+    /// A simple non-streaming request with server-default sampling.
+    pub fn new(model: &str, prompt: PromptInput, timeout_secs: u64) -> Self {
+        Self {
+            model: model.to_string(),
+            prompt,
+            options: SamplingOptions::default(),
+            keep_alive: None,
+            json_mode: false,
+            json_schema: None,
+            stream: false,
+            timeout_secs,
+        }
+    }
+}
+
+/// Token accounting and timings, as far as the backend reports them.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TokenUsage {
+    pub prompt_tokens: Option<u64>,
+    pub completion_tokens: Option<u64>,
+    /// Time spent evaluating the prompt (Ollama only).
+    pub prompt_eval_ns: Option<u64>,
+    /// Time spent generating (Ollama only).
+    pub eval_ns: Option<u64>,
+}
+
+/// The full generated text plus whatever usage info the backend reported.
+#[derive(Debug, Clone)]
+pub struct GenerateResponse {
+    pub text: String,
+    pub usage: TokenUsage,
+    /// Generation stopped because it hit the token limit (num_predict / max_tokens).
+    pub truncated: bool,
+}
+
+/// Callback receiving text fragments as they stream in.
+pub type TextCallback<'a> = Option<&'a mut (dyn FnMut(&str) + Send)>;
+
+// ============================================================================
+// TRAIT
+// ============================================================================
+
+/// A server that can generate text from a prompt.
+pub trait TextBackend: Send + Sync {
+    /// Short identifier for logs ("ollama", "openai").
+    fn name(&self) -> &'static str;
+
+    fn capabilities(&self) -> BackendCapabilities;
+
+    /// Run one generation. When `request.stream` is set, `on_text` receives
+    /// fragments as they arrive; the full text is returned either way.
+    /// Errors are typed (server down, model missing, context overflow, ...).
+    fn generate<'a>(
+        &'a self,
+        request: &'a GenerateRequest,
+        on_text: TextCallback<'a>,
+    ) -> BoxFuture<'a, Result<GenerateResponse, AppError>>;
+}
+
+/// Build the backend selected in the app config.
+pub fn backend_from_config(config: &AppConfig, client: &reqwest::Client) -> Box<dyn TextBackend> {
+    match config.text_backend.as_str() {
+        "openai" => Box::new(OpenAiBackend {
+            client: client.clone(),
+            base_url: config.openai_api_url.clone(),
+            api_key: config.openai_api_key.clone(),
+        }),
+        _ => Box::new(OllamaBackend {
+            client: client.clone(),
+            base_url: config.ollama_url.clone(),
+        }),
+    }
+}
+
+/// Send a POST. Only failing to reach the server is an error here; see
+/// `check_status` for the response.
+async fn send_json(
+    request: reqwest::RequestBuilder,
+    body: &Value,
+    timeout_secs: u64,
+    server: &str,
+) -> Result<reqwest::Response, AppError> {
+    request
+        .timeout(Duration::from_secs(timeout_secs))
+        .json(body)
+        .send()
+        .await
+        .map_err(|e| backend_request_error(server, &e))
+}
+
+/// Turn a non-2xx response into an error with the body text.
+async fn check_status(res: reqwest::Response, server: &str) -> Result<reqwest::Response, AppError> {
+    if !res.status().is_success() {
+        let status = res.status();
+        let text = res.text().await.unwrap_or_default();
+        return Err(backend_http_error(server, status, &text));
+    }
+    Ok(res)
+}
+
+/// Send a POST and turn non-2xx responses into an error with the body text.
+async fn post_json(
+    request: reqwest::RequestBuilder,
+    body: &Value,
+    timeout_secs: u64,
+    server: &str,
+) -> Result<reqwest::Response, AppError> {
+    check_status(send_json(request, body, timeout_secs, server).await?, server).await
+}
+
+// ============================================================================
+// OLLAMA
+// ============================================================================
+
+/// Ollama native API. Raw prompts and instructions go to `/api/generate`,
+/// chat message lists to `/api/chat`.
+pub struct OllamaBackend {
+    client: reqwest::Client,
+    base_url: String,
+}
+
+impl OllamaBackend {
+    fn build_body(request: &GenerateRequest) -> (&'static str, Value) {
+        let mut options = serde_json::Map::new();
+        if let Some(n) = request.options.num_ctx {
+            options.insert("num_ctx".into(), json!(n));
+        }
+        if let Some(n) = request.options.num_predict {
+            options.insert("num_predict".into(), json!(n));
+        }
+        if let Some(t) = request.options.temperature {
+            options.insert("temperature".into(), json!(t));
+        }
+        if let Some(p) = request.options.top_p {
+            options.insert("top_p".into(), json!(p));
+        }
+        if let Some(k) = request.options.top_k {
+            options.insert("top_k".into(), json!(k));
+        }
+        if let Some(r) = request.options.repeat_penalty {
+            options.insert("repeat_penalty".into(), json!(r));
+        }
+        if !request.options.stop.is_empty() {
+            options.insert("stop".into(), json!(request.options.stop));
+        }
+
+        let (endpoint, mut body) = match &request.prompt {
+            PromptInput::Raw(prompt) => (
+                "/api/generate",
+                json!({ "model": request.model, "prompt": prompt, "raw": true }),
+            ),
+            PromptInput::Instruction(prompt) => (
+                "/api/generate",
+                json!({ "model": request.model, "prompt": prompt }),
+            ),
+            PromptInput::Chat(messages) => (
+                "/api/chat",
+                json!({ "model": request.model, "messages": messages }),
+            ),
+        };
+
+        body["stream"] = json!(request.stream);
+        body["think"] = json!(false);
+        if let Some(ref keep_alive) = request.keep_alive {
+            body["keep_alive"] = json!(keep_alive);
+        }
+        if !options.is_empty() {
+            body["options"] = Value::Object(options);
+        }
+        if let Some(ref schema) = request.json_schema {
+            body["format"] = schema.clone();
+        } else if request.json_mode {
+            body["format"] = json!("json");
+        }
+        (endpoint, body)
+    }
+}
+
+impl TextBackend for OllamaBackend {
+    fn name(&self) -> &'static str {
+        "ollama"
+    }
+
+    fn capabilities(&self) -> BackendCapabilities {
+        BackendCapabilities {
+            raw_prompt: true,
+            chat: true,
+            json_mode: true,
+            json_schema: true,
+            reports_token_usage: true,
+            streaming: true,
+        }
+    }
+
+    fn generate<'a>(
+        &'a self,
+        request: &'a GenerateRequest,
+        mut on_text: TextCallback<'a>,
+    ) -> BoxFuture<'a, Result<GenerateResponse, AppError>> {
+        async move {
+            let (endpoint, body) = Self::build_body(request);
+            let res = post_json(
+                self.client.post(format!("{}{}", self.base_url, endpoint)),
+                &body,
+                request.timeout_secs,
+                "Ollama",
+            )
+            .await?;
+
+            let result = read_ollama_stream(res, |piece| {
+                if let Some(cb) = on_text.as_mut() {
+                    cb(piece);
+                }
+            })
+            .await
+            .map_err(|e| AppError::new(ErrorKind::GenerationFailed, e))?;
+
+            Ok(GenerateResponse {
+                text: result["response"].as_str().unwrap_or("").to_string(),
+                truncated: result["done_reason"].as_str() == Some("length"),
+                usage: TokenUsage {
+                    prompt_tokens: result["prompt_eval_count"].as_u64(),
+                    completion_tokens: result["eval_count"].as_u64(),
+                    prompt_eval_ns: result["prompt_eval_duration"].as_u64(),
+                    eval_ns: result["eval_duration"].as_u64(),
+                },
+            })
+        }
+        .boxed()
+    }
+}
+
+// ============================================================================
+// OPENAI-COMPATIBLE
+// ============================================================================
+
+/// Any server implementing OpenAI's `/v1/chat/completions`
+/// (llama.cpp server, vLLM, LM Studio, koboldcpp, ...).
+pub struct OpenAiBackend {
+    client: reqwest::Client,
+    /// Server root, with or without a trailing `/v1`.
+    base_url: String,
+    /// Bearer token; empty for local servers that don't check it.
+    api_key: String,
+}
+
+impl OpenAiBackend {
+    fn endpoint(&self) -> String {
+        let root = self.base_url.trim_end_matches('/');
+        let root = root.strip_suffix("/v1").unwrap_or(root);
+        format!("{}/v1/chat/completions", root)
+    }
+
+    fn post(&self) -> reqwest::RequestBuilder {
+        let req = self.client.post(self.endpoint());
+        if self.api_key.is_empty() {
+            req
+        } else {
+            req.bearer_auth(&self.api_key)
+        }
+    }
+
+    fn build_body(request: &GenerateRequest) -> Value {
+        let messages = match &request.prompt {
+            PromptInput::Chat(messages) => messages.clone(),
+            // Chat servers apply their own template; a pre-templated prompt
+            // still works as a single user turn, just without KV-prefix reuse.
+            PromptInput::Raw(prompt) | PromptInput::Instruction(prompt) => vec![ChatMessage {
+                role: "user".to_string(),
+                content: prompt.clone(),
+            }],
+        };
+
+        let mut body = json!({
+            "model": request.model,
+            "messages": messages,
+            "stream": request.stream,
+        });
+        if request.stream {
+            // Ask for a final usage chunk (ignored by servers that don't support it)
+            body["stream_options"] = json!({ "include_usage": true });
+        }
+        if let Some(n) = request.options.num_predict {
+            body["max_tokens"] = json!(n);
+        }
+        if let Some(t) = request.options.temperature {
+            body["temperature"] = json!(t);
+        }
+        if let Some(p) = request.options.top_p {
+            body["top_p"] = json!(p);
+        }
+        // top_k and repeat_penalty are not part of the OpenAI spec, but
+        // llama.cpp server, koboldcpp and vLLM all accept them as extensions.
+        if let Some(k) = request.options.top_k {
+            body["top_k"] = json!(k);
+        }
+        if let Some(r) = request.options.repeat_penalty {
+            body["repeat_penalty"] = json!(r);
+        }
+        if !request.options.stop.is_empty() {
+            body["stop"] = json!(request.options.stop);
+        }
+        if let Some(ref schema) = request.json_schema {
+            body["response_format"] = json!({
+                "type": "json_schema",
+                "json_schema": { "name": "story_turn", "strict": true, "schema": schema },
+            });
+        } else if request.json_mode {
+            body["response_format"] = json!({ "type": "json_object" });
+        }
+        body
+    }
+}
+
+impl TextBackend for OpenAiBackend {
+    fn name(&self) -> &'static str {
+        "openai"
+    }
+
+    fn capabilities(&self) -> BackendCapabilities {
+        BackendCapabilities {
+            raw_prompt: false,
+            chat: true,
+            json_mode: true,
+            json_schema: true,
+            reports_token_usage: true,
+            streaming: true,
+        }
+    }
+
+    fn generate<'a>(
+        &'a self,
+        request: &'a GenerateRequest,
+        mut on_text: TextCallback<'a>,
+    ) -> BoxFuture<'a, Result<GenerateResponse, AppError>> {
+        async move {
+            let mut body = Self::build_body(request);
+            let mut res = send_json(self.post(), &body, request.timeout_secs, "LLM server").await?;
+            // Servers without structured outputs reject a json_schema
+            // response_format; plain JSON mode is the next best thing
+            if res.status() == reqwest::StatusCode::BAD_REQUEST && request.json_schema.is_some() {
+                let text = res.text().await.unwrap_or_default();
+                println!(
+                    "[Backend] LLM server rejected the JSON schema ({}), retrying in json_object mode",
+                    text.trim()
+                );
+                body["response_format"] = json!({ "type": "json_object" });
+                res = send_json(self.post(), &body, request.timeout_secs, "LLM server").await?;
+            }
+            let res = check_status(res, "LLM server").await?;
+
+            let (text, usage, finish_reason) = if request.stream {
+                let output = read_openai_sse_stream(res, |piece| {
+                    if let Some(cb) = on_text.as_mut() {
+                        cb(piece);
+                    }
+                })
+                .await
+                .map_err(|e| AppError::new(ErrorKind::GenerationFailed, e))?;
+                (output.text, output.usage, output.finish_reason)
+            } else {
+                let json: Value = res
+                    .json()
+                    .await
+                    .map_err(|e| AppError::new(ErrorKind::GenerationFailed, format!("Failed to parse LLM server response: {}", e)))?;
+                let text = json["choices"][0]["message"]["content"]
+                    .as_str()
+                    .unwrap_or("")
+                    .to_string();
+                let finish_reason = json["choices"][0]["finish_reason"].as_str().map(String::from);
+                (text, json.get("usage").cloned(), finish_reason)
+            };
+
+            let usage = usage.unwrap_or(Value::Null);
+            Ok(GenerateResponse {
+                text,
+                truncated: finish_reason.as_deref() == Some("length"),
+                usage: TokenUsage {
+                    prompt_tokens: usage["prompt_tokens"].as_u64(),
+                    completion_tokens: usage["completion_tokens"].as_u64(),
+                    prompt_eval_ns: None,
+                    eval_ns: None,
+                },
+            })
+        }
+        .boxed()
+    }
+}
+
+// ============================================================================
+// COMMANDS
+// ============================================================================
+
+/// Name and capabilities of the configured text backend.
+#[derive(Debug, Clone, Serialize)]
+pub struct TextBackendInfo {
+    pub name: String,
+    pub capabilities: BackendCapabilities,
+}
+
+/// Describe the text backend selected in the config.
+///
+/// Frontend: `await invoke('get_text_backend_info')`
+#[tauri::command]
+pub fn get_text_backend_info(
+    config_state: State<'_, ConfigState>,
+    state: State<'_, OllamaState>,
+) -> Result<TextBackendInfo, AppError> {
+    let config = config_state.0.lock().map_err(|e| e.to_string())?;
+    let backend = backend_from_config(&config, &state.client);
+    Ok(TextBackendInfo {
+        name: backend.name().to_string(),
+        capabilities: backend.capabilities(),
+    })
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(prompt: PromptInput) -> GenerateRequest {
+        let mut req = GenerateRequest::new("test-model", prompt, 30);
+        req.options.num_predict = Some(256);
+        req.options.temperature = Some(0.8);
+        req
+    }
+
+    #[test]
+    fn test_ollama_raw_prompt_uses_generate() {
+        let mut req = request(PromptInput::Raw("<|im_start|>user\nhi".into()));
+        req.keep_alive = Some("30m".into());
+        req.options.stop = vec!["<|im_end|>".into()];
+        let (endpoint, body) = OllamaBackend::build_body(&req);
+        assert_eq!(endpoint, "/api/generate");
+        assert_eq!(body["raw"], true);
+        assert_eq!(body["keep_alive"], "30m");
+        assert_eq!(body["options"]["num_predict"], 256);
+        assert_eq!(body["options"]["stop"][0], "<|im_end|>");
+        assert!(body.get("format").is_none());
+    }
+
+    #[test]
+    fn test_ollama_chat_uses_chat_endpoint() {
+        let mut req = request(PromptInput::Chat(vec![ChatMessage {
+            role: "user".into(),
+            content: "hi".into(),
+        }]));
+        req.json_mode = true;
+        let (endpoint, body) = OllamaBackend::build_body(&req);
+        assert_eq!(endpoint, "/api/chat");
+        assert_eq!(body["messages"][0]["content"], "hi");
+        assert_eq!(body["format"], "json");
+    }
+
+    #[test]
+    fn test_json_schema_takes_precedence() {
+        let mut req = request(PromptInput::Raw("prompt".into()));
+        req.json_mode = true;
+        req.json_schema = Some(json!({ "type": "object" }));
+        let (_, ollama) = OllamaBackend::build_body(&req);
+        assert_eq!(ollama["format"]["type"], "object");
+        let openai = OpenAiBackend::build_body(&req);
+        assert_eq!(openai["response_format"]["type"], "json_schema");
+        assert_eq!(openai["response_format"]["json_schema"]["schema"]["type"], "object");
+    }
+
+    #[test]
+    fn test_ollama_instruction_omits_empty_options() {
+        let req = GenerateRequest::new("m", PromptInput::Instruction("sum".into()), 60);
+        let (_, body) = OllamaBackend::build_body(&req);
+        assert!(body.get("raw").is_none());
+        assert!(body.get("options").is_none());
+        assert_eq!(body["stream"], false);
+    }
+
+    #[test]
+    fn test_openai_body_maps_options() {
+        let mut req = request(PromptInput::Instruction("Summarize".into()));
+        req.stream = true;
+        req.json_mode = true;
+        let body = OpenAiBackend::build_body(&req);
+        assert_eq!(body["messages"][0]["role"], "user");
+        assert_eq!(body["max_tokens"], 256);
+        assert_eq!(body["response_format"]["type"], "json_object");
+        assert_eq!(body["stream_options"]["include_usage"], true);
+    }
+
+    #[test]
+    fn test_sampler_options_forwarded() {
+        let mut req = request(PromptInput::Instruction("Summarize".into()));
+        req.options.top_p = Some(0.9);
+        req.options.top_k = Some(40);
+        req.options.repeat_penalty = Some(1.1);
+        let (_, ollama) = OllamaBackend::build_body(&req);
+        assert_eq!(ollama["options"]["top_k"], 40);
+        assert_eq!(ollama["options"]["repeat_penalty"], 1.1);
+        let openai = OpenAiBackend::build_body(&req);
+        assert_eq!(openai["top_p"], 0.9);
+        assert_eq!(openai["top_k"], 40);
+    }
+
+    #[test]
+    fn test_openai_endpoint_normalizes_v1() {
+        let make = |url: &str| OpenAiBackend {
+            client: reqwest::Client::new(),
+            base_url: url.into(),
+            api_key: String::new(),
+        };
+        assert_eq!(make("http://127.0.0.1:8080").endpoint(), "http://127.0.0.1:8080/v1/chat/completions");
+        assert_eq!(make("http://127.0.0.1:8080/v1/").endpoint(), "http://127.0.0.1:8080/v1/chat/completions");
+    }
+}
@@ -14,6 +14,12 @@
 //   4. Optionally call the LLM itself to produce a higher-quality summary
 //   5. Keep the last N turns in full detail
 //   6. Always preserve: system prompt, character DB info, current location
+//   7. Store LLM summaries in `context_summaries` with the turn range they
+//      cover — the `messages` table always keeps the full story
+//   8. For long stories, older turns move into hierarchical scene/chapter/arc
+//      memory (see memory.rs) ahead of the rolling summary
+//   9. Lorebook entries triggered by recent turns are injected at their
+//      configured position within their own budget (see lorebook.rs)
 //
 // Usage:
 //   The `generate_story` command in chat.rs should call `build_compressed_context`
@@ -24,12 +30,21 @@ use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use sqlx::Row;
 
+use crate::models::LorePosition;
+use crate::text_gen::backend::{GenerateRequest, PromptInput, SamplingOptions, TextBackend};
+use crate::text_gen::lorebook::{FiredLore, Lorebook};
+use crate::text_gen::memory::{memory_coverage, select_memory, MemoryEntry, MEMORY_BUDGET_SHARE};
+use crate::text_gen::parser::CharacterEmotionalState;
+use crate::text_gen::recall::RecalledTurn;
+use crate::text_gen::template::PromptTemplate;
+use crate::text_gen::turns::TurnRecord;
 
 // ============================================================================
 // CONSTANTS
 // ============================================================================
 
-/// Qwen 3.5 9B context window size
+/// Default context window size (AppConfig.num_ctx). The live value comes from
+/// `ConversationContext::with_context_size`.
 pub const MAX_CONTEXT_TOKENS: usize = 8192;
 
 /// Trigger compression when estimated tokens exceed this fraction of the window
@@ -44,8 +59,12 @@ pub const RECENT_TURNS_TO_KEEP: usize = 6;
 pub const RESERVED_TOKENS: usize = 1500;
 
 /// Effective token budget for conversation history (compressed + recent)
+/// at the default context size. See `ConversationContext::history_token_budget`.
 pub const HISTORY_TOKEN_BUDGET: usize = MAX_CONTEXT_TOKENS - RESERVED_TOKENS; // ~6692
 
+/// Share of the history budget for recalled past turns ("RELEVANT MEMORIES").
+pub const RECALL_BUDGET_SHARE: f64 = 0.1;
+
 // ============================================================================
 // 1. TOKEN ESTIMATION
 // ============================================================================
@@ -75,8 +94,8 @@ pub fn estimate_messages_tokens(messages: &[Value]) -> usize {
 // 2. STORY TURN — one user action + one assistant response
 // ============================================================================
 
-/// A single conversation turn extracted from the DB's messages table.
-/// We pair each user message with the assistant response that follows it.
+/// A single conversation turn: one user message and the assistant response
+/// that follows it. Loaded from the typed `turns` table (see text_gen::turns).
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StoryTurn {
     /// 1-based turn number within this chat session
@@ -87,6 +106,15 @@ pub struct StoryTurn {
     pub assistant_response: String,
     /// One-line summary extracted from story_json.summary_hint (may be empty)
     pub summary_hint: String,
+    /// Narrative text of the response (story_json.response)
+    #[serde(default)]
+    pub story_text: String,
+    /// Location of the scene after this turn (may be empty)
+    #[serde(default)]
+    pub location: String,
+    /// Characters' emotional states at the end of this turn
+    #[serde(default)]
+    pub emotional_states: Vec<CharacterEmotionalState>,
     /// Cached token estimate for this turn (user + assistant combined)
     pub token_estimate: usize,
     /// Database ID of the assistant message for this turn (None if not loaded from DB)
@@ -97,26 +125,47 @@ pub struct StoryTurn {
 
 impl StoryTurn {
     /// Build a StoryTurn from a user message and the following assistant message.
-    /// Parses the assistant JSON to extract the summary_hint.
+    /// Parses the assistant JSON to extract the summary_hint, story text,
+    /// location and emotional states.
     pub fn from_messages(turn_number: usize, user_input: &str, assistant_response: &str) -> Self {
-        let summary_hint = extract_summary_hint(assistant_response);
         let token_estimate = estimate_tokens(user_input) + estimate_tokens(assistant_response) + 8;
         Self {
             turn_number,
             user_input: user_input.to_string(),
             assistant_response: assistant_response.to_string(),
-            summary_hint,
+            summary_hint: extract_summary_hint(assistant_response),
+            story_text: extract_story_text(assistant_response),
+            location: extract_location_from_response(assistant_response),
+            emotional_states: parse_emotional_states(assistant_response),
             token_estimate,
             message_id: None,
             image_path: None,
         }
     }
+
+    /// Build a StoryTurn from a row of the `turns` table. The parsed fields
+    /// come from its typed columns; the raw response is kept for display.
+    pub fn from_record(turn_number: usize, user_input: &str, assistant_response: &str, record: &TurnRecord) -> Self {
+        let token_estimate = estimate_tokens(user_input) + estimate_tokens(assistant_response) + 8;
+        Self {
+            turn_number,
+            user_input: user_input.to_string(),
+            assistant_response: assistant_response.to_string(),
+            summary_hint: record.summary_hint.clone(),
+            story_text: record.story_text.clone(),
+            location: record.location.clone(),
+            emotional_states: record.emotional_states.clone(),
+            token_estimate,
+            message_id: Some(record.assistant_message_id),
+            image_path: None,
+        }
+    }
 }
 
 /// Extract the summary_hint from a raw assistant response string.
 /// Handles the full LLM JSON format: { "story_json": { "summary_hint": "..." } }
 /// Also checks top-level "summary_hint" as a fallback.
-fn extract_summary_hint(raw: &str) -> String {
+pub(crate) fn extract_summary_hint(raw: &str) -> String {
     if let Ok(v) = serde_json::from_str::<Value>(raw) {
         // Primary path: story_json.summary_hint
         if let Some(hint) = v.get("story_json")
@@ -153,7 +202,7 @@ fn extract_summary_hint(raw: &str) -> String {
 /// Extract the location from a raw assistant response JSON.
 /// Checks ["location"], ["scene_json"]["location"], and ["story_json"]["scene_json"]["location"]
 /// in that order. Returns an empty string if not found.
-fn extract_location_from_response(text: &str) -> String {
+pub(crate) fn extract_location_from_response(text: &str) -> String {
     if let Ok(v) = serde_json::from_str::<Value>(text) {
         if let Some(loc) = v.get("location").and_then(|l| l.as_str()) {
             if !loc.is_empty() {
@@ -183,7 +232,7 @@ fn extract_location_from_response(text: &str) -> String {
 
 /// Extract just the story text from a raw assistant response (for display in recent turns).
 /// Pulls from story_json.response, falling back to the raw text.
-fn extract_story_text(raw: &str) -> String {
+pub(crate) fn extract_story_text(raw: &str) -> String {
     if let Ok(v) = serde_json::from_str::<Value>(raw) {
         // story_json.response (main LLM output format)
         if let Some(text) = v.get("story_json")
@@ -211,59 +260,86 @@ fn extract_story_text(raw: &str) -> String {
     raw.trim().to_string()
 }
 
-/// Extract the emotional_states array from a raw LLM response and format it
-/// as a multi-line string suitable for injection into the next prompt.
-/// Returns an empty string if no states are found or parseable.
-fn extract_emotional_states(raw: &str) -> String {
-    if let Ok(v) = serde_json::from_str::<Value>(raw) {
-        if let Some(states) = v.get("emotional_states").and_then(|s| s.as_array()) {
-            let mut lines = Vec::new();
-            for state in states {
-                let name = state.get("name").and_then(|n| n.as_str()).unwrap_or("");
-                let emotion = state.get("current_emotion").and_then(|e| e.as_str()).unwrap_or("");
-                let intensity = state.get("emotion_intensity").and_then(|i| i.as_str()).unwrap_or("medium");
-                let cause = state.get("emotion_cause").and_then(|c| c.as_str()).unwrap_or("");
-                let lingering = state.get("lingering_emotions")
-                    .and_then(|l| l.as_array())
-                    .map(|arr| arr.iter()
-                        .filter_map(|v| v.as_str())
-                        .collect::<Vec<_>>()
-                        .join(", "))
-                    .unwrap_or_default();
-
-                if !name.is_empty() && !emotion.is_empty() {
-                    let mut line = format!("- {}: {} ({}) — {}", name, emotion, intensity, cause);
-                    if !lingering.is_empty() {
-                        line.push_str(&format!(" [also feeling: {}]", lingering));
-                    }
-                    lines.push(line);
+/// Replace the story text in a raw assistant response, keeping the rest of
+/// the JSON (scene, characters, flags). Uses the same keys as
+/// `extract_story_text`; non-JSON responses are replaced entirely. The
+/// summary hint described the old text, so it is cleared.
+pub(crate) fn replace_story_text(raw: &str, new_text: &str) -> String {
+    let Ok(mut v) = serde_json::from_str::<Value>(raw) else {
+        return new_text.to_string();
+    };
+    let slot = if v.pointer("/story_json/response").is_some_and(|r| r.is_string()) {
+        v.pointer_mut("/story_json/response")
+    } else if v.get("response").is_some_and(|r| r.is_string()) {
+        v.get_mut("response")
+    } else if v.get("story").is_some_and(|r| r.is_string()) {
+        v.get_mut("story")
+    } else {
+        None
+    };
+    match slot {
+        Some(slot) => {
+            *slot = Value::String(new_text.to_string());
+            for pointer in ["/story_json/summary_hint", "/summary_hint"] {
+                if let Some(hint) = v.pointer_mut(pointer).filter(|h| h.is_string()) {
+                    *hint = Value::String(String::new());
                 }
             }
-            if !lines.is_empty() {
-                return lines.join("\n");
-            }
+            v.to_string()
         }
+        None => new_text.to_string(),
     }
-    String::new()
 }
 
-/// Extract a compact one-line emotion summary like "Elena: grieving, Marcus: worried"
-/// from a raw LLM response. Used for annotating compressed turn summaries.
-fn extract_brief_emotions(raw: &str) -> String {
-    if let Ok(v) = serde_json::from_str::<Value>(raw) {
-        if let Some(states) = v.get("emotional_states").and_then(|s| s.as_array()) {
-            let parts: Vec<String> = states.iter().filter_map(|state| {
-                let name = state.get("name").and_then(|n| n.as_str())?;
-                let emotion = state.get("current_emotion").and_then(|e| e.as_str())?;
-                if name.is_empty() || emotion.is_empty() { return None; }
-                Some(format!("{}: {}", name, emotion))
-            }).collect();
-            if !parts.is_empty() {
-                return parts.join(", ");
+/// Parse the emotional_states array from a raw LLM response. Entries without
+/// a name or emotion are dropped; a missing intensity defaults to "medium".
+pub(crate) fn parse_emotional_states(raw: &str) -> Vec<CharacterEmotionalState> {
+    let Ok(v) = serde_json::from_str::<Value>(raw) else {
+        return Vec::new();
+    };
+    let Some(states) = v.get("emotional_states").and_then(|s| s.as_array()) else {
+        return Vec::new();
+    };
+    states
+        .iter()
+        .filter_map(|state| serde_json::from_value::<CharacterEmotionalState>(state.clone()).ok())
+        .filter(|state| !state.name.is_empty() && !state.current_emotion.is_empty())
+        .map(|mut state| {
+            if state.emotion_intensity.is_empty() {
+                state.emotion_intensity = "medium".to_string();
             }
-        }
-    }
-    String::new()
+            state
+        })
+        .collect()
+}
+
+/// Format emotional states as a multi-line string suitable for injection
+/// into the next prompt. Returns an empty string if there are none.
+fn format_emotional_states(states: &[CharacterEmotionalState]) -> String {
+    states
+        .iter()
+        .filter(|s| !s.name.is_empty() && !s.current_emotion.is_empty())
+        .map(|s| {
+            let intensity = if s.emotion_intensity.is_empty() { "medium" } else { &s.emotion_intensity };
+            let mut line = format!("- {}: {} ({}) — {}", s.name, s.current_emotion, intensity, s.emotion_cause);
+            if !s.lingering_emotions.is_empty() {
+                line.push_str(&format!(" [also feeling: {}]", s.lingering_emotions.join(", ")));
+            }
+            line
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Format a compact one-line emotion summary like "Elena: grieving, Marcus: worried".
+/// Used for annotating compressed turn summaries.
+fn brief_emotions(states: &[CharacterEmotionalState]) -> String {
+    states
+        .iter()
+        .filter(|s| !s.name.is_empty() && !s.current_emotion.is_empty())
+        .map(|s| format!("{}: {}", s.name, s.current_emotion))
+        .collect::<Vec<_>>()
+        .join(", ")
 }
 
 // ============================================================================
@@ -295,6 +371,16 @@ pub struct ConversationContext {
     pub compressed: CompressedHistory,
     /// Total estimated tokens across all uncompressed turns
     pub total_turn_tokens: usize,
+    /// Context window of the model this conversation is sent to
+    #[serde(default = "default_max_context_tokens")]
+    pub max_context_tokens: usize,
+    /// Scene/chapter/arc summaries of the turns before `turns` and `compressed`
+    #[serde(default)]
+    pub memory: Vec<MemoryEntry>,
+}
+
+fn default_max_context_tokens() -> usize {
+    MAX_CONTEXT_TOKENS
 }
 
 impl ConversationContext {
@@ -303,6 +389,72 @@ impl ConversationContext {
             turns: Vec::new(),
             compressed: CompressedHistory::default(),
             total_turn_tokens: 0,
+            max_context_tokens: MAX_CONTEXT_TOKENS,
+            memory: Vec::new(),
+        }
+    }
+
+    /// Size the compression budgets for a model with a `num_ctx` token window.
+    pub fn with_context_size(mut self, num_ctx: usize) -> Self {
+        self.max_context_tokens = num_ctx;
+        self
+    }
+
+    /// Token budget for conversation history (compressed + recent).
+    pub fn history_token_budget(&self) -> usize {
+        self.max_context_tokens.saturating_sub(RESERVED_TOKENS)
+    }
+
+    /// Estimated token count above which older turns get compressed.
+    pub fn compression_threshold_tokens(&self) -> usize {
+        (self.max_context_tokens as f64 * COMPRESSION_THRESHOLD) as usize
+    }
+
+    /// Token budget for the hierarchical memory block.
+    pub fn memory_token_budget(&self) -> usize {
+        (self.history_token_budget() as f64 * MEMORY_BUDGET_SHARE) as usize
+    }
+
+    /// Token budget for the RELEVANT MEMORIES block.
+    pub fn recall_token_budget(&self) -> usize {
+        (self.history_token_budget() as f64 * RECALL_BUDGET_SHARE) as usize
+    }
+
+    /// Attach hierarchical memory and drop the turns it covers. Load the
+    /// rolling summary first (skipping it if it overlaps the memory range).
+    pub fn with_memory(mut self, memory: Vec<MemoryEntry>) -> Self {
+        if let Some((_, covered_until)) = memory_coverage(&memory) {
+            self.turns.retain(|t| t.message_id.map_or(true, |id| id > covered_until));
+            self.total_turn_tokens = self.turns.iter().map(|t| t.token_estimate).sum();
+        }
+        self.memory = memory;
+        self
+    }
+
+    /// Number of turns represented only by hierarchical memory.
+    pub fn memory_turn_count(&self) -> usize {
+        memory_coverage(&self.memory)
+            .map(|(last_turn, _)| last_turn.max(0) as usize)
+            .unwrap_or(0)
+    }
+
+    /// The full "story so far": memory at the most detailed level that fits
+    /// its budget, followed by the rolling summary of later turns.
+    pub fn story_so_far(&self) -> String {
+        let memory = select_memory(&self.memory, self.memory_token_budget());
+        match (memory.is_empty(), self.compressed.story_so_far.is_empty()) {
+            (true, _) => self.compressed.story_so_far.clone(),
+            (false, true) => memory,
+            (false, false) => format!("{}\n{}", memory, self.compressed.story_so_far),
+        }
+    }
+
+    /// Token estimate of `story_so_far`.
+    fn summary_tokens(&self) -> usize {
+        if self.memory.is_empty() {
+            self.compressed.token_estimate
+        } else {
+            estimate_tokens(&self.story_so_far())
         }
     }
 
@@ -340,6 +492,41 @@ impl ConversationContext {
         Self::from_message_pairs(&pairs)
     }
 
+    /// Build from a stored summary plus every turn of the chat.
+    /// `turns` are (assistant message id, user input, assistant response) in
+    /// order; turns the summary already covers are skipped. Turn numbers stay
+    /// absolute, so turn 150 is still "Turn 150" after compression.
+    pub fn from_summary_and_turns(summary: Option<&StoredSummary>, turns: &[(i64, String, String)]) -> Self {
+        let turns = turns
+            .iter()
+            .enumerate()
+            .map(|(i, (message_id, user_msg, assistant_msg))| {
+                let mut turn = StoryTurn::from_messages(i + 1, user_msg, assistant_msg);
+                turn.message_id = Some(*message_id);
+                turn
+            })
+            .collect();
+        Self::from_summary_and_story_turns(summary, turns)
+    }
+
+    /// Same as `from_summary_and_turns` for turns already loaded from the
+    /// `turns` table (numbered, with their assistant message ids).
+    pub fn from_summary_and_story_turns(summary: Option<&StoredSummary>, turns: Vec<StoryTurn>) -> Self {
+        let mut ctx = Self::new();
+        let covered_until = summary.map(|s| s.last_message_id).unwrap_or(0);
+        for turn in turns {
+            if turn.message_id.is_some_and(|id| id <= covered_until) {
+                continue;
+            }
+            ctx.total_turn_tokens += turn.token_estimate;
+            ctx.turns.push(turn);
+        }
+        if let Some(summary) = summary {
+            ctx.compressed = summary.to_compressed_history();
+        }
+        ctx
+    }
+
     /// Add a new turn to the context.
     pub fn add_turn(&mut self, user_input: &str, assistant_response: &str) {
         let turn_number = self.turns.len() + 1;
@@ -352,17 +539,16 @@ impl ConversationContext {
     pub fn needs_compression(&self, system_prompt_tokens: usize, character_db_tokens: usize) -> bool {
         let total = system_prompt_tokens
             + character_db_tokens
-            + self.compressed.token_estimate
+            + self.summary_tokens()
             + self.total_turn_tokens;
-        let threshold = (MAX_CONTEXT_TOKENS as f64 * COMPRESSION_THRESHOLD) as usize;
-        total > threshold
+        total > self.compression_threshold_tokens()
     }
 
     /// Returns how many tokens the full context would use (estimated).
     pub fn estimated_total_tokens(&self, system_prompt_tokens: usize, character_db_tokens: usize) -> usize {
         system_prompt_tokens
             + character_db_tokens
-            + self.compressed.token_estimate
+            + self.summary_tokens()
             + self.total_turn_tokens
     }
 
@@ -386,7 +572,7 @@ impl ConversationContext {
 
         // Add each turn's summary_hint with brief emotional state annotation
         for turn in turns_to_compress {
-            let emotions = extract_brief_emotions(&turn.assistant_response);
+            let emotions = brief_emotions(&turn.emotional_states);
             let emotion_suffix = if emotions.is_empty() {
                 String::new()
             } else {
@@ -397,9 +583,8 @@ impl ConversationContext {
                 story_lines.push(format!("Turn {}: {}{}", turn.turn_number, turn.summary_hint, emotion_suffix));
             } else {
                 // Fallback: use a truncated version of the story text
-                let story_text = extract_story_text(&turn.assistant_response);
-                let truncated: String = story_text.chars().take(100).collect();
-                let suffix = if story_text.len() > 100 { "..." } else { "" };
+                let truncated: String = turn.story_text.chars().take(100).collect();
+                let suffix = if turn.story_text.len() > 100 { "..." } else { "" };
                 story_lines.push(format!("Turn {}: {}{}{}", turn.turn_number, truncated, suffix, emotion_suffix));
             }
         }
@@ -410,11 +595,10 @@ impl ConversationContext {
         // always has an unambiguous "you are HERE now" marker even when all the full
         // turns describing how it arrived have been compressed away.
         let trailer = if let Some(last_turn) = turns_to_compress.last() {
-            let location = extract_location_from_response(&last_turn.assistant_response);
-            let location_str = if location.is_empty() {
+            let location_str = if last_turn.location.is_empty() {
                 "unknown".to_string()
             } else {
-                location
+                last_turn.location.clone()
             };
             let recent_event = if last_turn.summary_hint.is_empty() {
                 "unknown".to_string()
@@ -473,10 +657,9 @@ impl ConversationContext {
 
         // Add each turn's narrative text
         for turn in turns_to_compress {
-            let story_text = extract_story_text(&turn.assistant_response);
             story_texts.push(format!(
                 "Turn {} - Player said: \"{}\"\nStory: {}",
-                turn.turn_number, turn.user_input, story_text
+                turn.turn_number, turn.user_input, turn.story_text
             ));
         }
 
@@ -502,10 +685,10 @@ SUMMARY:"#,
         }
 
         let split_point = self.turns.len() - RECENT_TURNS_TO_KEEP;
-        let compressed_ids: Vec<usize> = self.turns[..split_point]
-            .iter()
-            .map(|t| t.turn_number)
-            .collect();
+        // The summary prompt includes the previous summary, so the new one
+        // covers everything compressed so far.
+        let mut compressed_ids = self.compressed.compressed_turn_ids.clone();
+        compressed_ids.extend(self.turns[..split_point].iter().map(|t| t.turn_number));
 
         let new_tokens = estimate_tokens(summary);
 
@@ -550,6 +733,47 @@ pub struct AssembledContext {
     pub recent_turn_count: usize,
     /// Number of turns that were compressed
     pub compressed_turn_count: usize,
+    /// Lorebook entries triggered by the turns actually in the prompt
+    pub lore_entries: Vec<FiredLore>,
+    /// Estimated tokens per prompt section
+    pub breakdown: PromptBreakdown,
+}
+
+/// Estimated tokens per section of an assembled prompt (recorded in turn
+/// traces, see text_gen::traces). The sections add up to the whole prompt.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PromptBreakdown {
+    /// System prompt and pronoun reminder
+    pub system: usize,
+    pub premise: usize,
+    /// Compressed "story so far" block
+    pub summary: usize,
+    pub characters: usize,
+    pub scene: usize,
+    /// Triggered lorebook entries, all positions
+    pub lore: usize,
+    /// Recent turns in full detail
+    pub history: usize,
+    /// Recalled past turns (RELEVANT MEMORIES)
+    pub recall: usize,
+    pub emotions: usize,
+    /// Current input, output instructions and template framing
+    pub input: usize,
+}
+
+impl PromptBreakdown {
+    pub fn total(&self) -> usize {
+        self.system
+            + self.premise
+            + self.summary
+            + self.characters
+            + self.scene
+            + self.lore
+            + self.history
+            + self.recall
+            + self.emotions
+            + self.input
+    }
 }
 
 /// Derive pronouns from the gender field.
@@ -672,8 +896,11 @@ fn build_dual_character_section(
     section
 }
 
-/// Assemble the final ChatML prompt string from its components.
+/// Assemble the final prompt string from its components, wrapping each turn
+/// in the model's chat template (ChatML, Llama 3, ... — see template.rs).
 /// Extracted so the budget enforcement loop can rebuild cheaply after dropping turns.
+/// Returns the prompt, the length of its stable prefix (BOS + system turn) and
+/// the per-section token breakdown.
 ///
 /// Section order — sorted by stability so llama.cpp KV-cache prefix reuse fires
 /// on as many tokens as possible between consecutive turns:
@@ -684,9 +911,11 @@ fn build_dual_character_section(
 ///   5. scene_context      — changes on location transitions
 ///   6. pronoun_reminder   — tied to the scene's character set
 ///   7. recent turns       — append-only history (do NOT reorder)
-///   8. persisted emotions — volatile (changes every turn) → tail
+///   8. relevant memories  — recalled per input → tail
+///      persisted emotions — volatile (changes every turn) → tail
 ///   9. current user input — always fresh → tail
 fn assemble_prompt_string(
+    template: &PromptTemplate,
     system_prompt: &str,
     story_premise: Option<&str>,
     compressed_summary: &str,
@@ -696,62 +925,84 @@ fn assemble_prompt_string(
     recent_turns: &[StoryTurn],
     current_user_input: &str,
     persisted_emotions: Option<&str>,
-) -> String {
-    let mut prompt = String::new();
+    recall_block: &str,
+    lorebook: &Lorebook,
+) -> (String, usize, PromptBreakdown) {
+    let mut system = String::new();
+    let mut breakdown = PromptBreakdown::default();
+    let lore = lorebook.activate(recent_turns, current_user_input);
 
     // ── 1. System prompt (most stable — never changes mid-story) ─────────────
-    prompt.push_str("<|im_start|>system\n");
-    prompt.push_str(system_prompt);
+    system.push_str(system_prompt);
+    breakdown.system = estimate_tokens(system_prompt);
 
     // ── 2. Story premise (stable across the whole story) ─────────────────────
     if let Some(premise) = story_premise {
         if !premise.is_empty() {
-            prompt.push_str("\n\nSTORY PREMISE:\n");
-            prompt.push_str(premise);
+            system.push_str("\n\nSTORY PREMISE:\n");
+            system.push_str(premise);
+            breakdown.premise = estimate_tokens(premise) + 4;
         }
     }
+    let premise_lore = lore.block(LorePosition::AfterPremise);
+    if !premise_lore.is_empty() {
+        system.push_str("\n\n");
+        system.push_str(&premise_lore);
+        breakdown.lore += estimate_tokens(&premise_lore);
+    }
 
     // ── 3. Compressed summary (stable until next compression event) ───────────
     if !compressed_summary.is_empty() {
-        prompt.push_str("\n\n=== STORY SO FAR ===\n");
-        prompt.push_str(compressed_summary);
-        prompt.push_str("\n=== END STORY SO FAR ===");
+        system.push_str("\n\n=== STORY SO FAR ===\n");
+        system.push_str(compressed_summary);
+        system.push_str("\n=== END STORY SO FAR ===");
+        breakdown.summary = estimate_tokens(compressed_summary) + 12;
     }
 
     // ── 4. Character database (changes only on edits or scene-roster shifts) ──
-    prompt.push_str("\n\n");
-    prompt.push_str(character_section);
+    system.push_str("\n\n");
+    system.push_str(character_section);
+    breakdown.characters = estimate_tokens(character_section);
+    let character_lore = lore.block(LorePosition::AfterCharacters);
+    if !character_lore.is_empty() {
+        system.push_str("\n\n");
+        system.push_str(&character_lore);
+        breakdown.lore += estimate_tokens(&character_lore);
+    }
 
     // ── 5. Scene context (changes on location transitions) ───────────────────
     if let Some(scene) = scene_context {
         if !scene.is_empty() {
-            prompt.push_str("\n\n");
-            prompt.push_str(scene);
+            system.push_str("\n\n");
+            system.push_str(scene);
+            breakdown.scene = estimate_tokens(scene);
         }
     }
 
     // ── 6. Pronoun reminder (tied to the scene's character set) ──────────────
     let pronoun_reminder = build_pronoun_reminder(scene_characters);
     if !pronoun_reminder.is_empty() {
-        prompt.push_str("\n");
-        prompt.push_str(&pronoun_reminder);
+        system.push_str("\n");
+        system.push_str(&pronoun_reminder);
+        breakdown.system += estimate_tokens(&pronoun_reminder);
     }
 
-    prompt.push_str("<|im_end|>");
+    let mut prompt = template.system(&system);
+    let stable_prefix_len = prompt.len();
 
     // ── 7. Recent turn history (append-only — do NOT reorder or reformat) ────
     for turn in recent_turns {
-        prompt.push_str(&format!("<|im_start|>user\n{}<|im_end|>", turn.user_input));
+        prompt.push_str(&template.user(&turn.user_input));
         if !turn.assistant_response.is_empty() {
-            let story_text = extract_story_text(&turn.assistant_response);
-            prompt.push_str(&format!("<|im_start|>assistant\n{}<|im_end|>", story_text));
+            prompt.push_str(&template.assistant(&turn.story_text));
         }
     }
+    breakdown.history = estimate_tokens(&prompt[stable_prefix_len..]);
 
     // ── 8. Volatile tail: emotional states (changes every turn) ──────────────
     // Prefer persisted emotional states (survive restarts); fall back to live extraction.
     let live_emotional_context = recent_turns.last()
-        .map(|t| extract_emotional_states(&t.assistant_response))
+        .map(|t| format_emotional_states(&t.emotional_states))
         .unwrap_or_default();
     let emotional_context = match persisted_emotions {
         Some(p) if !p.is_empty() => p.to_string(),
@@ -766,17 +1017,60 @@ fn assemble_prompt_string(
         )
     };
 
+    // Triggered lore changes with the recent turns, so it stays in the tail
+    let input_lore = lore.block(LorePosition::BeforeInput);
+    let lore_block = if input_lore.is_empty() { input_lore } else { format!("{}\n\n", input_lore) };
+    breakdown.lore += estimate_tokens(&lore_block);
+    breakdown.recall = estimate_tokens(recall_block);
+    breakdown.emotions = estimate_tokens(&emotional_block);
+
     // ── 9. Current user input (always fresh) ─────────────────────────────────
-    prompt.push_str(&format!(
-        "<|im_start|>user\n{}{}\n\n[Do NOT use <think> tags. Respond with raw JSON only, no preamble. Include ALL fields: turn_id, story_json, scene_json, characters_in_scene, emotional_states, generation_flags.]<|im_end|><|im_start|>assistant\n",
+    prompt.push_str(&template.user(&format!(
+        "{}{}{}{}\n\n[Do NOT use <think> tags. Respond with raw JSON only, no preamble. Include ALL fields: turn_id, story_json, scene_json, characters_in_scene, emotional_states, generation_flags.]",
+        lore_block,
+        recall_block,
         emotional_block,
         current_user_input,
-    ));
+    )));
+    prompt.push_str(template.generation_prompt());
+
+    // Whatever the named sections don't cover is input, instructions and framing
+    breakdown.input = estimate_tokens(&prompt).saturating_sub(breakdown.total());
 
-    prompt
+    (prompt, stable_prefix_len, breakdown)
 }
 
-/// Build the complete context string using ChatML template format.
+/// Render recalled past turns as the RELEVANT MEMORIES block. Best matches
+/// are taken first until `budget_tokens` is spent, then shown in story order.
+fn build_recall_block(memories: &[RecalledTurn], budget_tokens: usize) -> String {
+    let mut chosen: Vec<&RecalledTurn> = Vec::new();
+    let mut used = 0;
+    for memory in memories {
+        let cost = estimate_tokens(&memory.text) + 6;
+        if used + cost <= budget_tokens {
+            used += cost;
+            chosen.push(memory);
+        }
+    }
+    if chosen.is_empty() {
+        return String::new();
+    }
+    chosen.sort_by_key(|m| m.turn_number);
+
+    let excerpts = chosen
+        .iter()
+        .map(|m| format!("[Turn {}] {}", m.turn_number, m.text))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    format!(
+        "=== RELEVANT MEMORIES ===\nEarlier moments that may matter now. They already happened — use them for continuity, do NOT replay them.\n{}\n=== END RELEVANT MEMORIES ===\n\n",
+        excerpts
+    )
+}
+
+/// Build the complete context string in the model's chat template
+/// (ChatML shown below; Llama 3 / Mistral / Gemma / Alpaca / custom use their
+/// own delimiters in the same positions).
 ///
 /// Sections are ordered by stability so llama.cpp can maximally reuse its
 /// KV cache across consecutive turns (longest stable byte-prefix wins):
@@ -785,8 +1079,10 @@ fn assemble_prompt_string(
 /// <|im_start|>system
 /// [system prompt]           ← never changes
 /// [story premise]           ← stable across the whole story
+/// [lore: after_premise]     ← changes when those entries fire / stop firing
 /// [compressed summary]      ← stable until next compression event
 /// [character DB section]    ← changes on edits / scene-roster shifts
+/// [lore: after_characters]
 /// [scene context line]      ← changes on location transitions
 /// [pronoun reminder]        ← tied to the scene's character set
 /// <|im_end|>
@@ -794,6 +1090,8 @@ fn assemble_prompt_string(
 /// <|im_start|>assistant [turn N story text] <|im_end|>
 /// ...                       ← append-only; old turns never reformatted
 /// <|im_start|>user
+/// [lore: before_input]      ← entries triggered by recent turns + input
+/// [relevant memories]       ← recalled past turns for this input
 /// [emotional states]        ← volatile (changes every turn)
 /// [current user input]      ← always fresh
 /// <|im_end|>
@@ -809,6 +1107,9 @@ pub fn build_compressed_context(
     current_user_input: &str,
     max_prompt_tokens: usize,
     persisted_emotions: Option<&str>,
+    relevant_memories: &[RecalledTurn],
+    lorebook: &Lorebook,
+    template: &PromptTemplate,
 ) -> AssembledContext {
     // --- Step 1: Build stable components once (not rebuilt inside the budget loop) ---
     let character_section = build_dual_character_section(scene_characters, all_characters);
@@ -830,9 +1131,11 @@ pub fn build_compressed_context(
 
     // --- Step 3: Assemble the prompt string ---
     let mut recent_turns: Vec<StoryTurn> = conversation.turns.clone();
-    let compressed_summary = conversation.compressed.story_so_far.clone();
+    let compressed_summary = conversation.story_so_far();
+    let recall_block = build_recall_block(relevant_memories, conversation.recall_token_budget());
 
-    let mut prompt = assemble_prompt_string(
+    let (mut prompt, mut stable_prefix_len, mut breakdown) = assemble_prompt_string(
+        template,
         system_prompt,
         story_premise,
         &compressed_summary,
@@ -842,6 +1145,8 @@ pub fn build_compressed_context(
         &recent_turns,
         current_user_input,
         persisted_emotions,
+        &recall_block,
+        lorebook,
     );
 
     // --- Step 4: Budget enforcement — trim oldest turns until prompt fits ---
@@ -862,7 +1167,8 @@ pub fn build_compressed_context(
             "[Context] Budget exceeded ({} tokens > {}), dropping turn {} to fit",
             estimated, max_prompt_tokens, removed.turn_number
         );
-        prompt = assemble_prompt_string(
+        (prompt, stable_prefix_len, breakdown) = assemble_prompt_string(
+            template,
             system_prompt,
             story_premise,
             &compressed_summary,
@@ -872,22 +1178,21 @@ pub fn build_compressed_context(
             &recent_turns,
             current_user_input,
             persisted_emotions,
+            &recall_block,
+            lorebook,
         );
     }
 
     let estimated_tokens = estimate_tokens(&prompt);
+    let lore_entries = lorebook.activate(&recent_turns, current_user_input).fired;
 
-    // Debug: log a hash of the stable prefix (system block only, up to and
-    // including the first <|im_end|>). This hash must be identical across
-    // consecutive turns whenever the story setup hasn't changed.
+    // Debug: log a hash of the stable prefix (BOS + system turn, up to and
+    // including the template's system suffix). This hash must be identical
+    // across consecutive turns whenever the story setup hasn't changed.
     {
         use std::collections::hash_map::DefaultHasher;
         use std::hash::{Hash, Hasher};
-        let system_end_token = "<|im_end|>";
-        let prefix_end = prompt.find(system_end_token)
-            .map(|i| i + system_end_token.len())
-            .unwrap_or(prompt.len());
-        let stable_prefix = &prompt[..prefix_end];
+        let stable_prefix = &prompt[..stable_prefix_len];
         let mut hasher = DefaultHasher::new();
         stable_prefix.hash(&mut hasher);
         println!(
@@ -904,6 +1209,8 @@ pub fn build_compressed_context(
         was_compressed,
         recent_turn_count: recent_turns.len(),
         compressed_turn_count: conversation.compressed.compressed_turn_ids.len(),
+        lore_entries,
+        breakdown,
     }
 }
 
@@ -918,14 +1225,27 @@ pub fn build_compressed_chat_messages(
     scene_context: Option<&str>,
     current_user_input: &str,
     persisted_emotions: Option<&str>,
+    relevant_memories: &[RecalledTurn],
+    lorebook: &Lorebook,
 ) -> (Vec<Value>, bool) {
-    let character_section = build_dual_character_section(scene_characters, all_characters);
+    let lore = lorebook.activate(&conversation.turns, current_user_input);
+    let with_lore = |section: String, position: LorePosition| {
+        let block = lore.block(position);
+        if block.is_empty() { section } else { format!("{}\n{}\n", section, block) }
+    };
+    let character_section = with_lore(
+        build_dual_character_section(scene_characters, all_characters),
+        LorePosition::AfterCharacters,
+    );
     let scene_section = scene_context
         .map(|s| format!("\n{}\n", s))
         .unwrap_or_default();
-    let premise_section = story_premise
-        .map(|p| format!("\nSTORY PREMISE:\n{}\n", p))
-        .unwrap_or_default();
+    let premise_section = with_lore(
+        story_premise
+            .map(|p| format!("\nSTORY PREMISE:\n{}\n", p))
+            .unwrap_or_default(),
+        LorePosition::AfterPremise,
+    );
 
     let full_system = format!(
         "{}\n{}{}{}",
@@ -944,10 +1264,11 @@ pub fn build_compressed_chat_messages(
     let mut messages: Vec<Value> = Vec::new();
 
     // System message with compressed history embedded
-    let system_content = if !conversation.compressed.story_so_far.is_empty() {
+    let story_so_far = conversation.story_so_far();
+    let system_content = if !story_so_far.is_empty() {
         format!(
             "{}\n\n=== STORY SO FAR ===\n{}\n=== END STORY SO FAR ===",
-            full_system, conversation.compressed.story_so_far
+            full_system, story_so_far
         )
     } else {
         full_system
@@ -958,14 +1279,13 @@ pub fn build_compressed_chat_messages(
     for turn in &conversation.turns {
         messages.push(json!({"role": "user", "content": turn.user_input}));
         if !turn.assistant_response.is_empty() {
-            let story_text = extract_story_text(&turn.assistant_response);
-            messages.push(json!({"role": "assistant", "content": story_text}));
+            messages.push(json!({"role": "assistant", "content": turn.story_text}));
         }
     }
 
     // Prefer persisted emotional states (survive restarts); fall back to live extraction.
     let live_emotional_context = conversation.turns.last()
-        .map(|t| extract_emotional_states(&t.assistant_response))
+        .map(|t| format_emotional_states(&t.emotional_states))
         .unwrap_or_default();
     let emotional_context = match persisted_emotions {
         Some(p) if !p.is_empty() => p.to_string(),
@@ -982,8 +1302,13 @@ pub fn build_compressed_chat_messages(
 
     // Current input with pronoun reminder appended to the JSON format instruction
     let pronoun_reminder = build_pronoun_reminder(scene_characters);
+    let recall_block = build_recall_block(relevant_memories, conversation.recall_token_budget());
+    let input_lore = lore.block(LorePosition::BeforeInput);
+    let lore_block = if input_lore.is_empty() { input_lore } else { format!("{}\n\n", input_lore) };
     let final_user_content = format!(
-        "{}{}\n\n[Do NOT use <think> tags. Respond with raw JSON only, no preamble. Include ALL fields: turn_id, story_json, scene_json, characters_in_scene, emotional_states, generation_flags.{}]",
+        "{}{}{}{}\n\n[Do NOT use <think> tags. Respond with raw JSON only, no preamble. Include ALL fields: turn_id, story_json, scene_json, characters_in_scene, emotional_states, generation_flags.{}]",
+        lore_block,
+        recall_block,
         emotional_block,
         current_user_input,
         pronoun_reminder
@@ -1020,75 +1345,71 @@ pub async fn load_persisted_emotional_states(
         Err(_) => return String::new(),
     };
 
-    if rows.is_empty() {
-        return String::new();
-    }
-
-    let mut lines = Vec::new();
-    for row in &rows {
-        let name: String = row.get("character_name");
-        let emotion: String = row.get("current_emotion");
-        let intensity: String = row.get("emotion_intensity");
-        let cause: String = row.get("emotion_cause");
-        let lingering_raw: String = row.get("lingering_emotions");
+    let states: Vec<CharacterEmotionalState> = rows
+        .iter()
+        .map(|row| CharacterEmotionalState {
+            name: row.get("character_name"),
+            current_emotion: row.get("current_emotion"),
+            emotion_intensity: row.get("emotion_intensity"),
+            emotion_cause: row.get("emotion_cause"),
+            lingering_emotions: serde_json::from_str(&row.get::<String, _>("lingering_emotions"))
+                .unwrap_or_default(),
+        })
+        .collect();
+    format_persisted_emotions(&states)
+}
 
-        let mut line = format!("{}: {} ({}) — {}", name, emotion, intensity, cause);
+/// Format an emotion snapshot (`messages.emotion_snapshot`) like
+/// `load_persisted_emotional_states`. Regenerating a turn builds its context
+/// from the snapshot before it while the table still holds the emotions
+/// after the candidate being replaced.
+pub fn format_emotion_snapshot(snapshot: &str) -> String {
+    format_persisted_emotions(&serde_json::from_str::<Vec<CharacterEmotionalState>>(snapshot).unwrap_or_default())
+}
 
-        // Parse lingering emotions JSON array and append if non-empty
-        if let Ok(lingering) = serde_json::from_str::<Vec<String>>(&lingering_raw) {
-            if !lingering.is_empty() {
-                line.push_str(&format!(" [also feeling: {}]", lingering.join(", ")));
+fn format_persisted_emotions(states: &[CharacterEmotionalState]) -> String {
+    states
+        .iter()
+        .map(|s| {
+            let mut line = format!("{}: {} ({}) — {}", s.name, s.current_emotion, s.emotion_intensity, s.emotion_cause);
+            if !s.lingering_emotions.is_empty() {
+                line.push_str(&format!(" [also feeling: {}]", s.lingering_emotions.join(", ")));
             }
-        }
-
-        lines.push(line);
-    }
-
-    lines.join("\n")
+            line
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
 // ============================================================================
 // 7. LLM SUMMARIZATION HELPER
 // ============================================================================
 
-/// Call Ollama to produce a high-quality summary of older turns.
+/// Ask the configured text backend for a high-quality summary of older turns.
 /// This is async and meant to be called from a Tauri command.
 ///
 /// Returns the summary text on success.
 pub async fn summarize_with_llm(
-    client: &reqwest::Client,
-    base_url: &str,
+    backend: &dyn TextBackend,
+    model: &str,
     prompt: &str,
 ) -> Result<String, String> {
-    let body = json!({
-        "model": "Story_v27",
-        "prompt": prompt,
-        "stream": false,
-        "think": false,
-        "keep_alive": crate::text_gen::prompts::KEEP_ALIVE_GENERATING,
-        "options": {
-            "num_ctx": 4096,
-            "temperature": 0.3
-        }
-    });
+    let request = GenerateRequest {
+        options: SamplingOptions {
+            num_ctx: Some(4096),
+            temperature: Some(0.3),
+            ..SamplingOptions::default()
+        },
+        keep_alive: Some(crate::text_gen::prompts::KEEP_ALIVE_GENERATING.to_string()),
+        ..GenerateRequest::new(model, PromptInput::Instruction(prompt.to_string()), 120)
+    };
 
-    let res = client
-        .post(format!("{}/api/generate", base_url))
-        .json(&body)
-        .send()
+    let res = backend
+        .generate(&request, None)
         .await
         .map_err(|e| format!("LLM summarization request failed: {}", e))?;
 
-    let api_res: Value = res
-        .json()
-        .await
-        .map_err(|e| format!("Failed to parse LLM summarization response: {}", e))?;
-
-    let summary = api_res["response"]
-        .as_str()
-        .unwrap_or("")
-        .trim()
-        .to_string();
+    let summary = res.text.trim().to_string();
 
     if summary.is_empty() {
         Err("LLM returned empty summary".to_string())
@@ -1112,6 +1433,10 @@ pub struct CompressionDiagnostics {
     pub compression_threshold: usize,
     pub needs_compression: bool,
     pub compressed_summary_preview: String,
+    /// Scene/chapter/arc summaries stored for this chat
+    pub memory_entries: usize,
+    /// Lorebook entries the recent turns would trigger (filled by the caller)
+    pub lore_entries: Vec<FiredLore>,
 }
 
 /// Build diagnostics from a conversation context.
@@ -1121,25 +1446,142 @@ pub fn get_diagnostics(
     character_db_tokens: usize,
 ) -> CompressionDiagnostics {
     let total_est = conversation.estimated_total_tokens(system_prompt_tokens, character_db_tokens);
-    let threshold = (MAX_CONTEXT_TOKENS as f64 * COMPRESSION_THRESHOLD) as usize;
+    let threshold = conversation.compression_threshold_tokens();
 
     CompressionDiagnostics {
-        total_turns: conversation.turns.len() + conversation.compressed.compressed_turn_ids.len(),
-        compressed_turns: conversation.compressed.compressed_turn_ids.len(),
+        total_turns: conversation.turns.len()
+            + conversation.compressed.compressed_turn_ids.len()
+            + conversation.memory_turn_count(),
+        compressed_turns: conversation.compressed.compressed_turn_ids.len() + conversation.memory_turn_count(),
         recent_turns: conversation.turns.len(),
         estimated_total_tokens: total_est,
-        max_context_tokens: MAX_CONTEXT_TOKENS,
+        max_context_tokens: conversation.max_context_tokens,
         compression_threshold: threshold,
         needs_compression: total_est > threshold,
-        compressed_summary_preview: conversation.compressed.story_so_far
+        compressed_summary_preview: conversation.story_so_far()
             .chars()
             .take(200)
             .collect::<String>(),
+        memory_entries: conversation.memory.len(),
+        lore_entries: Vec::new(),
+    }
+}
+
+// ============================================================================
+// 8. PERSISTED SUMMARIES — `context_summaries` table
+// ============================================================================
+//
+// Compression never deletes from `messages`. Each LLM summary is stored as a
+// row recording the turn range it replaces and the id of the last assistant
+// message it covers; the prompt is built from the newest summary plus the
+// turns after it.
+
+/// One row of `context_summaries`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredSummary {
+    pub id: i64,
+    pub chat_id: i64,
+    /// First turn number covered (0 for summaries migrated from the old
+    /// destructive compression, whose turns no longer exist)
+    pub first_turn: i64,
+    /// Last turn number covered
+    pub last_turn: i64,
+    /// Messages with an id up to and including this one are covered
+    pub last_message_id: i64,
+    pub summary: String,
+    pub created_at: Option<String>,
+}
+
+impl StoredSummary {
+    pub fn to_compressed_history(&self) -> CompressedHistory {
+        let compressed_turn_ids = if self.first_turn > 0 {
+            (self.first_turn as usize..=self.last_turn as usize).collect()
+        } else {
+            Vec::new()
+        };
+        CompressedHistory {
+            story_so_far: self.summary.clone(),
+            compressed_turn_ids,
+            token_estimate: estimate_tokens(&self.summary),
+        }
+    }
+}
+
+fn summary_from_row(row: &sqlx::sqlite::SqliteRow) -> StoredSummary {
+    StoredSummary {
+        id: row.get("id"),
+        chat_id: row.get("chat_id"),
+        first_turn: row.get("first_turn"),
+        last_turn: row.get("last_turn"),
+        last_message_id: row.get("last_message_id"),
+        summary: row.get("summary"),
+        created_at: row.get("created_at"),
     }
 }
 
+/// The newest summary for a chat, if compression has ever run.
+pub async fn load_latest_summary(
+    db: &sqlx::SqlitePool,
+    chat_id: i64,
+) -> Result<Option<StoredSummary>, String> {
+    let row = sqlx::query(
+        "SELECT id, chat_id, first_turn, last_turn, last_message_id, summary, created_at \
+         FROM context_summaries WHERE chat_id = ? ORDER BY id DESC LIMIT 1",
+    )
+    .bind(chat_id)
+    .fetch_optional(db)
+    .await
+    .map_err(|e| format!("Failed to load context summary: {}", e))?;
+
+    Ok(row.as_ref().map(summary_from_row))
+}
+
+/// All summaries for a chat, oldest first.
+pub async fn list_summaries(
+    db: &sqlx::SqlitePool,
+    chat_id: i64,
+) -> Result<Vec<StoredSummary>, String> {
+    let rows = sqlx::query(
+        "SELECT id, chat_id, first_turn, last_turn, last_message_id, summary, created_at \
+         FROM context_summaries WHERE chat_id = ? ORDER BY id ASC",
+    )
+    .bind(chat_id)
+    .fetch_all(db)
+    .await
+    .map_err(|e| format!("Failed to load context summaries: {}", e))?;
+
+    Ok(rows.iter().map(summary_from_row).collect())
+}
+
+/// Store the summary held in `compressed`. `last_message_id` is the assistant
+/// message of the last turn it covers.
+pub async fn save_summary(
+    db: &sqlx::SqlitePool,
+    chat_id: i64,
+    compressed: &CompressedHistory,
+    last_message_id: i64,
+) -> Result<i64, String> {
+    let first_turn = compressed.compressed_turn_ids.iter().min().copied().unwrap_or(0) as i64;
+    let last_turn = compressed.compressed_turn_ids.iter().max().copied().unwrap_or(0) as i64;
+
+    let result = sqlx::query(
+        "INSERT INTO context_summaries (chat_id, first_turn, last_turn, last_message_id, summary) \
+         VALUES (?, ?, ?, ?, ?)",
+    )
+    .bind(chat_id)
+    .bind(first_turn)
+    .bind(last_turn)
+    .bind(last_message_id)
+    .bind(&compressed.story_so_far)
+    .execute(db)
+    .await
+    .map_err(|e| format!("Failed to save context summary: {}", e))?;
+
+    Ok(result.last_insert_rowid())
+}
+
 // ============================================================================
-// 8. TESTS
+// 9. TESTS
 // ============================================================================
 
 #[cfg(test)]
@@ -1279,6 +1721,9 @@ mod tests {
             "Look around",
             5120,
             None,
+            &[],
+            &Lorebook::default(),
+            &PromptTemplate::chatml(),
         );
 
         assert!(result.prompt.contains("You are a story engine."));
@@ -1287,6 +1732,32 @@ mod tests {
         assert!(result.prompt.contains("Look around"));
         assert!(result.prompt.contains("Welcome!"));
         assert!(result.estimated_tokens > 0);
+        assert!(result.prompt.starts_with("<|im_start|>system\nYou are a story engine."));
+        assert!(result.prompt.ends_with("<|im_end|><|im_start|>assistant\n"));
+
+        // Every section is accounted for and adds up to the whole prompt
+        let b = &result.breakdown;
+        assert!(b.system > 0 && b.premise > 0 && b.characters > 0 && b.history > 0 && b.input > 0);
+        assert_eq!(b.summary, 0);
+        assert_eq!(b.scene, 0);
+        assert_eq!(b.total(), result.estimated_tokens);
+    }
+
+    #[test]
+    fn test_build_compressed_context_llama3_template() {
+        let pairs = vec![
+            ("Hello".to_string(), r#"{"story_json":{"response":"Welcome!","summary_hint":"Greeting"}}"#.to_string()),
+        ];
+        let mut ctx = ConversationContext::from_message_pairs(&pairs);
+        let template = PromptTemplate::llama3();
+        let result = build_compressed_context(
+            &mut ctx, "System", &[], &[], None, None, "Look around", 5120, None, &[], &Lorebook::default(), &template,
+        );
+
+        assert!(result.prompt.starts_with("<|begin_of_text|><|start_header_id|>system<|end_header_id|>\n\nSystem"));
+        assert!(result.prompt.contains("<|start_header_id|>assistant<|end_header_id|>\n\nWelcome!<|eot_id|>"));
+        assert!(result.prompt.ends_with("<|start_header_id|>assistant<|end_header_id|>\n\n"));
+        assert!(!result.prompt.contains("<|im_start|>"));
     }
 
     #[test]
@@ -1305,6 +1776,8 @@ mod tests {
             None,
             "Go east",
             None,
+            &[],
+            &Lorebook::default(),
         );
 
         assert!(!compressed);
@@ -1317,6 +1790,63 @@ mod tests {
         assert!(messages[3]["content"].as_str().unwrap_or("").starts_with("Go east"));
     }
 
+    #[test]
+    fn test_recall_block_respects_budget() {
+        let recalled = vec![
+            RecalledTurn { turn_number: 40, message_id: 80, text: "The letter was burned.".to_string(), score: 0.9 },
+            RecalledTurn { turn_number: 12, message_id: 24, text: "Elena found a letter in the library.".to_string(), score: 0.8 },
+            RecalledTurn { turn_number: 30, message_id: 60, text: "x".repeat(400), score: 0.7 },
+        ];
+        let block = build_recall_block(&recalled, 60);
+        assert!(block.starts_with("=== RELEVANT MEMORIES ==="));
+        // Shown in story order; the oversized excerpt doesn't fit
+        assert!(block.find("[Turn 12]").unwrap() < block.find("[Turn 40]").unwrap());
+        assert!(!block.contains("[Turn 30]"));
+        assert_eq!(build_recall_block(&[], 60), "");
+
+        let mut ctx = ConversationContext::new();
+        let result = build_compressed_context(&mut ctx, "System", &[], &[], None, None, "The letter?", 5120, None, &recalled, &Lorebook::default(), &PromptTemplate::chatml());
+        assert!(result.prompt.contains("[Turn 12] Elena found a letter"));
+    }
+
+    #[test]
+    fn test_lore_injected_at_position() {
+        let lore = |id: i64, name: &str, keys: &[&str], position: LorePosition, always_on: bool| crate::models::LoreEntry {
+            id,
+            story_id: None,
+            name: name.to_string(),
+            keys: keys.iter().map(|k| k.to_string()).collect(),
+            content: format!("All about {}.", name),
+            priority: 0,
+            position,
+            always_on,
+            enabled: true,
+            created_at: None,
+        };
+        let lorebook = Lorebook::new(
+            vec![
+                lore(1, "Magic Rules", &[], LorePosition::AfterPremise, true),
+                lore(2, "Iron Guild", &["guild"], LorePosition::BeforeInput, false),
+                lore(3, "Sunken City", &["sunken city"], LorePosition::BeforeInput, false),
+            ],
+            500,
+        );
+
+        let mut ctx = ConversationContext::new();
+        let result = build_compressed_context(
+            &mut ctx, "System", &[], &[], Some("A premise"), None, "I visit the guild hall", 5120, None, &[], &lorebook, &PromptTemplate::chatml(),
+        );
+        let premise_at = result.prompt.find("STORY PREMISE").unwrap();
+        let rules_at = result.prompt.find("Magic Rules: All about Magic Rules.").unwrap();
+        let guild_at = result.prompt.find("Iron Guild: All about Iron Guild.").unwrap();
+        let input_at = result.prompt.find("I visit the guild hall").unwrap();
+        assert!(premise_at < rules_at && rules_at < result.prompt.find("<|im_end|>").unwrap());
+        assert!(guild_at < input_at);
+        assert!(!result.prompt.contains("Sunken City:"));
+        let fired: Vec<i64> = result.lore_entries.iter().map(|f| f.id).collect();
+        assert_eq!(fired, vec![1, 2]);
+    }
+
     #[test]
     fn test_from_db_rows() {
         let rows = vec![
@@ -1371,6 +1901,17 @@ mod tests {
         assert_eq!(extract_story_text("Just plain text."), "Just plain text.");
     }
 
+    #[test]
+    fn test_replace_story_text_keeps_other_fields() {
+        let full = r#"{"story_json":{"response":"The door opens.","summary_hint":"Door opened."},"scene_json":{"location":"Hall"}}"#;
+        let edited = replace_story_text(full, "The door stays shut.");
+        assert_eq!(extract_story_text(&edited), "The door stays shut.");
+        assert_eq!(extract_summary_hint(&edited), "");
+        assert!(edited.contains("\"location\":\"Hall\""));
+
+        assert_eq!(replace_story_text("Just plain text.", "New text."), "New text.");
+    }
+
     #[test]
     fn test_diagnostics() {
         let pairs = vec![
@@ -1384,4 +1925,91 @@ mod tests {
         assert_eq!(diag.max_context_tokens, MAX_CONTEXT_TOKENS);
         assert!(!diag.needs_compression);
     }
+
+    #[test]
+    fn test_budgets_scale_with_context_size() {
+        let ctx = ConversationContext::new();
+        assert_eq!(ctx.history_token_budget(), HISTORY_TOKEN_BUDGET);
+
+        let big = ConversationContext::new().with_context_size(32768);
+        assert_eq!(big.compression_threshold_tokens(), 24576);
+        assert_eq!(big.history_token_budget(), 32768 - RESERVED_TOKENS);
+        // 10k tokens of history needs compressing at 8k but not at 32k
+        assert!(ctx.needs_compression(10_000, 0));
+        assert!(!big.needs_compression(10_000, 0));
+    }
+
+    fn numbered_turns(n: i64) -> Vec<(i64, String, String)> {
+        (1..=n)
+            .map(|i| {
+                (
+                    i * 2,
+                    format!("Action {}", i),
+                    format!(r#"{{"story_json":{{"response":"Event {}","summary_hint":"Hint {}"}}}}"#, i, i),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_from_summary_skips_covered_turns() {
+        let summary = StoredSummary {
+            id: 1,
+            chat_id: 7,
+            first_turn: 1,
+            last_turn: 4,
+            last_message_id: 8,
+            summary: "Four turns happened.".to_string(),
+            created_at: None,
+        };
+        let ctx = ConversationContext::from_summary_and_turns(Some(&summary), &numbered_turns(10));
+        assert_eq!(ctx.turns.len(), 6);
+        assert_eq!(ctx.turns[0].turn_number, 5);
+        assert_eq!(ctx.turns[0].message_id, Some(10));
+        assert_eq!(ctx.compressed.compressed_turn_ids, vec![1, 2, 3, 4]);
+        assert_eq!(ctx.compressed.story_so_far, "Four turns happened.");
+
+        let full = ConversationContext::from_summary_and_turns(None, &numbered_turns(10));
+        assert_eq!(full.turns.len(), 10);
+        assert!(full.compressed.story_so_far.is_empty());
+    }
+
+    #[test]
+    fn test_llm_summary_extends_previous_range() {
+        let summary = StoredSummary {
+            id: 1,
+            chat_id: 7,
+            first_turn: 1,
+            last_turn: 4,
+            last_message_id: 8,
+            summary: "Four turns happened.".to_string(),
+            created_at: None,
+        };
+        let mut ctx = ConversationContext::from_summary_and_turns(Some(&summary), &numbered_turns(14));
+        ctx.apply_llm_summary("Fourteen turns happened.");
+        assert_eq!(ctx.compressed.compressed_turn_ids, (1..=8).collect::<Vec<_>>());
+        assert_eq!(ctx.turns.len(), RECENT_TURNS_TO_KEEP);
+        assert_eq!(ctx.turns[0].turn_number, 9);
+    }
+
+    #[test]
+    fn test_memory_replaces_covered_turns() {
+        use crate::text_gen::memory::MemoryLevel;
+        let memory = vec![MemoryEntry {
+            id: 1,
+            chat_id: 7,
+            level: MemoryLevel::Scene,
+            scene_id: Some(3),
+            first_turn: 1,
+            last_turn: 3,
+            last_message_id: 6,
+            summary: "They met at the docks.".to_string(),
+            parent_id: None,
+        }];
+        let ctx = ConversationContext::from_summary_and_turns(None, &numbered_turns(5)).with_memory(memory);
+        assert_eq!(ctx.turns.len(), 2);
+        assert_eq!(ctx.memory_turn_count(), 3);
+        assert_eq!(ctx.story_so_far(), "[Scene — turns 1-3] They met at the docks.");
+        assert_eq!(get_diagnostics(&ctx, 0, 0).total_turns, 5);
+    }
 }
\ No newline at end of file
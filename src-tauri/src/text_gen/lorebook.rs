@@ -0,0 +1,304 @@
+// src-tauri/src/text_gen/lorebook.rs
+//
+// Lorebook / World-Info Activation
+// ==================================
+// World facts (factions, magic rules, places) live in the `lore_entries`
+// table, either scoped to one story or global (story_id NULL). Entries are
+// edited through commands/lore.rs.
+//
+// Each turn, `assemble_prompt_string` scans the last LORE_SCAN_DEPTH turns
+// plus the player's input for each entry's keys (case-insensitive, whole
+// words). Matching entries and always-on entries are injected at their
+// position, highest priority first, until the lore token budget
+// (AppConfig.lore_token_budget) is spent. Entries that matched but didn't fit
+// are still reported so the diagnostics can show why they were left out.
+
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+
+use crate::models::{LoreEntry, LorePosition};
+use crate::text_gen::context::{estimate_tokens, StoryTurn};
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+/// Recent turns (plus the current input) scanned for lore keys.
+pub const LORE_SCAN_DEPTH: usize = 3;
+
+/// Framing overhead per injected entry ("Name: " + separators).
+const ENTRY_OVERHEAD_TOKENS: usize = 4;
+
+// ============================================================================
+// ACTIVATION
+// ============================================================================
+
+/// The lore entries available to one story, with the token budget for a turn.
+#[derive(Debug, Clone, Default)]
+pub struct Lorebook {
+    pub entries: Vec<LoreEntry>,
+    pub token_budget: usize,
+}
+
+/// One entry that fired this turn, for diagnostics.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FiredLore {
+    pub id: i64,
+    pub name: String,
+    pub position: LorePosition,
+    /// The key that matched (None for always-on entries)
+    pub trigger: Option<String>,
+    pub tokens: usize,
+    /// False if the entry matched but didn't fit in the budget
+    pub injected: bool,
+}
+
+/// Result of scanning a turn: what to inject and what fired.
+pub struct LoreActivation<'a> {
+    injected: Vec<&'a LoreEntry>,
+    pub fired: Vec<FiredLore>,
+}
+
+fn entry_tokens(entry: &LoreEntry) -> usize {
+    estimate_tokens(&entry.name) + estimate_tokens(&entry.content) + ENTRY_OVERHEAD_TOKENS
+}
+
+/// Whole-word, case-insensitive key match. `haystack` must already be lowercase.
+fn contains_key(haystack: &str, key: &str) -> bool {
+    let key = key.trim().to_lowercase();
+    if key.is_empty() {
+        return false;
+    }
+    haystack.match_indices(&key).any(|(start, _)| {
+        let before = haystack[..start].chars().next_back();
+        let after = haystack[start + key.len()..].chars().next();
+        !before.is_some_and(|c| c.is_alphanumeric()) && !after.is_some_and(|c| c.is_alphanumeric())
+    })
+}
+
+/// Player inputs and story text of the last LORE_SCAN_DEPTH turns, plus the
+/// current input, lowercased.
+fn scan_text(recent_turns: &[StoryTurn], current_input: &str) -> String {
+    let start = recent_turns.len().saturating_sub(LORE_SCAN_DEPTH);
+    let mut text = String::new();
+    for turn in &recent_turns[start..] {
+        text.push_str(&turn.user_input);
+        text.push('\n');
+        text.push_str(&turn.story_text);
+        text.push('\n');
+    }
+    text.push_str(current_input);
+    text.to_lowercase()
+}
+
+impl Lorebook {
+    pub fn new(entries: Vec<LoreEntry>, token_budget: usize) -> Self {
+        Self { entries, token_budget }
+    }
+
+    /// Find the entries triggered by recent turns + input and fit them into
+    /// the budget (priority desc, always-on first on ties, then oldest).
+    pub fn activate(&self, recent_turns: &[StoryTurn], current_input: &str) -> LoreActivation<'_> {
+        if self.entries.is_empty() {
+            return LoreActivation { injected: Vec::new(), fired: Vec::new() };
+        }
+        let scan = scan_text(recent_turns, current_input);
+
+        let mut candidates: Vec<(&LoreEntry, Option<String>)> = self
+            .entries
+            .iter()
+            .filter(|e| e.enabled)
+            .filter_map(|e| {
+                if e.always_on {
+                    Some((e, None))
+                } else {
+                    e.keys
+                        .iter()
+                        .find(|k| contains_key(&scan, k))
+                        .map(|k| (e, Some(k.trim().to_string())))
+                }
+            })
+            .collect();
+        candidates.sort_by(|(a, _), (b, _)| {
+            b.priority
+                .cmp(&a.priority)
+                .then(b.always_on.cmp(&a.always_on))
+                .then(a.id.cmp(&b.id))
+        });
+
+        let mut used = 0;
+        let mut injected = Vec::new();
+        let mut fired = Vec::new();
+        for (entry, trigger) in candidates {
+            let tokens = entry_tokens(entry);
+            let fits = used + tokens <= self.token_budget;
+            if fits {
+                used += tokens;
+                injected.push(entry);
+            }
+            fired.push(FiredLore {
+                id: entry.id,
+                name: entry.name.clone(),
+                position: entry.position,
+                trigger,
+                tokens,
+                injected: fits,
+            });
+        }
+
+        LoreActivation { injected, fired }
+    }
+}
+
+impl LoreActivation<'_> {
+    /// The WORLD INFO block for one insertion position ("" if nothing fired there).
+    pub fn block(&self, position: LorePosition) -> String {
+        let entries: Vec<String> = self
+            .injected
+            .iter()
+            .filter(|e| e.position == position)
+            .map(|e| format!("{}: {}", e.name, e.content.trim()))
+            .collect();
+        if entries.is_empty() {
+            return String::new();
+        }
+        format!("=== WORLD INFO ===\n{}\n=== END WORLD INFO ===", entries.join("\n\n"))
+    }
+}
+
+// ============================================================================
+// PERSISTENCE
+// ============================================================================
+
+/// Map a `lore_entries` row. Unparseable key lists load as empty.
+pub(crate) fn lore_from_row(row: &sqlx::sqlite::SqliteRow) -> LoreEntry {
+    let keys: String = row.get("keys");
+    let position: String = row.get("position");
+    LoreEntry {
+        id: row.get("id"),
+        story_id: row.get("story_id"),
+        name: row.get("name"),
+        keys: serde_json::from_str(&keys).unwrap_or_default(),
+        content: row.get("content"),
+        priority: row.get("priority"),
+        position: LorePosition::parse(&position),
+        always_on: row.get("always_on"),
+        enabled: row.get("enabled"),
+        created_at: row.get("created_at"),
+    }
+}
+
+/// Enabled entries for a story plus all global entries.
+pub async fn load_lorebook(
+    db: &sqlx::SqlitePool,
+    story_id: Option<i64>,
+    token_budget: usize,
+) -> Result<Lorebook, String> {
+    let rows = sqlx::query(
+        "SELECT id, story_id, name, keys, content, priority, position, always_on, enabled, created_at \
+         FROM lore_entries \
+         WHERE enabled = 1 AND (story_id IS NULL OR story_id = ?) \
+         ORDER BY priority DESC, id",
+    )
+    .bind(story_id)
+    .fetch_all(db)
+    .await
+    .map_err(|e| format!("Failed to load lorebook: {}", e))?;
+
+    Ok(Lorebook::new(rows.iter().map(lore_from_row).collect(), token_budget))
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: i64, name: &str, keys: &[&str], priority: i64) -> LoreEntry {
+        LoreEntry {
+            id,
+            story_id: None,
+            name: name.to_string(),
+            keys: keys.iter().map(|k| k.to_string()).collect(),
+            content: format!("Facts about {}.", name),
+            priority,
+            position: LorePosition::BeforeInput,
+            always_on: false,
+            enabled: true,
+            created_at: None,
+        }
+    }
+
+    fn turn(n: usize, user: &str, story: &str) -> StoryTurn {
+        let response = serde_json::json!({"story_json": {"response": story, "summary_hint": ""}}).to_string();
+        StoryTurn::from_messages(n, user, &response)
+    }
+
+    #[test]
+    fn test_contains_key_matches_whole_words() {
+        let text = "the iron guild met at dawn.";
+        assert!(contains_key(text, "Iron Guild"));
+        assert!(contains_key(text, "dawn"));
+        assert!(!contains_key(text, "guil"));
+        assert!(!contains_key(text, "  "));
+    }
+
+    #[test]
+    fn test_activation_scans_recent_turns_and_input() {
+        let book = Lorebook::new(
+            vec![
+                entry(1, "Iron Guild", &["iron guild"], 0),
+                entry(2, "Moon Magic", &["moonstone"], 0),
+                entry(3, "Old Capital", &["Varenholm"], 0),
+            ],
+            500,
+        );
+        let turns = vec![
+            turn(1, "I look around", "A banner of the Iron Guild hangs here."),
+            turn(2, "Wait", "Nothing happens."),
+        ];
+        let activation = book.activate(&turns, "I pick up the moonstone");
+        let ids: Vec<i64> = activation.fired.iter().map(|f| f.id).collect();
+        assert_eq!(ids, vec![1, 2]);
+        assert_eq!(activation.fired[1].trigger.as_deref(), Some("moonstone"));
+    }
+
+    #[test]
+    fn test_always_on_disabled_and_scan_depth() {
+        let mut always = entry(1, "Magic Rules", &[], 0);
+        always.always_on = true;
+        let mut disabled = entry(2, "Disabled", &["door"], 0);
+        disabled.enabled = false;
+        let old = entry(3, "Lighthouse", &["lighthouse"], 0);
+        let book = Lorebook::new(vec![always, disabled, old], 500);
+
+        let mut turns = vec![turn(1, "Go to the lighthouse", "You arrive.")];
+        for n in 2..=(LORE_SCAN_DEPTH + 1) {
+            turns.push(turn(n, "Open the door", "It creaks."));
+        }
+        let activation = book.activate(&turns, "");
+        assert_eq!(activation.fired.len(), 1);
+        assert_eq!(activation.fired[0].id, 1);
+        assert!(activation.fired[0].trigger.is_none());
+    }
+
+    #[test]
+    fn test_budget_keeps_highest_priority() {
+        let low = entry(1, "Low", &["harbor"], 0);
+        let high = entry(2, "High", &["harbor"], 10);
+        let budget = entry_tokens(&high);
+        let book = Lorebook::new(vec![low, high], budget);
+
+        let activation = book.activate(&[], "We reach the harbor");
+        assert_eq!(activation.fired.len(), 2);
+        assert!(activation.fired.iter().any(|f| f.id == 2 && f.injected));
+        assert!(activation.fired.iter().any(|f| f.id == 1 && !f.injected));
+
+        let block = activation.block(LorePosition::BeforeInput);
+        assert!(block.contains("High: Facts about High."));
+        assert!(!block.contains("Low:"));
+        assert!(activation.block(LorePosition::AfterPremise).is_empty());
+    }
+}
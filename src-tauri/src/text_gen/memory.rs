@@ -0,0 +1,717 @@
+// src-tauri/src/text_gen/memory.rs
+//
+// Hierarchical Story Memory
+// ==========================
+// A flat "story so far" that is re-summarized on every compression slowly
+// crushes the opening of a long campaign. This module keeps three levels of
+// summaries in the `story_memory` table instead:
+//
+//   scene   — one per closed scene. A scene closes when `sync_scene_from_turn`
+//             moves the story to a different scene (tracked via
+//             `messages.scene_id`), or after SCENE_MAX_TURNS turns.
+//   chapter — rolls up SCENES_PER_CHAPTER consecutive scene summaries.
+//   arc     — a single per-chat summary that absorbs CHAPTERS_PER_ARC chapters
+//             at a time. Only this level is ever re-summarized.
+//
+// Rolled-up rows are kept (with `parent_id` set), so the context assembler can
+// pick the most detailed level that fits the memory budget:
+//   all scenes  →  chapters + newer scenes  →  arc + newer chapters + scenes
+//
+// Memory is only built once a chat's context overflows (the orchestrator's
+// compression pre-pass), and each turn does at most
+// MAX_MEMORY_UPDATES_PER_TURN summarization calls.
+
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use std::collections::HashMap;
+use tauri::State;
+
+use crate::cancellation::GenerationJob;
+use crate::error::AppError;
+use crate::state::OllamaState;
+use crate::text_gen::backend::TextBackend;
+use crate::text_gen::context::{estimate_tokens, summarize_with_llm, StoredSummary, RECENT_TURNS_TO_KEEP};
+use crate::text_gen::turns::load_timeline;
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+/// A scene that runs longer than this is closed and summarized in parts.
+pub const SCENE_MAX_TURNS: usize = 8;
+
+/// Unrolled scene summaries that trigger a chapter roll-up.
+pub const SCENES_PER_CHAPTER: usize = 5;
+
+/// Unrolled chapter summaries that get folded into the arc.
+pub const CHAPTERS_PER_ARC: usize = 4;
+
+/// Upper bound on summarization calls per story turn.
+pub const MAX_MEMORY_UPDATES_PER_TURN: usize = 2;
+
+/// Share of the history token budget the memory block may use.
+pub const MEMORY_BUDGET_SHARE: f64 = 0.35;
+
+const SCENE_PROMPT: &str = r#"Summarize this scene of an interactive story in 2-4 sentences.
+Preserve: who was present, where it happened, what changed, unresolved threads, and each character's emotional shift.
+Do NOT add new story content. Only summarize what happened.
+
+{{TEXT}}
+
+SCENE SUMMARY:"#;
+
+const CHAPTER_PROMPT: &str = r#"Combine these consecutive scene summaries of an interactive story into one chapter summary (4-6 sentences).
+Keep key plot points, character relationships, discoveries, and unresolved conflicts. Drop minor details.
+Do NOT add new story content.
+
+{{TEXT}}
+
+CHAPTER SUMMARY:"#;
+
+const ARC_PROMPT: &str = r#"Update the overall story arc summary with the chapters that follow it (5-8 sentences).
+Always keep: the opening premise, how the main characters met, major turning points, and open goals or mysteries.
+Do NOT add new story content.
+
+{{TEXT}}
+
+ARC SUMMARY:"#;
+
+// ============================================================================
+// TYPES
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MemoryLevel {
+    Scene,
+    Chapter,
+    Arc,
+}
+
+impl MemoryLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Scene => "scene",
+            Self::Chapter => "chapter",
+            Self::Arc => "arc",
+        }
+    }
+
+    fn from_db(value: &str) -> Self {
+        match value {
+            "chapter" => Self::Chapter,
+            "arc" => Self::Arc,
+            _ => Self::Scene,
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Scene => "Scene",
+            Self::Chapter => "Chapter",
+            Self::Arc => "Arc",
+        }
+    }
+}
+
+/// One row of `story_memory`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryEntry {
+    pub id: i64,
+    pub chat_id: i64,
+    pub level: MemoryLevel,
+    /// Scene the turns happened in (scene level only)
+    pub scene_id: Option<i64>,
+    pub first_turn: i64,
+    pub last_turn: i64,
+    /// Assistant message of the last turn covered
+    pub last_message_id: i64,
+    pub summary: String,
+    /// The chapter/arc this entry has been rolled up into
+    pub parent_id: Option<i64>,
+}
+
+/// Minimal per-turn data needed to find scene boundaries.
+#[derive(Debug, Clone)]
+pub struct TurnMarker {
+    pub turn_number: usize,
+    /// Assistant message id
+    pub message_id: i64,
+    pub scene_id: Option<i64>,
+}
+
+/// A run of consecutive turns in one scene that is ready to be summarized.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    pub scene_id: Option<i64>,
+    pub first_turn: usize,
+    pub last_turn: usize,
+    pub last_message_id: i64,
+}
+
+// ============================================================================
+// SEGMENTATION
+// ============================================================================
+
+/// Last turn number and message id covered by scene summaries, if any.
+pub fn memory_coverage(entries: &[MemoryEntry]) -> Option<(i64, i64)> {
+    entries
+        .iter()
+        .filter(|e| e.level == MemoryLevel::Scene)
+        .map(|e| (e.last_turn, e.last_message_id))
+        .max_by_key(|(_, message_id)| *message_id)
+}
+
+/// Closed scene segments after `covered_until`, oldest first.
+///
+/// The last `keep_recent` turns are never summarized. Untagged turns inherit
+/// the previous turn's scene. The open (still running) segment is left out.
+pub fn closed_segments(turns: &[TurnMarker], covered_until: i64, keep_recent: usize) -> Vec<Segment> {
+    let live_from = turns.len().saturating_sub(keep_recent);
+    let mut segments = Vec::new();
+    let mut current: Option<Segment> = None;
+    let mut last_scene: Option<i64> = None;
+
+    for (i, turn) in turns.iter().enumerate() {
+        let scene_id = turn.scene_id.or(last_scene);
+        last_scene = scene_id;
+        if turn.message_id <= covered_until {
+            continue;
+        }
+
+        // A scene change closes the open segment (even inside the recent window)
+        if let Some(seg) = current.take() {
+            if seg.scene_id == scene_id {
+                current = Some(seg);
+            } else {
+                segments.push(seg);
+            }
+        }
+        if i >= live_from {
+            break;
+        }
+
+        let seg = current.get_or_insert(Segment {
+            scene_id,
+            first_turn: turn.turn_number,
+            last_turn: turn.turn_number,
+            last_message_id: turn.message_id,
+        });
+        seg.last_turn = turn.turn_number;
+        seg.last_message_id = turn.message_id;
+
+        if seg.last_turn + 1 - seg.first_turn >= SCENE_MAX_TURNS {
+            segments.extend(current.take());
+        }
+    }
+
+    segments
+}
+
+// ============================================================================
+// LEVEL SELECTION
+// ============================================================================
+
+/// The entries to show at each level of detail, most detailed first.
+fn detail_levels(entries: &[MemoryEntry]) -> Vec<Vec<&MemoryEntry>> {
+    let of = |level: MemoryLevel| entries.iter().filter(move |e| e.level == level);
+    let unrolled = |level: MemoryLevel| of(level).filter(|e| e.parent_id.is_none());
+
+    let mut levels: Vec<Vec<&MemoryEntry>> = vec![
+        of(MemoryLevel::Scene).collect(),
+        of(MemoryLevel::Chapter).chain(unrolled(MemoryLevel::Scene)).collect(),
+        of(MemoryLevel::Arc)
+            .chain(unrolled(MemoryLevel::Chapter))
+            .chain(unrolled(MemoryLevel::Scene))
+            .collect(),
+    ];
+    for level in levels.iter_mut() {
+        level.sort_by_key(|e| e.first_turn);
+    }
+    levels
+}
+
+/// Render entries as the memory block of the "story so far".
+fn render(entries: &[&MemoryEntry]) -> String {
+    entries
+        .iter()
+        .map(|e| {
+            if e.first_turn == e.last_turn {
+                format!("[{} — turn {}] {}", e.level.label(), e.first_turn, e.summary)
+            } else {
+                format!("[{} — turns {}-{}] {}", e.level.label(), e.first_turn, e.last_turn, e.summary)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Pick the most detailed level whose rendering fits `budget_tokens`
+/// (the coarsest level if none does) and render it.
+pub fn select_memory(entries: &[MemoryEntry], budget_tokens: usize) -> String {
+    let levels = detail_levels(entries);
+    let mut rendered = String::new();
+    for level in &levels {
+        if level.is_empty() {
+            continue;
+        }
+        rendered = render(level);
+        if estimate_tokens(&rendered) <= budget_tokens {
+            break;
+        }
+    }
+    rendered
+}
+
+/// A rolling summary (`context_summaries`) that starts inside the memory range
+/// would repeat it. Legacy summaries (first_turn 0) cover deleted turns only.
+pub fn summary_overlaps_memory(summary: &StoredSummary, entries: &[MemoryEntry]) -> bool {
+    match memory_coverage(entries) {
+        Some((last_turn, _)) => summary.first_turn > 0 && summary.first_turn <= last_turn,
+        None => false,
+    }
+}
+
+// ============================================================================
+// DATABASE
+// ============================================================================
+
+/// All memory entries for a chat.
+pub async fn load_memory<'e, E>(executor: E, chat_id: i64) -> Result<Vec<MemoryEntry>, String>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Sqlite>,
+{
+    let rows = sqlx::query(
+        "SELECT id, chat_id, level, scene_id, first_turn, last_turn, last_message_id, summary, parent_id \
+         FROM story_memory WHERE chat_id = ? ORDER BY first_turn ASC, id ASC",
+    )
+    .bind(chat_id)
+    .fetch_all(executor)
+    .await
+    .map_err(|e| format!("Failed to load story memory: {}", e))?;
+
+    Ok(rows
+        .iter()
+        .map(|row| MemoryEntry {
+            id: row.get("id"),
+            chat_id: row.get("chat_id"),
+            level: MemoryLevel::from_db(&row.get::<String, _>("level")),
+            scene_id: row.get("scene_id"),
+            first_turn: row.get("first_turn"),
+            last_turn: row.get("last_turn"),
+            last_message_id: row.get("last_message_id"),
+            summary: row.get("summary"),
+            parent_id: row.get("parent_id"),
+        })
+        .collect())
+}
+
+/// Turn markers plus (user, story text) for every turn of a chat.
+async fn load_turns(
+    db: &sqlx::SqlitePool,
+    chat_id: i64,
+) -> Result<Vec<(TurnMarker, String, String)>, String> {
+    Ok(load_timeline(db, chat_id)
+        .await?
+        .into_iter()
+        .enumerate()
+        .map(|(i, t)| {
+            let marker = TurnMarker {
+                turn_number: i + 1,
+                message_id: t.record.assistant_message_id,
+                scene_id: t.record.scene_id,
+            };
+            (marker, t.user_input, t.record.story_text)
+        })
+        .collect())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn insert_entry<'e, E>(
+    executor: E,
+    chat_id: i64,
+    level: MemoryLevel,
+    scene_id: Option<i64>,
+    first_turn: i64,
+    last_turn: i64,
+    last_message_id: i64,
+    summary: &str,
+) -> Result<i64, String>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Sqlite>,
+{
+    let result = sqlx::query(
+        "INSERT INTO story_memory \
+         (chat_id, level, scene_id, first_turn, last_turn, last_message_id, summary) \
+         VALUES (?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(chat_id)
+    .bind(level.as_str())
+    .bind(scene_id)
+    .bind(first_turn)
+    .bind(last_turn)
+    .bind(last_message_id)
+    .bind(summary)
+    .execute(executor)
+    .await
+    .map_err(|e| format!("Failed to save {} summary: {}", level.as_str(), e))?;
+
+    Ok(result.last_insert_rowid())
+}
+
+/// Copy the entries of `from_chat` that end at or before `until_message_id`
+/// into `to_chat` (used when forking a story branch). Roll-ups are kept when
+/// the parent entry is copied too. Runs on the caller's transaction. Returns
+/// how many entries were copied.
+pub async fn copy_memory(
+    conn: &mut sqlx::SqliteConnection,
+    from_chat: i64,
+    to_chat: i64,
+    until_message_id: i64,
+) -> Result<usize, String> {
+    let entries: Vec<MemoryEntry> = load_memory(&mut *conn, from_chat)
+        .await?
+        .into_iter()
+        .filter(|e| e.last_message_id <= until_message_id)
+        .collect();
+
+    let mut new_ids: HashMap<i64, i64> = HashMap::new();
+    for e in &entries {
+        let id = insert_entry(&mut *conn, to_chat, e.level, e.scene_id, e.first_turn, e.last_turn, e.last_message_id, &e.summary).await?;
+        new_ids.insert(e.id, id);
+    }
+    for e in &entries {
+        if let Some(parent) = e.parent_id.and_then(|p| new_ids.get(&p)) {
+            sqlx::query("UPDATE story_memory SET parent_id = ? WHERE id = ?")
+                .bind(parent)
+                .bind(new_ids[&e.id])
+                .execute(&mut *conn)
+                .await
+                .map_err(|e| format!("Failed to copy memory roll-up: {}", e))?;
+        }
+    }
+    Ok(entries.len())
+}
+
+/// Drop the entries covering messages after `keep_until` (a rewind or an
+/// edit). Entries rolled up into a dropped chapter or arc become unrolled
+/// again, so the assembler still sees them and the next update re-rolls them.
+pub async fn drop_memory_after(
+    conn: &mut sqlx::SqliteConnection,
+    chat_id: i64,
+    keep_until: i64,
+) -> Result<(), String> {
+    sqlx::query("DELETE FROM story_memory WHERE chat_id = ? AND last_message_id > ?")
+        .bind(chat_id)
+        .bind(keep_until)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| format!("Failed to invalidate story_memory: {}", e))?;
+    sqlx::query(
+        "UPDATE story_memory SET parent_id = NULL WHERE chat_id = ? AND parent_id IS NOT NULL          AND parent_id NOT IN (SELECT id FROM story_memory)",
+    )
+    .bind(chat_id)
+    .execute(&mut *conn)
+    .await
+    .map_err(|e| format!("Failed to unroll story memory: {}", e))?;
+    Ok(())
+}
+
+async fn set_parent(db: &sqlx::SqlitePool, children: &[&MemoryEntry], parent_id: i64) -> Result<(), String> {
+    for child in children {
+        sqlx::query("UPDATE story_memory SET parent_id = ? WHERE id = ?")
+            .bind(parent_id)
+            .bind(child.id)
+            .execute(db)
+            .await
+            .map_err(|e| format!("Failed to roll up summary {}: {}", child.id, e))?;
+    }
+    Ok(())
+}
+
+// ============================================================================
+// MAINTENANCE
+// ============================================================================
+
+/// Bring a chat's memory up to date: summarize closed scenes, then roll
+/// scenes into chapters and chapters into the arc. Stops after
+/// MAX_MEMORY_UPDATES_PER_TURN summarization calls; the rest waits for the
+/// next turn. Returns the number of summaries written.
+pub async fn update_story_memory(
+    db: &sqlx::SqlitePool,
+    backend: &dyn TextBackend,
+    model: &str,
+    job: &GenerationJob,
+    chat_id: i64,
+) -> Result<usize, String> {
+    let mut entries = load_memory(db, chat_id).await?;
+    let turns = load_turns(db, chat_id).await?;
+    let mut updates = 0;
+
+    // 1. Scene summaries for closed scenes
+    let covered_until = memory_coverage(&entries).map(|(_, id)| id).unwrap_or(0);
+    let markers: Vec<TurnMarker> = turns.iter().map(|(m, _, _)| m.clone()).collect();
+    for seg in closed_segments(&markers, covered_until, RECENT_TURNS_TO_KEEP) {
+        if updates >= MAX_MEMORY_UPDATES_PER_TURN {
+            return Ok(updates);
+        }
+        job.check()?;
+
+        let text = turns[seg.first_turn - 1..seg.last_turn]
+            .iter()
+            .map(|(m, user, story)| format!("Turn {} - Player said: \"{}\"\nStory: {}", m.turn_number, user, story))
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        let summary = job
+            .run(summarize_with_llm(backend, model, &SCENE_PROMPT.replace("{{TEXT}}", &text)))
+            .await?;
+
+        insert_entry(
+            db,
+            chat_id,
+            MemoryLevel::Scene,
+            seg.scene_id,
+            seg.first_turn as i64,
+            seg.last_turn as i64,
+            seg.last_message_id,
+            &summary,
+        )
+        .await?;
+        println!(
+            "[Memory] Scene summary for turns {}-{} (chat_id={})",
+            seg.first_turn, seg.last_turn, chat_id
+        );
+        updates += 1;
+    }
+    if updates > 0 {
+        entries = load_memory(db, chat_id).await?;
+    }
+
+    // 2. Chapter roll-up of the oldest unrolled scenes
+    let scenes: Vec<&MemoryEntry> = entries
+        .iter()
+        .filter(|e| e.level == MemoryLevel::Scene && e.parent_id.is_none())
+        .collect();
+    if scenes.len() >= SCENES_PER_CHAPTER && updates < MAX_MEMORY_UPDATES_PER_TURN {
+        job.check()?;
+        let group = &scenes[..SCENES_PER_CHAPTER];
+        let summary = job
+            .run(summarize_with_llm(backend, model, &CHAPTER_PROMPT.replace("{{TEXT}}", &render(group))))
+            .await?;
+        let first = group[0];
+        let last = group[group.len() - 1];
+        let chapter_id = insert_entry(
+            db,
+            chat_id,
+            MemoryLevel::Chapter,
+            None,
+            first.first_turn,
+            last.last_turn,
+            last.last_message_id,
+            &summary,
+        )
+        .await?;
+        set_parent(db, group, chapter_id).await?;
+        println!(
+            "[Memory] Chapter summary for turns {}-{} (chat_id={})",
+            first.first_turn, last.last_turn, chat_id
+        );
+        updates += 1;
+        entries = load_memory(db, chat_id).await?;
+    }
+
+    // 3. Fold the oldest unrolled chapters into the arc
+    let chapters: Vec<&MemoryEntry> = entries
+        .iter()
+        .filter(|e| e.level == MemoryLevel::Chapter && e.parent_id.is_none())
+        .collect();
+    if chapters.len() >= CHAPTERS_PER_ARC && updates < MAX_MEMORY_UPDATES_PER_TURN {
+        job.check()?;
+        let group = &chapters[..CHAPTERS_PER_ARC];
+        let arc = entries.iter().find(|e| e.level == MemoryLevel::Arc);
+        let mut parts: Vec<&MemoryEntry> = arc.into_iter().collect();
+        parts.extend(group.iter().copied());
+        let summary = job
+            .run(summarize_with_llm(backend, model, &ARC_PROMPT.replace("{{TEXT}}", &render(&parts))))
+            .await?;
+
+        let last = group[group.len() - 1];
+        let arc_id = match arc {
+            Some(arc) => {
+                sqlx::query(
+                    "UPDATE story_memory SET summary = ?, last_turn = ?, last_message_id = ? WHERE id = ?",
+                )
+                .bind(&summary)
+                .bind(last.last_turn)
+                .bind(last.last_message_id)
+                .bind(arc.id)
+                .execute(db)
+                .await
+                .map_err(|e| format!("Failed to update arc summary: {}", e))?;
+                arc.id
+            }
+            None => {
+                insert_entry(
+                    db,
+                    chat_id,
+                    MemoryLevel::Arc,
+                    None,
+                    group[0].first_turn,
+                    last.last_turn,
+                    last.last_message_id,
+                    &summary,
+                )
+                .await?
+            }
+        };
+        set_parent(db, group, arc_id).await?;
+        println!("[Memory] Arc summary now covers up to turn {} (chat_id={})", last.last_turn, chat_id);
+        updates += 1;
+    }
+
+    Ok(updates)
+}
+
+// ============================================================================
+// COMMANDS
+// ============================================================================
+
+/// All scene, chapter and arc summaries for a chat.
+///
+/// ## Frontend usage
+/// ```typescript
+/// const memory = await invoke('get_story_memory', { chatId });
+/// ```
+#[tauri::command]
+pub async fn get_story_memory(
+    chat_id: i64,
+    state: State<'_, OllamaState>,
+) -> Result<Vec<MemoryEntry>, AppError> {
+    load_memory(&state.db, chat_id).await.map_err(AppError::database)
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn markers(scenes: &[Option<i64>]) -> Vec<TurnMarker> {
+        scenes
+            .iter()
+            .enumerate()
+            .map(|(i, s)| TurnMarker { turn_number: i + 1, message_id: (i as i64 + 1) * 2, scene_id: *s })
+            .collect()
+    }
+
+    fn entry(id: i64, level: MemoryLevel, first: i64, last: i64, parent: Option<i64>) -> MemoryEntry {
+        MemoryEntry {
+            id,
+            chat_id: 1,
+            level,
+            scene_id: None,
+            first_turn: first,
+            last_turn: last,
+            last_message_id: last * 2,
+            summary: format!("{} {}-{} {}", level.as_str(), first, last, "word ".repeat(40)),
+            parent_id: parent,
+        }
+    }
+
+    #[test]
+    fn test_scene_change_closes_segment() {
+        // Scene 1 for 3 turns, scene 2 for 2, then 6 recent turns in scene 3
+        let mut scenes = vec![Some(1), None, Some(1), Some(2), Some(2)];
+        scenes.extend(vec![Some(3); 6]);
+        let segs = closed_segments(&markers(&scenes), 0, 6);
+        assert_eq!(segs.len(), 2);
+        assert_eq!((segs[0].first_turn, segs[0].last_turn), (1, 3));
+        assert_eq!((segs[1].first_turn, segs[1].last_turn, segs[1].last_message_id), (4, 5, 10));
+
+        // Already covered turns are skipped
+        let segs = closed_segments(&markers(&scenes), 6, 6);
+        assert_eq!(segs.len(), 1);
+        assert_eq!(segs[0].first_turn, 4);
+    }
+
+    #[test]
+    fn test_open_scene_is_split_at_max_length() {
+        let scenes = vec![None; SCENE_MAX_TURNS * 2 + 3 + RECENT_TURNS_TO_KEEP];
+        let segs = closed_segments(&markers(&scenes), 0, RECENT_TURNS_TO_KEEP);
+        assert_eq!(segs.len(), 2);
+        assert_eq!(segs[1].last_turn, SCENE_MAX_TURNS * 2);
+    }
+
+    #[test]
+    fn test_select_memory_prefers_detail_that_fits() {
+        let entries = vec![
+            entry(1, MemoryLevel::Scene, 1, 5, Some(3)),
+            entry(2, MemoryLevel::Scene, 6, 9, Some(3)),
+            entry(3, MemoryLevel::Chapter, 1, 9, None),
+            entry(4, MemoryLevel::Scene, 10, 12, None),
+        ];
+        let detailed = select_memory(&entries, 10_000);
+        assert!(detailed.contains("[Scene — turns 1-5]"));
+        assert!(!detailed.contains("[Chapter"));
+
+        let coarse = select_memory(&entries, 130);
+        assert!(coarse.starts_with("[Chapter — turns 1-9]"));
+        assert!(coarse.contains("[Scene — turns 10-12]"));
+        assert!(!coarse.contains("turns 1-5"));
+    }
+
+    #[test]
+    fn test_summary_overlap() {
+        let entries = vec![entry(1, MemoryLevel::Scene, 1, 5, None)];
+        let mut summary = StoredSummary {
+            id: 1,
+            chat_id: 1,
+            first_turn: 3,
+            last_turn: 9,
+            last_message_id: 18,
+            summary: String::new(),
+            created_at: None,
+        };
+        assert!(summary_overlaps_memory(&summary, &entries));
+        summary.first_turn = 6;
+        assert!(!summary_overlaps_memory(&summary, &entries));
+        summary.first_turn = 0;
+        assert!(!summary_overlaps_memory(&summary, &entries));
+    }
+
+    #[tokio::test]
+    async fn test_dropping_a_roll_up_unrolls_its_children() {
+        let db = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::migrations::migrate(&db, None).await.unwrap();
+        let chat_id = sqlx::query("INSERT INTO chats (title) VALUES ('Story')")
+            .execute(&db)
+            .await
+            .unwrap()
+            .last_insert_rowid();
+
+        let first = insert_entry(&db, chat_id, MemoryLevel::Scene, None, 1, 3, 6, "first").await.unwrap();
+        insert_entry(&db, chat_id, MemoryLevel::Scene, None, 4, 6, 12, "second").await.unwrap();
+        let chapter = insert_entry(&db, chat_id, MemoryLevel::Chapter, None, 1, 6, 12, "chapter").await.unwrap();
+        let scenes = load_memory(&db, chat_id).await.unwrap();
+        let scenes: Vec<&MemoryEntry> = scenes.iter().filter(|e| e.level == MemoryLevel::Scene).collect();
+        set_parent(&db, &scenes, chapter).await.unwrap();
+
+        // Rewind into the second scene: it and its chapter go
+        let mut conn = db.acquire().await.unwrap();
+        drop_memory_after(&mut conn, chat_id, 8).await.unwrap();
+        drop(conn);
+
+        let entries = load_memory(&db, chat_id).await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].id, first);
+        assert_eq!(entries[0].parent_id, None);
+        // Still shown at the coarser levels
+        assert!(detail_levels(&entries).iter().all(|level| level.len() == 1));
+    }
+}
@@ -1,4 +1,14 @@
+pub mod backend;
 pub mod context;
+pub mod lorebook;
+pub mod memory;
+pub mod parse_stats;
 pub mod parser;
 pub mod prompts;
+pub mod recall;
+pub mod repair;
 pub mod orchestrator;
+pub mod streaming;
+pub mod template;
+pub mod traces;
+pub mod turns;
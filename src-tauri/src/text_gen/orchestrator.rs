@@ -6,7 +6,8 @@
 //
 //   1. Receive user input (player's action/dialogue)
 //   2. Build compressed context using text_gen::context
-//   3. Call Ollama with the assembled prompt
+//   3. Call Ollama with the assembled prompt (prose streamed via text_gen::streaming)
+//      (truncated or malformed output is repaired via text_gen::repair)
 //   4. Parse the response using text_gen::parser
 //   5. Look up characters from the database
 //   6. Check generation_flags — if generate_image: true:
@@ -17,23 +18,37 @@
 //   8. Return everything to the frontend
 
 use serde::{Deserialize, Serialize};
-use serde_json::{json, Value};
 use sqlx::Row;
 
 use tauri::{AppHandle, Emitter, Manager, State};
 
+use crate::cancellation::{chat_job_key, CancelRegistry};
 use crate::config::ConfigState;
-use crate::image_gen::comfyui::{self as comfyui_api, CharacterInput, ImageGenRequest};
+use crate::error::{AppError, ErrorKind};
+use crate::image_gen::comfyui::{self as comfyui_api, MAX_SCENE_CHARACTERS};
 use crate::image_gen::masks::{self as mask_generator, MaskCharacter};
+use crate::commands::branch::is_shared_with_branch;
+use crate::commands::history::{parse_roster_additions, set_active_scene, set_roster_additions, turn_state_before};
+use crate::commands::story::{load_generation_overrides, load_story_bible};
+use crate::text_gen::backend::{backend_from_config, GenerateRequest, PromptInput};
 use crate::text_gen::context::{
-    build_compressed_context, estimate_tokens, get_diagnostics, load_persisted_emotional_states,
-    CharacterInfo, CompressionDiagnostics, ConversationContext, RECENT_TURNS_TO_KEEP,
+    build_compressed_chat_messages, build_compressed_context, estimate_tokens, format_emotion_snapshot,
+    get_diagnostics, list_summaries, load_latest_summary, load_persisted_emotional_states, save_summary,
+    CharacterInfo, CompressionDiagnostics, ConversationContext, StoredSummary, RECENT_TURNS_TO_KEEP,
 };
+use crate::text_gen::lorebook::{load_lorebook, FiredLore, Lorebook};
+use crate::text_gen::memory::{load_memory, summary_overlaps_memory, update_story_memory};
+use crate::text_gen::parse_stats::{record_parse_status, ParseRecord};
+use crate::text_gen::traces::{record_trace, TraceTimings, TurnTrace};
+use crate::text_gen::turns::{insert_turn, load_story_turns, set_turn_image, TurnRecord};
+use crate::text_gen::recall::{embedder_from_config, index_chat_turns, recall_turns, RecalledTurn, MAX_INDEX_PER_TURN};
+use crate::text_gen::repair::{looks_truncated, repair_turn_output};
 use crate::text_gen::parser::{self as llm_parser, CharacterEmotionalState, ParseStatus, ParsedTurn, SceneJson};
 use crate::text_gen::prompts::{
-    get_response_length_config, NUM_CTX, OLLAMA_MAX_RETRIES, OLLAMA_REQUEST_TIMEOUT_SECS,
-    STORY_MODEL, SYSTEM_PROMPT,
+    get_response_length_config, GenerationSettings, OLLAMA_MAX_RETRIES, OLLAMA_REQUEST_TIMEOUT_SECS,
+    SYSTEM_PROMPT,
 };
+use crate::text_gen::streaming::{StoryTextExtractor, StoryTokenPayload, STORY_TOKEN_EVENT};
 use std::time::Duration;
 use crate::models::CharacterLookup;
 use crate::state::{OllamaState, SceneHintState};
@@ -46,9 +61,11 @@ use crate::state::{OllamaState, SceneHintState};
 const DEFAULT_IMAGE_WIDTH: u32 = 1152;
 const DEFAULT_IMAGE_HEIGHT: u32 = 768;
 
-/// Workflow filenames (relative to app data dir's "workflows" folder).
+/// Optional custom workflow templates (relative to the app data dir). Without
+/// them the scene graph is built in code (image_gen::comfyui::workflow).
 /// Single character: uses IPAdapter FaceID without masks.
-/// Multi character: uses IPAdapter FaceID with per-character attention masks.
+/// Multi character: uses IPAdapter FaceID with per-character attention masks;
+/// the pipeline adds a branch per character beyond the template's own.
 const WORKFLOW_1CHAR: &str = "workflows/scene_workflow_1char.json";
 const WORKFLOW_2CHAR: &str = "workflows/scene_workflow_2char.json";
 
@@ -86,6 +103,10 @@ pub struct OrchestratorCompressionInfo {
     pub compression_threshold: usize,
     pub needs_compression: bool,
     pub compressed_summary_preview: String,
+    pub memory_entries: usize,
+    /// Lorebook entries triggered this turn (injected or over budget)
+    #[serde(default)]
+    pub lore_entries: Vec<FiredLore>,
 }
 
 /// Full result of a story turn, returned to the Svelte frontend.
@@ -125,6 +146,20 @@ pub struct ScenePromptPreview {
     pub negative: String,
 }
 
+/// One alternative response ("swipe") to a turn's user message.
+/// Returned by `list_turn_candidates`; only the selected one is in the story.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TurnCandidate {
+    pub message_id: i64,
+    /// Raw assistant JSON, same as `messages.content`
+    pub content: String,
+    /// The user input it answers
+    pub user_input: String,
+    pub image_path: Option<String>,
+    pub selected: bool,
+    pub timestamp: String,
+}
+
 // ============================================================================
 // HELPER: Extract JSON from text that may have surrounding prose
 // ============================================================================
@@ -143,44 +178,40 @@ fn extract_json_from_text(text: &str) -> Option<String> {
 // DATABASE HELPERS
 // ============================================================================
 
-/// Load conversation history as (user, assistant) pairs for context building.
-/// from_message_pairs expects Vec<(user_input, assistant_response)>.
+/// Load the conversation for context building: hierarchical memory, the newest
+/// stored summary, and every turn after the ranges they cover. The `messages` table always holds
+/// the full story; see `context_summaries` in migrations.rs. Branch chats include
+/// the history they share with their parent (see commands::branch).
+/// `before` is the user message of a turn being regenerated.
 async fn load_conversation_history(
     db: &sqlx::SqlitePool,
     chat_id: i64,
-) -> Result<Vec<(String, String)>, String> {
-    let rows = sqlx::query(
-        "SELECT role, content FROM messages WHERE chat_id = ? ORDER BY rowid ASC",
-    )
-    .bind(chat_id)
-    .fetch_all(db)
-    .await
-    .map_err(|e| format!("Failed to load messages: {}", e))?;
-
-    println!("[DEBUG] Loaded {} raw messages from DB for chat_id={}", rows.len(), chat_id);
+    before: Option<i64>,
+) -> Result<ConversationContext, String> {
+    let mut turns = load_story_turns(db, chat_id).await?;
+    if let Some(user_id) = before {
+        turns.retain(|t| t.message_id.map_or(true, |id| id < user_id));
+    }
+    let turn_count = turns.len();
 
-    // Pair up user + assistant messages into tuples
-    let mut pairs: Vec<(String, String)> = Vec::new();
-    let mut pending_user: Option<String> = None;
+    println!("[DEBUG] Loaded {} turns from DB for chat_id={}", turn_count, chat_id);
 
-    for row in &rows {
-        let role: String = row.get("role");
-        let content: String = row.get("content");
-        match role.as_str() {
-            "user" => {
-                pending_user = Some(content);
-            }
-            "assistant" => {
-                if let Some(user_msg) = pending_user.take() {
-                    pairs.push((user_msg, content));
-                }
-            }
-            _ => {}
-        }
-    }
+    // A rolling summary that overlaps the memory range is superseded by it
+    let memory = load_memory(db, chat_id).await?;
+    let summary = load_latest_summary(db, chat_id)
+        .await?
+        .filter(|s| !summary_overlaps_memory(s, &memory));
+    let conversation =
+        ConversationContext::from_summary_and_story_turns(summary.as_ref(), turns).with_memory(memory);
 
-    println!("[DEBUG] Paired into {} turns for chat_id={}", pairs.len(), chat_id);
-    Ok(pairs)
+    println!(
+        "[DEBUG] Built context from {} turns for chat_id={} ({} covered by summaries, {} memory entries)",
+        turn_count,
+        chat_id,
+        turn_count - conversation.turns.len(),
+        conversation.memory.len()
+    );
+    Ok(conversation)
 }
 
 /// Load all characters for a story (or all characters globally if no story_id).
@@ -222,14 +253,15 @@ async fn load_characters_for_context(
         .collect())
 }
 
-/// Load only the characters pinned to the active scene.
+/// Load only the characters pinned to the active scene, minus `exclude`.
 /// Returns an empty vec if scene_id is None or has no characters.
 async fn load_scene_characters_for_context(
     db: &sqlx::SqlitePool,
     scene_id: i64,
+    exclude: &[i64],
 ) -> Result<Vec<CharacterInfo>, String> {
     let rows = sqlx::query(
-        "SELECT c.name, c.age, c.gender, c.personality, c.sd_prompt, c.default_clothing, c.is_pov \
+        "SELECT c.id, c.name, c.age, c.gender, c.personality, c.sd_prompt, c.default_clothing, c.is_pov \
          FROM characters c \
          INNER JOIN scene_characters sc ON sc.character_id = c.id \
          WHERE sc.scene_id = ? ORDER BY c.name",
@@ -241,6 +273,7 @@ async fn load_scene_characters_for_context(
 
     Ok(rows
         .iter()
+        .filter(|r| !exclude.contains(&r.get::<i64, _>("id")))
         .map(|r| CharacterInfo {
             name: r.get("name"),
             age: r.get("age"),
@@ -273,6 +306,8 @@ fn normalize_location(raw: &str) -> String {
 
 /// - Sets story_premises.active_scene_id.
 ///
+/// Returns the scene id and the character ids newly added to its roster (kept
+/// with the turn so a rewind can undo them).
 /// Best-effort: returns Ok(None) if story_id is None or location is empty.
 /// Never propagates errors upward — log and continue.
 async fn sync_scene_from_turn(
@@ -280,7 +315,7 @@ async fn sync_scene_from_turn(
     story_id: i64,
     scene_json: &SceneJson,
     character_names: &[String],
-) -> Result<Option<i64>, String> {
+) -> Result<Option<(i64, Vec<i64>)>, String> {
     let location = scene_json.location.trim().to_string();
     if location.is_empty() {
         return Ok(None);
@@ -346,6 +381,7 @@ async fn sync_scene_from_turn(
     // 4. Additively sync scene_characters from LLM output.
     //    Characters mentioned this turn are ADDED to the scene roster.
     //    Characters are NEVER removed automatically — only via the UI.
+    let mut added = Vec::new();
     for name in character_names {
         let trimmed = name.trim();
         if trimmed.is_empty() {
@@ -366,37 +402,49 @@ async fn sync_scene_from_turn(
 
         if let Some(row) = char_row {
             let char_id: i64 = row.get("id");
-            sqlx::query(
+            let inserted = sqlx::query(
                 "INSERT OR IGNORE INTO scene_characters (scene_id, character_id) VALUES (?, ?)",
             )
             .bind(scene_id)
             .bind(char_id)
             .execute(db)
             .await
-            .ok();
+            .map(|r| r.rows_affected() > 0)
+            .unwrap_or(false);
+            if inserted {
+                added.push(char_id);
+            }
             println!("[Scene] Ensured character '{}' (id={}) in scene id={}", trimmed, char_id, scene_id);
         } else {
             println!("[Scene] Character '{}' not found in story roster — skipped", trimmed);
         }
     }
 
-    Ok(Some(scene_id))
+    Ok(Some((scene_id, added)))
 }
 
-/// Load the active story premise (most recent).
-async fn load_story_premise(db: &sqlx::SqlitePool) -> Result<Option<String>, String> {
-    let row = sqlx::query(
-        "SELECT title, description FROM story_premises ORDER BY created_at DESC LIMIT 1",
-    )
-    .fetch_optional(db)
-    .await
-    .map_err(|e| format!("Failed to load story premise: {}", e))?;
+/// Load the story bible of the story being played, rendered for the prompt.
+/// Falls back to the story linked to `chat_id` when no story_id was passed;
+/// chats without a story get no premise.
+async fn load_story_premise(
+    db: &sqlx::SqlitePool,
+    story_id: Option<i64>,
+    chat_id: i64,
+) -> Result<Option<String>, String> {
+    let story_id = match story_id {
+        Some(id) => Some(id),
+        None => sqlx::query("SELECT id FROM story_premises WHERE chat_id = ?")
+            .bind(chat_id)
+            .fetch_optional(db)
+            .await
+            .map_err(|e| format!("Failed to load story premise: {}", e))?
+            .map(|r| r.get("id")),
+    };
+    let Some(story_id) = story_id else {
+        return Ok(None);
+    };
 
-    Ok(row.map(|r| {
-        let title: String = r.get("title");
-        let desc: String = r.get("description");
-        format!("Title: {}\nPremise: {}", title, desc)
-    }))
+    Ok(load_story_bible(db, story_id).await?.map(|b| b.to_prompt_section()))
 }
 
 /// Look up scene characters in the database.
@@ -509,13 +557,13 @@ fn build_characters_in_scene(
         .collect()
 }
 
-/// Tell Ollama to unload the current model from VRAM immediately.
+/// Tell Ollama to unload the given model from VRAM immediately.
 /// This frees GPU memory for ComfyUI image generation.
 /// The model will be automatically reloaded on the next /api/generate call.
-pub(crate) async fn unload_ollama_model(ollama_url: &str) {
+pub(crate) async fn unload_ollama_model(ollama_url: &str, model: &str) {
     println!(
         "[VRAM] Requesting Ollama to unload model '{}' from VRAM...",
-        STORY_MODEL
+        model
     );
     let client = reqwest::Client::new();
     let url = format!("{}/api/generate", ollama_url);
@@ -523,7 +571,7 @@ pub(crate) async fn unload_ollama_model(ollama_url: &str) {
     let result = client
         .post(&url)
         .json(&serde_json::json!({
-            "model": STORY_MODEL,
+            "model": model,
             "keep_alive": crate::text_gen::prompts::KEEP_ALIVE_UNLOAD
         }))
         .timeout(Duration::from_secs(10))
@@ -553,6 +601,25 @@ pub(crate) async fn unload_ollama_model(ollama_url: &str) {
     tokio::time::sleep(Duration::from_millis(500)).await;
 }
 
+/// Unload the model a story generates with: its model override, else the
+/// app's story model. Without a story, the app's story model.
+pub(crate) async fn unload_story_model(
+    db: &sqlx::SqlitePool,
+    config_state: &ConfigState,
+    story_id: Option<i64>,
+) -> Result<(), String> {
+    let (ollama_url, app_settings) = {
+        let config = config_state.0.lock().map_err(|e| e.to_string())?;
+        (config.ollama_url.clone(), GenerationSettings::from_config(&config))
+    };
+    let overrides = load_generation_overrides(db, story_id).await.unwrap_or_else(|e| {
+        println!("[VRAM] {}, unloading the app's story model", e);
+        Default::default()
+    });
+    unload_ollama_model(&ollama_url, &app_settings.with_overrides(&overrides).model).await;
+    Ok(())
+}
+
 /// Tell ComfyUI to unload all models from VRAM.
 /// Uses the /free endpoint to release GPU memory after image generation.
 /// Models will be automatically reloaded on the next generation request.
@@ -593,15 +660,15 @@ pub(crate) async fn unload_comfyui_models(comfyui_url: &str) {
 
 /// Free all GPU memory by unloading both Ollama and ComfyUI models.
 /// Called when switching stories or from a manual "Free VRAM" button.
+/// `story_id` is the story whose model may be loaded (its model override).
 #[tauri::command]
-pub async fn free_vram(config_state: State<'_, ConfigState>) -> Result<(), String> {
-    let ollama_url = {
-        let config = config_state.0.lock().map_err(|e| e.to_string())?;
-        config.ollama_url.clone()
-    };
-
-    unload_ollama_model(&ollama_url).await;
-    unload_comfyui_models("http://localhost:8188").await;
+pub async fn free_vram(
+    story_id: Option<i64>,
+    state: State<'_, OllamaState>,
+    config_state: State<'_, ConfigState>,
+) -> Result<(), AppError> {
+    unload_story_model(&state.db, &config_state, story_id).await?;
+    unload_comfyui_models(comfyui_api::DEFAULT_COMFYUI_URL).await;
 
     println!("[VRAM] All models unloaded — GPU memory freed");
     Ok(())
@@ -669,224 +736,142 @@ fn estimate_character_db_tokens(characters: &[CharacterInfo]) -> usize {
 // IMAGE GENERATION SUB-PIPELINE
 // ============================================================================
 
-/// Select the correct workflow file based on how many renderable characters
-/// are in the scene.
+/// Select a custom workflow template for the number of renderable characters
+/// in the scene, if one has been placed in the app data "workflows" folder.
 ///
 /// - 1 character  → scene_workflow_1char.json (no masks, simpler/faster)
-/// - 2+ characters → scene_workflow_2char.json (with per-character masks)
+/// - 2+ characters → scene_workflow_2char.json (with per-character masks),
+///   fitted by the ComfyUI pipeline to one branch per character
 ///
-/// Both files must be placed in the app data "workflows" folder.
-fn select_workflow(num_chars: usize, app_data: &std::path::Path) -> Result<String, String> {
+/// `None` means the built-in scene graph is used.
+fn select_workflow(num_chars: usize, app_data: &std::path::Path) -> Option<String> {
     let filename = match num_chars {
         1 => WORKFLOW_1CHAR,
         _ => WORKFLOW_2CHAR,
     };
 
     let path = app_data.join(filename);
-
     if path.exists() {
         println!(
-            "[Orchestrator] Selected {} workflow for {} character(s): {}",
+            "[Orchestrator] Using custom {} workflow for {} character(s): {}",
             if num_chars == 1 { "single-character" } else { "multi-character" },
             num_chars,
             path.display()
         );
-        Ok(path.to_string_lossy().to_string())
+        Some(path.to_string_lossy().to_string())
     } else {
-        Err(format!(
-            "Workflow file not found: {}. \
-             Please copy your workflow JSON files to: {}\\workflows\\",
-            path.display(),
-            app_data.display()
-        ))
+        println!("[Orchestrator] Using built-in scene workflow for {} character(s)", num_chars);
+        None
     }
 }
 
-/// Run the image generation sub-pipeline:
-///   1. Filter to renderable characters with reference images
-///   2. Select the right workflow (1-char vs 2-char)
-///   3. Generate a color mask (skipped for single character)
-///   4. Build the ComfyUI request
-///   5. Call generate_scene_image
-///
-/// Returns (image_path, None) on success, (None, Some(error)) on failure.
-async fn generate_image_for_scene(
-    parsed: &ParsedTurn,
-    characters_in_scene: &[CharacterInScene],
-    lookup_results: &[(llm_parser::SceneCharacterRaw, Option<CharacterLookup>)],
-    app: &AppHandle,
-) -> (Option<String>, Option<String>) {
-    // 1. Filter to renderable characters that have reference images
-    let renderable: Vec<_> = characters_in_scene
-        .iter()
-        .zip(lookup_results.iter())
-        .filter(|(cis, _)| cis.needs_render && cis.has_reference_image)
-        .collect();
+/// Regions for characters placed by order rather than by the LLM: the three
+/// foreground columns first, then the background and seated spots.
+const SCENE_REGION_ORDER: [&str; MAX_SCENE_CHARACTERS] = [
+    "left",
+    "center",
+    "right",
+    "left-background",
+    "right-background",
+    "center-background",
+    "left-seated",
+    "right-seated",
+    "center-seated",
+];
+
+/// The configured scene character limit, within what the workflows support.
+fn scene_character_limit(max_scene_characters: usize) -> usize {
+    max_scene_characters.clamp(1, MAX_SCENE_CHARACTERS)
+}
 
-    if renderable.is_empty() {
-        println!(
-            "[Orchestrator] No renderable characters with reference images — skipping image gen"
-        );
-        return (
-            None,
-            Some("No characters with reference images available for rendering".to_string()),
-        );
+/// Regions for `num_chars` characters. One character takes the center, two
+/// split the frame left and right.
+fn scene_regions(num_chars: usize) -> Vec<String> {
+    match num_chars {
+        1 => vec!["center".to_string()],
+        2 => vec!["left".to_string(), "right".to_string()],
+        n => SCENE_REGION_ORDER.iter().take(n).map(|r| r.to_string()).collect(),
     }
+}
 
-    let app_data = match app.path().app_data_dir() {
-        Ok(p) => p,
-        Err(e) => {
-            return (None, Some(format!("Failed to get app data dir: {}", e)));
-        }
-    };
-
-    let num_chars = renderable.len();
-
-    // 2. Select workflow based on character count
-    let workflow_template = match select_workflow(num_chars, &app_data) {
-        Ok(p) => p,
-        Err(e) => return (None, Some(e)),
-    };
-
-    // 3. Generate per-character masks (only needed for multi-character scenes)
-    let mask_paths: Vec<String> = if num_chars > 1 {
-        let masks_dir = app_data.join("masks");
-        let mut paths = Vec::new();
-
-        for (i, (cis, _)) in renderable.iter().enumerate().take(2) {
-            let mask_chars = vec![MaskCharacter {
-                name: cis.name.clone(),
-                region: cis.region.clone(),
-                color_index: 0, // Always 0 — each mask has one char, red channel
-            }];
-
-            let filename = format!("scene_mask_char{}.png", i);
-            match mask_generator::generate_mask(
-                &mask_chars,
-                DEFAULT_IMAGE_WIDTH,
-                DEFAULT_IMAGE_HEIGHT,
-                &masks_dir,
-                Some(&filename),
-            ) {
-                Ok(r) => {
-                    println!("[Orchestrator] Mask for '{}': {} (region={})", cis.name, r.path, cis.region);
-                    paths.push(r.path);
-                }
-                Err(e) => {
-                    return (None, Some(format!("Mask gen failed for '{}': {}", cis.name, e)));
-                }
-            }
-        }
-        paths
-    } else {
-        vec![] // 1-char doesn't need masks
-    };
-
-    // 4. Build ComfyUI character inputs
-    let comfy_characters: Vec<CharacterInput> = renderable
-        .iter()
-        .map(|(cis, (_, db_char))| {
-            let ref_path = db_char
-                .as_ref()
-                .and_then(|c| c.master_image_path.clone())
-                .unwrap_or_default();
-
-            CharacterInput {
-                name: cis.name.clone(),
-                reference_image_path: ref_path,
-                region: cis.region.clone(),
-                prompt: character_prompt_fragment(cis, db_char.as_ref()),
-            }
-        })
-        .collect();
-
-    // 5. Build the scene prompt, including character descriptions so the
-    //    model actually generates people in the image.
-    //    IPAdapter applies the face, but the base prompt must mention a person
-    //    or the model will only generate the environment.
-    let mut scene_prompt = parsed.scene_prompt_fragment();
-
-    // Append ipadapter character descriptions
-    for (cis, (_, db_char)) in &renderable {
-        let fragment = character_prompt_fragment(cis, db_char.as_ref());
-        if !fragment.is_empty() {
-            let region_prefix = match cis.region.to_lowercase().as_str() {
-                "left" => "person on the left, ",
-                "right" => "person on the right, ",
-                _ => "person, ",
-            };
-            scene_prompt = format!("{}, {}{}", scene_prompt, region_prefix, fragment);
-        } else {
-            // No fragment data — at minimum add "a person" so IPAdapter has something to work with
-            scene_prompt = format!("{}, a person", scene_prompt);
-        }
+/// Danbooru-style subject count tag from character genders ("1girl",
+/// "2girls 1boy", "1boy 2people"). These help SDXL place exactly the right
+/// number of people.
+fn subject_count_tag(genders: &[&str]) -> String {
+    let girls = genders.iter().filter(|g| **g == "female").count();
+    let boys = genders.iter().filter(|g| **g == "male").count();
+    let others = genders.len() - girls - boys;
+
+    let mut tags = Vec::new();
+    match girls {
+        0 => {}
+        1 => tags.push("1girl".to_string()),
+        n => tags.push(format!("{}girls", n)),
     }
-
-    // Append prompt-only character descriptions (no reference image)
-    for cis in characters_in_scene.iter().filter(|c| c.needs_render && !c.has_reference_image) {
-        if let Some(ref desc) = cis.prompt_only_description {
-            let region_prefix = match cis.region.to_lowercase().as_str() {
-                "left" => "on the left, ",
-                "right" => "on the right, ",
-                _ => "",
-            };
-            scene_prompt = format!("{}, {}{}", scene_prompt, region_prefix, desc);
-        }
+    match boys {
+        0 => {}
+        1 => tags.push("1boy".to_string()),
+        n => tags.push(format!("{}boys", n)),
     }
+    match others {
+        0 => {}
+        1 => tags.push("1person".to_string()),
+        n => tags.push(format!("{}people", n)),
+    }
+    tags.join(" ")
+}
 
-    println!("[Orchestrator] Final scene prompt: {}...", &scene_prompt[..scene_prompt.len().min(150)]);
-
-    // Prepend face quality emphasis tags — these dramatically help SDXL
-    // render clean eyes and facial features, matching A1111 defaults.
-    let scene_prompt = format!(
-        "(masterpiece, best quality:1.2), (detailed face:1.3), (detailed eyes:1.3), (sharp focus:1.1), {}",
-        scene_prompt
-    );
-
-    // 6. Build the full image generation request
-    let request = ImageGenRequest {
-        scene_prompt,
-        characters: comfy_characters,
-        mask_paths,
-        workflow_template,
-        comfyui_url: None,
-        seed: Some(rand::random::<i64>().abs()),
-        steps: Some(30),
-        cfg: Some(5.5),
-        width: Some(DEFAULT_IMAGE_WIDTH),
-        height: Some(DEFAULT_IMAGE_HEIGHT),
-        negative_prompt: Some(
-            "(worst quality, low quality:1.4), (bad anatomy:1.3), (bad hands:1.4), \
-             close-up, closeup, head shot, headshot, cropped, zoomed in, \
-             cowboy hat, cowboy, western clothing".to_string()
-        ),
-        timeout_secs: None,
-        controlnet_image_path: None,
-        controlnet_strength: None,
+/// Prompt prefix placing a character in its region
+/// ("a person seated on the left side of the scene,").
+fn region_prompt_prefix(region: &str) -> String {
+    let region = llm_parser::CharacterRegion::from_str_loose(region);
+    let side = match region {
+        llm_parser::CharacterRegion::Left
+        | llm_parser::CharacterRegion::LeftSeated
+        | llm_parser::CharacterRegion::LeftBackground => "on the left side of the scene",
+        llm_parser::CharacterRegion::Right
+        | llm_parser::CharacterRegion::RightSeated
+        | llm_parser::CharacterRegion::RightBackground => "on the right side of the scene",
+        _ => "in the center of the scene",
     };
+    if region.is_seated() {
+        format!("a person seated {},", side)
+    } else if region.is_background() {
+        format!("a person in the background {},", side)
+    } else {
+        format!("a person {},", side)
+    }
+}
 
-    // 7. Free VRAM: unload Ollama before ComfyUI needs the GPU
-    unload_ollama_model("http://localhost:11434").await;
+/// Write one single-character mask per character for the multi-character
+/// workflow, named `{file_prefix}{i}.png`. Returns the mask paths in order.
+fn generate_scene_masks(
+    names: &[String],
+    regions: &[String],
+    masks_dir: &std::path::Path,
+    file_prefix: &str,
+) -> Result<Vec<String>, String> {
+    let mut paths = Vec::new();
+    for (i, (name, region)) in names.iter().zip(regions).enumerate() {
+        let mask_chars = vec![MaskCharacter {
+            name: name.clone(),
+            region: region.clone(),
+            color_index: 0, // MUST be 0 (red) — ImageToMask reads red channel
+        }];
 
-    // 8. Run the ComfyUI pipeline
-    let output_dir = app_data.join("scene_images");
-    match comfyui_api::generate_scene_image(&request, &output_dir).await {
-        Ok(result) => {
-            let primary_image = result.image_paths.first().cloned();
-            println!(
-                "[Orchestrator] Image generated: {:?} ({} images total)",
-                primary_image,
-                result.image_paths.len()
-            );
-            unload_comfyui_models("http://localhost:8188").await;
-            println!("[VRAM] Image generation complete — both models unloaded, VRAM clean");
-            (primary_image, None)
-        }
-        Err(e) => {
-            println!("[Orchestrator] Image generation failed: {}", e);
-            (None, Some(e.to_string()))
-        }
+        let filename = format!("{}{}.png", file_prefix, i);
+        let mask = mask_generator::generate_mask(
+            &mask_chars,
+            DEFAULT_IMAGE_WIDTH,
+            DEFAULT_IMAGE_HEIGHT,
+            masks_dir,
+            Some(&filename),
+        )
+        .map_err(|e| format!("Mask gen failed for '{}': {}", name, e))?;
+        println!("[Orchestrator] Mask for '{}': {} (region={})", name, mask.path, region);
+        paths.push(mask.path);
     }
+    Ok(paths)
 }
 
 // ============================================================================
@@ -894,42 +879,80 @@ async fn generate_image_for_scene(
 // ============================================================================
 
 /// Save the user message and assistant response to the messages table.
-/// Also saves the generated image path to the images table if present.
+/// Also saves the generated image path to the images table if present, and
+/// the parsed `turn` to the turns table. `turn.scene_id` tags the assistant
+/// message with the scene it happened in (used for scene boundaries in story
+/// memory).
+///
+/// `reply_to` is set when regenerating: the user message already exists and
+/// the response is stored as a new selected candidate for it, together with
+/// `user_input`, which becomes the user message's text.
+///
+/// The assistant row also holds the turn's state snapshot: the active scene
+/// (`scene_id`), the `roster_additions` made to it and the emotional states.
+#[allow(clippy::too_many_arguments)]
 async fn save_turn_to_db(
     db: &sqlx::SqlitePool,
     chat_id: i64,
     user_input: &str,
     raw_assistant_response: &str,
     image_path: Option<&str>,
+    roster_additions: &[i64],
+    reply_to: Option<i64>,
+    turn: &TurnRecord,
 ) -> Result<i64, String> {
+    let emotion_snapshot = snapshot_emotional_states(db, chat_id).await;
+    let roster_additions = serde_json::to_string(roster_additions).map_err(|e| e.to_string())?;
+
     let mut tx = db
         .begin()
         .await
         .map_err(|e| format!("Transaction start failed: {}", e))?;
 
-    // Update chat title if still "New Chat"
-    let title_preview: String = user_input.chars().take(40).collect();
-    sqlx::query("UPDATE chats SET title = ? WHERE id = ? AND title = 'New Chat'")
-        .bind(&title_preview)
-        .bind(chat_id)
-        .execute(&mut *tx)
-        .await
-        .ok();
+    let user_msg_id = match reply_to {
+        Some(id) => {
+            sqlx::query("UPDATE messages SET content = ? WHERE id = ?")
+                .bind(user_input)
+                .bind(id)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| format!("Failed to update user message: {}", e))?;
+            id
+        }
+        None => {
+            // Update chat title if still "New Chat"
+            let title_preview: String = user_input.chars().take(40).collect();
+            sqlx::query("UPDATE chats SET title = ? WHERE id = ? AND title = 'New Chat'")
+                .bind(&title_preview)
+                .bind(chat_id)
+                .execute(&mut *tx)
+                .await
+                .ok();
 
-    // Save user message
-    sqlx::query("INSERT INTO messages (chat_id, role, content) VALUES (?, 'user', ?)")
-        .bind(chat_id)
-        .bind(user_input)
-        .execute(&mut *tx)
-        .await
-        .map_err(|e| format!("Failed to save user message: {}", e))?;
+            // Save user message
+            sqlx::query("INSERT INTO messages (chat_id, role, content) VALUES (?, 'user', ?)")
+                .bind(chat_id)
+                .bind(user_input)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| format!("Failed to save user message: {}", e))?
+                .last_insert_rowid()
+        }
+    };
 
     // Save assistant message
     let result = sqlx::query(
-        "INSERT INTO messages (chat_id, role, content) VALUES (?, 'assistant', ?)",
+        "INSERT INTO messages \
+         (chat_id, role, content, scene_id, reply_to, emotion_snapshot, roster_additions, user_input) \
+         VALUES (?, 'assistant', ?, ?, ?, ?, ?, ?)",
     )
     .bind(chat_id)
     .bind(raw_assistant_response)
+    .bind(turn.scene_id)
+    .bind(user_msg_id)
+    .bind(emotion_snapshot)
+    .bind(&roster_additions)
+    .bind(reply_to.map(|_| user_input))
     .execute(&mut *tx)
     .await
     .map_err(|e| format!("Failed to save assistant message: {}", e))?;
@@ -937,8 +960,9 @@ async fn save_turn_to_db(
     let assistant_msg_id = result.last_insert_rowid();
 
     // Save image reference if an image was generated
+    let mut image_id = None;
     if let Some(path) = image_path {
-        sqlx::query(
+        let result = sqlx::query(
             "INSERT INTO images (message_id, chat_id, file_path) VALUES (?, ?, ?)",
         )
         .bind(assistant_msg_id)
@@ -947,8 +971,18 @@ async fn save_turn_to_db(
         .execute(&mut *tx)
         .await
         .map_err(|e| format!("Failed to save image record: {}", e))?;
+        image_id = Some(result.last_insert_rowid());
     }
 
+    let turn = TurnRecord {
+        chat_id,
+        user_message_id: Some(user_msg_id),
+        assistant_message_id: assistant_msg_id,
+        image_id,
+        ..turn.clone()
+    };
+    insert_turn(&mut *tx, &turn).await?;
+
     tx.commit()
         .await
         .map_err(|e| format!("Transaction commit failed: {}", e))?;
@@ -958,11 +992,16 @@ async fn save_turn_to_db(
 
 /// Persist the emotional states returned by the LLM into the DB so they
 /// survive app restarts and can be injected back into context on resume.
-async fn persist_emotional_states(
-    db: &sqlx::SqlitePool,
+async fn persist_emotional_states<'c, A>(
+    db: A,
     chat_id: i64,
     states: &[crate::text_gen::parser::CharacterEmotionalState],
-) {
+) where
+    A: sqlx::Acquire<'c, Database = sqlx::Sqlite>,
+{
+    let Ok(mut conn) = db.acquire().await else {
+        return;
+    };
     for s in states {
         if s.name.is_empty() {
             continue;
@@ -986,11 +1025,55 @@ async fn persist_emotional_states(
         .bind(&s.emotion_intensity)
         .bind(&s.emotion_cause)
         .bind(&lingering_json)
-        .execute(db)
+        .execute(&mut *conn)
         .await;
     }
 }
 
+/// All persisted emotional states of a chat as JSON, stored on each assistant
+/// message so switching candidates can restore them.
+async fn snapshot_emotional_states(db: &sqlx::SqlitePool, chat_id: i64) -> Option<String> {
+    let rows = sqlx::query(
+        "SELECT character_name, current_emotion, emotion_intensity, emotion_cause, lingering_emotions \
+         FROM character_emotional_states WHERE chat_id = ? ORDER BY character_name",
+    )
+    .bind(chat_id)
+    .fetch_all(db)
+    .await
+    .ok()?;
+
+    let states: Vec<CharacterEmotionalState> = rows
+        .iter()
+        .map(|r| CharacterEmotionalState {
+            name: r.get("character_name"),
+            current_emotion: r.get("current_emotion"),
+            emotion_intensity: r.get("emotion_intensity"),
+            emotion_cause: r.get("emotion_cause"),
+            lingering_emotions: serde_json::from_str(&r.get::<String, _>("lingering_emotions"))
+                .unwrap_or_default(),
+        })
+        .collect();
+    serde_json::to_string(&states).ok()
+}
+
+/// Replace a chat's persisted emotional states with a snapshot taken by
+/// `snapshot_emotional_states`.
+pub(crate) async fn restore_emotional_states<'c, A>(db: A, chat_id: i64, snapshot: &str) -> Result<(), String>
+where
+    A: sqlx::Acquire<'c, Database = sqlx::Sqlite>,
+{
+    let states: Vec<CharacterEmotionalState> = serde_json::from_str(snapshot)
+        .map_err(|e| format!("Invalid emotion snapshot: {}", e))?;
+    let mut conn = db.acquire().await.map_err(|e| format!("Failed to open connection: {}", e))?;
+    sqlx::query("DELETE FROM character_emotional_states WHERE chat_id = ?")
+        .bind(chat_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| format!("Failed to clear emotional states: {}", e))?;
+    persist_emotional_states(&mut *conn, chat_id, &states).await;
+    Ok(())
+}
+
 // ============================================================================
 // THE MAIN ORCHESTRATOR COMMAND
 // ============================================================================
@@ -1014,9 +1097,31 @@ pub async fn process_story_turn(
     state: State<'_, OllamaState>,
     config_state: State<'_, ConfigState>,
     hint_state: State<'_, SceneHintState>,
+    cancel_registry: State<'_, CancelRegistry>,
     app: AppHandle,
-) -> Result<StoryTurnResult, String> {
+) -> Result<StoryTurnResult, AppError> {
+    run_story_turn(chat_id, user_input, story_id, None, state, config_state, hint_state, cancel_registry, app).await
+}
+
+/// The turn pipeline behind `process_story_turn` and the regenerate commands.
+/// `regeneration` is set when adding a candidate to the last turn.
+#[allow(clippy::too_many_arguments)]
+async fn run_story_turn(
+    chat_id: i64,
+    user_input: String,
+    story_id: Option<i64>,
+    regeneration: Option<RegenerationBase>,
+    state: State<'_, OllamaState>,
+    config_state: State<'_, ConfigState>,
+    hint_state: State<'_, SceneHintState>,
+    cancel_registry: State<'_, CancelRegistry>,
+    app: AppHandle,
+) -> Result<StoryTurnResult, AppError> {
     let start_time = std::time::Instant::now();
+    // Registered for the whole turn so `cancel_generation` can abort it.
+    // Nothing is written to `messages` until the LLM response is in hand.
+    let job = cancel_registry.register(chat_job_key(chat_id));
+    let reply_to = regeneration.as_ref().map(|base| base.user_id);
     println!(
         "\n[Orchestrator] ========== TURN START (chat={}, story={:?}) ==========",
         chat_id, story_id
@@ -1047,13 +1152,32 @@ pub async fn process_story_turn(
     let context_start = std::time::Instant::now();
 
     // Read config settings and build the effective system prompt
-    let (content_rating, response_length, keep_alive_setting) = {
+    let (content_rating, response_length, keep_alive_setting, stream_responses, structured_output, backend, app_settings, embedder, recall_top_k, lore_token_budget, trace_retention, max_scene_characters) = {
         let config = config_state.0.lock().map_err(|e| e.to_string())?;
-        (config.content_rating.clone(), config.response_length.clone(), config.keep_alive.clone())
+        (
+            config.content_rating.clone(),
+            config.response_length.clone(),
+            config.keep_alive.clone(),
+            config.stream_responses,
+            config.structured_output,
+            backend_from_config(&config, &state.client),
+            GenerationSettings::from_config(&config),
+            embedder_from_config(&config, &state.client),
+            config.recall_top_k,
+            config.lore_token_budget,
+            config.trace_retention,
+            config.max_scene_characters,
+        )
     };
 
+    // Per-story model / sampler overrides win over the app settings
+    let overrides = load_generation_overrides(&state.db, story_id).await?;
+    let settings = app_settings.with_overrides(&overrides);
+    let num_ctx = settings.effective_num_ctx();
+
     let length_config = get_response_length_config(&response_length);
-    let max_prompt_tokens = NUM_CTX as usize - length_config.num_predict as usize;
+    let num_predict = settings.effective_num_predict(&length_config);
+    let max_prompt_tokens = settings.max_prompt_tokens(&length_config);
 
     let content_instruction = if content_rating == "sfw" {
         "\n\nCONTENT POLICY: This is a SFW (Safe For Work) story. Keep all content \
@@ -1069,20 +1193,29 @@ pub async fn process_story_turn(
         content_instruction
     );
 
-    let mut message_rows = load_conversation_history(&state.db, chat_id).await?;
-    let story_premise = load_story_premise(&state.db).await?;
+    let mut conversation = load_conversation_history(&state.db, chat_id, reply_to)
+        .await?
+        .with_context_size(num_ctx as usize);
+    let story_premise = load_story_premise(&state.db, story_id, chat_id).await?;
+    let lorebook = load_lorebook(&state.db, story_id, lore_token_budget)
+        .await
+        .unwrap_or_else(|e| {
+            println!("[WARN] {} — continuing without lore", e);
+            Lorebook::default()
+        });
 
     // Load current active scene (if any) to filter characters and build scene context
-    let prior_active_scene_id: Option<i64> = if let Some(sid) = story_id {
-        sqlx::query("SELECT active_scene_id FROM story_premises WHERE id = ?")
+    // (a regenerated turn starts from the scene before it)
+    let prior_active_scene_id: Option<i64> = match (story_id, &regeneration) {
+        (Some(_), Some(base)) => base.scene_id,
+        (Some(sid), None) => sqlx::query("SELECT active_scene_id FROM story_premises WHERE id = ?")
             .bind(sid)
             .fetch_optional(&state.db)
             .await
             .ok()
             .flatten()
-            .and_then(|row| row.get("active_scene_id"))
-    } else {
-        None
+            .and_then(|row| row.get("active_scene_id")),
+        (None, _) => None,
     };
 
     // All story characters (full roster, always loaded)
@@ -1090,7 +1223,8 @@ pub async fn process_story_turn(
 
     // Characters in the active scene (for focused LLM context); falls back to all chars
     let scene_characters = if let Some(scene_id) = prior_active_scene_id {
-        let sc = load_scene_characters_for_context(&state.db, scene_id).await?;
+        let exclude = regeneration.as_ref().map_or(&[][..], |base| base.replaced_additions(scene_id));
+        let sc = load_scene_characters_for_context(&state.db, scene_id, exclude).await?;
         if sc.is_empty() { all_characters.clone() } else { sc }
     } else {
         all_characters.clone()
@@ -1129,151 +1263,139 @@ pub async fn process_story_turn(
     let system_prompt_tokens = estimate_tokens(&full_system_prompt);
     let char_token_estimate_all = estimate_character_db_tokens(&all_characters);
 
+    // ── Story Memory Pre-pass ─────────────────────────────────────────────────
+    // Once the context overflows, summarize closed scenes and roll them up into
+    // chapters and the arc (see memory.rs). Turns covered by memory drop out of
+    // the conversation; the rolling summary below handles what remains.
+    if conversation.needs_compression(system_prompt_tokens, char_token_estimate_all) {
+        let _ = app.emit("compression-started", ());
+        match update_story_memory(&state.db, backend.as_ref(), &settings.model, &job, chat_id).await {
+            Ok(0) => {}
+            Ok(updates) => {
+                println!("[Orchestrator] Story memory updated ({} summaries)", updates);
+                match load_conversation_history(&state.db, chat_id, reply_to).await {
+                    Ok(reloaded) => conversation = reloaded.with_context_size(num_ctx as usize),
+                    Err(e) => println!("[WARN] Failed to reload history after memory update: {}", e),
+                }
+            }
+            Err(e) => {
+                job.check()?;
+                println!("[WARN] Story memory update failed: {} — using rolling summary", e);
+            }
+        }
+        let _ = app.emit("compression-done", ());
+    }
+
     // ── LLM Compression Pre-pass ──────────────────────────────────────────────
     // Before building the context, check if compression is needed. If so, call
     // Ollama for a high-quality LLM summary rather than the fast hint-concat path.
-    {
-        let temp_ctx = ConversationContext::from_message_pairs(&message_rows);
-        if temp_ctx.needs_compression(system_prompt_tokens, char_token_estimate_all) {
-            if let Some(summary_prompt) = temp_ctx.build_llm_summary_prompt() {
-                let _ = app.emit("compression-started", ());
-                println!("[Orchestrator] LLM compression triggered — requesting high-quality summary");
-
-                let summary_body = json!({
-                    "model": "Story_v27",
-                    "prompt": summary_prompt,
-                    "stream": false,
-                    "think": false,
-                    "keep_alive": crate::text_gen::prompts::KEEP_ALIVE_GENERATING,
-                });
-
-                let llm_result = state.client
-                    .post(format!("{}/api/generate", state.base_url))
-                    .timeout(Duration::from_secs(60))
-                    .json(&summary_body)
-                    .send()
-                    .await;
-
-                match llm_result {
-                    Ok(res) => match res.json::<Value>().await {
-                        Ok(json_val) => {
-                            let summary_text = json_val["response"]
-                                .as_str()
-                                .unwrap_or("")
-                                .trim()
-                                .to_string();
-                            if !summary_text.is_empty() {
-                                // Persist: delete old messages, insert compressed summary
-                                let recent_limit = (RECENT_TURNS_TO_KEEP * 2) as i64;
-                                let summary_content =
-                                    format!("[COMPRESSED SUMMARY]\n{}", summary_text);
-                                let persist_result: Result<(), String> = async {
-                                    let mut tx = state.db.begin().await
-                                        .map_err(|e| format!("Begin tx: {}", e))?;
-
-                                    // Remove any previous compressed summary messages
-                                    sqlx::query(
-                                        "DELETE FROM messages \
-                                         WHERE chat_id = ? AND role = 'system' \
-                                         AND content LIKE '[COMPRESSED SUMMARY]%'",
-                                    )
-                                    .bind(chat_id)
-                                    .execute(&mut *tx)
-                                    .await
-                                    .map_err(|e| format!("Delete old summaries: {}", e))?;
-
-                                    // Delete user/assistant rows older than the recent N*2
-                                    sqlx::query(
-                                        "DELETE FROM messages \
-                                         WHERE chat_id = ? \
-                                           AND (role = 'user' OR role = 'assistant') \
-                                           AND id NOT IN ( \
-                                               SELECT id FROM ( \
-                                                   SELECT id FROM messages \
-                                                   WHERE chat_id = ? \
-                                                     AND (role = 'user' OR role = 'assistant') \
-                                                   ORDER BY id DESC LIMIT ? \
-                                               ) \
-                                           )",
-                                    )
-                                    .bind(chat_id)
-                                    .bind(chat_id)
-                                    .bind(recent_limit)
-                                    .execute(&mut *tx)
-                                    .await
-                                    .map_err(|e| format!("Delete old messages: {}", e))?;
-
-                                    // Insert compressed summary as a system row at timestamp 0
-                                    // (sorts first in timestamp-ASC queries)
-                                    sqlx::query(
-                                        "INSERT INTO messages \
-                                         (chat_id, role, content, timestamp) \
-                                         VALUES (?, 'system', ?, 0)",
-                                    )
-                                    .bind(chat_id)
-                                    .bind(&summary_content)
-                                    .execute(&mut *tx)
-                                    .await
-                                    .map_err(|e| format!("Insert summary: {}", e))?;
-
-                                    tx.commit().await
-                                        .map_err(|e| format!("Commit tx: {}", e))?;
-                                    Ok(())
-                                }
-                                .await;
-
-                                match persist_result {
-                                    Ok(()) => {
-                                        println!(
-                                            "[Orchestrator] LLM compression persisted to DB \
-                                             ({} chars)",
-                                            summary_text.len()
-                                        );
-                                        // Reload so build_compressed_context sees trimmed history
-                                        match load_conversation_history(&state.db, chat_id).await {
-                                            Ok(reloaded) => message_rows = reloaded,
-                                            Err(e) => println!(
-                                                "[WARN] Failed to reload messages after compression: {}",
-                                                e
-                                            ),
-                                        }
-                                    }
-                                    Err(e) => {
-                                        println!(
-                                            "[WARN] LLM compression DB persist failed: {} \
-                                             — falling through to hint-concat path",
-                                            e
-                                        );
-                                    }
-                                }
-                            } else {
-                                println!(
-                                    "[WARN] LLM compression returned empty summary \
-                                     — falling through to hint-concat path"
-                                );
+    // The summary is stored in `context_summaries`; no messages are deleted.
+    if conversation.needs_compression(system_prompt_tokens, char_token_estimate_all) {
+        if let Some(summary_prompt) = conversation.build_llm_summary_prompt() {
+            let _ = app.emit("compression-started", ());
+            println!("[Orchestrator] LLM compression triggered — requesting high-quality summary");
+
+            let summary_request = GenerateRequest {
+                keep_alive: Some(crate::text_gen::prompts::KEEP_ALIVE_GENERATING.to_string()),
+                ..GenerateRequest::new(
+                    &settings.model,
+                    PromptInput::Instruction(summary_prompt),
+                    60,
+                )
+            };
+
+            let llm_result = job.run(backend.generate(&summary_request, None)).await;
+
+            match llm_result {
+                Ok(res) => {
+                    let summary_text = res.text.trim().to_string();
+                    if !summary_text.is_empty() {
+                        // Assistant message of the last turn the summary replaces
+                        let split_point = conversation.turns.len() - RECENT_TURNS_TO_KEEP;
+                        let covered_until = conversation.turns[split_point - 1].message_id;
+
+                        conversation.apply_llm_summary(&summary_text);
+
+                        let persist_result = match covered_until {
+                            Some(message_id) => {
+                                save_summary(&state.db, chat_id, &conversation.compressed, message_id).await
                             }
+                            None => Err("compressed turns have no message id".to_string()),
+                        };
+                        match persist_result {
+                            Ok(summary_id) => println!(
+                                "[Orchestrator] LLM compression saved as summary {} \
+                                 ({} turns, {} chars)",
+                                summary_id,
+                                conversation.compressed.compressed_turn_ids.len(),
+                                summary_text.len()
+                            ),
+                            Err(e) => println!(
+                                "[WARN] LLM compression summary not persisted: {} \
+                                 — using it for this turn only",
+                                e
+                            ),
                         }
-                        Err(e) => println!(
-                            "[WARN] LLM compression parse error: {} \
-                             — falling through to hint-concat path",
-                            e
-                        ),
-                    },
-                    Err(e) => println!(
-                        "[WARN] LLM compression request failed: {} \
-                         — falling through to hint-concat path",
-                        e
-                    ),
+                    } else {
+                        println!(
+                            "[WARN] LLM compression returned empty summary \
+                             — falling through to hint-concat path"
+                        );
+                    }
                 }
-
-                let _ = app.emit("compression-done", ());
+                Err(e) => println!(
+                    "[WARN] LLM compression request failed: {} \
+                     — falling through to hint-concat path",
+                    e
+                ),
             }
+
+            let _ = app.emit("compression-done", ());
         }
     }
 
-    let persisted_emotions = load_persisted_emotional_states(&state.db, chat_id).await;
+    job.check()?;
+
+    // ── Long-term recall ──────────────────────────────────────────────────────
+    // Index any unembedded turns, then recall the past turns closest to this
+    // input that aren't already in the prompt. Best-effort (see recall.rs).
+    let recalled = match &embedder {
+        Some(embedder) => {
+            let recall: Result<Vec<RecalledTurn>, String> = job
+                .run(async {
+                    index_chat_turns(&state.db, embedder.as_ref(), chat_id, MAX_INDEX_PER_TURN).await?;
+                    let before = conversation.turns.first().and_then(|t| t.message_id);
+                    recall_turns(&state.db, embedder.as_ref(), chat_id, &user_input, recall_top_k, before).await
+                })
+                .await;
+            match recall {
+                Ok(turns) => {
+                    if !turns.is_empty() {
+                        println!(
+                            "[Recall] Recalled turns {:?} for this input",
+                            turns.iter().map(|t| t.turn_number).collect::<Vec<_>>()
+                        );
+                    }
+                    turns
+                }
+                Err(e) => {
+                    job.check()?;
+                    println!("[WARN] Long-term recall skipped: {}", e);
+                    Vec::new()
+                }
+            }
+        }
+        None => Vec::new(),
+    };
+
+    let persisted_emotions = match regeneration.as_ref().and_then(|base| base.emotion_snapshot.as_deref()) {
+        Some(snapshot) => format_emotion_snapshot(snapshot),
+        None => load_persisted_emotional_states(&state.db, chat_id).await,
+    };
 
-    let mut conversation = ConversationContext::from_message_pairs(&message_rows);
+    // Turn delimiters for the raw prompt must match the model's training format
+    let (template_format, prompt_template) = settings.prompt_template();
+    println!("[Orchestrator] Prompt template: {} (model {})", template_format.as_str(), settings.model);
 
     let assembled = build_compressed_context(
         &mut conversation,
@@ -1285,8 +1407,23 @@ pub async fn process_story_turn(
         &effective_user_input,
         max_prompt_tokens,
         if persisted_emotions.is_empty() { None } else { Some(&persisted_emotions) },
+        &recalled,
+        &lorebook,
+        &prompt_template,
     );
 
+    if !assembled.lore_entries.is_empty() {
+        println!(
+            "[Lorebook] Fired: {}",
+            assembled
+                .lore_entries
+                .iter()
+                .map(|l| if l.injected { l.name.clone() } else { format!("{} (over budget)", l.name) })
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+
     let char_token_estimate = estimate_character_db_tokens(&scene_characters);
     let diag = get_diagnostics(&conversation, system_prompt_tokens, char_token_estimate);
 
@@ -1299,89 +1436,169 @@ pub async fn process_story_turn(
         compression_threshold: diag.compression_threshold,
         needs_compression: diag.needs_compression,
         compressed_summary_preview: diag.compressed_summary_preview.clone(),
+        memory_entries: diag.memory_entries,
+        lore_entries: assembled.lore_entries.clone(),
     };
 
+    let context_elapsed = context_start.elapsed();
     println!(
         "[Orchestrator] Context built in {:.1}s ({} turns, ~{} tokens, {} chars in DB, compressed={})",
-        context_start.elapsed().as_secs_f64(),
+        context_elapsed.as_secs_f64(),
         diag.total_turns,
         diag.estimated_total_tokens,
         scene_characters.len(),
         assembled.was_compressed,
     );
 
-    // ── Step 2: Call Ollama ───────────────────────────────────────────
+    // ── Step 2: Call the LLM backend ──────────────────────────────────
 
     let ollama_start = std::time::Instant::now();
 
     println!(
-        "[DEBUG] Ollama request — turns in context: {} ({} recent + {} compressed), prompt tokens: ~{}, budget: {}, num_ctx: {}, num_predict: {} (length={})",
+        "[DEBUG] {} request ({}) — turns in context: {} ({} recent + {} compressed), prompt tokens: ~{}, budget: {}, num_ctx: {}, num_predict: {} (length={})",
+        backend.name(),
+        settings.model,
         diag.total_turns,
         diag.recent_turns,
         diag.compressed_turns,
         estimate_tokens(&assembled.prompt),
         max_prompt_tokens,
-        NUM_CTX,
-        length_config.num_predict,
+        num_ctx,
+        num_predict,
         response_length,
     );
 
-    let request_body = json!({
-        "model": STORY_MODEL,
-        "prompt": assembled.prompt,
-        "raw": true,
-        "stream": false,
-        "think": false,
-        "keep_alive": &keep_alive_setting,
-        "options": {
-            "num_ctx": NUM_CTX,
-            "num_predict": length_config.num_predict,
-            "temperature": 0.8
-        }
-    });
+    // Raw-prompt backends get the KV-cache-ordered prompt in the model's template; chat-only
+    // backends get the same context as a message list.
+    let raw_prompt = backend.capabilities().raw_prompt;
+    let prompt_input = if raw_prompt {
+        PromptInput::Raw(assembled.prompt.clone())
+    } else {
+        let (messages, _) = build_compressed_chat_messages(
+            &mut conversation,
+            &full_system_prompt,
+            &scene_characters,
+            &all_characters,
+            story_premise.as_deref(),
+            scene_context.as_deref(),
+            &effective_user_input,
+            if persisted_emotions.is_empty() { None } else { Some(persisted_emotions.as_str()) },
+            &recalled,
+            &lorebook,
+        );
+        PromptInput::Chat(
+            messages
+                .into_iter()
+                .filter_map(|m| serde_json::from_value(m).ok())
+                .collect(),
+        )
+    };
+
+    // Everything needed to debug this turn later (see traces.rs)
+    let (prompt_format, traced_prompt) = match &prompt_input {
+        PromptInput::Chat(messages) => ("chat", serde_json::to_string_pretty(messages).unwrap_or_default()),
+        PromptInput::Raw(prompt) | PromptInput::Instruction(prompt) => ("raw", prompt.clone()),
+    };
+    let mut trace = TurnTrace {
+        chat_id,
+        story_id,
+        user_input: user_input.clone(),
+        prompt_format: prompt_format.to_string(),
+        prompt: traced_prompt,
+        breakdown: assembled.breakdown.clone(),
+        backend: backend.name().to_string(),
+        model: settings.model.clone(),
+        timings: TraceTimings {
+            context_ms: context_elapsed.as_millis() as u64,
+            ..TraceTimings::default()
+        },
+        ..TurnTrace::default()
+    };
+
+    // Constrain decoding to the turn schema so scene/character/emotion data
+    // can't be lost to malformed JSON (the parser's Fallback path).
+    let use_schema = structured_output && backend.capabilities().json_schema;
+
+    let mut options = settings.sampling_options(&length_config);
+    if raw_prompt {
+        // No server-side template is applied, so stop on the template's end-of-turn
+        options.stop = prompt_template.stop.clone();
+    }
+
+    let story_request = GenerateRequest {
+        model: settings.model.clone(),
+        prompt: prompt_input,
+        options,
+        keep_alive: Some(keep_alive_setting.clone()),
+        json_mode: false,
+        json_schema: if use_schema { Some(llm_parser::llm_turn_output_schema()) } else { None },
+        stream: stream_responses,
+        timeout_secs: OLLAMA_REQUEST_TIMEOUT_SECS,
+    };
 
-    let api_res: Value = {
-        let mut last_err = String::new();
+    let api_res = {
+        let mut last_err = AppError::new(ErrorKind::GenerationFailed, "No generation attempt was made");
         let mut success = None;
         for attempt in 1..=OLLAMA_MAX_RETRIES {
-            let result = state
-                .client
-                .post(format!("{}/api/generate", state.base_url))
-                .timeout(Duration::from_secs(OLLAMA_REQUEST_TIMEOUT_SECS))
-                .json(&request_body)
-                .send()
-                .await;
+            job.check()?;
+
+            // Tell the frontend to drop prose from any previous (failed) attempt
+            let _ = app.emit(
+                STORY_TOKEN_EVENT,
+                StoryTokenPayload { chat_id, text: String::new(), reset: true },
+            );
+
+            let mut extractor = StoryTextExtractor::new();
+            let mut on_text = |piece: &str| {
+                let text = extractor.push(piece);
+                if !text.is_empty() {
+                    let _ = app.emit(
+                        STORY_TOKEN_EVENT,
+                        StoryTokenPayload { chat_id, text, reset: false },
+                    );
+                }
+            };
+            let result = job.run(backend.generate(&story_request, Some(&mut on_text))).await;
             match result {
                 Ok(res) => {
-                    match res.json::<Value>().await {
-                        Ok(json) => {
-                            success = Some(json);
-                            break;
-                        }
-                        Err(e) => {
-                            last_err = format!("Failed to parse Ollama response: {}", e);
-                            println!("[ERROR] Attempt {}/{} — parse error: {}", attempt, OLLAMA_MAX_RETRIES, last_err);
-                        }
-                    }
+                    success = Some(res);
+                    break;
                 }
                 Err(e) => {
-                    last_err = format!("Ollama request failed: {}", e);
+                    // Don't retry a request the user aborted
+                    job.check()?;
+                    last_err = e;
                     println!("[ERROR] Attempt {}/{} — {}", attempt, OLLAMA_MAX_RETRIES, last_err);
+                    trace.retries.push(format!("Attempt {}: {}", attempt, last_err));
+                    // Retrying can't fix a missing model or an oversized prompt
+                    if !last_err.retryable {
+                        break;
+                    }
                 }
             }
             if attempt < OLLAMA_MAX_RETRIES {
                 tokio::time::sleep(Duration::from_secs(2)).await;
             }
         }
-        success.ok_or(last_err)?
+        match success {
+            Some(res) => res,
+            None => {
+                trace.parse_status = "error".to_string();
+                trace.error = Some(last_err.message.clone());
+                trace.timings.generation_ms = ollama_start.elapsed().as_millis() as u64;
+                trace.timings.total_ms = start_time.elapsed().as_millis() as u64;
+                record_trace(&state.db, &trace, trace_retention).await;
+                return Err(last_err);
+            }
+        }
     };
 
     // KV cache telemetry — verifies that llama.cpp is reusing the prefix.
     {
-        let prompt_eval_count = api_res["prompt_eval_count"].as_u64().unwrap_or(0);
-        let prompt_eval_ns = api_res["prompt_eval_duration"].as_u64().unwrap_or(0);
-        let eval_count = api_res["eval_count"].as_u64().unwrap_or(0);
-        let eval_ns = api_res["eval_duration"].as_u64().unwrap_or(0);
+        let prompt_eval_count = api_res.usage.prompt_tokens.unwrap_or(0);
+        let prompt_eval_ns = api_res.usage.prompt_eval_ns.unwrap_or(0);
+        let eval_count = api_res.usage.completion_tokens.unwrap_or(0);
+        let eval_ns = api_res.usage.eval_ns.unwrap_or(0);
         let estimated_prompt_tokens = estimate_tokens(&assembled.prompt) as u64;
         let cache_hit_pct = if estimated_prompt_tokens > 0 {
             100.0 * (1.0 - (prompt_eval_count as f64 / estimated_prompt_tokens as f64))
@@ -1397,18 +1614,18 @@ pub async fn process_story_turn(
             eval_count,
             eval_ns as f64 / 1_000_000_000.0,
         );
+        trace.timings.prompt_eval_tokens = api_res.usage.prompt_tokens;
+        trace.timings.prompt_eval_ms = api_res.usage.prompt_eval_ns.map(|ns| ns / 1_000_000);
+        trace.timings.eval_tokens = api_res.usage.completion_tokens;
+        trace.timings.eval_ms = api_res.usage.eval_ns.map(|ns| ns / 1_000_000);
+        trace.timings.cache_reuse_pct = api_res.usage.prompt_tokens.map(|_| cache_hit_pct.max(0.0));
     }
+    trace.raw_response = api_res.text.clone();
 
-    let response_text_raw = api_res["response"]
-        .as_str()
-        .ok_or_else(|| "No 'response' field in Ollama API result".to_string())?;
+    let response_text_raw = api_res.text.as_str();
 
     // Strip <think>...</think> blocks the model may emit before the JSON
-    let response_text = {
-        let re = regex::Regex::new(r"(?s)<think>.*?</think>").unwrap();
-        let cleaned = re.replace_all(response_text_raw, "");
-        cleaned.trim().to_string()
-    };
+    let response_text = llm_parser::strip_think_tags(response_text_raw);
 
     if response_text_raw.contains("<think>") {
         println!(
@@ -1420,25 +1637,38 @@ pub async fn process_story_turn(
 
     println!("[Orchestrator][DEBUG] Raw LLM response JSON:\n{}", &response_text);
 
-    {
-        let trimmed = response_text.trim_end();
-        let last_char = trimmed.chars().last().unwrap_or('\0');
-        let appears_truncated = !matches!(last_char, '.' | '!' | '?' | '"' | '\'' | '}');
-        println!(
-            "[DEBUG] Ollama response — {:.1}s, {} chars, last_char={:?}, truncated={}",
-            ollama_start.elapsed().as_secs_f64(),
-            response_text.len(),
-            last_char,
-            appears_truncated,
-        );
-    }
-
+    // ── Step 2b: Self-repair truncated or malformed output ────────────
+    // Only an unclosed JSON object or a hit token limit gets a "continue";
+    // prose that merely ends mid-sentence gets a "fix".
+    let truncated = looks_truncated(&response_text, api_res.truncated);
     println!(
-        "[Orchestrator] Ollama responded in {:.1}s ({} chars)",
+        "[Orchestrator] Ollama responded in {:.1}s ({} chars, truncated={}, hit_num_predict={})",
         ollama_start.elapsed().as_secs_f64(),
-        response_text.len()
+        response_text.len(),
+        truncated,
+        api_res.truncated
     );
 
+    let repair = repair_turn_output(
+        backend.as_ref(),
+        &story_request,
+        &job,
+        response_text,
+        truncated,
+    )
+    .await?;
+    let response_text = repair.text;
+    for note in &repair.notes {
+        println!("[Orchestrator] {}", note);
+    }
+    trace.timings.generation_ms = ollama_start.elapsed().as_millis() as u64;
+
+    // Last cancellation point — from here on the turn is persisted as a whole.
+    job.check()?;
+    if let Some(base) = &regeneration {
+        rewind_for_regeneration(&state.db, chat_id, base).await?;
+    }
+
     // ── Step 3: Parse the LLM output ──────────────────────────────────
 
     let mut parsed = llm_parser::parse_llm_output(&response_text);
@@ -1448,7 +1678,7 @@ pub async fn process_story_turn(
         serde_json::to_string_pretty(&parsed.turn).unwrap_or_else(|_| "SERIALIZATION_FAILED".to_string())
     );
 
-    let (parse_status, parse_warnings) = match &parsed.status {
+    let (parse_status, mut parse_warnings) = match &parsed.status {
         ParseStatus::Ok => ("ok".to_string(), vec![]),
         ParseStatus::Partial(w) => {
             for warn in w {
@@ -1501,14 +1731,32 @@ pub async fn process_story_turn(
         }
     };
 
+    // Record repair attempts alongside the parse warnings
+    parse_warnings.extend(repair.notes);
+
     println!(
-        "[Orchestrator] Parse status: {} (turn_id={}, {} characters, generate_image={})",
+        "[Orchestrator] Parse status: {} (turn_id={}, {} characters, generate_image={}, structured={})",
         parse_status,
         parsed.turn.turn_id,
         parsed.turn.characters_in_scene.len(),
-        parsed.should_generate_image()
+        parsed.should_generate_image(),
+        use_schema,
     );
 
+    record_parse_status(
+        &state.db,
+        &ParseRecord {
+            chat_id,
+            story_id,
+            status: &parse_status,
+            warning_count: parse_warnings.len(),
+            structured: use_schema,
+            backend: backend.name(),
+            model: &settings.model,
+        },
+    )
+    .await;
+
     // Persist emotional states to DB (best-effort; non-fatal on failure).
     // Skipped only on a pure fallback where emotional_states is empty.
     if !parsed.turn.emotional_states.is_empty() {
@@ -1552,28 +1800,18 @@ pub async fn process_story_turn(
         if renderable_with_refs.is_empty() {
             (None, None)
         } else {
-            let num_chars = renderable_with_refs.len().min(2);
-            let regions: Vec<&str> = if num_chars == 1 { vec!["center"] } else { vec!["left", "right"] };
+            let num_chars = renderable_with_refs.len().min(scene_character_limit(max_scene_characters));
+            let regions = scene_regions(num_chars);
 
             let genders: Vec<&'static str> = renderable_with_refs.iter()
                 .take(num_chars)
                 .map(|(_, db)| infer_gender(db))
                 .collect();
-
-            let subject_count_tag = match num_chars {
-                1 => match genders[0] { "female" => "1girl", "male" => "1boy", _ => "1person" },
-                2 => match (genders[0], genders[1]) {
-                    ("female", "female") => "2girls",
-                    ("male",   "male")   => "2boys",
-                    ("female", "male") | ("male", "female") => "1girl 1boy",
-                    _ => "2people",
-                },
-                _ => "people",
-            };
+            let subject_count_tag = subject_count_tag(&genders);
 
             let mut char_segs: Vec<String> = Vec::new();
             for (i, (_, db)) in renderable_with_refs.iter().enumerate().take(num_chars) {
-                let region = regions.get(i).copied().unwrap_or("center");
+                let region = regions.get(i).map(|s| s.as_str()).unwrap_or("center");
                 let mut parts: Vec<String> = Vec::new();
                 if let Some(ref sd) = db.sd_prompt {
                     let clean: String = sd
@@ -1603,12 +1841,7 @@ pub async fn process_story_turn(
                     parts.push("soft feminine features, smooth jawline, delicate face, no cleft chin".to_string());
                 }
                 let desc = if parts.is_empty() { "a person".to_string() } else { parts.join(", ") };
-                let prefix = match region {
-                    "left"  => "a person on the left side of the scene,",
-                    "right" => "a person on the right side of the scene,",
-                    _       => "a person in the center of the scene,",
-                };
-                char_segs.push(format!("{} {}", prefix, desc));
+                char_segs.push(format!("{} {}", region_prompt_prefix(region), desc));
             }
 
             let sp = parsed.scene_prompt_fragment();
@@ -1661,7 +1894,7 @@ pub async fn process_story_turn(
     // Best-effort: if story_id is set and the LLM returned scene data,
     // auto-create/match a scene and sync its characters.
 
-    let post_turn_active_scene_id: Option<i64> = if let (Some(sid), Some(scene_json)) =
+    let (post_turn_active_scene_id, roster_additions): (Option<i64>, Vec<i64>) = if let (Some(sid), Some(scene_json)) =
         (story_id, &parsed.turn.scene_json)
     {
         let char_names: Vec<String> = parsed
@@ -1672,14 +1905,15 @@ pub async fn process_story_turn(
             .collect();
 
         match sync_scene_from_turn(&state.db, sid, scene_json, &char_names).await {
-            Ok(id) => id,
+            Ok(Some((id, added))) => (Some(id), added),
+            Ok(None) => (None, Vec::new()),
             Err(e) => {
                 println!("[Scene] sync_scene_from_turn error (non-fatal): {}", e);
-                prior_active_scene_id
+                (prior_active_scene_id, Vec::new())
             }
         }
     } else {
-        prior_active_scene_id
+        (prior_active_scene_id, Vec::new())
     };
 
     // ── Step 6: Save to database ──────────────────────────────────────
@@ -1687,12 +1921,24 @@ pub async fn process_story_turn(
     let raw_content =
         extract_json_from_text(&response_text).unwrap_or_else(|| response_text.to_string());
 
+    let turn_record = TurnRecord {
+        scene_id: post_turn_active_scene_id,
+        parse_status: parse_status.clone(),
+        model: Some(settings.model.clone()),
+        backend: Some(backend.name().to_string()),
+        elapsed_ms: Some(start_time.elapsed().as_millis() as i64),
+        ..TurnRecord::from_parsed(&parsed)
+    };
+
     let assistant_message_id = match save_turn_to_db(
         &state.db,
         chat_id,
         &user_input,
         &raw_content,
         generated_image_path.as_deref(),
+        &roster_additions,
+        reply_to,
+        &turn_record,
     )
     .await
     {
@@ -1714,6 +1960,12 @@ pub async fn process_story_turn(
         total_elapsed.as_secs_f64()
     );
 
+    trace.assistant_message_id = assistant_message_id;
+    trace.parse_status = parse_status.clone();
+    trace.parse_warnings = parse_warnings.clone();
+    trace.timings.total_ms = total_elapsed.as_millis() as u64;
+    record_trace(&state.db, &trace, trace_retention).await;
+
     Ok(StoryTurnResult {
         turn_id: parsed.turn.turn_id,
         story_text: parsed.story_text().to_string(),
@@ -1742,10 +1994,13 @@ pub async fn generate_scene_image_for_turn(
     character_poses: Option<Vec<String>>,
     positive_prompt_override: Option<String>,
     negative_prompt_override: Option<String>,
+    job_id: Option<String>,
     app: AppHandle,
     state: State<'_, OllamaState>,
     config_state: State<'_, ConfigState>,
-) -> Result<String, String> {
+    cancel_registry: State<'_, CancelRegistry>,
+) -> Result<String, AppError> {
+    let job = cancel_registry.register(job_id.unwrap_or_else(|| "scene-image".to_string()));
     println!(
         "[Orchestrator] generate_scene_image_for_turn called: story_id={:?}, prompt_len={}",
         story_id, scene_prompt.len()
@@ -1878,21 +2133,36 @@ pub async fn generate_scene_image_for_turn(
         .collect();
 
     if characters.is_empty() {
-        return Err(
+        return Err(AppError::invalid_input(
             "No characters with reference images found. \
              To generate scene images, open a character in the Character panel, \
-             generate a Master Portrait, then save it as the reference image.".to_string()
-        );
+             generate a Master Portrait, then save it as the reference image.",
+        ));
     }
 
-    let num_chars = characters.len().min(2); // Max 2 for current workflows
-    let workflow_path = select_workflow(num_chars, &app_data)?;
+    // Read config values needed for ControlNet, content rating and the character limit.
+    let (content_rating, controlnet_enabled, controlnet_strength, max_scene_characters) = {
+        let config = config_state.0.lock().map_err(|e| e.to_string())?;
+        (
+            config.content_rating.clone(),
+            config.controlnet_pose_enabled,
+            config.controlnet_pose_strength,
+            config.max_scene_characters,
+        )
+    };
+
+    let num_chars = characters.len().min(scene_character_limit(max_scene_characters));
+    if characters.len() > num_chars {
+        println!(
+            "[Orchestrator] {} characters with references, rendering the first {} (max_scene_characters)",
+            characters.len(),
+            num_chars
+        );
+    }
+    let workflow_path = select_workflow(num_chars, &app_data);
 
     // Assign regions up-front — used by both mask generation and char_inputs
-    let regions: Vec<String> = match num_chars {
-        1 => vec!["center".to_string()],
-        _ => vec!["left".to_string(), "right".to_string()],
-    };
+    let regions = scene_regions(num_chars);
 
     // ═══════════════════════════════════════════════════════════════════
     // CRITICAL: Enrich the scene prompt with character descriptions.
@@ -1901,35 +2171,11 @@ pub async fn generate_scene_image_for_turn(
     // the model won't generate one, and the face reference is wasted.
     // ═══════════════════════════════════════════════════════════════════
 
-    // Determine Danbooru-style subject count tags from character genders.
-    // These help SDXL place exactly the right number of people.
     let genders: Vec<&'static str> = characters.iter()
         .take(num_chars)
         .map(infer_gender)
         .collect();
-
-    let subject_count_tag = match num_chars {
-        1 => match genders[0] {
-            "female" => "1girl",
-            "male"   => "1boy",
-            _        => "1person",
-        },
-        2 => match (genders[0], genders[1]) {
-            ("female", "female")                   => "2girls",
-            ("male",   "male")                     => "2boys",
-            ("female", "male") | ("male", "female") => "1girl 1boy",
-            ("female", _) | (_, "female")          => "1girl 1person",
-            ("male",   _) | (_, "male")            => "1boy 1person",
-            _                                      => "2people",
-        },
-        _ => "people",
-    };
-
-    // Read config values needed for ControlNet and content rating.
-    let (content_rating, controlnet_enabled, controlnet_strength) = {
-        let config = config_state.0.lock().map_err(|e| e.to_string())?;
-        (config.content_rating.clone(), config.controlnet_pose_enabled, config.controlnet_pose_strength)
-    };
+    let subject_count_tag = subject_count_tag(&genders);
 
     // Prefer the LLM's declared pose; fall back to prose keyword scan.
     let declared_pose = character_poses
@@ -2041,12 +2287,7 @@ pub async fn generate_scene_image_for_turn(
             char_parts.join(", ")
         };
 
-        let region_prefix = match region {
-            "left" => "a person on the left side of the scene,",
-            "right" => "a person on the right side of the scene,",
-            _ => "a person in the center of the scene,",
-        };
-        char_segments.push(format!("{} {}", region_prefix, char_desc));
+        char_segments.push(format!("{} {}", region_prompt_prefix(region), char_desc));
     }
 
     // Append active scene metadata to the prompt as a fallback if the LLM's scene
@@ -2116,34 +2357,10 @@ pub async fn generate_scene_image_for_turn(
         (enriched_prompt, None)
     };
 
-    // Generate per-character masks for 2-char workflow
+    // Generate per-character masks for the multi-character workflow
     let mask_paths: Vec<String> = if num_chars > 1 {
-        let masks_dir = app_data.join("masks");
-        let mut paths = Vec::new();
-
-        for (i, region) in regions.iter().enumerate().take(num_chars) {
-            let mask_chars = vec![mask_generator::MaskCharacter {
-                name: characters.get(i).map(|c| c.name.clone()).unwrap_or_default(),
-                region: region.to_string(),
-                color_index: 0, // MUST be 0 (red) — ImageToMask reads red channel
-            }];
-
-            let filename = format!("scene_mask_char{}.png", i);
-            match mask_generator::generate_mask(
-                &mask_chars,
-                DEFAULT_IMAGE_WIDTH,
-                DEFAULT_IMAGE_HEIGHT,
-                &masks_dir,
-                Some(&filename),
-            ) {
-                Ok(r) => {
-                    println!("[Orchestrator] Mask for char {}: {} (region={})", i, r.path, region);
-                    paths.push(r.path);
-                }
-                Err(e) => return Err(format!("Mask gen failed for char {}: {}", i, e)),
-            }
-        }
-        paths
+        let names: Vec<String> = characters.iter().take(num_chars).map(|c| c.name.clone()).collect();
+        generate_scene_masks(&names, &regions, &app_data.join("masks"), "scene_mask_char")?
     } else {
         vec![]
     };
@@ -2222,7 +2439,7 @@ pub async fn generate_scene_image_for_turn(
     println!(
         "[Orchestrator] Submitting to ComfyUI: {} character(s), workflow={}, output_dir={}",
         request.characters.len(),
-        request.workflow_template,
+        request.workflow_template.as_deref().unwrap_or("built-in"),
         output_dir.display()
     );
     println!(
@@ -2231,25 +2448,20 @@ pub async fn generate_scene_image_for_turn(
     );
 
     // Free VRAM: unload Ollama model before ComfyUI needs the GPU
-    {
-        let ollama_url = {
-            let config = config_state.0.lock().map_err(|e| e.to_string())?;
-            config.ollama_url.clone()
-        };
-        unload_ollama_model(&ollama_url).await;
-    }
+    unload_story_model(&state.db, &config_state, story_id).await?;
 
-    let result = comfyui_api::generate_scene_image(&request, &output_dir)
+    let mut on_progress = comfyui_api::image_progress_emitter(&app, Some(job.key().to_string()));
+    let result = comfyui_api::generate_scene_image(&request, &output_dir, Some(&job), Some(&mut on_progress))
         .await
         .map_err(|e| {
             println!("[Orchestrator] ComfyUI call FAILED: {}", e);
-            e.to_string()
+            AppError::from(e)
         })?;
 
     let image_path = result.image_paths.into_iter().next()
-        .ok_or_else(|| "No image generated".to_string())?;
+        .ok_or_else(|| AppError::new(ErrorKind::ImageGenerationFailed, "ComfyUI returned no image"))?;
     println!("[Orchestrator] Scene image generated successfully: {}", image_path);
-    unload_comfyui_models("http://localhost:8188").await;
+    unload_comfyui_models(comfyui_api::DEFAULT_COMFYUI_URL).await;
     println!("[VRAM] Image generation complete — both models unloaded, VRAM clean");
     Ok(image_path)
 }
@@ -2265,10 +2477,15 @@ pub async fn illustrate_scene_custom(
     message_id: i64,
     positive_prompt: String,
     negative_prompt: String,
+    job_id: Option<String>,
     config_state: State<'_, ConfigState>,
     state: State<'_, OllamaState>,
+    cancel_registry: State<'_, CancelRegistry>,
     app: AppHandle,
-) -> Result<String, String> {
+) -> Result<String, AppError> {
+    let job = cancel_registry.register(
+        job_id.unwrap_or_else(|| format!("illustrate:{}", message_id)),
+    );
     println!(
         "[Orchestrator] illustrate_scene_custom: story={} message={}",
         story_id, message_id
@@ -2298,45 +2515,22 @@ pub async fn illustrate_scene_custom(
         .collect();
 
     if all_characters.is_empty() {
-        return Err(
+        return Err(AppError::invalid_input(
             "No characters with reference images found. \
              To generate scene images, open a character in the Character panel, \
-             generate a Master Portrait, then save it as the reference image."
-                .to_string(),
-        );
+             generate a Master Portrait, then save it as the reference image.",
+        ));
     }
 
-    let num_chars = all_characters.len().min(2);
-    let workflow_path = select_workflow(num_chars, &app_data)?;
+    let max_scene_characters = config_state.0.lock().map_err(|e| e.to_string())?.max_scene_characters;
+    let num_chars = all_characters.len().min(scene_character_limit(max_scene_characters));
+    let workflow_path = select_workflow(num_chars, &app_data);
+    let regions = scene_regions(num_chars);
 
-    let regions: Vec<String> = match num_chars {
-        1 => vec!["center".to_string()],
-        _ => vec!["left".to_string(), "right".to_string()],
-    };
-
-    // Generate per-character masks for 2-char workflow (matches existing pipeline)
+    // Generate per-character masks for the multi-character workflow (matches existing pipeline)
     let mask_paths: Vec<String> = if num_chars > 1 {
-        let masks_dir = app_data.join("masks");
-        let mut paths = Vec::new();
-        for (i, region) in regions.iter().enumerate().take(num_chars) {
-            let mask_chars = vec![mask_generator::MaskCharacter {
-                name: all_characters.get(i).map(|c| c.name.clone()).unwrap_or_default(),
-                region: region.to_string(),
-                color_index: 0,
-            }];
-            let filename = format!("custom_mask_char{}.png", i);
-            match mask_generator::generate_mask(
-                &mask_chars,
-                DEFAULT_IMAGE_WIDTH,
-                DEFAULT_IMAGE_HEIGHT,
-                &masks_dir,
-                Some(&filename),
-            ) {
-                Ok(r) => paths.push(r.path),
-                Err(e) => return Err(format!("Mask gen failed for char {}: {}", i, e)),
-            }
-        }
-        paths
+        let names: Vec<String> = all_characters.iter().take(num_chars).map(|c| c.name.clone()).collect();
+        generate_scene_masks(&names, &regions, &app_data.join("masks"), "custom_mask_char")?
     } else {
         vec![]
     };
@@ -2377,41 +2571,30 @@ pub async fn illustrate_scene_custom(
     };
 
     // Free VRAM before ComfyUI needs the GPU
-    {
-        let ollama_url = {
-            let config = config_state.0.lock().map_err(|e| e.to_string())?;
-            config.ollama_url.clone()
-        };
-        unload_ollama_model(&ollama_url).await;
-    }
+    unload_story_model(&state.db, &config_state, Some(story_id)).await?;
 
     let output_dir = app_data.join("generated_images");
-    let result = comfyui_api::generate_scene_image(&request, &output_dir)
+    let mut on_progress = comfyui_api::image_progress_emitter(&app, Some(job.key().to_string()));
+    let result = comfyui_api::generate_scene_image(&request, &output_dir, Some(&job), Some(&mut on_progress))
         .await
         .map_err(|e| {
             println!("[Orchestrator] Custom illustration FAILED: {}", e);
-            e.to_string()
+            AppError::from(e)
         })?;
 
     let image_path = result
         .image_paths
         .into_iter()
         .next()
-        .ok_or_else(|| "No image generated".to_string())?;
+        .ok_or_else(|| AppError::new(ErrorKind::ImageGenerationFailed, "ComfyUI returned no image"))?;
 
-    unload_comfyui_models("http://localhost:8188").await;
+    unload_comfyui_models(comfyui_api::DEFAULT_COMFYUI_URL).await;
     println!("[Orchestrator] Custom illustration complete: {}", image_path);
 
-    // Persist the image — insert or replace so re-illustration overwrites the old entry
-    sqlx::query(
-        "INSERT OR REPLACE INTO images (message_id, chat_id, file_path) VALUES (?, ?, ?)",
-    )
-    .bind(message_id)
-    .bind(chat_id)
-    .bind(&image_path)
-    .execute(&state.db)
-    .await
-    .ok();
+    // Persist the image, replacing any earlier one, and link it to the turn
+    set_turn_image(&state.db, message_id, chat_id, &image_path)
+        .await
+        .map_err(AppError::database)?;
 
     Ok(image_path)
 }
@@ -2427,13 +2610,13 @@ pub async fn preview_scene_prompt(
     app: AppHandle,
     state: State<'_, OllamaState>,
     config_state: State<'_, ConfigState>,
-) -> Result<ScenePromptPreview, String> {
+) -> Result<ScenePromptPreview, AppError> {
     let app_data = app.path().app_data_dir()
         .map_err(|e| format!("Failed to get app data dir: {}", e))?;
 
-    let (content_rating, _controlnet_enabled, _controlnet_strength) = {
+    let (content_rating, max_scene_characters) = {
         let config = config_state.0.lock().map_err(|e| e.to_string())?;
-        (config.content_rating.clone(), config.controlnet_pose_enabled, config.controlnet_pose_strength)
+        (config.content_rating.clone(), config.max_scene_characters)
     };
 
     let all_characters = get_characters_with_references(story_id, &state).await?;
@@ -2521,36 +2704,18 @@ pub async fn preview_scene_prompt(
         .collect();
 
     if characters.is_empty() {
-        return Err(
+        return Err(AppError::invalid_input(
             "No characters with reference images found. \
              To generate scene images, open a character, generate a Master Portrait, \
-             then save it as the reference image.".to_string()
-        );
+             then save it as the reference image.",
+        ));
     }
 
-    let num_chars = characters.len().min(2);
-    let regions: Vec<String> = match num_chars {
-        1 => vec!["center".to_string()],
-        _ => vec!["left".to_string(), "right".to_string()],
-    };
+    let num_chars = characters.len().min(scene_character_limit(max_scene_characters));
+    let regions = scene_regions(num_chars);
 
     let genders: Vec<&'static str> = characters.iter().take(num_chars).map(infer_gender).collect();
-    let subject_count_tag = match num_chars {
-        1 => match genders[0] {
-            "female" => "1girl",
-            "male"   => "1boy",
-            _        => "1person",
-        },
-        2 => match (genders[0], genders[1]) {
-            ("female", "female")                   => "2girls",
-            ("male",   "male")                     => "2boys",
-            ("female", "male") | ("male", "female") => "1girl 1boy",
-            ("female", _) | (_, "female")          => "1girl 1person",
-            ("male",   _) | (_, "male")            => "1boy 1person",
-            _                                      => "2people",
-        },
-        _ => "people",
-    };
+    let subject_count_tag = subject_count_tag(&genders);
 
     let declared_pose = character_poses
         .as_ref()
@@ -2608,12 +2773,7 @@ pub async fn preview_scene_prompt(
         } else {
             char_parts.join(", ")
         };
-        let region_prefix = match region {
-            "left" => "a person on the left side of the scene,",
-            "right" => "a person on the right side of the scene,",
-            _ => "a person in the center of the scene,",
-        };
-        char_segments.push(format!("{} {}", region_prefix, char_desc));
+        char_segments.push(format!("{} {}", region_prompt_prefix(region), char_desc));
     }
 
     // Enhance scene_prompt with active scene metadata
@@ -2894,13 +3054,241 @@ async fn get_characters_with_references(
 }
 
 // ============================================================================
-// REGENERATE COMMAND
+// REGENERATE / SWIPE COMMANDS
 // ============================================================================
+// Regenerating never deletes the previous response. Each response to a user
+// message is a candidate (`messages.reply_to` = the user message id); exactly
+// one is selected and only selected rows are read back as story history.
+// Candidates carry their own image, input and state snapshot.
+
+/// The candidate a regeneration replaces, with the roster additions it made.
+struct ReplacedCandidate {
+    id: i64,
+    scene_id: Option<i64>,
+    roster_additions: Vec<i64>,
+}
+
+/// What a regenerated turn is built on: the story as it was before the turn.
+/// Nothing is written while the new candidate is generated — the replaced
+/// one stays selected until `rewind_for_regeneration`, after the last
+/// cancellation point.
+struct RegenerationBase {
+    /// The user message the new candidate answers
+    user_id: i64,
+    /// Its current text (the input of the selected candidate)
+    user_input: String,
+    replaced: Option<ReplacedCandidate>,
+    /// Active scene before the turn
+    scene_id: Option<i64>,
+    /// Emotional states before the turn; None = keep the current ones
+    emotion_snapshot: Option<String>,
+}
+
+impl RegenerationBase {
+    /// Characters the replaced candidate added to `scene_id`, which aren't in
+    /// its roster for the new one.
+    fn replaced_additions(&self, scene_id: i64) -> &[i64] {
+        match &self.replaced {
+            Some(r) if r.scene_id == Some(scene_id) => &r.roster_additions,
+            _ => &[],
+        }
+    }
+}
+
+/// Load what regenerating the chat's last turn builds on. Read-only.
+async fn load_regeneration_base(db: &sqlx::SqlitePool, chat_id: i64) -> Result<RegenerationBase, String> {
+    let user_row = sqlx::query(
+        "SELECT id, content FROM messages WHERE chat_id = ? AND role = 'user' ORDER BY id DESC LIMIT 1",
+    )
+    .bind(chat_id)
+    .fetch_optional(db)
+    .await
+    .map_err(|e| format!("Failed to load messages: {}", e))?
+    .ok_or_else(|| "No user message found to regenerate".to_string())?;
+    let user_id: i64 = user_row.get("id");
+    if is_shared_with_branch(db, chat_id, user_id).await? {
+        return Err("This turn is shared with a story branch — fork from it instead".to_string());
+    }
+
+    let replaced = sqlx::query(
+        "SELECT id, scene_id, roster_additions FROM messages \
+         WHERE chat_id = ? AND role = 'assistant' AND is_selected = 1 AND id > ? \
+         ORDER BY id DESC LIMIT 1",
+    )
+    .bind(chat_id)
+    .bind(user_id)
+    .fetch_optional(db)
+    .await
+    .map_err(|e| format!("Failed to load messages: {}", e))?
+    .map(|r| ReplacedCandidate {
+        id: r.get("id"),
+        scene_id: r.get("scene_id"),
+        roster_additions: parse_roster_additions(r.get("roster_additions")),
+    });
+
+    let mut conn = db.acquire().await.map_err(|e| format!("Failed to open connection: {}", e))?;
+    let (scene_id, emotion_snapshot) = match turn_state_before(&mut conn, chat_id, user_id).await? {
+        Some(state) => state,
+        // The first turn: keep the scene the story opened in
+        None => {
+            let scene_id = sqlx::query("SELECT active_scene_id FROM story_premises WHERE chat_id = ?")
+                .bind(chat_id)
+                .fetch_optional(&mut *conn)
+                .await
+                .map_err(|e| format!("Failed to load active scene: {}", e))?
+                .and_then(|r| r.get("active_scene_id"));
+            (scene_id, Some("[]".to_string()))
+        }
+    };
+
+    Ok(RegenerationBase {
+        user_id,
+        user_input: user_row.get("content"),
+        replaced,
+        scene_id,
+        emotion_snapshot,
+    })
+}
+
+/// Turn the replaced candidate into an unselected one and rewind its scene,
+/// roster and emotional states to `base`, ready for the new candidate to be
+/// saved. Candidates that don't record their input yet are stamped with the
+/// user message's text, which the new candidate may replace.
+async fn rewind_for_regeneration(db: &sqlx::SqlitePool, chat_id: i64, base: &RegenerationBase) -> Result<(), String> {
+    let mut tx = db.begin().await.map_err(|e| format!("Transaction start failed: {}", e))?;
+
+    if let Some(replaced) = &base.replaced {
+        // Rows saved before swipes existed have no reply_to yet
+        sqlx::query("UPDATE messages SET reply_to = COALESCE(reply_to, ?), is_selected = 0 WHERE id = ?")
+            .bind(base.user_id)
+            .bind(replaced.id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to keep previous response: {}", e))?;
+        if let Some(scene_id) = replaced.scene_id {
+            set_roster_additions(&mut tx, scene_id, &replaced.roster_additions, false).await?;
+        }
+        println!("[Orchestrator] Kept assistant message id={} as an unselected candidate", replaced.id);
+    }
+    sqlx::query("UPDATE messages SET user_input = ? WHERE reply_to = ? AND role = 'assistant' AND user_input IS NULL")
+        .bind(&base.user_input)
+        .bind(base.user_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| format!("Failed to record candidate input: {}", e))?;
+
+    set_active_scene(&mut tx, chat_id, base.scene_id).await?;
+    if let Some(snapshot) = &base.emotion_snapshot {
+        restore_emotional_states(&mut *tx, chat_id, snapshot).await?;
+    }
+
+    tx.commit().await.map_err(|e| format!("Transaction commit failed: {}", e))
+}
+
+/// Make `message_id` the selected candidate of its turn and restore the
+/// state saved with it: the user input it answers, the active scene, its
+/// roster additions (undoing those of the candidate it replaces) and the
+/// emotional states. Returns the chat id.
+async fn select_candidate(db: &sqlx::SqlitePool, message_id: i64) -> Result<i64, String> {
+    let mut tx = db.begin().await.map_err(|e| format!("Transaction start failed: {}", e))?;
+
+    let row = sqlx::query(
+        "SELECT chat_id, reply_to, scene_id, roster_additions, emotion_snapshot, user_input \
+         FROM messages WHERE id = ? AND role = 'assistant'",
+    )
+    .bind(message_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| format!("Failed to load candidate: {}", e))?
+    .ok_or_else(|| format!("Assistant message {} not found", message_id))?;
+    let chat_id: i64 = row.get("chat_id");
+    let reply_to: Option<i64> = row.get("reply_to");
+    let mut scene_id: Option<i64> = row.get("scene_id");
+
+    if let Some(user_id) = reply_to {
+        let deselected = sqlx::query(
+            "SELECT scene_id, roster_additions FROM messages \
+             WHERE reply_to = ? AND role = 'assistant' AND is_selected = 1 AND id != ?",
+        )
+        .bind(user_id)
+        .bind(message_id)
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|e| format!("Failed to load candidates: {}", e))?;
+        for other in &deselected {
+            if let Some(other_scene) = other.get::<Option<i64>, _>("scene_id") {
+                let additions = parse_roster_additions(other.get("roster_additions"));
+                set_roster_additions(&mut tx, other_scene, &additions, false).await?;
+            }
+        }
+
+        sqlx::query("UPDATE messages SET is_selected = (id = ?) WHERE reply_to = ? AND role = 'assistant'")
+            .bind(message_id)
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to select candidate: {}", e))?;
+        if let Some(input) = row.get::<Option<String>, _>("user_input") {
+            sqlx::query("UPDATE messages SET content = ? WHERE id = ?")
+                .bind(input)
+                .bind(user_id)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| format!("Failed to restore user input: {}", e))?;
+        }
+
+        // A turn without a scene leaves the one it started in active
+        if scene_id.is_none() {
+            scene_id = turn_state_before(&mut tx, chat_id, user_id).await?.and_then(|(scene, _)| scene);
+        }
+    }
+
+    if let Some(scene) = row.get::<Option<i64>, _>("scene_id") {
+        let additions = parse_roster_additions(row.get("roster_additions"));
+        set_roster_additions(&mut tx, scene, &additions, true).await?;
+    }
+    if scene_id.is_some() {
+        set_active_scene(&mut tx, chat_id, scene_id).await?;
+    }
+    if let Some(snapshot) = row.get::<Option<String>, _>("emotion_snapshot") {
+        restore_emotional_states(&mut *tx, chat_id, &snapshot).await?;
+    }
+
+    tx.commit().await.map_err(|e| format!("Transaction commit failed: {}", e))?;
+    Ok(chat_id)
+}
+
+/// Run a regenerated turn on `base`. If it fails, is cancelled or can't be
+/// saved, the replaced candidate is selected again.
+#[allow(clippy::too_many_arguments)]
+async fn run_regeneration(
+    chat_id: i64,
+    user_input: String,
+    story_id: Option<i64>,
+    base: RegenerationBase,
+    state: State<'_, OllamaState>,
+    config_state: State<'_, ConfigState>,
+    hint_state: State<'_, SceneHintState>,
+    cancel_registry: State<'_, CancelRegistry>,
+    app: AppHandle,
+) -> Result<StoryTurnResult, AppError> {
+    let db = state.db.clone();
+    let replaced = base.replaced.as_ref().map(|r| r.id);
+    let result = run_story_turn(chat_id, user_input, story_id, Some(base), state, config_state, hint_state, cancel_registry, app).await;
+    let saved = matches!(&result, Ok(turn) if turn.assistant_message_id.is_some());
+    if !saved {
+        if let Some(asst_id) = replaced {
+            select_candidate(&db, asst_id).await.ok();
+        }
+    }
+    result
+}
 
 /// Regenerate the last AI response for a chat turn.
 ///
-/// Deletes the last user + assistant message pair from the DB, then
-/// re-runs the full story turn pipeline with the same user input.
+/// The full story turn pipeline re-runs with the same user input, from the
+/// state before the turn, and saves a new selected candidate. The current
+/// response is kept as an unselected candidate.
 ///
 /// ## Frontend usage
 /// ```typescript
@@ -2913,92 +3301,37 @@ pub async fn regenerate_story(
     state: State<'_, OllamaState>,
     config_state: State<'_, ConfigState>,
     hint_state: State<'_, SceneHintState>,
+    cancel_registry: State<'_, CancelRegistry>,
     app: AppHandle,
-) -> Result<StoryTurnResult, String> {
+) -> Result<StoryTurnResult, AppError> {
     println!(
         "\n[Orchestrator] ========== REGENERATE (chat={}, story={:?}) ==========",
         id, story_id
     );
 
-    // 1. Load all messages to find the last user + assistant pair
-    let rows = sqlx::query(
-        "SELECT id, role, content FROM messages WHERE chat_id = ? ORDER BY timestamp ASC",
-    )
-    .bind(id)
-    .fetch_all(&state.db)
-    .await
-    .map_err(|e| format!("Failed to load messages: {}", e))?;
-
-    let mut last_user_id: Option<i64> = None;
-    let mut last_user_content: Option<String> = None;
-    let mut last_assistant_id: Option<i64> = None;
-
-    for row in &rows {
-        let role: String = row.get("role");
-        let msg_id: i64 = row.get("id");
-        let content: String = row.get("content");
-        match role.as_str() {
-            "user" => {
-                last_user_id = Some(msg_id);
-                last_user_content = Some(content);
-            }
-            "assistant" => {
-                last_assistant_id = Some(msg_id);
-            }
-            _ => {}
-        }
-    }
-
-    let user_input = last_user_content
-        .ok_or_else(|| "No user message found to regenerate".to_string())?;
+    let base = load_regeneration_base(&state.db, id).await?;
+    let user_input = base.user_input.clone();
     println!(
         "[Orchestrator] Regenerating from user input: {:?}",
         &user_input[..user_input.len().min(80)]
     );
 
-    // 2. Delete last assistant message and its image record
-    if let Some(asst_id) = last_assistant_id {
-        sqlx::query("DELETE FROM images WHERE message_id = ?")
-            .bind(asst_id)
-            .execute(&state.db)
-            .await
-            .map_err(|e| format!("Failed to delete image record: {}", e))?;
-
-        sqlx::query("DELETE FROM messages WHERE id = ?")
-            .bind(asst_id)
-            .execute(&state.db)
-            .await
-            .map_err(|e| format!("Failed to delete assistant message: {}", e))?;
-
-        println!("[Orchestrator] Deleted assistant message id={}", asst_id);
-    }
-
-    // 3. Delete last user message (process_story_turn will re-save it)
-    if let Some(user_id) = last_user_id {
-        sqlx::query("DELETE FROM messages WHERE id = ?")
-            .bind(user_id)
-            .execute(&state.db)
-            .await
-            .map_err(|e| format!("Failed to delete user message: {}", e))?;
-
-        println!("[Orchestrator] Deleted user message id={}", user_id);
-    }
-
-    // 4. Re-run the full turn pipeline with the same user input
-    process_story_turn(id, user_input, story_id, state, config_state, hint_state, app).await
+    run_regeneration(id, user_input, story_id, base, state, config_state, hint_state, cancel_registry, app).await
 }
 
 /// Regenerate the last AI response with modified user input.
 ///
-/// Like `regenerate_story`, deletes the last user + assistant pair,
-/// then re-runs the pipeline with the NEW user input provided.
+/// Like `regenerate_story`, but the new candidate answers the edited input.
+/// The input is stored with the candidate and only replaces the user message
+/// once the candidate is saved; earlier candidates keep the text they were
+/// written for.
 ///
 /// ## Frontend usage
 /// ```typescript
 /// const result = await invoke('regenerate_story_with_input', {
 ///   id: chatId,
-///   userInput: "edited text",
-///   storyId
+///   userInput: editedText,
+///   storyId,
 /// });
 /// ```
 #[tauri::command]
@@ -3009,78 +3342,90 @@ pub async fn regenerate_story_with_input(
     state: State<'_, OllamaState>,
     config_state: State<'_, ConfigState>,
     hint_state: State<'_, SceneHintState>,
+    cancel_registry: State<'_, CancelRegistry>,
     app: AppHandle,
-) -> Result<StoryTurnResult, String> {
+) -> Result<StoryTurnResult, AppError> {
     println!(
         "\n[Orchestrator] ========== REGENERATE WITH EDIT (chat={}, story={:?}) ==========",
         id, story_id
     );
-
-    // 1. Load all messages to find the last user + assistant pair
-    let rows = sqlx::query(
-        "SELECT id, role, content FROM messages WHERE chat_id = ? ORDER BY timestamp ASC",
-    )
-    .bind(id)
-    .fetch_all(&state.db)
-    .await
-    .map_err(|e| format!("Failed to load messages: {}", e))?;
-
-    let mut last_user_id: Option<i64> = None;
-    let mut last_assistant_id: Option<i64> = None;
-
-    for row in &rows {
-        let role: String = row.get("role");
-        let msg_id: i64 = row.get("id");
-        match role.as_str() {
-            "user" => {
-                last_user_id = Some(msg_id);
-            }
-            "assistant" => {
-                last_assistant_id = Some(msg_id);
-            }
-            _ => {}
-        }
-    }
-
-    if last_user_id.is_none() {
-        return Err("No user message found to regenerate".to_string());
-    }
-
     println!(
         "[Orchestrator] Regenerating with edited input: {:?}",
         &user_input[..user_input.len().min(80)]
     );
 
-    // 2. Delete last assistant message and its image record
-    if let Some(asst_id) = last_assistant_id {
-        sqlx::query("DELETE FROM images WHERE message_id = ?")
-            .bind(asst_id)
-            .execute(&state.db)
-            .await
-            .map_err(|e| format!("Failed to delete image record: {}", e))?;
-
-        sqlx::query("DELETE FROM messages WHERE id = ?")
-            .bind(asst_id)
-            .execute(&state.db)
-            .await
-            .map_err(|e| format!("Failed to delete assistant message: {}", e))?;
+    let base = load_regeneration_base(&state.db, id).await?;
+    run_regeneration(id, user_input, story_id, base, state, config_state, hint_state, cancel_registry, app).await
+}
 
-        println!("[Orchestrator] Deleted assistant message id={}", asst_id);
-    }
+/// All candidates for the turn that `message_id` (any of its candidates)
+/// belongs to, oldest first.
+#[tauri::command]
+pub async fn list_turn_candidates(
+    message_id: i64,
+    state: State<'_, OllamaState>,
+) -> Result<Vec<TurnCandidate>, AppError> {
+    let rows = sqlx::query(
+        "SELECT m.id, m.content, m.is_selected, m.timestamp, i.file_path AS image_path, \
+           COALESCE(m.user_input, u.content, '') AS user_input \
+         FROM messages m \
+         LEFT JOIN images i ON i.message_id = m.id \
+         LEFT JOIN messages u ON u.id = m.reply_to \
+         WHERE m.role = 'assistant' \
+           AND (m.id = ?1 OR m.reply_to = (SELECT reply_to FROM messages WHERE id = ?1)) \
+         ORDER BY m.id ASC",
+    )
+    .bind(message_id)
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| format!("Failed to load candidates: {}", e))?;
 
-    // 3. Delete last user message (process_story_turn will save the NEW input)
-    if let Some(user_id) = last_user_id {
-        sqlx::query("DELETE FROM messages WHERE id = ?")
-            .bind(user_id)
-            .execute(&state.db)
-            .await
-            .map_err(|e| format!("Failed to delete user message: {}", e))?;
+    Ok(rows
+        .iter()
+        .map(|r| TurnCandidate {
+            message_id: r.get("id"),
+            content: r.get("content"),
+            user_input: r.get("user_input"),
+            image_path: r.get("image_path"),
+            selected: r.get("is_selected"),
+            timestamp: r.get::<Option<String>, _>("timestamp").unwrap_or_default(),
+        })
+        .collect())
+}
 
-        println!("[Orchestrator] Deleted user message id={}", user_id);
+/// Switch the latest turn to another candidate. Older turns are locked:
+/// summaries and story memory may already be built from their text.
+#[tauri::command]
+pub async fn select_turn_candidate(
+    message_id: i64,
+    state: State<'_, OllamaState>,
+) -> Result<(), AppError> {
+    let candidate = sqlx::query("SELECT chat_id, reply_to FROM messages WHERE id = ?")
+        .bind(message_id)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|e| format!("Failed to load candidate: {}", e))?
+        .ok_or_else(|| AppError::not_found(format!("Assistant message {} not found", message_id)))?;
+    let candidate_chat: i64 = candidate.get("chat_id");
+    let reply_to: Option<i64> = candidate.get("reply_to");
+    let latest_user: Option<i64> = sqlx::query(
+        "SELECT MAX(id) AS id FROM messages WHERE role = 'user' AND chat_id = ?",
+    )
+    .bind(candidate_chat)
+    .fetch_one(&state.db)
+    .await
+    .map_err(|e| format!("Failed to load messages: {}", e))?
+    .get("id");
+    let Some(user_id) = reply_to.filter(|id| Some(*id) == latest_user) else {
+        return Err(AppError::invalid_input("Only the latest turn's candidates can be switched"));
+    };
+    if is_shared_with_branch(&state.db, candidate_chat, user_id).await? {
+        return Err(AppError::invalid_input("This turn is shared with a story branch — fork from it instead"));
     }
 
-    // 4. Re-run the full turn pipeline with the EDITED user input
-    process_story_turn(id, user_input, story_id, state, config_state, hint_state, app).await
+    let chat_id = select_candidate(&state.db, message_id).await?;
+    println!("[Orchestrator] Selected candidate id={} for chat {}", message_id, chat_id);
+    Ok(())
 }
 
 // ============================================================================
@@ -3093,21 +3438,25 @@ pub async fn regenerate_story_with_input(
 pub async fn get_compression_diagnostics(
     chat_id: i64,
     state: State<'_, OllamaState>,
-) -> Result<CompressionDiagnostics, String> {
-    let rows = sqlx::query(
-        "SELECT role, content FROM messages WHERE chat_id = ? ORDER BY timestamp ASC",
-    )
-    .bind(chat_id)
-    .fetch_all(&state.db)
-    .await
-    .map_err(|e| e.to_string())?;
-
-    let db_rows: Vec<(String, String)> = rows
-        .iter()
-        .map(|r| (r.get("role"), r.get("content")))
-        .collect();
+    config_state: State<'_, ConfigState>,
+) -> Result<CompressionDiagnostics, AppError> {
+    // Budgets depend on the context size of the story that owns this chat
+    let (app_settings, lore_token_budget) = {
+        let config = config_state.0.lock().map_err(|e| e.to_string())?;
+        (GenerationSettings::from_config(&config), config.lore_token_budget)
+    };
+    let story_id: Option<i64> = sqlx::query("SELECT id FROM story_premises WHERE chat_id = ?")
+        .bind(chat_id)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|e| e.to_string())?
+        .map(|r| r.get("id"));
+    let overrides = load_generation_overrides(&state.db, story_id).await?;
+    let num_ctx = app_settings.with_overrides(&overrides).effective_num_ctx();
 
-    let conversation = ConversationContext::from_db_rows(&db_rows);
+    let conversation = load_conversation_history(&state.db, chat_id, None)
+        .await?
+        .with_context_size(num_ctx as usize);
 
     let system_tokens = estimate_tokens(SYSTEM_PROMPT);
 
@@ -3127,7 +3476,22 @@ pub async fn get_compression_diagnostics(
         })
         .sum();
 
-    Ok(get_diagnostics(&conversation, system_tokens, char_tokens))
+    // Entries the recent turns would trigger on the next turn (before any input)
+    let lorebook = load_lorebook(&state.db, story_id, lore_token_budget).await?;
+    let mut diagnostics = get_diagnostics(&conversation, system_tokens, char_tokens);
+    diagnostics.lore_entries = lorebook.activate(&conversation.turns, "").fired;
+
+    Ok(diagnostics)
+}
+
+/// All stored compression summaries for a chat, oldest first, with the turn
+/// range each one covers. The turns themselves remain in `messages`.
+#[tauri::command]
+pub async fn get_context_summaries(
+    chat_id: i64,
+    state: State<'_, OllamaState>,
+) -> Result<Vec<StoredSummary>, AppError> {
+    Ok(list_summaries(&state.db, chat_id).await?)
 }
 
 // ============================================================================
@@ -3252,4 +3616,109 @@ mod tests {
 
         assert_eq!(estimate_character_db_tokens(&[]), 0);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_scene_layout_for_groups() {
+        assert_eq!(scene_regions(1), vec!["center"]);
+        assert_eq!(scene_regions(2), vec!["left", "right"]);
+        assert_eq!(scene_regions(4), vec!["left", "center", "right", "left-background"]);
+        assert_eq!(scene_character_limit(0), 1);
+        assert_eq!(scene_character_limit(50), MAX_SCENE_CHARACTERS);
+
+        assert_eq!(subject_count_tag(&["female"]), "1girl");
+        assert_eq!(subject_count_tag(&["male", "female"]), "1girl 1boy");
+        assert_eq!(subject_count_tag(&["female", "female", "male", "unknown"]), "2girls 1boy 1person");
+        assert_eq!(subject_count_tag(&["unknown", "unknown", "unknown"]), "3people");
+
+        assert_eq!(region_prompt_prefix("left"), "a person on the left side of the scene,");
+        assert_eq!(region_prompt_prefix("right-seated"), "a person seated on the right side of the scene,");
+        assert_eq!(region_prompt_prefix("center-background"), "a person in the background in the center of the scene,");
+    }
+
+    #[tokio::test]
+    async fn test_switching_candidates_restores_input_scene_and_roster() {
+        let db = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::migrations::migrate(&db, None).await.unwrap();
+        let insert = |sql: &'static str| {
+            let db = db.clone();
+            async move { sqlx::query(sql).execute(&db).await.unwrap().last_insert_rowid() }
+        };
+        let chat_id = insert("INSERT INTO chats (title) VALUES ('Story')").await;
+        let tavern = insert("INSERT INTO scenes (name) VALUES ('Tavern')").await;
+        let forest = insert("INSERT INTO scenes (name) VALUES ('Forest')").await;
+        let mira = insert("INSERT INTO characters (name) VALUES ('Mira')").await;
+        sqlx::query("INSERT INTO story_premises (title, description, chat_id, active_scene_id) VALUES ('S', '', ?, ?)")
+            .bind(chat_id)
+            .bind(tavern)
+            .execute(&db)
+            .await
+            .unwrap();
+
+        // What sync_scene_from_turn does for a turn: activate a scene, add to its roster
+        let enter = |scene_id: i64| {
+            let db = db.clone();
+            async move {
+                sqlx::query("UPDATE story_premises SET active_scene_id = ? WHERE chat_id = ?")
+                    .bind(scene_id)
+                    .bind(chat_id)
+                    .execute(&db)
+                    .await
+                    .unwrap();
+                sqlx::query("INSERT INTO scene_characters (scene_id, character_id) VALUES (?, ?)")
+                    .bind(scene_id)
+                    .bind(mira)
+                    .execute(&db)
+                    .await
+                    .unwrap();
+            }
+        };
+        let turn = |scene_id: i64| TurnRecord { scene_id: Some(scene_id), ..TurnRecord::from_raw("Mira waves.") };
+        let state = |db: sqlx::SqlitePool| async move {
+            let input: String = sqlx::query("SELECT content FROM messages WHERE role = 'user'")
+                .fetch_one(&db)
+                .await
+                .unwrap()
+                .get(0);
+            let scene: i64 = sqlx::query("SELECT active_scene_id FROM story_premises")
+                .fetch_one(&db)
+                .await
+                .unwrap()
+                .get(0);
+            let roster: Vec<i64> = sqlx::query("SELECT scene_id FROM scene_characters ORDER BY scene_id")
+                .fetch_all(&db)
+                .await
+                .unwrap()
+                .iter()
+                .map(|r| r.get(0))
+                .collect();
+            (input, scene, roster)
+        };
+
+        enter(tavern).await;
+        let first = save_turn_to_db(&db, chat_id, "Wait", "{}", None, &[mira], None, &turn(tavern))
+            .await
+            .unwrap();
+
+        // Regenerating with edited input leaves the turn alone until the new candidate is ready
+        let base = load_regeneration_base(&db, chat_id).await.unwrap();
+        assert_eq!(base.scene_id, Some(tavern));
+        assert_eq!(base.replaced_additions(tavern), &[mira]);
+        assert_eq!(state(db.clone()).await, ("Wait".to_string(), tavern, vec![tavern]));
+
+        rewind_for_regeneration(&db, chat_id, &base).await.unwrap();
+        enter(forest).await;
+        let second = save_turn_to_db(&db, chat_id, "Leave", "{}", None, &[mira], Some(base.user_id), &turn(forest))
+            .await
+            .unwrap();
+        assert_eq!(state(db.clone()).await, ("Leave".to_string(), forest, vec![forest]));
+
+        select_candidate(&db, first).await.unwrap();
+        assert_eq!(state(db.clone()).await, ("Wait".to_string(), tavern, vec![tavern]));
+        select_candidate(&db, second).await.unwrap();
+        assert_eq!(state(db.clone()).await, ("Leave".to_string(), forest, vec![forest]));
+    }
+}
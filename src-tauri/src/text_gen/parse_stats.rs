@@ -0,0 +1,176 @@
+// src-tauri/src/text_gen/parse_stats.rs
+//
+// Parse Status Statistics
+// =========================
+// Records the parse outcome (ok / partial / fallback) of every story turn so
+// we can compare how often structured output (a JSON schema sent as Ollama's
+// `format`) still ends in a fallback versus free-form generation.
+//
+// Rows live in the `parse_stats` table (created in migrations.rs). Recording is
+// best-effort: a failed insert is logged and never fails the turn.
+
+use serde::Serialize;
+use sqlx::Row;
+use tauri::State;
+
+use crate::error::AppError;
+use crate::state::OllamaState;
+
+// ============================================================================
+// RECORDING
+// ============================================================================
+
+/// One parse outcome, as written by the orchestrator after each turn.
+pub struct ParseRecord<'a> {
+    pub chat_id: i64,
+    pub story_id: Option<i64>,
+    /// "ok", "partial" or "fallback"
+    pub status: &'a str,
+    pub warning_count: usize,
+    /// Whether the request carried a JSON schema
+    pub structured: bool,
+    pub backend: &'a str,
+    pub model: &'a str,
+}
+
+/// Insert a parse outcome. Non-fatal on failure.
+pub async fn record_parse_status(pool: &sqlx::SqlitePool, record: &ParseRecord<'_>) {
+    let result = sqlx::query(
+        "INSERT INTO parse_stats \
+         (chat_id, story_id, status, warning_count, structured, backend, model) \
+         VALUES (?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(record.chat_id)
+    .bind(record.story_id)
+    .bind(record.status)
+    .bind(record.warning_count as i64)
+    .bind(record.structured)
+    .bind(record.backend)
+    .bind(record.model)
+    .execute(pool)
+    .await;
+
+    if let Err(e) = result {
+        println!("[ParseStats] Failed to record parse status: {}", e);
+    }
+}
+
+// ============================================================================
+// AGGREGATION
+// ============================================================================
+
+/// Outcome counts for one generation mode.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ParseStatusCounts {
+    pub total: i64,
+    pub ok: i64,
+    pub partial: i64,
+    pub fallback: i64,
+    /// fallback / total (0.0 when there are no turns)
+    pub fallback_rate: f64,
+}
+
+impl ParseStatusCounts {
+    fn add(&mut self, status: &str, count: i64) {
+        match status {
+            "ok" => self.ok += count,
+            "partial" => self.partial += count,
+            "fallback" => self.fallback += count,
+            _ => {}
+        }
+        self.total += count;
+        self.fallback_rate = if self.total > 0 {
+            self.fallback as f64 / self.total as f64
+        } else {
+            0.0
+        };
+    }
+}
+
+/// Parse statistics split by whether structured output was used.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ParseStats {
+    pub overall: ParseStatusCounts,
+    pub structured: ParseStatusCounts,
+    pub unstructured: ParseStatusCounts,
+}
+
+fn aggregate(rows: &[(String, bool, i64)]) -> ParseStats {
+    let mut stats = ParseStats::default();
+    for (status, structured, count) in rows {
+        stats.overall.add(status, *count);
+        if *structured {
+            stats.structured.add(status, *count);
+        } else {
+            stats.unstructured.add(status, *count);
+        }
+    }
+    stats
+}
+
+// ============================================================================
+// COMMANDS
+// ============================================================================
+
+/// Parse outcome counts for one story, or across all stories if `storyId` is null.
+///
+/// ## Frontend usage
+/// ```typescript
+/// const stats = await invoke('get_parse_stats', { storyId });
+/// console.log(stats.structured.fallback_rate, stats.unstructured.fallback_rate);
+/// ```
+#[tauri::command]
+pub async fn get_parse_stats(
+    story_id: Option<i64>,
+    state: State<'_, OllamaState>,
+) -> Result<ParseStats, AppError> {
+    let rows = sqlx::query(
+        "SELECT status, structured, COUNT(*) AS n FROM parse_stats \
+         WHERE ? IS NULL OR story_id = ? \
+         GROUP BY status, structured",
+    )
+    .bind(story_id)
+    .bind(story_id)
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| AppError::database(format!("Failed to load parse stats: {}", e)))?;
+
+    let counts: Vec<(String, bool, i64)> = rows
+        .iter()
+        .map(|r| (r.get("status"), r.get("structured"), r.get("n")))
+        .collect();
+
+    Ok(aggregate(&counts))
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aggregate_splits_by_mode() {
+        let rows = vec![
+            ("ok".to_string(), true, 18),
+            ("fallback".to_string(), true, 2),
+            ("ok".to_string(), false, 6),
+            ("partial".to_string(), false, 2),
+            ("fallback".to_string(), false, 2),
+        ];
+        let stats = aggregate(&rows);
+        assert_eq!(stats.overall.total, 30);
+        assert_eq!(stats.structured.fallback, 2);
+        assert!((stats.structured.fallback_rate - 0.1).abs() < 1e-9);
+        assert!((stats.unstructured.fallback_rate - 0.2).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_empty_stats_have_zero_rate() {
+        let stats = aggregate(&[]);
+        assert_eq!(stats.overall.total, 0);
+        assert_eq!(stats.overall.fallback_rate, 0.0);
+    }
+}
@@ -70,6 +70,22 @@ impl CharacterRegion {
         }
     }
 
+    /// Every named region, in prompt order (excludes `Other`).
+    pub fn known_variants() -> [Self; 10] {
+        [
+            Self::Left,
+            Self::Center,
+            Self::Right,
+            Self::LeftSeated,
+            Self::CenterSeated,
+            Self::RightSeated,
+            Self::LeftBackground,
+            Self::CenterBackground,
+            Self::RightBackground,
+            Self::OffScreen,
+        ]
+    }
+
     pub fn is_seated(&self) -> bool {
         matches!(self, Self::LeftSeated | Self::CenterSeated | Self::RightSeated)
     }
@@ -138,6 +154,21 @@ impl CharacterPose {
             Self::Custom(s) => s.as_str(),
         }
     }
+
+    /// Every named pose (excludes `Custom`).
+    pub fn known_variants() -> [Self; 9] {
+        [
+            Self::Standing,
+            Self::Sitting,
+            Self::LyingDown,
+            Self::Running,
+            Self::Kneeling,
+            Self::Leaning,
+            Self::Driving,
+            Self::Cooking,
+            Self::Fighting,
+        ]
+    }
 }
 
 impl fmt::Display for CharacterPose {
@@ -178,6 +209,11 @@ impl CharacterView {
         }
     }
 
+    /// Every named view (excludes `Other`).
+    pub fn known_variants() -> [Self; 4] {
+        [Self::Portrait, Self::UpperBody, Self::FullBody, Self::None]
+    }
+
     /// True if this view requires rendering an image of the character.
     pub fn needs_render(&self) -> bool {
         !matches!(self, Self::None)
@@ -345,6 +381,84 @@ pub struct LlmTurnOutput {
     pub generation_flags: Option<GenerationFlags>,
 }
 
+// ============================================================================
+// JSON SCHEMA — structured output
+// ============================================================================
+
+/// JSON schema for `LlmTurnOutput`, sent as Ollama's `format` (or an OpenAI
+/// `response_format`) so the server constrains decoding to valid turns.
+///
+/// Property order matches the system prompt — `story_json.response` comes
+/// first so prose can still be streamed while the rest is generated.
+/// Region, view and pose are restricted to the named enum variants. Every
+/// object lists all its properties as required and allows no others, as
+/// OpenAI's strict structured outputs demand.
+pub fn llm_turn_output_schema() -> serde_json::Value {
+    use serde_json::json;
+
+    fn string_enum(values: Vec<&str>) -> serde_json::Value {
+        json!({ "type": "string", "enum": values })
+    }
+    fn object(properties: serde_json::Value) -> serde_json::Value {
+        let required: Vec<String> = properties
+            .as_object()
+            .map(|p| p.keys().cloned().collect())
+            .unwrap_or_default();
+        json!({ "type": "object", "properties": properties, "required": required, "additionalProperties": false })
+    }
+
+    let string = json!({ "type": "string" });
+    let boolean = json!({ "type": "boolean" });
+
+    let regions = CharacterRegion::known_variants();
+    let views = CharacterView::known_variants();
+    let poses = CharacterPose::known_variants();
+
+    object(json!({
+        "turn_id": { "type": "integer" },
+        "story_json": object(json!({
+            "response": string,
+            "summary_hint": string,
+        })),
+        "scene_json": object(json!({
+            "location": string,
+            "location_type": string_enum(vec!["interior", "exterior"]),
+            "time_of_day": string,
+            "weather": string,
+            "lighting": string,
+            "mood": string,
+        })),
+        "characters_in_scene": {
+            "type": "array",
+            "items": object(json!({
+                "name": string,
+                "region": string_enum(regions.iter().map(|r| r.as_str()).collect()),
+                "view": string_enum(views.iter().map(|v| v.as_str()).collect()),
+                "pose": string_enum(poses.iter().map(|p| p.as_str()).collect()),
+                "action": string,
+                "expression": string,
+                "clothing": string,
+                "facing": string,
+            })),
+        },
+        "emotional_states": {
+            "type": "array",
+            "items": object(json!({
+                "name": string,
+                "current_emotion": string,
+                "emotion_intensity": string_enum(vec!["low", "medium", "high", "overwhelming"]),
+                "emotion_cause": string,
+                "lingering_emotions": { "type": "array", "items": string },
+            })),
+        },
+        "generation_flags": object(json!({
+            "generate_image": boolean,
+            "scene_changed": boolean,
+            "characters_changed": boolean,
+        })),
+    }))
+}
+
 // ============================================================================
 // PARSE RESULT
 // ============================================================================
@@ -670,7 +784,9 @@ fn empty_turn() -> LlmTurnOutput {
 // VALIDATION
 // ============================================================================
 
-fn validate_turn(turn: &LlmTurnOutput) -> Vec<String> {
+/// List everything missing or empty in a parsed turn. Also used by the
+/// self-repair loop (text_gen::repair) as the error list sent back to the model.
+pub fn validate_turn(turn: &LlmTurnOutput) -> Vec<String> {
     let mut warnings = Vec::new();
 
     if turn.story_json.is_none() {
@@ -710,6 +826,13 @@ fn validate_turn(turn: &LlmTurnOutput) -> Vec<String> {
 // TEXT HELPERS
 // ============================================================================
 
+/// Remove `<think>...</think>` blocks the model may emit before the JSON.
+pub fn strip_think_tags(text: &str) -> String {
+    static THINK_RE: std::sync::OnceLock<regex::Regex> = std::sync::OnceLock::new();
+    let re = THINK_RE.get_or_init(|| regex::Regex::new(r"(?s)<think>.*?</think>").unwrap());
+    re.replace_all(text, "").trim().to_string()
+}
+
 /// Replace literal newlines/carriage returns/tabs inside JSON string values
 /// with their `\n`/`\r`/`\t` escape sequences.
 ///
@@ -1162,4 +1285,37 @@ mod tests {
         let parsed: serde_json::Value = serde_json::from_str(&stripped).unwrap();
         assert_eq!(parsed["url"], "https://example.com/path");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_known_variants_round_trip() {
+        for r in CharacterRegion::known_variants() {
+            assert_eq!(CharacterRegion::from_str_loose(r.as_str()), r);
+        }
+        for v in CharacterView::known_variants() {
+            assert_eq!(CharacterView::from_str_loose(v.as_str()), v);
+        }
+        for p in CharacterPose::known_variants() {
+            assert_eq!(CharacterPose::from_str_loose(p.as_str()), p);
+        }
+    }
+
+    #[test]
+    fn test_turn_schema_shape() {
+        let schema = llm_turn_output_schema();
+        let keys: Vec<&String> = schema["properties"].as_object().unwrap().keys().collect();
+        // story_json must precede the scene data so prose streams first
+        assert_eq!(keys[0], "turn_id");
+        assert_eq!(keys[1], "story_json");
+        assert_eq!(schema["required"].as_array().unwrap().len(), 6);
+        assert_eq!(schema["additionalProperties"], false);
+        assert_eq!(schema["properties"]["story_json"]["additionalProperties"], false);
+
+        let character = &schema["properties"]["characters_in_scene"]["items"]["properties"];
+        assert!(character["region"]["enum"].as_array().unwrap().contains(&"left-seated".into()));
+        assert!(character["view"]["enum"].as_array().unwrap().contains(&"UPPER-BODY".into()));
+        assert!(character["pose"]["enum"].as_array().unwrap().contains(&"LYING-DOWN".into()));
+
+        let emotion = &schema["properties"]["emotional_states"]["items"]["properties"];
+        assert_eq!(emotion["lingering_emotions"]["type"], "array");
+    }
+}
@@ -2,12 +2,26 @@
 //
 // LLM configuration and system prompt for StoryEngine.
 
-/// The Ollama model name used for story generation.
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::config::AppConfig;
+use crate::text_gen::backend::SamplingOptions;
+use crate::text_gen::template::{resolve_template, PromptTemplate, TemplateFormat};
+
+/// Default model name used for story generation (AppConfig.story_model).
 pub const STORY_MODEL: &str = "Story_v27";
 
-/// Context window size passed to Ollama.
+/// Default context window size passed to the backend (AppConfig.num_ctx).
 pub const NUM_CTX: u32 = 8192;
 
+/// Default sampling temperature for story turns (AppConfig.temperature).
+pub const DEFAULT_TEMPERATURE: f64 = 0.8;
+
+/// Smallest context window we accept from config or a story override.
+/// Below this the system prompt + character DB alone would not fit.
+pub const MIN_NUM_CTX: u32 = 2048;
+
 /// Maximum number of tokens to generate per response.
 pub const NUM_PREDICT: u32 = 3072;
 
@@ -52,6 +66,163 @@ pub fn get_response_length_config(setting: &str) -> ResponseLengthConfig {
     }
 }
 
+// ============================================================================
+// GENERATION SETTINGS — model + sampler, app-wide with per-story overrides
+// ============================================================================
+
+/// Per-story overrides stored on story_premises. `None` = use the app setting.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GenerationOverrides {
+    pub model: Option<String>,
+    pub temperature: Option<f64>,
+    pub top_p: Option<f64>,
+    pub top_k: Option<u32>,
+    pub repeat_penalty: Option<f64>,
+    pub num_ctx: Option<u32>,
+    pub num_predict: Option<u32>,
+}
+
+/// The effective model and sampler settings for one story turn.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GenerationSettings {
+    pub model: String,
+    pub temperature: f64,
+    pub top_p: Option<f64>,
+    pub top_k: Option<u32>,
+    pub repeat_penalty: Option<f64>,
+    pub num_ctx: u32,
+    /// `None` = derive from the response_length setting.
+    pub num_predict: Option<u32>,
+    /// AppConfig.model_templates / custom_template, resolved against `model`
+    pub model_templates: HashMap<String, TemplateFormat>,
+    pub custom_template: PromptTemplate,
+}
+
+impl GenerationSettings {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            model: config.story_model.clone(),
+            temperature: config.temperature,
+            top_p: config.top_p,
+            top_k: config.top_k,
+            repeat_penalty: config.repeat_penalty,
+            num_ctx: config.num_ctx,
+            num_predict: config.num_predict,
+            model_templates: config.model_templates.clone(),
+            custom_template: config.custom_template.clone(),
+        }
+    }
+
+    /// Apply a story's overrides on top of the app-wide settings.
+    pub fn with_overrides(mut self, overrides: &GenerationOverrides) -> Self {
+        if let Some(model) = overrides.model.as_ref().filter(|m| !m.trim().is_empty()) {
+            self.model = model.trim().to_string();
+        }
+        if let Some(t) = overrides.temperature {
+            self.temperature = t;
+        }
+        if overrides.top_p.is_some() {
+            self.top_p = overrides.top_p;
+        }
+        if overrides.top_k.is_some() {
+            self.top_k = overrides.top_k;
+        }
+        if overrides.repeat_penalty.is_some() {
+            self.repeat_penalty = overrides.repeat_penalty;
+        }
+        if let Some(n) = overrides.num_ctx {
+            self.num_ctx = n;
+        }
+        if overrides.num_predict.is_some() {
+            self.num_predict = overrides.num_predict;
+        }
+        self
+    }
+
+    /// Context window actually sent to the backend (never below MIN_NUM_CTX).
+    pub fn effective_num_ctx(&self) -> u32 {
+        self.num_ctx.max(MIN_NUM_CTX)
+    }
+
+    /// Tokens to generate: the explicit setting, or the response-length default.
+    /// Capped at half the context window so the prompt always has room.
+    pub fn effective_num_predict(&self, length_config: &ResponseLengthConfig) -> u32 {
+        self.num_predict
+            .unwrap_or(length_config.num_predict)
+            .min(self.effective_num_ctx() / 2)
+    }
+
+    /// Maximum prompt size: whatever the response doesn't need.
+    pub fn max_prompt_tokens(&self, length_config: &ResponseLengthConfig) -> usize {
+        (self.effective_num_ctx() - self.effective_num_predict(length_config)) as usize
+    }
+
+    /// Chat template for raw prompts to the effective model.
+    pub fn prompt_template(&self) -> (TemplateFormat, PromptTemplate) {
+        resolve_template(&self.model, &self.model_templates, &self.custom_template)
+    }
+
+    pub fn sampling_options(&self, length_config: &ResponseLengthConfig) -> SamplingOptions {
+        SamplingOptions {
+            num_ctx: Some(self.effective_num_ctx()),
+            num_predict: Some(self.effective_num_predict(length_config)),
+            temperature: Some(self.temperature),
+            top_p: self.top_p,
+            top_k: self.top_k,
+            repeat_penalty: self.repeat_penalty,
+            stop: Vec::new(),
+        }
+    }
+}
+
+// ============================================================================
+// STORY BIBLE — per-story premise and world rules, injected as the premise
+// ============================================================================
+
+/// A story's premise plus the world facts the model must respect.
+/// Stored on story_premises; edited via `update_story_bible`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StoryBible {
+    pub title: String,
+    pub premise: String,
+    #[serde(default)]
+    pub genre: Option<String>,
+    #[serde(default)]
+    pub tone: Option<String>,
+    #[serde(default)]
+    pub setting: Option<String>,
+    #[serde(default)]
+    pub themes: Vec<String>,
+    /// Rules the story must never break ("magic always has a cost")
+    #[serde(default)]
+    pub hard_rules: Vec<String>,
+}
+
+impl StoryBible {
+    /// Text for the STORY PREMISE section of the system block. Empty fields
+    /// are left out so a bare title + premise renders as before.
+    pub fn to_prompt_section(&self) -> String {
+        let mut lines = vec![
+            format!("Title: {}", self.title),
+            format!("Premise: {}", self.premise),
+        ];
+        let fields = [("Genre", &self.genre), ("Tone", &self.tone), ("Setting", &self.setting)];
+        for (label, value) in fields {
+            if let Some(v) = value.as_deref().map(str::trim).filter(|v| !v.is_empty()) {
+                lines.push(format!("{}: {}", label, v));
+            }
+        }
+        if !self.themes.is_empty() {
+            lines.push(format!("Themes: {}", self.themes.join(", ")));
+        }
+        if !self.hard_rules.is_empty() {
+            lines.push("HARD RULES (never break these):".to_string());
+            lines.extend(self.hard_rules.iter().map(|r| format!("- {}", r)));
+        }
+        lines.join("\n")
+    }
+}
+
 /// Timeout in seconds for each Ollama request attempt.
 pub const OLLAMA_REQUEST_TIMEOUT_SECS: u64 = 120;
 
@@ -126,3 +297,59 @@ scene_json.location should be the PLACE NAME only (e.g. "Marcus's apartment", "t
 time_of_day, weather, lighting, and mood in scene_json can change freely turn to turn — they describe the current moment, not the place itself.
 Set scene_changed to true ONLY when the characters physically move to a DIFFERENT location.
 If a [SCENE CHANGE] directive is included in the player input, write a natural transition to that location. Do not acknowledge the directive directly — weave the scene change seamlessly into the narrative."#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_story_overrides_replace_app_settings() {
+        let base = GenerationSettings::from_config(&AppConfig::default());
+        assert_eq!(base.model, STORY_MODEL);
+        assert_eq!(base.num_ctx, NUM_CTX);
+
+        let overrides = GenerationOverrides {
+            model: Some("qwen3:14b".to_string()),
+            num_ctx: Some(32768),
+            top_k: Some(40),
+            ..Default::default()
+        };
+        let settings = base.with_overrides(&overrides);
+        assert_eq!(settings.model, "qwen3:14b");
+        assert_eq!(settings.num_ctx, 32768);
+        assert_eq!(settings.top_k, Some(40));
+        assert_eq!(settings.temperature, DEFAULT_TEMPERATURE);
+    }
+
+    #[test]
+    fn test_num_predict_capped_by_context() {
+        let mut settings = GenerationSettings::from_config(&AppConfig::default());
+        let long = get_response_length_config("long");
+        assert_eq!(settings.effective_num_predict(&long), 4096);
+        assert_eq!(settings.max_prompt_tokens(&long), 4096);
+
+        settings.num_ctx = 4096;
+        assert_eq!(settings.effective_num_predict(&long), 2048);
+
+        settings.num_ctx = 512;
+        assert_eq!(settings.effective_num_ctx(), MIN_NUM_CTX);
+    }
+
+    #[test]
+    fn test_story_bible_section() {
+        let mut bible = StoryBible {
+            title: "Ashfall".to_string(),
+            premise: "A city under a volcano.".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(bible.to_prompt_section(), "Title: Ashfall\nPremise: A city under a volcano.");
+
+        bible.tone = Some("grim".to_string());
+        bible.genre = Some("  ".to_string());
+        bible.hard_rules = vec!["No one leaves the city.".to_string()];
+        let section = bible.to_prompt_section();
+        assert!(section.contains("\nTone: grim"));
+        assert!(!section.contains("Genre"));
+        assert!(section.ends_with("HARD RULES (never break these):\n- No one leaves the city."));
+    }
+}
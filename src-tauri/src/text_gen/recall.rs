@@ -0,0 +1,504 @@
+// src-tauri/src/text_gen/recall.rs
+//
+// Retrieval-Augmented Long-Term Recall
+// ======================================
+// Summaries keep the gist of old turns, but not the specifics a player may
+// bring back up ("remember the letter from the library?"). This module keeps
+// an embedding per past turn (summary_hint + story text) in the
+// `turn_embeddings` table and, each turn, finds the past turns closest to the
+// player's input. `build_compressed_context` then injects them as a
+// "RELEVANT MEMORIES" block within its token budget.
+//
+// Embeddings come from an `Embedder` — Ollama's `/api/embeddings` in the app,
+// a stub in tests. Vectors are stored as little-endian f32 BLOBs and compared
+// with cosine similarity in Rust (a chat has at most a few thousand turns).
+// Every step is best-effort: a missing embedding model only disables recall.
+
+use futures_util::future::BoxFuture;
+use futures_util::FutureExt;
+use serde::Serialize;
+use serde_json::{json, Value};
+use sqlx::Row;
+use std::time::Duration;
+use tauri::State;
+
+use crate::commands::branch::{IN_LINEAGE, LINEAGE_CTE};
+use crate::config::{AppConfig, ConfigState};
+use crate::error::AppError;
+use crate::state::OllamaState;
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+/// Turns embedded per story turn; older history is indexed over several turns.
+pub const MAX_INDEX_PER_TURN: usize = 24;
+
+/// Matches below this cosine similarity are never recalled.
+pub const MIN_RECALL_SCORE: f32 = 0.35;
+
+/// Characters of story text embedded per turn.
+const MAX_EMBED_CHARS: usize = 1500;
+
+const EMBED_TIMEOUT_SECS: u64 = 30;
+
+// ============================================================================
+// EMBEDDER
+// ============================================================================
+
+/// Anything that turns text into a vector.
+pub trait Embedder: Send + Sync {
+    /// Model name, stored with each vector so a model change triggers re-indexing.
+    fn model(&self) -> &str;
+
+    fn embed<'a>(&'a self, text: &'a str) -> BoxFuture<'a, Result<Vec<f32>, String>>;
+}
+
+/// Ollama `/api/embeddings`.
+pub struct OllamaEmbedder {
+    client: reqwest::Client,
+    base_url: String,
+    model: String,
+}
+
+impl Embedder for OllamaEmbedder {
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn embed<'a>(&'a self, text: &'a str) -> BoxFuture<'a, Result<Vec<f32>, String>> {
+        async move {
+            let res = self
+                .client
+                .post(format!("{}/api/embeddings", self.base_url.trim_end_matches('/')))
+                .timeout(Duration::from_secs(EMBED_TIMEOUT_SECS))
+                .json(&json!({ "model": self.model, "prompt": text }))
+                .send()
+                .await
+                .map_err(|e| format!("Embedding request failed: {}", e))?;
+
+            if !res.status().is_success() {
+                let status = res.status();
+                let body = res.text().await.unwrap_or_default();
+                return Err(format!("Embedding endpoint returned {}: {}", status, body));
+            }
+
+            let body: Value = res
+                .json()
+                .await
+                .map_err(|e| format!("Invalid embedding response: {}", e))?;
+            let vector: Vec<f32> = body["embedding"]
+                .as_array()
+                .ok_or("Embedding response has no 'embedding' array")?
+                .iter()
+                .filter_map(|v| v.as_f64().map(|f| f as f32))
+                .collect();
+
+            if vector.is_empty() {
+                Err("Embedding endpoint returned an empty vector".to_string())
+            } else {
+                Ok(vector)
+            }
+        }
+        .boxed()
+    }
+}
+
+/// Build the embedder from the app config, or None when recall is disabled.
+/// An empty `embedding_url` reuses the Ollama URL.
+pub fn embedder_from_config(config: &AppConfig, client: &reqwest::Client) -> Option<Box<dyn Embedder>> {
+    if !config.recall_enabled || config.embedding_model.trim().is_empty() {
+        return None;
+    }
+    let base_url = if config.embedding_url.trim().is_empty() {
+        config.ollama_url.clone()
+    } else {
+        config.embedding_url.clone()
+    };
+    Some(Box::new(OllamaEmbedder {
+        client: client.clone(),
+        base_url,
+        model: config.embedding_model.clone(),
+    }))
+}
+
+// ============================================================================
+// VECTORS
+// ============================================================================
+
+fn encode_vector(vector: &[f32]) -> Vec<u8> {
+    vector.iter().flat_map(|f| f.to_le_bytes()).collect()
+}
+
+fn decode_vector(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect()
+}
+
+/// Cosine similarity; 0.0 for mismatched or zero vectors.
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() || a.is_empty() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+/// The text embedded (and later injected) for one turn.
+fn turn_excerpt(user_input: &str, hint: &str, story_text: &str) -> String {
+    let story: String = story_text.chars().take(MAX_EMBED_CHARS).collect();
+    if hint.is_empty() {
+        format!("Player: {}\n{}", user_input, story)
+    } else {
+        format!("{}\nPlayer: {}\n{}", hint, user_input, story)
+    }
+}
+
+// ============================================================================
+// INDEX
+// ============================================================================
+
+/// Embed turns of a chat that have no vector for the current model yet,
+/// oldest first, at most `limit` per call. Returns how many were indexed.
+pub async fn index_chat_turns(
+    db: &sqlx::SqlitePool,
+    embedder: &dyn Embedder,
+    chat_id: i64,
+    limit: usize,
+) -> Result<usize, String> {
+    let rows = sqlx::query(&format!(
+        "{}SELECT m.id, u.content AS user_input, t.summary_hint, t.story_text, te.model AS indexed_model \
+         FROM turns t \
+         INNER JOIN messages m ON m.id = t.assistant_message_id {} \
+         INNER JOIN messages u ON u.id = t.user_message_id \
+         LEFT JOIN turn_embeddings te ON te.message_id = m.id \
+         WHERE m.is_selected = 1 ORDER BY m.id ASC",
+        LINEAGE_CTE, IN_LINEAGE
+    ))
+    .bind(chat_id)
+    .fetch_all(db)
+    .await
+    .map_err(|e| format!("Failed to load messages for indexing: {}", e))?;
+
+    // (assistant message id, turn number, excerpt) for turns needing a vector
+    let mut pending: Vec<(i64, i64, String)> = Vec::new();
+    for (i, row) in rows.iter().enumerate() {
+        let indexed_model: Option<String> = row.get("indexed_model");
+        if indexed_model.as_deref() != Some(embedder.model()) {
+            let excerpt = turn_excerpt(
+                &row.get::<String, _>("user_input"),
+                &row.get::<String, _>("summary_hint"),
+                &row.get::<String, _>("story_text"),
+            );
+            pending.push((row.get("id"), i as i64 + 1, excerpt));
+        }
+    }
+
+    let mut indexed = 0;
+    for (message_id, turn_number, text) in pending.into_iter().take(limit) {
+        let vector = embedder.embed(&text).await?;
+        sqlx::query(
+            "INSERT OR REPLACE INTO turn_embeddings \
+             (message_id, chat_id, turn_number, model, text, embedding) \
+             VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(message_id)
+        .bind(chat_id)
+        .bind(turn_number)
+        .bind(embedder.model())
+        .bind(&text)
+        .bind(encode_vector(&vector))
+        .execute(db)
+        .await
+        .map_err(|e| format!("Failed to store embedding: {}", e))?;
+        indexed += 1;
+    }
+
+    if indexed > 0 {
+        println!("[Recall] Indexed {} turn(s) for chat_id={}", indexed, chat_id);
+    }
+    Ok(indexed)
+}
+
+// ============================================================================
+// SEARCH
+// ============================================================================
+
+/// A past turn recalled for the current input.
+#[derive(Debug, Clone, Serialize)]
+pub struct RecalledTurn {
+    pub turn_number: usize,
+    pub message_id: i64,
+    pub text: String,
+    pub score: f32,
+}
+
+/// Highest-scoring candidates above MIN_RECALL_SCORE, best first.
+fn rank(query: &[f32], candidates: Vec<(RecalledTurn, Vec<f32>)>, top_k: usize) -> Vec<RecalledTurn> {
+    let mut scored: Vec<RecalledTurn> = candidates
+        .into_iter()
+        .map(|(mut turn, vector)| {
+            turn.score = cosine_similarity(query, &vector);
+            turn
+        })
+        .filter(|t| t.score >= MIN_RECALL_SCORE)
+        .collect();
+    scored.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+    scored.truncate(top_k);
+    scored
+}
+
+/// The `top_k` indexed turns most similar to `query`. Only turns with an
+/// assistant message id below `before_message_id` are considered, so turns
+/// already in the prompt are not recalled twice.
+pub async fn recall_turns(
+    db: &sqlx::SqlitePool,
+    embedder: &dyn Embedder,
+    chat_id: i64,
+    query: &str,
+    top_k: usize,
+    before_message_id: Option<i64>,
+) -> Result<Vec<RecalledTurn>, String> {
+    if top_k == 0 || query.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let rows = sqlx::query(&format!(
+        "{}SELECT te.message_id, te.turn_number, te.text, te.embedding FROM turn_embeddings te \
+         INNER JOIN messages m ON m.id = te.message_id {} \
+         WHERE te.model = ? AND m.is_selected = 1 \
+           AND (? IS NULL OR te.message_id < ?)",
+        LINEAGE_CTE, IN_LINEAGE
+    ))
+    .bind(chat_id)
+    .bind(embedder.model())
+    .bind(before_message_id)
+    .bind(before_message_id)
+    .fetch_all(db)
+    .await
+    .map_err(|e| format!("Failed to load embeddings: {}", e))?;
+
+    if rows.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let query_vector = embedder.embed(query).await?;
+    let candidates = rows
+        .iter()
+        .map(|r| {
+            let turn = RecalledTurn {
+                turn_number: r.get::<i64, _>("turn_number") as usize,
+                message_id: r.get("message_id"),
+                text: r.get("text"),
+                score: 0.0,
+            };
+            (turn, decode_vector(&r.get::<Vec<u8>, _>("embedding")))
+        })
+        .collect();
+
+    Ok(rank(&query_vector, candidates, top_k))
+}
+
+// ============================================================================
+// COMMANDS
+// ============================================================================
+
+/// Search a chat's past turns the same way the context builder does.
+///
+/// ## Frontend usage
+/// ```typescript
+/// const hits = await invoke('search_story_memories', { chatId, query: 'the library letter', topK: 5 });
+/// ```
+#[tauri::command]
+pub async fn search_story_memories(
+    chat_id: i64,
+    query: String,
+    top_k: Option<usize>,
+    state: State<'_, OllamaState>,
+    config_state: State<'_, ConfigState>,
+) -> Result<Vec<RecalledTurn>, AppError> {
+    let (embedder, default_top_k) = {
+        let config = config_state.0.lock().map_err(|e| e.to_string())?;
+        (embedder_from_config(&config, &state.client), config.recall_top_k)
+    };
+    let embedder = embedder.ok_or_else(|| AppError::invalid_input("Long-term recall is disabled in settings"))?;
+
+    index_chat_turns(&state.db, embedder.as_ref(), chat_id, MAX_INDEX_PER_TURN).await?;
+    Ok(recall_turns(&state.db, embedder.as_ref(), chat_id, &query, top_k.unwrap_or(default_top_k), None).await?)
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text_gen::turns::{insert_turn, TurnRecord};
+
+    /// Bag-of-words embedder over a fixed vocabulary, under a model name.
+    struct StubEmbedder(&'static str);
+
+    const VOCAB: &[&str] = &["letter", "library", "sword", "river", "tavern"];
+
+    fn bag_of_words(text: &str) -> Vec<f32> {
+        let lower = text.to_lowercase();
+        VOCAB.iter().map(|w| lower.matches(w).count() as f32).collect()
+    }
+
+    impl Embedder for StubEmbedder {
+        fn model(&self) -> &str {
+            self.0
+        }
+
+        fn embed<'a>(&'a self, text: &'a str) -> BoxFuture<'a, Result<Vec<f32>, String>> {
+            async move { Ok(bag_of_words(text)) }.boxed()
+        }
+    }
+
+    fn candidate(turn_number: usize, text: &str) -> (RecalledTurn, Vec<f32>) {
+        let vector = bag_of_words(text);
+        let turn = RecalledTurn { turn_number, message_id: turn_number as i64 * 2, text: text.to_string(), score: 0.0 };
+        (turn, vector)
+    }
+
+    #[test]
+    fn test_vector_round_trip() {
+        let v = vec![0.25f32, -1.5, 3.0];
+        assert_eq!(decode_vector(&encode_vector(&v)), v);
+    }
+
+    #[test]
+    fn test_cosine_similarity() {
+        assert!((cosine_similarity(&[1.0, 0.0], &[2.0, 0.0]) - 1.0).abs() < 1e-6);
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]), 0.0);
+        assert_eq!(cosine_similarity(&[1.0], &[1.0, 2.0]), 0.0);
+    }
+
+    #[test]
+    fn test_rank_recalls_the_referenced_turn() {
+        let candidates = vec![
+            candidate(3, "They crossed the river at dawn."),
+            candidate(12, "Elena hid the letter in the library."),
+            candidate(20, "A brawl broke out in the tavern."),
+        ];
+        let query = bag_of_words("remember the letter from the library?");
+        let hits = rank(&query, candidates, 2);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].turn_number, 12);
+    }
+
+    /// Add a user + assistant message pair with its turn row. Returns the
+    /// assistant message id.
+    async fn add_turn(db: &sqlx::SqlitePool, chat_id: i64, user: &str, story: &str, selected: bool) -> i64 {
+        let insert = |role: &'static str, content: String| async move {
+            sqlx::query("INSERT INTO messages (chat_id, role, content, is_selected) VALUES (?, ?, ?, ?)")
+                .bind(chat_id)
+                .bind(role)
+                .bind(content)
+                .bind(selected)
+                .execute(db)
+                .await
+                .unwrap()
+                .last_insert_rowid()
+        };
+        let user_message_id = insert("user", user.to_string()).await;
+        let assistant_message_id = insert("assistant", story.to_string()).await;
+        let turn = TurnRecord {
+            chat_id,
+            user_message_id: Some(user_message_id),
+            assistant_message_id,
+            story_text: story.to_string(),
+            ..TurnRecord::from_raw(story)
+        };
+        insert_turn(db, &turn).await.unwrap();
+        assistant_message_id
+    }
+
+    #[tokio::test]
+    async fn test_index_and_recall_turns() {
+        let db = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::migrations::migrate(&db, None).await.unwrap();
+        let chat_id = sqlx::query("INSERT INTO chats (title) VALUES ('Story')")
+            .execute(&db)
+            .await
+            .unwrap()
+            .last_insert_rowid();
+
+        let river = add_turn(&db, chat_id, "Cross", "They crossed the river at dawn.", true).await;
+        let letter = add_turn(&db, chat_id, "Read", "Elena hid the letter in the library.", true).await;
+        // A discarded regeneration of the same turn is never indexed
+        add_turn(&db, chat_id, "Read", "The library letter burned.", false).await;
+        add_turn(&db, chat_id, "Drink", "A brawl broke out in the tavern.", true).await;
+
+        let stub = StubEmbedder("stub");
+        assert_eq!(index_chat_turns(&db, &stub, chat_id, 2).await.unwrap(), 2);
+        assert_eq!(index_chat_turns(&db, &stub, chat_id, 10).await.unwrap(), 1);
+        assert_eq!(index_chat_turns(&db, &stub, chat_id, 10).await.unwrap(), 0);
+
+        let hits = recall_turns(&db, &stub, chat_id, "the letter in the library", 3, None).await.unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!((hits[0].message_id, hits[0].turn_number), (letter, 2));
+
+        // Turns from the cutoff on are already in the prompt
+        let hits = recall_turns(&db, &stub, chat_id, "the letter in the library", 3, Some(letter)).await.unwrap();
+        assert!(hits.is_empty());
+
+        // A new embedding model re-indexes everything; old vectors aren't searched
+        let v2 = StubEmbedder("stub-v2");
+        assert!(recall_turns(&db, &v2, chat_id, "river", 3, None).await.unwrap().is_empty());
+        assert_eq!(index_chat_turns(&db, &v2, chat_id, 10).await.unwrap(), 3);
+        let hits = recall_turns(&db, &v2, chat_id, "river", 3, None).await.unwrap();
+        assert_eq!(hits.iter().map(|h| h.message_id).collect::<Vec<_>>(), vec![river]);
+
+        // A branch forked after the first turn only sees the shared turn
+        let story_id = sqlx::query("INSERT INTO story_premises (title, description) VALUES ('S', '')")
+            .execute(&db)
+            .await
+            .unwrap()
+            .last_insert_rowid();
+        let branch_id = sqlx::query("INSERT INTO chats (title) VALUES ('Branch')")
+            .execute(&db)
+            .await
+            .unwrap()
+            .last_insert_rowid();
+        sqlx::query(
+            "INSERT INTO story_branches (story_id, chat_id, parent_chat_id, shared_until_id, shared_turns, name) \
+             VALUES (?, ?, ?, ?, 1, 'B')",
+        )
+        .bind(story_id)
+        .bind(branch_id)
+        .bind(chat_id)
+        .bind(river)
+        .execute(&db)
+        .await
+        .unwrap();
+        let own = add_turn(&db, branch_id, "Search", "The river hides a sword.", true).await;
+        assert_eq!(index_chat_turns(&db, &v2, branch_id, 10).await.unwrap(), 1);
+        let hits = recall_turns(&db, &v2, branch_id, "the river and the library letter", 3, None).await.unwrap();
+        let mut ids: Vec<i64> = hits.iter().map(|h| h.message_id).collect();
+        ids.sort_unstable();
+        assert_eq!(ids, vec![river, own]);
+    }
+
+    #[test]
+    fn test_turn_excerpt_leads_with_hint() {
+        assert_eq!(
+            turn_excerpt("Open it", "Door opened.", "The door creaks."),
+            "Door opened.\nPlayer: Open it\nThe door creaks."
+        );
+        assert_eq!(turn_excerpt("Open it", "", "The door creaks."), "Player: Open it\nThe door creaks.");
+    }
+}
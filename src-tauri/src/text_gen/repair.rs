@@ -0,0 +1,377 @@
+// src-tauri/src/text_gen/repair.rs
+//
+// Self-Repair Loop for Story Turn Output
+// ========================================
+// When a story turn comes back truncated or malformed, ask the model to fix
+// it instead of accepting a degraded Partial/Fallback turn.
+//
+// Two repair requests, at most MAX_REPAIR_ATTEMPTS in total per turn:
+//   - "continue" — output stopped at num_predict (or the JSON was left open).
+//                  The truncated text is appended to the original prompt and
+//                  the model finishes the JSON from where it stopped.
+//   - "fix"      — output is complete but invalid. The `validate_turn`
+//                  errors are sent back with the output and the model returns
+//                  corrected JSON.
+//
+// A repaired candidate only replaces the current text if it parses better.
+// Every attempt and its outcome is returned as a note for `parse_warnings`.
+
+use crate::cancellation::GenerationJob;
+use crate::text_gen::backend::{ChatMessage, GenerateRequest, PromptInput, TextBackend};
+use crate::text_gen::context::estimate_tokens;
+use crate::text_gen::parser::{parse_llm_output, strip_think_tags, validate_turn, ParseStatus, ParsedTurn};
+
+/// Upper bound on repair requests per turn (continue + fix combined).
+pub const MAX_REPAIR_ATTEMPTS: u32 = 2;
+
+/// Fewest tokens a continuation must be allowed to generate to be worth sending.
+const MIN_CONTINUE_TOKENS: u32 = 64;
+
+/// Validation warnings that don't justify another LLM call.
+const BENIGN_WARNINGS: &[&str] = &["characters_in_scene is empty", "turn_id is 0"];
+
+const CONTINUE_INSTRUCTION: &str = "Your previous response was cut off. Continue EXACTLY where it stopped — \
+do not repeat anything already written and do not start a new object. Output only the remaining JSON.";
+
+const FIX_PROMPT: &str = "Your previous story turn was not valid. Problems found:\n{{ERRORS}}\n\n\
+Here is what you wrote:\n{{OUTPUT}}\n\n\
+Rewrite it as ONE complete JSON object with ALL fields: turn_id, story_json, scene_json, \
+characters_in_scene, emotional_states, generation_flags. Keep the story text and details unchanged; \
+only fix the structure. Respond with raw JSON only, no preamble.";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RepairKind {
+    Continue,
+    Fix,
+}
+
+impl RepairKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Continue => "continue",
+            Self::Fix => "fix",
+        }
+    }
+}
+
+/// Result of the repair loop.
+pub struct RepairOutcome {
+    /// The best output seen (the original if no repair helped)
+    pub text: String,
+    /// One line per attempt, for the turn's `parse_warnings`
+    pub notes: Vec<String>,
+}
+
+// ============================================================================
+// DECISION
+// ============================================================================
+
+/// Output looks cut off: the backend said so, or a JSON object was opened
+/// but never closed.
+pub fn looks_truncated(text: &str, backend_truncated: bool) -> bool {
+    let trimmed = text.trim();
+    backend_truncated || (trimmed.starts_with('{') && !trimmed.ends_with('}'))
+}
+
+/// Validation errors worth asking the model to fix.
+fn blocking_errors(parsed: &ParsedTurn) -> Vec<String> {
+    let mut errors: Vec<String> = validate_turn(&parsed.turn)
+        .into_iter()
+        .filter(|w| !BENIGN_WARNINGS.iter().any(|b| w.starts_with(b)))
+        .collect();
+    if matches!(parsed.status, ParseStatus::Fallback) {
+        errors.insert(0, "output was not valid JSON".to_string());
+    }
+    errors
+}
+
+/// Which repair (if any) a parsed output needs.
+pub fn repair_kind(parsed: &ParsedTurn, truncated: bool) -> Option<RepairKind> {
+    if truncated {
+        Some(RepairKind::Continue)
+    } else if !blocking_errors(parsed).is_empty() {
+        Some(RepairKind::Fix)
+    } else {
+        None
+    }
+}
+
+/// Lower is better: (status rank, number of blocking errors).
+fn quality(parsed: &ParsedTurn) -> (u8, usize) {
+    let rank = match parsed.status {
+        ParseStatus::Ok => 0,
+        ParseStatus::Partial(_) => 1,
+        ParseStatus::Fallback => 2,
+    };
+    (rank, blocking_errors(parsed).len())
+}
+
+fn status_label(parsed: &ParsedTurn) -> &'static str {
+    match parsed.status {
+        ParseStatus::Ok => "ok",
+        ParseStatus::Partial(_) => "partial",
+        ParseStatus::Fallback => "fallback",
+    }
+}
+
+// ============================================================================
+// REQUESTS
+// ============================================================================
+
+/// Estimated tokens of a prompt, using the context builder's estimate.
+fn prompt_tokens(prompt: &PromptInput) -> usize {
+    match prompt {
+        PromptInput::Raw(text) | PromptInput::Instruction(text) => estimate_tokens(text),
+        PromptInput::Chat(messages) => messages.iter().map(|m| estimate_tokens(&m.content) + 4).sum(),
+    }
+}
+
+/// Re-send the original prompt with the truncated output appended.
+/// Schema constraints are dropped — they would force a fresh object.
+///
+/// With a known `num_ctx`, `num_predict` is capped at what's left of the
+/// context after the prompt and partial output. None when fewer than
+/// MIN_CONTINUE_TOKENS are left — the server would truncate the prompt.
+fn continue_request(base: &GenerateRequest, partial: &str) -> Option<GenerateRequest> {
+    let prompt = match &base.prompt {
+        // Raw prompts end with the assistant header, so the model simply
+        // keeps writing after the partial output.
+        PromptInput::Raw(prompt) => PromptInput::Raw(format!("{}{}", prompt, partial)),
+        PromptInput::Instruction(prompt) => PromptInput::Instruction(format!(
+            "{}\n\n{}\n\n{}",
+            prompt, partial, CONTINUE_INSTRUCTION
+        )),
+        PromptInput::Chat(messages) => {
+            let mut messages = messages.clone();
+            messages.push(ChatMessage { role: "assistant".to_string(), content: partial.to_string() });
+            messages.push(ChatMessage { role: "user".to_string(), content: CONTINUE_INSTRUCTION.to_string() });
+            PromptInput::Chat(messages)
+        }
+    };
+    let mut options = base.options.clone();
+    if let Some(num_ctx) = options.num_ctx {
+        let used = u32::try_from(prompt_tokens(&prompt)).unwrap_or(u32::MAX);
+        let remaining = num_ctx.saturating_sub(used);
+        if remaining < MIN_CONTINUE_TOKENS {
+            return None;
+        }
+        options.num_predict = Some(options.num_predict.map_or(remaining, |n| n.min(remaining)));
+    }
+    Some(GenerateRequest {
+        prompt,
+        options,
+        json_mode: false,
+        json_schema: None,
+        stream: false,
+        ..base.clone()
+    })
+}
+
+/// Ask for corrected JSON, listing the validation errors.
+fn fix_request(base: &GenerateRequest, output: &str, errors: &[String]) -> GenerateRequest {
+    let error_list = errors
+        .iter()
+        .map(|e| format!("- {}", e))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let prompt = FIX_PROMPT
+        .replace("{{ERRORS}}", &error_list)
+        .replace("{{OUTPUT}}", output);
+    GenerateRequest {
+        prompt: PromptInput::Instruction(prompt),
+        json_mode: base.json_schema.is_none(),
+        stream: false,
+        ..base.clone()
+    }
+}
+
+/// Join a continuation onto the truncated text. Chat servers sometimes
+/// restart the whole object instead of continuing — use it alone then.
+fn merge_continuation(partial: &str, continuation: &str) -> String {
+    let cont = continuation.trim_start();
+    if cont.starts_with('{') && cont.contains("\"story_json\"") {
+        cont.to_string()
+    } else {
+        format!("{}{}", partial, continuation)
+    }
+}
+
+// ============================================================================
+// LOOP
+// ============================================================================
+
+/// Run up to MAX_REPAIR_ATTEMPTS continue/fix requests against `backend`.
+///
+/// `base_request` is the story request that produced `text`; `truncated`
+/// is whether that output looked cut off.
+pub async fn repair_turn_output(
+    backend: &dyn TextBackend,
+    base_request: &GenerateRequest,
+    job: &GenerationJob,
+    text: String,
+    truncated: bool,
+) -> Result<RepairOutcome, String> {
+    let mut text = text;
+    let mut truncated = truncated;
+    let mut notes = Vec::new();
+
+    for attempt in 1..=MAX_REPAIR_ATTEMPTS {
+        let parsed = parse_llm_output(&text);
+        let kind = match repair_kind(&parsed, truncated) {
+            Some(kind) => kind,
+            None => break,
+        };
+        job.check()?;
+
+        let errors = blocking_errors(&parsed);
+        let request = match kind {
+            RepairKind::Continue => match continue_request(base_request, &text) {
+                Some(request) => request,
+                None => {
+                    notes.push(format!(
+                        "Repair {}/{} (continue) skipped: no room left in the context",
+                        attempt, MAX_REPAIR_ATTEMPTS
+                    ));
+                    // The next attempt asks for a fix instead
+                    truncated = false;
+                    continue;
+                }
+            },
+            RepairKind::Fix => fix_request(base_request, &text, &errors),
+        };
+        println!(
+            "[Repair] Attempt {}/{} ({}) — status {}, {} error(s)",
+            attempt,
+            MAX_REPAIR_ATTEMPTS,
+            kind.as_str(),
+            status_label(&parsed),
+            errors.len()
+        );
+
+        let res = match job.run(backend.generate(&request, None)).await {
+            Ok(res) => res,
+            Err(e) => {
+                job.check()?;
+                notes.push(format!(
+                    "Repair {}/{} ({}) failed: {}",
+                    attempt, MAX_REPAIR_ATTEMPTS, kind.as_str(), e
+                ));
+                break;
+            }
+        };
+
+        let output = strip_think_tags(&res.text);
+        let candidate = match kind {
+            RepairKind::Continue => merge_continuation(&text, &output),
+            RepairKind::Fix => output,
+        };
+        let repaired = parse_llm_output(&candidate);
+
+        if quality(&repaired) < quality(&parsed) || (kind == RepairKind::Continue && quality(&repaired) == quality(&parsed)) {
+            notes.push(format!(
+                "Repair {}/{} ({}): {} -> {}",
+                attempt,
+                MAX_REPAIR_ATTEMPTS,
+                kind.as_str(),
+                status_label(&parsed),
+                status_label(&repaired)
+            ));
+            text = candidate;
+            truncated = looks_truncated(&text, res.truncated);
+        } else {
+            notes.push(format!(
+                "Repair {}/{} ({}): result was {} — kept original",
+                attempt,
+                MAX_REPAIR_ATTEMPTS,
+                kind.as_str(),
+                status_label(&repaired)
+            ));
+            // A continuation that didn't help won't help twice; try fixing instead
+            truncated = false;
+        }
+    }
+
+    Ok(RepairOutcome { text, notes })
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMPLETE: &str = r#"{"turn_id":3,"story_json":{"response":"Rain fell.","summary_hint":"Rain."},"scene_json":{"location":"street"},"characters_in_scene":[{"name":"Elena"}],"emotional_states":[],"generation_flags":{"generate_image":false}}"#;
+
+    #[test]
+    fn test_looks_truncated() {
+        assert!(looks_truncated(r#"{"turn_id":1,"story_json":{"response":"The door"#, false));
+        assert!(!looks_truncated(COMPLETE, false));
+        assert!(looks_truncated(COMPLETE, true));
+        assert!(!looks_truncated("Plain prose.", false));
+    }
+
+    #[test]
+    fn test_repair_kind() {
+        assert_eq!(repair_kind(&parse_llm_output(COMPLETE), false), None);
+        assert_eq!(
+            repair_kind(&parse_llm_output(r#"{"turn_id":1,"story_json":{"response":"Hi""#), true),
+            Some(RepairKind::Continue)
+        );
+        // Missing scene_json and generation_flags
+        let partial = r#"{"turn_id":1,"story_json":{"response":"Hi","summary_hint":""},"characters_in_scene":[{"name":"A"}]}"#;
+        assert_eq!(repair_kind(&parse_llm_output(partial), false), Some(RepairKind::Fix));
+    }
+
+    #[test]
+    fn test_merge_continuation() {
+        let partial = &COMPLETE[..60];
+        let merged = merge_continuation(partial, &COMPLETE[60..]);
+        assert_eq!(merged, COMPLETE);
+        assert!(matches!(parse_llm_output(&merged).status, ParseStatus::Ok));
+        // A restarted object replaces the partial text
+        assert_eq!(merge_continuation(partial, COMPLETE), COMPLETE);
+    }
+
+    #[test]
+    fn test_continue_request_appends_partial_to_raw_prompt() {
+        let mut base = GenerateRequest::new("m", PromptInput::Raw("<|im_start|>assistant\n".into()), 30);
+        base.json_schema = Some(serde_json::json!({"type": "object"}));
+        let req = continue_request(&base, "{\"turn_id\":1").unwrap();
+        match req.prompt {
+            PromptInput::Raw(p) => assert!(p.ends_with("assistant\n{\"turn_id\":1")),
+            _ => panic!("expected raw prompt"),
+        }
+        assert!(req.json_schema.is_none());
+    }
+
+    #[test]
+    fn test_continue_request_fits_the_context() {
+        let mut base = GenerateRequest::new("m", PromptInput::Raw("p".repeat(400)), 30);
+        base.options.num_ctx = Some(400);
+        base.options.num_predict = Some(1000);
+        // 400-char prompt + 400-char partial ≈ 200 tokens, 200 left
+        let req = continue_request(&base, &"x".repeat(400)).unwrap();
+        assert_eq!(req.options.num_predict, Some(200));
+
+        // No room left: skip rather than overflow the context
+        assert!(continue_request(&base, &"x".repeat(1200)).is_none());
+
+        // Unknown context size: left to the server
+        base.options.num_ctx = None;
+        let req = continue_request(&base, &"x".repeat(1200)).unwrap();
+        assert_eq!(req.options.num_predict, Some(1000));
+    }
+
+    #[test]
+    fn test_fix_request_lists_errors() {
+        let base = GenerateRequest::new("m", PromptInput::Raw(String::new()), 30);
+        let req = fix_request(&base, "{}", &["scene_json is missing".to_string()]);
+        match req.prompt {
+            PromptInput::Instruction(p) => assert!(p.contains("- scene_json is missing")),
+            _ => panic!("expected instruction prompt"),
+        }
+        assert!(req.json_mode);
+    }
+}
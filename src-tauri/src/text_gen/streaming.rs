@@ -0,0 +1,533 @@
+// src-tauri/src/text_gen/streaming.rs
+//
+// Streaming Story Turns
+// ======================
+// Reads streamed LLM responses (Ollama NDJSON, OpenAI-compatible SSE) and
+// pulls the narrative prose
+// out of `story_json.response` while the rest of the turn JSON is still being
+// generated. Each decoded fragment is forwarded to the frontend as a
+// `story-token` event so writers see the story appear as it is written.
+//
+// The full response is still accumulated and returned in the same shape as a
+// non-streaming call, so parsing and persistence downstream are unchanged.
+
+use futures_util::StreamExt;
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::OnceLock;
+
+// ============================================================================
+// EVENT PAYLOAD
+// ============================================================================
+
+/// Tauri event emitted for every chunk of decoded story prose.
+pub const STORY_TOKEN_EVENT: &str = "story-token";
+
+/// Payload of a `story-token` event.
+#[derive(Debug, Clone, Serialize)]
+pub struct StoryTokenPayload {
+    pub chat_id: i64,
+    /// Newly decoded prose since the previous event.
+    pub text: String,
+    /// True when the frontend should discard any prose received so far
+    /// (sent at the start of every request attempt, including retries).
+    pub reset: bool,
+}
+
+// ============================================================================
+// INCREMENTAL PROSE EXTRACTOR
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ExtractState {
+    /// Waiting for `"story_json": { ... "response": "` to appear.
+    Seeking,
+    /// Inside the `response` string value; decoding as it arrives.
+    InString,
+    /// The closing quote of the `response` value has been seen.
+    Done,
+}
+
+/// Incrementally extracts the `story_json.response` string from a partially
+/// received LLM turn JSON.
+///
+/// Feed raw model output with [`push`](Self::push); each call returns the
+/// newly decoded prose (JSON escapes resolved), or an empty string if nothing
+/// new is available yet. Incomplete escape sequences at the end of the buffer
+/// are held back until the next chunk completes them.
+pub struct StoryTextExtractor {
+    buffer: String,
+    /// Byte offset in `buffer` of the next character to decode.
+    cursor: usize,
+    state: ExtractState,
+    /// Pending UTF-16 high surrogate from a `\uD8xx` escape.
+    high_surrogate: Option<u16>,
+}
+
+impl StoryTextExtractor {
+    pub fn new() -> Self {
+        Self {
+            buffer: String::new(),
+            cursor: 0,
+            state: ExtractState::Seeking,
+            high_surrogate: None,
+        }
+    }
+
+    /// True once the full `response` string has been decoded.
+    pub fn is_done(&self) -> bool {
+        self.state == ExtractState::Done
+    }
+
+    /// Append a chunk of raw model output and return any newly decoded prose.
+    pub fn push(&mut self, chunk: &str) -> String {
+        self.buffer.push_str(chunk);
+
+        if self.state == ExtractState::Seeking {
+            match find_response_value_start(&self.buffer) {
+                Some(start) => {
+                    self.cursor = start;
+                    self.state = ExtractState::InString;
+                }
+                None => return String::new(),
+            }
+        }
+
+        if self.state == ExtractState::InString {
+            self.decode_available()
+        } else {
+            String::new()
+        }
+    }
+
+    /// Decode string content from `cursor` up to the end of the buffer,
+    /// stopping early at the closing quote or an incomplete escape.
+    fn decode_available(&mut self) -> String {
+        let mut out = String::new();
+        let bytes = self.buffer.as_bytes();
+        let mut pos = self.cursor;
+
+        while pos < bytes.len() {
+            // A high surrogate only pairs with a `\u` low surrogate right after it
+            let unicode_escape = bytes[pos] == b'\\' && bytes.get(pos + 1).map_or(true, |&b| b == b'u');
+            if !unicode_escape && self.high_surrogate.take().is_some() {
+                out.push('\u{FFFD}');
+            }
+
+            match bytes[pos] {
+                b'"' => {
+                    self.state = ExtractState::Done;
+                    pos += 1;
+                    break;
+                }
+                b'\\' => {
+                    let Some(&esc) = bytes.get(pos + 1) else { break };
+                    let simple = match esc {
+                        b'n' => Some('\n'),
+                        b't' => Some('\t'),
+                        b'r' => Some('\r'),
+                        b'b' => Some('\u{08}'),
+                        b'f' => Some('\u{0C}'),
+                        b'"' => Some('"'),
+                        b'\\' => Some('\\'),
+                        b'/' => Some('/'),
+                        _ => None,
+                    };
+                    if let Some(c) = simple {
+                        out.push(c);
+                        pos += 2;
+                        continue;
+                    }
+                    if esc == b'u' {
+                        let hex = &bytes[pos + 2..bytes.len().min(pos + 6)];
+                        if !hex.iter().all(u8::is_ascii_hexdigit) {
+                            // Invalid escape — keep the `u` and decode on from there
+                            if self.high_surrogate.take().is_some() {
+                                out.push('\u{FFFD}');
+                            }
+                            out.push('u');
+                            pos += 2;
+                            continue;
+                        }
+                        if hex.len() < 4 {
+                            break; // rest of the escape hasn't arrived yet
+                        }
+                        let unit = u16::from_str_radix(&self.buffer[pos + 2..pos + 6], 16).unwrap_or(0xFFFD);
+                        pos += 6;
+                        match (self.high_surrogate.take(), unit) {
+                            (Some(high), 0xDC00..=0xDFFF) => {
+                                let code = 0x10000
+                                    + (((high as u32) - 0xD800) << 10)
+                                    + ((unit as u32) - 0xDC00);
+                                out.push(char::from_u32(code).unwrap_or('\u{FFFD}'));
+                            }
+                            (pending, u) => {
+                                if pending.is_some() {
+                                    out.push('\u{FFFD}');
+                                }
+                                if (0xD800..=0xDBFF).contains(&u) {
+                                    self.high_surrogate = Some(u);
+                                } else {
+                                    // Lone low surrogates aren't chars either
+                                    out.push(char::from_u32(u as u32).unwrap_or('\u{FFFD}'));
+                                }
+                            }
+                        }
+                        continue;
+                    }
+                    // Unknown escape — keep the character literally
+                    let ch = self.buffer[pos + 1..].chars().next().unwrap_or('\u{FFFD}');
+                    out.push(ch);
+                    pos += 1 + ch.len_utf8();
+                }
+                _ => {
+                    // Copy a whole UTF-8 character (buffer is always valid UTF-8)
+                    let ch = self.buffer[pos..].chars().next().unwrap_or('\u{FFFD}');
+                    out.push(ch);
+                    pos += ch.len_utf8();
+                }
+            }
+        }
+
+        self.cursor = pos;
+        out
+    }
+}
+
+impl Default for StoryTextExtractor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Locate the byte offset just past the opening quote of the
+/// `story_json.response` string value, if it has arrived yet.
+fn find_response_value_start(buffer: &str) -> Option<usize> {
+    static RE: OnceLock<regex::Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| {
+        regex::Regex::new(r#"(?s)"story_json"\s*:\s*\{.*?"response"\s*:\s*""#).unwrap()
+    });
+    re.find(buffer).map(|m| m.end())
+}
+
+// ============================================================================
+// RESPONSE STREAM READERS
+// ============================================================================
+
+/// Split a streamed HTTP body into lines and hand each one to `on_line`.
+/// A trailing line without a newline is delivered at the end of the body.
+async fn for_each_line<F>(res: reqwest::Response, mut on_line: F) -> Result<(), String>
+where
+    F: FnMut(&[u8]) -> Result<(), String>,
+{
+    let mut stream = res.bytes_stream();
+    let mut line_buf: Vec<u8> = Vec::new();
+
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| format!("LLM stream interrupted: {}", e))?;
+        line_buf.extend_from_slice(&chunk);
+
+        while let Some(nl) = line_buf.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = line_buf.drain(..=nl).collect();
+            on_line(&line)?;
+        }
+    }
+
+    if !line_buf.is_empty() {
+        on_line(&line_buf)?;
+    }
+    Ok(())
+}
+
+/// Read an Ollama NDJSON body (`/api/generate` or `/api/chat`).
+///
+/// `on_text` is called with every text fragment as it arrives. The returned
+/// value mirrors a non-streaming `/api/generate` result: the final `done`
+/// chunk (with timing/eval counters) plus the concatenated `response`.
+///
+/// Also accepts a non-streaming body (`"stream": false`), which is just a
+/// single JSON object and is handled as a one-line stream.
+pub async fn read_ollama_stream<F>(res: reqwest::Response, mut on_text: F) -> Result<Value, String>
+where
+    F: FnMut(&str),
+{
+    let mut full_text = String::new();
+    let mut final_chunk: Option<Value> = None;
+
+    for_each_line(res, |line| {
+        handle_ndjson_line(line, &mut full_text, &mut final_chunk, &mut on_text)
+    })
+    .await?;
+
+    let mut result = final_chunk
+        .ok_or_else(|| "Ollama stream ended without a final chunk".to_string())?;
+    result["response"] = Value::String(full_text);
+    Ok(result)
+}
+
+/// Everything collected from an OpenAI-compatible SSE stream.
+#[derive(Debug, Default)]
+pub struct SseOutput {
+    /// Concatenated `delta.content` text
+    pub text: String,
+    /// The `usage` object, if the server sent one
+    pub usage: Option<Value>,
+    /// `choices[0].finish_reason` of the last chunk ("stop", "length", ...)
+    pub finish_reason: Option<String>,
+}
+
+/// Read an OpenAI-compatible server-sent-events body (`"stream": true`).
+///
+/// `on_text` is called with every `choices[0].delta.content` fragment.
+pub async fn read_openai_sse_stream<F>(
+    res: reqwest::Response,
+    mut on_text: F,
+) -> Result<SseOutput, String>
+where
+    F: FnMut(&str),
+{
+    let mut output = SseOutput::default();
+
+    for_each_line(res, |line| handle_sse_line(line, &mut output, &mut on_text)).await?;
+
+    Ok(output)
+}
+
+/// Parse one SSE line (`data: {...}`), forwarding its delta text.
+fn handle_sse_line<F>(
+    line: &[u8],
+    output: &mut SseOutput,
+    on_text: &mut F,
+) -> Result<(), String>
+where
+    F: FnMut(&str),
+{
+    let line = String::from_utf8_lossy(line);
+    let Some(data) = line.trim().strip_prefix("data:") else {
+        // Comments (": keep-alive"), event names and blank separators
+        return Ok(());
+    };
+    let data = data.trim();
+    if data.is_empty() || data == "[DONE]" {
+        return Ok(());
+    }
+
+    let chunk: Value = serde_json::from_str(data)
+        .map_err(|e| format!("Failed to parse LLM stream chunk: {}", e))?;
+
+    if let Some(err) = chunk.get("error") {
+        let msg = err["message"].as_str().or_else(|| err.as_str()).unwrap_or("unknown error");
+        return Err(format!("LLM server error: {}", msg));
+    }
+
+    let delta = &chunk["choices"][0];
+    let piece = delta["delta"]["content"]
+        .as_str()
+        .or_else(|| delta["text"].as_str())
+        .unwrap_or("");
+    if !piece.is_empty() {
+        output.text.push_str(piece);
+        on_text(piece);
+    }
+
+    if let Some(reason) = delta["finish_reason"].as_str() {
+        output.finish_reason = Some(reason.to_string());
+    }
+    if chunk["usage"].is_object() {
+        output.usage = Some(chunk["usage"].clone());
+    }
+    Ok(())
+}
+
+/// Parse one NDJSON line, forwarding its text fragment and remembering the
+/// final `done: true` chunk.
+fn handle_ndjson_line<F>(
+    line: &[u8],
+    full_text: &mut String,
+    final_chunk: &mut Option<Value>,
+    on_text: &mut F,
+) -> Result<(), String>
+where
+    F: FnMut(&str),
+{
+    let line = String::from_utf8_lossy(line);
+    let line = line.trim();
+    if line.is_empty() {
+        return Ok(());
+    }
+
+    let chunk: Value = serde_json::from_str(line)
+        .map_err(|e| format!("Failed to parse Ollama response: {}", e))?;
+
+    if let Some(err) = chunk["error"].as_str() {
+        return Err(format!("Ollama error: {}", err));
+    }
+
+    // /api/generate streams `response`; /api/chat streams `message.content`
+    let piece = chunk["response"]
+        .as_str()
+        .or_else(|| chunk["message"]["content"].as_str());
+    if let Some(piece) = piece {
+        if !piece.is_empty() {
+            full_text.push_str(piece);
+            on_text(piece);
+        }
+    }
+
+    if chunk["done"].as_bool().unwrap_or(false) {
+        *final_chunk = Some(chunk);
+    }
+    Ok(())
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed_all(chunks: &[&str]) -> (String, bool) {
+        let mut ex = StoryTextExtractor::new();
+        let mut out = String::new();
+        for c in chunks {
+            out.push_str(&ex.push(c));
+        }
+        (out, ex.is_done())
+    }
+
+    #[test]
+    fn test_extractor_whole_document() {
+        let raw = r#"{"turn_id": 3, "story_json": {"response": "The door creaks open.", "summary_hint": "door"}}"#;
+        let (text, done) = feed_all(&[raw]);
+        assert_eq!(text, "The door creaks open.");
+        assert!(done);
+    }
+
+    #[test]
+    fn test_extractor_token_by_token() {
+        let raw = r#"{"story_json": {"response": "Rain \"taps\" on glass.\nShe waits.", "summary_hint": "x"}}"#;
+        let chunks: Vec<String> = raw.chars().map(|c| c.to_string()).collect();
+        let refs: Vec<&str> = chunks.iter().map(|s| s.as_str()).collect();
+        let (text, done) = feed_all(&refs);
+        assert_eq!(text, "Rain \"taps\" on glass.\nShe waits.");
+        assert!(done);
+    }
+
+    #[test]
+    fn test_extractor_split_escape() {
+        let (text, done) = feed_all(&[
+            r#"{"story_json": {"response": "a\"#,
+            r#"nb\u00"#,
+            r#"e9c"#,
+        ]);
+        assert_eq!(text, "a\nbéc");
+        assert!(!done);
+    }
+
+    #[test]
+    fn test_extractor_surrogate_pair() {
+        let (text, _) = feed_all(&[r#"{"story_json": {"response": "\ud83d\ude00!""#]);
+        assert_eq!(text, "😀!");
+    }
+
+    #[test]
+    fn test_extractor_invalid_unicode_escape() {
+        // Non-hex text right after `\u`, including a multi-byte character
+        let (text, done) = feed_all(&[r#"{"story_json": {"response": "a\u12"#, r#"é\uzz b""#]);
+        assert_eq!(text, "au12éuzz b");
+        assert!(done);
+    }
+
+    #[test]
+    fn test_extractor_unpaired_surrogates() {
+        let (text, done) = feed_all(&[
+            r#"{"story_json": {"response": "\ud83dx \ude00 \ud83d\u0041 \ud83d"#,
+            r#"""#,
+        ]);
+        assert_eq!(text, "\u{FFFD}x \u{FFFD} \u{FFFD}A \u{FFFD}");
+        assert!(done);
+    }
+
+    #[test]
+    fn test_extractor_surrogate_pair_split_across_chunks() {
+        let (text, _) = feed_all(&[r#"{"story_json": {"response": "\ud83d"#, r#"\"#, r#"ude00!""#]);
+        assert_eq!(text, "😀!");
+    }
+
+    #[test]
+    fn test_extractor_summary_hint_first() {
+        let raw = r#"{"story_json": {"summary_hint": "hint", "response": "Prose."}}"#;
+        let (text, _) = feed_all(&[raw]);
+        assert_eq!(text, "Prose.");
+    }
+
+    #[test]
+    fn test_extractor_ignores_text_after_close() {
+        let mut ex = StoryTextExtractor::new();
+        assert_eq!(ex.push(r#"{"story_json": {"response": "Hi""#), "Hi");
+        assert_eq!(ex.push(r#", "summary_hint": "more text"}}"#), "");
+    }
+
+    #[test]
+    fn test_extractor_no_story_json() {
+        let (text, done) = feed_all(&["Once upon a time, ", "there was no JSON."]);
+        assert!(text.is_empty());
+        assert!(!done);
+    }
+
+    #[test]
+    fn test_handle_ndjson_line_accumulates() {
+        let mut full = String::new();
+        let mut last = None;
+        let mut seen: Vec<String> = Vec::new();
+        let mut cb = |s: &str| seen.push(s.to_string());
+
+        handle_ndjson_line(br#"{"response":"{\"a\"","done":false}"#, &mut full, &mut last, &mut cb).unwrap();
+        handle_ndjson_line(br#"{"response":":1}","done":false}"#, &mut full, &mut last, &mut cb).unwrap();
+        handle_ndjson_line(br#"{"response":"","done":true,"eval_count":7}"#, &mut full, &mut last, &mut cb).unwrap();
+
+        assert_eq!(full, r#"{"a":1}"#);
+        assert_eq!(seen.len(), 2);
+        assert_eq!(last.unwrap()["eval_count"], 7);
+    }
+
+    #[test]
+    fn test_handle_ndjson_line_chat_message() {
+        let mut full = String::new();
+        let mut last = None;
+        handle_ndjson_line(
+            br#"{"message":{"role":"assistant","content":"Hello"},"done":true}"#,
+            &mut full,
+            &mut last,
+            &mut |_| {},
+        )
+        .unwrap();
+        assert_eq!(full, "Hello");
+        assert!(last.is_some());
+    }
+
+    #[test]
+    fn test_handle_sse_lines() {
+        let mut out = SseOutput::default();
+        let mut cb = |_: &str| {};
+
+        handle_sse_line(br#"data: {"choices":[{"delta":{"content":"Once "}}]}"#, &mut out, &mut cb).unwrap();
+        handle_sse_line(b": keep-alive", &mut out, &mut cb).unwrap();
+        handle_sse_line(br#"data: {"choices":[{"delta":{"content":"upon"},"finish_reason":"length"}],"usage":{"prompt_tokens":12,"completion_tokens":2}}"#, &mut out, &mut cb).unwrap();
+        handle_sse_line(b"data: [DONE]", &mut out, &mut cb).unwrap();
+
+        assert_eq!(out.text, "Once upon");
+        assert_eq!(out.usage.unwrap()["prompt_tokens"], 12);
+        assert_eq!(out.finish_reason.as_deref(), Some("length"));
+    }
+
+    #[test]
+    fn test_handle_ndjson_line_error() {
+        let mut full = String::new();
+        let mut last = None;
+        let result = handle_ndjson_line(br#"{"error":"model not found"}"#, &mut full, &mut last, &mut |_| {});
+        assert!(result.unwrap_err().contains("model not found"));
+    }
+}
@@ -0,0 +1,288 @@
+// src-tauri/src/text_gen/template.rs
+//
+// Chat Template Formats for Raw Prompts
+// =======================================
+// Raw-prompt backends (Ollama with `"raw": true`) receive a fully templated
+// string, so the turn delimiters must match what the model was trained on.
+// ChatML tokens sent to a Llama 3 finetune produce garbage.
+//
+// A `PromptTemplate` is the set of strings wrapped around each role's turn.
+// Built-ins cover ChatML, Llama 3, Mistral `[INST]`, Gemma and Alpaca; a
+// user-defined template lives in AppConfig.custom_template.
+//
+// Selection is per model: AppConfig.model_templates maps a model name to a
+// format. Models not listed there are detected from their name, falling back
+// to ChatML (the format of the bundled StoryEngine modelfile).
+//
+// Templates without a system role (Mistral, Gemma, Alpaca) render the system
+// block as plain text or a leading user turn; it still comes first, so the
+// KV-cache-friendly section order in context::assemble_prompt_string holds.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// ============================================================================
+// FORMATS
+// ============================================================================
+
+/// Template families selectable in config.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TemplateFormat {
+    ChatMl,
+    Llama3,
+    Mistral,
+    Gemma,
+    Alpaca,
+    /// AppConfig.custom_template
+    Custom,
+}
+
+impl TemplateFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::ChatMl => "chatml",
+            Self::Llama3 => "llama3",
+            Self::Mistral => "mistral",
+            Self::Gemma => "gemma",
+            Self::Alpaca => "alpaca",
+            Self::Custom => "custom",
+        }
+    }
+}
+
+/// Guess the format from an Ollama / GGUF model name. Unknown names get
+/// ChatML (Qwen, the bundled modelfile, most roleplay finetunes).
+pub fn detect_format(model: &str) -> TemplateFormat {
+    let name = model.to_lowercase();
+    if ["llama3", "llama-3", "llama_3"].iter().any(|k| name.contains(k)) {
+        TemplateFormat::Llama3
+    } else if ["mistral", "mixtral", "ministral"].iter().any(|k| name.contains(k)) {
+        TemplateFormat::Mistral
+    } else if name.contains("gemma") {
+        TemplateFormat::Gemma
+    } else if name.contains("alpaca") {
+        TemplateFormat::Alpaca
+    } else {
+        TemplateFormat::ChatMl
+    }
+}
+
+// ============================================================================
+// TEMPLATE
+// ============================================================================
+
+/// Strings wrapped around each turn of a raw prompt.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PromptTemplate {
+    /// Emitted once, before the system block
+    #[serde(default)]
+    pub bos: String,
+    #[serde(default)]
+    pub system_prefix: String,
+    #[serde(default)]
+    pub system_suffix: String,
+    #[serde(default)]
+    pub user_prefix: String,
+    #[serde(default)]
+    pub user_suffix: String,
+    #[serde(default)]
+    pub assistant_prefix: String,
+    #[serde(default)]
+    pub assistant_suffix: String,
+    /// Stop sequences sent with raw prompts
+    #[serde(default)]
+    pub stop: Vec<String>,
+}
+
+fn s(text: &str) -> String {
+    text.to_string()
+}
+
+impl PromptTemplate {
+    pub fn chatml() -> Self {
+        Self {
+            bos: String::new(),
+            system_prefix: s("<|im_start|>system\n"),
+            system_suffix: s("<|im_end|>"),
+            user_prefix: s("<|im_start|>user\n"),
+            user_suffix: s("<|im_end|>"),
+            assistant_prefix: s("<|im_start|>assistant\n"),
+            assistant_suffix: s("<|im_end|>"),
+            stop: vec![s("<|im_end|>")],
+        }
+    }
+
+    pub fn llama3() -> Self {
+        Self {
+            bos: s("<|begin_of_text|>"),
+            system_prefix: s("<|start_header_id|>system<|end_header_id|>\n\n"),
+            system_suffix: s("<|eot_id|>"),
+            user_prefix: s("<|start_header_id|>user<|end_header_id|>\n\n"),
+            user_suffix: s("<|eot_id|>"),
+            assistant_prefix: s("<|start_header_id|>assistant<|end_header_id|>\n\n"),
+            assistant_suffix: s("<|eot_id|>"),
+            stop: vec![s("<|eot_id|>")],
+        }
+    }
+
+    /// Mistral has no system role — the system block is plain text before
+    /// the first `[INST]`.
+    pub fn mistral() -> Self {
+        Self {
+            bos: s("<s>"),
+            system_prefix: String::new(),
+            system_suffix: s("\n\n"),
+            user_prefix: s("[INST] "),
+            user_suffix: s(" [/INST]"),
+            assistant_prefix: String::new(),
+            assistant_suffix: s("</s>"),
+            stop: vec![s("</s>"), s("[INST]")],
+        }
+    }
+
+    /// Gemma has no system role — the system block is sent as a user turn.
+    pub fn gemma() -> Self {
+        Self {
+            bos: s("<bos>"),
+            system_prefix: s("<start_of_turn>user\n"),
+            system_suffix: s("<end_of_turn>\n"),
+            user_prefix: s("<start_of_turn>user\n"),
+            user_suffix: s("<end_of_turn>\n"),
+            assistant_prefix: s("<start_of_turn>model\n"),
+            assistant_suffix: s("<end_of_turn>\n"),
+            stop: vec![s("<end_of_turn>")],
+        }
+    }
+
+    pub fn alpaca() -> Self {
+        Self {
+            bos: String::new(),
+            system_prefix: String::new(),
+            system_suffix: s("\n\n"),
+            user_prefix: s("### Instruction:\n"),
+            user_suffix: s("\n\n"),
+            assistant_prefix: s("### Response:\n"),
+            assistant_suffix: s("\n\n"),
+            stop: vec![s("### Instruction:")],
+        }
+    }
+
+    /// Built-in template for a format (`None` for Custom).
+    pub fn builtin(format: TemplateFormat) -> Option<Self> {
+        match format {
+            TemplateFormat::ChatMl => Some(Self::chatml()),
+            TemplateFormat::Llama3 => Some(Self::llama3()),
+            TemplateFormat::Mistral => Some(Self::mistral()),
+            TemplateFormat::Gemma => Some(Self::gemma()),
+            TemplateFormat::Alpaca => Some(Self::alpaca()),
+            TemplateFormat::Custom => None,
+        }
+    }
+
+    /// A template needs at least some role markers to be usable.
+    pub fn is_usable(&self) -> bool {
+        !self.user_prefix.is_empty() || !self.assistant_prefix.is_empty()
+    }
+
+    /// BOS + the system turn. This is the stable prefix of every prompt.
+    pub fn system(&self, content: &str) -> String {
+        format!("{}{}{}{}", self.bos, self.system_prefix, content, self.system_suffix)
+    }
+
+    pub fn user(&self, content: &str) -> String {
+        format!("{}{}{}", self.user_prefix, content, self.user_suffix)
+    }
+
+    pub fn assistant(&self, content: &str) -> String {
+        format!("{}{}{}", self.assistant_prefix, content, self.assistant_suffix)
+    }
+
+    /// Opens the assistant turn the model should write.
+    pub fn generation_prompt(&self) -> &str {
+        &self.assistant_prefix
+    }
+}
+
+// ============================================================================
+// SELECTION
+// ============================================================================
+
+/// The template for `model`: the configured format if listed in
+/// `model_templates`, otherwise detected from the name. An unusable custom
+/// template falls back to ChatML.
+pub fn resolve_template(
+    model: &str,
+    model_templates: &HashMap<String, TemplateFormat>,
+    custom: &PromptTemplate,
+) -> (TemplateFormat, PromptTemplate) {
+    let format = model_templates
+        .get(model)
+        .or_else(|| model_templates.get(model.split(':').next().unwrap_or(model)))
+        .copied()
+        .unwrap_or_else(|| detect_format(model));
+
+    match PromptTemplate::builtin(format) {
+        Some(template) => (format, template),
+        None if custom.is_usable() => (format, custom.clone()),
+        None => {
+            println!("[Template] Custom template for {} has no role markers — using ChatML", model);
+            (TemplateFormat::ChatMl, PromptTemplate::chatml())
+        }
+    }
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_format() {
+        assert_eq!(detect_format("llama3.1:8b-instruct"), TemplateFormat::Llama3);
+        assert_eq!(detect_format("Meta-Llama-3-8B.gguf"), TemplateFormat::Llama3);
+        assert_eq!(detect_format("mistral-nemo:12b"), TemplateFormat::Mistral);
+        assert_eq!(detect_format("gemma2:9b"), TemplateFormat::Gemma);
+        assert_eq!(detect_format("Story_v27"), TemplateFormat::ChatMl);
+    }
+
+    #[test]
+    fn test_resolve_prefers_config_then_tagless_name() {
+        let mut map = HashMap::new();
+        map.insert("Story_v27".to_string(), TemplateFormat::Llama3);
+        map.insert("mymodel".to_string(), TemplateFormat::Alpaca);
+        let custom = PromptTemplate::default();
+
+        assert_eq!(resolve_template("Story_v27", &map, &custom).0, TemplateFormat::Llama3);
+        assert_eq!(resolve_template("mymodel:q4", &map, &custom).0, TemplateFormat::Alpaca);
+        assert_eq!(resolve_template("gemma2:9b", &map, &custom).0, TemplateFormat::Gemma);
+    }
+
+    #[test]
+    fn test_custom_template_falls_back_when_empty() {
+        let mut map = HashMap::new();
+        map.insert("m".to_string(), TemplateFormat::Custom);
+
+        let (format, template) = resolve_template("m", &map, &PromptTemplate::default());
+        assert_eq!(format, TemplateFormat::ChatMl);
+        assert_eq!(template, PromptTemplate::chatml());
+
+        let custom = PromptTemplate { user_prefix: s("USER: "), assistant_prefix: s("BOT: "), ..Default::default() };
+        let (format, template) = resolve_template("m", &map, &custom);
+        assert_eq!(format, TemplateFormat::Custom);
+        assert_eq!(template.user("hi"), "USER: hi");
+    }
+
+    #[test]
+    fn test_llama3_turns() {
+        let t = PromptTemplate::llama3();
+        assert_eq!(
+            t.system("Rules"),
+            "<|begin_of_text|><|start_header_id|>system<|end_header_id|>\n\nRules<|eot_id|>"
+        );
+        assert_eq!(t.generation_prompt(), "<|start_header_id|>assistant<|end_header_id|>\n\n");
+    }
+}
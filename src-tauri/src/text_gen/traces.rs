@@ -0,0 +1,291 @@
+// src-tauri/src/text_gen/traces.rs
+//
+// Turn Traces
+// ===========
+// A debugging record of every story turn: the prompt exactly as sent, its
+// per-section token breakdown, the raw LLM response, parse warnings, failed
+// attempts and timings (including the KV-cache telemetry the orchestrator
+// logs). Turns that fail after all retries are traced too, with their error.
+//
+// Rows live in the `turn_traces` table (created in migrations.rs). Only the newest
+// `AppConfig::trace_retention` traces are kept; 0 disables tracing. Recording
+// is best-effort: a failed insert is logged and never fails the turn.
+
+use serde::{Deserialize, Serialize};
+use sqlx::sqlite::SqliteRow;
+use sqlx::Row;
+use tauri::{AppHandle, Manager, State};
+
+use crate::error::{AppError, ErrorKind};
+use crate::state::OllamaState;
+use crate::text_gen::context::PromptBreakdown;
+
+// ============================================================================
+// TYPES
+// ============================================================================
+
+/// Timings and backend-reported usage for one turn.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TraceTimings {
+    /// Loading history, memory, recall and assembling the prompt
+    pub context_ms: u64,
+    /// All generation attempts plus self-repair
+    pub generation_ms: u64,
+    /// The whole turn, start to save
+    pub total_ms: u64,
+    pub prompt_eval_tokens: Option<u64>,
+    pub prompt_eval_ms: Option<u64>,
+    pub eval_tokens: Option<u64>,
+    pub eval_ms: Option<u64>,
+    /// Estimated share of the prompt reused from llama.cpp's KV cache
+    pub cache_reuse_pct: Option<f64>,
+}
+
+/// The full trace of one story turn.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TurnTrace {
+    pub id: i64,
+    pub chat_id: i64,
+    pub story_id: Option<i64>,
+    /// The saved assistant message (None if the turn failed or was rewound)
+    pub assistant_message_id: Option<i64>,
+    pub user_input: String,
+    /// "raw" (templated prompt string) or "chat" (message list as JSON)
+    pub prompt_format: String,
+    pub prompt: String,
+    pub breakdown: PromptBreakdown,
+    /// Backend output before <think> stripping and self-repair
+    pub raw_response: String,
+    /// "ok", "partial", "fallback", or "error" if generation failed
+    pub parse_status: String,
+    pub parse_warnings: Vec<String>,
+    /// Errors of the failed generation attempts
+    pub retries: Vec<String>,
+    pub error: Option<String>,
+    pub timings: TraceTimings,
+    pub backend: String,
+    pub model: String,
+    pub created_at: Option<String>,
+}
+
+/// A trace without its large text fields, for listing.
+#[derive(Debug, Clone, Serialize)]
+pub struct TurnTraceSummary {
+    pub id: i64,
+    pub chat_id: i64,
+    pub story_id: Option<i64>,
+    pub assistant_message_id: Option<i64>,
+    pub user_input: String,
+    pub parse_status: String,
+    pub prompt_tokens: i64,
+    pub retry_count: usize,
+    pub total_ms: u64,
+    pub backend: String,
+    pub model: String,
+    pub created_at: Option<String>,
+}
+
+fn json_column<T: serde::de::DeserializeOwned + Default>(row: &SqliteRow, column: &str) -> T {
+    row.get::<Option<String>, _>(column)
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+fn trace_from_row(row: &SqliteRow) -> TurnTrace {
+    TurnTrace {
+        id: row.get("id"),
+        chat_id: row.get("chat_id"),
+        story_id: row.get("story_id"),
+        assistant_message_id: row.get("assistant_message_id"),
+        user_input: row.get("user_input"),
+        prompt_format: row.get("prompt_format"),
+        prompt: row.get("prompt"),
+        breakdown: json_column(row, "token_breakdown"),
+        raw_response: row.get("raw_response"),
+        parse_status: row.get("parse_status"),
+        parse_warnings: json_column(row, "parse_warnings"),
+        retries: json_column(row, "retries"),
+        error: row.get("error"),
+        timings: json_column(row, "timings"),
+        backend: row.get("backend"),
+        model: row.get("model"),
+        created_at: row.get("created_at"),
+    }
+}
+
+fn summary_from_row(row: &SqliteRow) -> TurnTraceSummary {
+    let retries: Vec<String> = json_column(row, "retries");
+    let timings: TraceTimings = json_column(row, "timings");
+    TurnTraceSummary {
+        id: row.get("id"),
+        chat_id: row.get("chat_id"),
+        story_id: row.get("story_id"),
+        assistant_message_id: row.get("assistant_message_id"),
+        user_input: row.get("user_input"),
+        parse_status: row.get("parse_status"),
+        prompt_tokens: row.get("prompt_tokens"),
+        retry_count: retries.len(),
+        total_ms: timings.total_ms,
+        backend: row.get("backend"),
+        model: row.get("model"),
+        created_at: row.get("created_at"),
+    }
+}
+
+// ============================================================================
+// RECORDING
+// ============================================================================
+
+/// Insert a trace and prune all but the newest `retention` traces.
+/// `retention` 0 records nothing. Non-fatal on failure.
+pub async fn record_trace(pool: &sqlx::SqlitePool, trace: &TurnTrace, retention: usize) {
+    if retention == 0 {
+        return;
+    }
+    let result = sqlx::query(
+        "INSERT INTO turn_traces \
+         (chat_id, story_id, assistant_message_id, user_input, prompt_format, prompt, \
+          token_breakdown, prompt_tokens, raw_response, parse_status, parse_warnings, retries, \
+          error, timings, backend, model) \
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(trace.chat_id)
+    .bind(trace.story_id)
+    .bind(trace.assistant_message_id)
+    .bind(&trace.user_input)
+    .bind(&trace.prompt_format)
+    .bind(&trace.prompt)
+    .bind(serde_json::to_string(&trace.breakdown).ok())
+    .bind(trace.breakdown.total() as i64)
+    .bind(&trace.raw_response)
+    .bind(&trace.parse_status)
+    .bind(serde_json::to_string(&trace.parse_warnings).ok())
+    .bind(serde_json::to_string(&trace.retries).ok())
+    .bind(&trace.error)
+    .bind(serde_json::to_string(&trace.timings).ok())
+    .bind(&trace.backend)
+    .bind(&trace.model)
+    .execute(pool)
+    .await;
+
+    if let Err(e) = result {
+        println!("[Trace] Failed to record turn trace: {}", e);
+        return;
+    }
+
+    let pruned = sqlx::query(
+        "DELETE FROM turn_traces WHERE id NOT IN \
+         (SELECT id FROM turn_traces ORDER BY id DESC LIMIT ?)",
+    )
+    .bind(retention as i64)
+    .execute(pool)
+    .await;
+    if let Err(e) = pruned {
+        println!("[Trace] Failed to prune turn traces: {}", e);
+    }
+}
+
+// ============================================================================
+// QUERIES
+// ============================================================================
+
+/// Columns read by `summary_from_row`.
+const SUMMARY_COLUMNS: &str = "id, chat_id, story_id, assistant_message_id, user_input, parse_status, \
+     prompt_tokens, retries, timings, backend, model, created_at";
+
+/// Traces of a story or chat (or all traces), newest first.
+async fn load_traces(
+    db: &sqlx::SqlitePool,
+    columns: &str,
+    story_id: Option<i64>,
+    chat_id: Option<i64>,
+    limit: Option<i64>,
+) -> Result<Vec<SqliteRow>, String> {
+    sqlx::query(&format!(
+        "SELECT {} FROM turn_traces \
+         WHERE (?1 IS NULL OR story_id = ?1) AND (?2 IS NULL OR chat_id = ?2) \
+         ORDER BY id DESC LIMIT ?3",
+        columns
+    ))
+    .bind(story_id)
+    .bind(chat_id)
+    .bind(limit.unwrap_or(-1))
+    .fetch_all(db)
+    .await
+    .map_err(|e| format!("Failed to load turn traces: {}", e))
+}
+
+/// List traces, newest first, without prompts and responses.
+///
+/// Frontend usage:
+/// const traces = await invoke('list_turn_traces', { storyId, chatId: null, limit: 50 });
+#[tauri::command]
+pub async fn list_turn_traces(
+    story_id: Option<i64>,
+    chat_id: Option<i64>,
+    limit: Option<i64>,
+    state: State<'_, OllamaState>,
+) -> Result<Vec<TurnTraceSummary>, AppError> {
+    Ok(load_traces(&state.db, SUMMARY_COLUMNS, story_id, chat_id, limit)
+        .await
+        .map_err(AppError::database)?
+        .iter()
+        .map(summary_from_row)
+        .collect())
+}
+
+/// Fetch one full trace.
+#[tauri::command]
+pub async fn get_turn_trace(trace_id: i64, state: State<'_, OllamaState>) -> Result<TurnTrace, AppError> {
+    sqlx::query("SELECT * FROM turn_traces WHERE id = ?")
+        .bind(trace_id)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to load turn trace: {}", e)))?
+        .map(|row| trace_from_row(&row))
+        .ok_or_else(|| AppError::not_found(format!("Turn trace {} not found", trace_id)))
+}
+
+/// Write the full traces of a story or chat (or all traces) to a JSON file
+/// in the app's exports folder. Returns the file path.
+#[tauri::command]
+pub async fn export_turn_traces(
+    story_id: Option<i64>,
+    chat_id: Option<i64>,
+    state: State<'_, OllamaState>,
+    app: AppHandle,
+) -> Result<String, AppError> {
+    let traces: Vec<TurnTrace> = load_traces(&state.db, "*", story_id, chat_id, None)
+        .await
+        .map_err(AppError::database)?
+        .iter()
+        .map(trace_from_row)
+        .collect();
+    let json = serde_json::to_string_pretty(&traces)
+        .map_err(|e| format!("Failed to serialize traces: {}", e))?;
+
+    let export_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app dir: {}", e))?
+        .join("exports");
+    std::fs::create_dir_all(&export_dir)
+        .map_err(|e| AppError::new(ErrorKind::Io, format!("Failed to create export dir: {}", e)))?;
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let scope = match (story_id, chat_id) {
+        (Some(id), _) => format!("story{}", id),
+        (None, Some(id)) => format!("chat{}", id),
+        (None, None) => "all".to_string(),
+    };
+    let filepath = export_dir.join(format!("traces_{}_{}.json", scope, now));
+    std::fs::write(&filepath, json)
+        .map_err(|e| AppError::new(ErrorKind::Io, format!("Failed to write traces: {}", e)))?;
+
+    let path_str = filepath.to_string_lossy().to_string();
+    println!("[Trace] Exported {} turn traces to {}", traces.len(), path_str);
+    Ok(path_str)
+}
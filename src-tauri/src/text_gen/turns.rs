@@ -0,0 +1,500 @@
+// src-tauri/src/text_gen/turns.rs
+//
+// Typed Story Turns
+// =================
+// Every assistant message of a story has one row in the `turns` table
+// (created in migrations.rs) holding what the parser got out of it: story text,
+// summary hint, scene, characters, emotional states and generation flags,
+// plus the turn's scene, image, parse status and model/timing metadata.
+//
+// The orchestrator writes the row in the same transaction as the messages
+// (see save_turn_to_db). Turns saved before the table existed are backfilled
+// by its migration from the raw message JSON with parse_status 'legacy'.
+//
+// Readers (context assembly, memory, recall, story load/export) use the typed
+// columns instead of re-parsing `messages.content`, which stays the raw LLM
+// output. Rows are deleted with their assistant message (ON DELETE CASCADE).
+
+use serde::{Deserialize, Serialize};
+use sqlx::sqlite::SqliteRow;
+use sqlx::Row;
+use tauri::State;
+
+use crate::commands::branch::{IN_LINEAGE, LINEAGE_CTE};
+use crate::error::AppError;
+use crate::state::OllamaState;
+use crate::text_gen::context::{
+    extract_location_from_response, extract_story_text, extract_summary_hint, parse_emotional_states,
+    StoryTurn,
+};
+use crate::text_gen::parser::{
+    CharacterEmotionalState, GenerationFlags, LlmTurnOutput, ParsedTurn, SceneCharacterRaw, SceneJson,
+};
+
+/// Columns of `turns t`, in the order `record_from_row` reads them.
+const TURN_COLUMNS: &str = "t.id, t.chat_id, t.user_message_id, t.assistant_message_id, \
+     t.story_text, t.summary_hint, t.location, t.scene_json, t.characters, t.emotional_states, \
+     t.generation_flags, t.scene_id, t.image_id, t.parse_status, t.model, t.backend, t.elapsed_ms, \
+     t.created_at";
+
+// ============================================================================
+// TYPES
+// ============================================================================
+
+/// One row of the `turns` table.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TurnRecord {
+    pub id: i64,
+    pub chat_id: i64,
+    pub user_message_id: Option<i64>,
+    pub assistant_message_id: i64,
+    pub story_text: String,
+    pub summary_hint: String,
+    /// scene_json.location, denormalized for queries (may be empty)
+    pub location: String,
+    pub scene: Option<SceneJson>,
+    pub characters: Vec<SceneCharacterRaw>,
+    pub emotional_states: Vec<CharacterEmotionalState>,
+    pub flags: Option<GenerationFlags>,
+    /// The story's active scene after this turn
+    pub scene_id: Option<i64>,
+    pub image_id: Option<i64>,
+    /// "ok", "partial", "fallback", or "legacy" for backfilled turns
+    pub parse_status: String,
+    pub model: Option<String>,
+    pub backend: Option<String>,
+    pub elapsed_ms: Option<i64>,
+    pub created_at: Option<String>,
+}
+
+impl TurnRecord {
+    /// Build the typed fields from the parser's output. Ids, scene, image and
+    /// metadata are filled in by the caller.
+    pub fn from_parsed(parsed: &ParsedTurn) -> Self {
+        Self {
+            story_text: parsed.story_text().to_string(),
+            summary_hint: parsed.summary_hint().to_string(),
+            location: parsed.scene().map(|s| s.location.clone()).unwrap_or_default(),
+            scene: parsed.turn.scene_json.clone(),
+            characters: parsed.turn.characters_in_scene.clone(),
+            emotional_states: parsed.emotional_states().to_vec(),
+            flags: parsed.turn.generation_flags.clone(),
+            ..Self::default()
+        }
+    }
+
+    /// Build the typed fields from a stored raw response (backfill). Uses the
+    /// same lenient extractors context assembly used before this table.
+    pub fn from_raw(raw: &str) -> Self {
+        let output = serde_json::from_str::<LlmTurnOutput>(raw).ok();
+        Self {
+            story_text: extract_story_text(raw),
+            summary_hint: extract_summary_hint(raw),
+            location: extract_location_from_response(raw),
+            scene: output.as_ref().and_then(|o| o.scene_json.clone()),
+            characters: output.as_ref().map(|o| o.characters_in_scene.clone()).unwrap_or_default(),
+            emotional_states: parse_emotional_states(raw),
+            flags: output.and_then(|o| o.generation_flags),
+            parse_status: "legacy".to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// True if `name` is in the scene or has an emotional state this turn.
+    pub fn has_character(&self, name: &str) -> bool {
+        self.characters.iter().any(|c| c.name.eq_ignore_ascii_case(name))
+            || self.emotional_states.iter().any(|s| s.name.eq_ignore_ascii_case(name))
+    }
+}
+
+/// A turn of a chat's timeline: the typed record plus the user input and raw
+/// response it was parsed from.
+#[derive(Debug, Clone)]
+pub struct TimelineTurn {
+    pub user_input: String,
+    pub raw_response: String,
+    pub image_path: Option<String>,
+    pub record: TurnRecord,
+}
+
+fn record_from_row(row: &SqliteRow) -> TurnRecord {
+    fn json<T: serde::de::DeserializeOwned + Default>(row: &SqliteRow, column: &str) -> T {
+        row.get::<Option<String>, _>(column)
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+    TurnRecord {
+        id: row.get("id"),
+        chat_id: row.get("chat_id"),
+        user_message_id: row.get("user_message_id"),
+        assistant_message_id: row.get("assistant_message_id"),
+        story_text: row.get("story_text"),
+        summary_hint: row.get("summary_hint"),
+        location: row.get("location"),
+        scene: json(row, "scene_json"),
+        characters: json(row, "characters"),
+        emotional_states: json(row, "emotional_states"),
+        flags: json(row, "generation_flags"),
+        scene_id: row.get("scene_id"),
+        image_id: row.get("image_id"),
+        parse_status: row.get("parse_status"),
+        model: row.get("model"),
+        backend: row.get("backend"),
+        elapsed_ms: row.get("elapsed_ms"),
+        created_at: row.get("created_at"),
+    }
+}
+
+// ============================================================================
+// WRITING
+// ============================================================================
+
+/// Insert a turn row. Runs on the caller's transaction so the row is saved
+/// together with its messages.
+pub async fn insert_turn<'e, E>(executor: E, turn: &TurnRecord) -> Result<i64, String>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Sqlite>,
+{
+    let result = sqlx::query(
+        "INSERT INTO turns \
+         (chat_id, user_message_id, assistant_message_id, story_text, summary_hint, location, \
+          scene_json, characters, emotional_states, generation_flags, scene_id, image_id, \
+          parse_status, model, backend, elapsed_ms, created_at) \
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, COALESCE(?, CURRENT_TIMESTAMP))",
+    )
+    .bind(turn.chat_id)
+    .bind(turn.user_message_id)
+    .bind(turn.assistant_message_id)
+    .bind(&turn.story_text)
+    .bind(&turn.summary_hint)
+    .bind(&turn.location)
+    .bind(turn.scene.as_ref().and_then(|s| serde_json::to_string(s).ok()))
+    .bind(serde_json::to_string(&turn.characters).unwrap_or_else(|_| "[]".to_string()))
+    .bind(serde_json::to_string(&turn.emotional_states).unwrap_or_else(|_| "[]".to_string()))
+    .bind(turn.flags.as_ref().and_then(|f| serde_json::to_string(f).ok()))
+    .bind(turn.scene_id)
+    .bind(turn.image_id)
+    .bind(&turn.parse_status)
+    .bind(&turn.model)
+    .bind(&turn.backend)
+    .bind(turn.elapsed_ms)
+    .bind(&turn.created_at)
+    .execute(executor)
+    .await
+    .map_err(|e| format!("Failed to save turn: {}", e))?;
+    Ok(result.last_insert_rowid())
+}
+
+/// Save the image for an assistant message, replacing any earlier one, and
+/// link it to the message's turn. Used by "Illustrate Scene" and "Redraw
+/// Image", which run after the turn was saved. Returns the image id.
+pub async fn set_turn_image(
+    db: &sqlx::SqlitePool,
+    message_id: i64,
+    chat_id: i64,
+    file_path: &str,
+) -> Result<i64, String> {
+    let mut tx = db.begin().await.map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+    sqlx::query("DELETE FROM images WHERE message_id = ?")
+        .bind(message_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| format!("Failed to remove old image record: {}", e))?;
+
+    let image_id = sqlx::query("INSERT INTO images (message_id, chat_id, file_path) VALUES (?, ?, ?)")
+        .bind(message_id)
+        .bind(chat_id)
+        .bind(file_path)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| format!("Failed to save image record: {}", e))?
+        .last_insert_rowid();
+
+    sqlx::query("UPDATE turns SET image_id = ? WHERE assistant_message_id = ?")
+        .bind(image_id)
+        .bind(message_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| format!("Failed to link image to turn: {}", e))?;
+
+    tx.commit().await.map_err(|e| format!("Transaction commit failed: {}", e))?;
+    Ok(image_id)
+}
+
+/// Create turn rows for story messages saved before the `turns` table
+/// existed. Returns how many were created. Runs once, in the migration that
+/// creates the table (see migrations.rs).
+pub async fn backfill_turns(conn: &mut sqlx::SqliteConnection) -> Result<usize, String> {
+    let rows = sqlx::query(
+        "SELECT m.id, m.chat_id, m.content, m.scene_id, m.timestamp, \
+           COALESCE(m.reply_to, (SELECT MAX(u.id) FROM messages u \
+             WHERE u.chat_id = m.chat_id AND u.role = 'user' AND u.id < m.id)) AS user_message_id, \
+           (SELECT MAX(i.id) FROM images i WHERE i.message_id = m.id) AS image_id \
+         FROM messages m \
+         LEFT JOIN turns t ON t.assistant_message_id = m.id \
+         WHERE m.role = 'assistant' AND t.id IS NULL \
+           AND m.chat_id IN (SELECT chat_id FROM story_premises WHERE chat_id IS NOT NULL \
+                             UNION SELECT chat_id FROM story_branches) \
+         ORDER BY m.id ASC",
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| format!("Failed to load messages for backfill: {}", e))?;
+
+    for row in &rows {
+        let content: String = row.get("content");
+        let turn = TurnRecord {
+            chat_id: row.get("chat_id"),
+            user_message_id: row.get("user_message_id"),
+            assistant_message_id: row.get("id"),
+            scene_id: row.get("scene_id"),
+            image_id: row.get("image_id"),
+            created_at: row.get("timestamp"),
+            ..TurnRecord::from_raw(&content)
+        };
+        insert_turn(&mut *conn, &turn).await?;
+    }
+    Ok(rows.len())
+}
+
+// ============================================================================
+// READING
+// ============================================================================
+
+/// Every selected turn of a chat's timeline (including turns shared from
+/// parent branches), oldest first.
+pub async fn load_timeline(db: &sqlx::SqlitePool, chat_id: i64) -> Result<Vec<TimelineTurn>, String> {
+    let rows = sqlx::query(&format!(
+        "{}SELECT {}, u.content AS user_input, m.content AS raw_response, i.file_path AS image_path \
+         FROM turns t \
+         INNER JOIN messages m ON m.id = t.assistant_message_id {} \
+         INNER JOIN messages u ON u.id = t.user_message_id \
+         LEFT JOIN images i ON i.id = t.image_id \
+         WHERE m.is_selected = 1 \
+         ORDER BY m.id ASC",
+        LINEAGE_CTE, TURN_COLUMNS, IN_LINEAGE
+    ))
+    .bind(chat_id)
+    .fetch_all(db)
+    .await
+    .map_err(|e| format!("Failed to load turns: {}", e))?;
+
+    Ok(rows
+        .iter()
+        .map(|row| TimelineTurn {
+            user_input: row.get("user_input"),
+            raw_response: row.get("raw_response"),
+            image_path: row.get("image_path"),
+            record: record_from_row(row),
+        })
+        .collect())
+}
+
+/// The chat's timeline as numbered StoryTurns (1-based, with message ids and
+/// image paths), for context assembly and the story view.
+pub async fn load_story_turns(db: &sqlx::SqlitePool, chat_id: i64) -> Result<Vec<StoryTurn>, String> {
+    Ok(load_timeline(db, chat_id)
+        .await?
+        .iter()
+        .enumerate()
+        .map(|(i, t)| {
+            let mut turn = StoryTurn::from_record(i + 1, &t.user_input, &t.raw_response, &t.record);
+            turn.image_path = t.image_path.clone();
+            turn
+        })
+        .collect())
+}
+
+// ============================================================================
+// SEARCH
+// ============================================================================
+
+/// Filters for `search_turns`. Every set filter must match (case-insensitive).
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TurnFilter {
+    /// Substring of the turn's location
+    #[serde(default)]
+    pub location: Option<String>,
+    /// Name of a character in the scene or with an emotional state
+    #[serde(default)]
+    pub character: Option<String>,
+    /// Substring of the player input or story text
+    #[serde(default)]
+    pub text: Option<String>,
+}
+
+impl TurnFilter {
+    pub fn matches(&self, turn: &TimelineTurn) -> bool {
+        let contains = |haystack: &str, needle: &str| haystack.to_lowercase().contains(&needle.to_lowercase());
+        let set = |f: &Option<String>| f.as_deref().map(str::trim).filter(|s| !s.is_empty()).map(String::from);
+
+        if let Some(location) = set(&self.location) {
+            if !contains(&turn.record.location, &location) {
+                return false;
+            }
+        }
+        if let Some(character) = set(&self.character) {
+            if !turn.record.has_character(&character) {
+                return false;
+            }
+        }
+        if let Some(text) = set(&self.text) {
+            if !contains(&turn.user_input, &text) && !contains(&turn.record.story_text, &text) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// A turn returned by `search_turns`.
+#[derive(Debug, Clone, Serialize)]
+pub struct TurnMatch {
+    /// 1-based position in the story's timeline
+    pub turn_number: usize,
+    pub user_input: String,
+    pub image_path: Option<String>,
+    #[serde(flatten)]
+    pub turn: TurnRecord,
+}
+
+/// Find turns of a story's active timeline, e.g. every turn at the tavern.
+///
+/// Frontend usage:
+/// const turns = await invoke('search_turns', { storyId, filter: { location: 'tavern' } });
+#[tauri::command]
+pub async fn search_turns(
+    story_id: i64,
+    filter: TurnFilter,
+    state: State<'_, OllamaState>,
+) -> Result<Vec<TurnMatch>, AppError> {
+    let chat_id: Option<i64> = sqlx::query("SELECT chat_id FROM story_premises WHERE id = ?")
+        .bind(story_id)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to load story: {}", e)))?
+        .ok_or_else(|| AppError::not_found(format!("Story {} not found", story_id)))?
+        .get("chat_id");
+    let Some(chat_id) = chat_id else {
+        return Ok(Vec::new());
+    };
+
+    Ok(load_timeline(&state.db, chat_id)
+        .await
+        .map_err(AppError::database)?
+        .into_iter()
+        .enumerate()
+        .filter(|(_, t)| filter.matches(t))
+        .map(|(i, t)| TurnMatch {
+            turn_number: i + 1,
+            user_input: t.user_input,
+            image_path: t.image_path,
+            turn: t.record,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    fn timeline_turn(user: &str, raw: &str) -> TimelineTurn {
+        TimelineTurn {
+            user_input: user.to_string(),
+            raw_response: raw.to_string(),
+            image_path: None,
+            record: TurnRecord::from_raw(raw),
+        }
+    }
+
+    const TAVERN: &str = r#"{"story_json":{"response":"Mira pours the ale.","summary_hint":"Ale poured."},
+        "scene_json":{"location":"The Rusty Tavern"},
+        "characters_in_scene":[{"name":"Mira","region":"left"}],
+        "emotional_states":[{"name":"Mira","current_emotion":"amused"}],
+        "generation_flags":{"generate_image":true}}"#;
+
+    #[test]
+    fn test_from_raw_parses_typed_fields() {
+        let turn = TurnRecord::from_raw(TAVERN);
+        assert_eq!(turn.story_text, "Mira pours the ale.");
+        assert_eq!(turn.summary_hint, "Ale poured.");
+        assert_eq!(turn.location, "The Rusty Tavern");
+        assert_eq!(turn.characters.len(), 1);
+        assert_eq!(turn.emotional_states[0].emotion_intensity, "medium");
+        assert!(turn.flags.is_some_and(|f| f.generate_image));
+        assert_eq!(turn.parse_status, "legacy");
+
+        let plain = TurnRecord::from_raw("Just prose.");
+        assert_eq!(plain.story_text, "Just prose.");
+        assert!(plain.scene.is_none() && plain.characters.is_empty());
+    }
+
+    #[test]
+    fn test_filter_matches_location_character_and_text() {
+        let turn = timeline_turn("Order a drink", TAVERN);
+        let filter = |location: Option<&str>, character: Option<&str>, text: Option<&str>| TurnFilter {
+            location: location.map(String::from),
+            character: character.map(String::from),
+            text: text.map(String::from),
+        };
+
+        assert!(filter(None, None, None).matches(&turn));
+        assert!(filter(Some("tavern"), None, None).matches(&turn));
+        assert!(filter(Some("tavern"), Some("mira"), Some("DRINK")).matches(&turn));
+        assert!(filter(None, None, Some("ale")).matches(&turn));
+        assert!(!filter(Some("forest"), None, None).matches(&turn));
+        assert!(!filter(None, Some("Marcus"), None).matches(&turn));
+    }
+
+    #[tokio::test]
+    async fn test_reillustrating_replaces_the_turn_image() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::migrations::migrate(&pool, None).await.unwrap();
+
+        let chat_id = sqlx::query("INSERT INTO chats (title) VALUES ('Story')")
+            .execute(&pool)
+            .await
+            .unwrap()
+            .last_insert_rowid();
+        let mut message_ids = Vec::new();
+        for (role, content) in [("user", "Order a drink"), ("assistant", TAVERN)] {
+            let id = sqlx::query("INSERT INTO messages (chat_id, role, content) VALUES (?, ?, ?)")
+                .bind(chat_id)
+                .bind(role)
+                .bind(content)
+                .execute(&pool)
+                .await
+                .unwrap()
+                .last_insert_rowid();
+            message_ids.push(id);
+        }
+        let turn = TurnRecord {
+            chat_id,
+            user_message_id: Some(message_ids[0]),
+            assistant_message_id: message_ids[1],
+            ..TurnRecord::from_raw(TAVERN)
+        };
+        insert_turn(&pool, &turn).await.unwrap();
+
+        set_turn_image(&pool, message_ids[1], chat_id, "first.png").await.unwrap();
+        let image_id = set_turn_image(&pool, message_ids[1], chat_id, "second.png").await.unwrap();
+
+        let timeline = load_timeline(&pool, chat_id).await.unwrap();
+        assert_eq!(timeline.len(), 1);
+        assert_eq!(timeline[0].image_path.as_deref(), Some("second.png"));
+        assert_eq!(timeline[0].record.image_id, Some(image_id));
+        let images: i64 = sqlx::query("SELECT COUNT(*) FROM images WHERE message_id = ?")
+            .bind(message_ids[1])
+            .fetch_one(&pool)
+            .await
+            .unwrap()
+            .get(0);
+        assert_eq!(images, 1);
+    }
+}
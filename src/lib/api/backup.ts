@@ -0,0 +1,38 @@
+// src/lib/api/backup.ts — Database backup API layer
+// Snapshots of storyteller.db (optionally with generated images and portraits)
+// in the app's backups folder. Restoring backs up the current data first.
+
+import { invoke } from '@tauri-apps/api/core';
+
+export interface BackupInfo {
+  file_name: string;
+  path: string;
+  /** "manual", "scheduled", "pre_restore" or "pre_migration_v<N>" */
+  kind: string;
+  /** Unix seconds */
+  created_at: number;
+  size_bytes: number;
+  includes_media: boolean;
+}
+
+/** Back up now; includeMedia defaults to the backup_include_media setting. */
+export async function createBackup(includeMedia?: boolean): Promise<BackupInfo> {
+  return invoke('create_backup', { includeMedia: includeMedia ?? null });
+}
+
+/** All backups, newest first. */
+export async function listBackups(): Promise<BackupInfo[]> {
+  return invoke('list_backups');
+}
+
+/**
+ * Replace all stories, characters and scenes with a backup's. Resolves to the
+ * backup of the data that was replaced. Reload everything afterwards.
+ */
+export async function restoreBackup(fileName: string): Promise<BackupInfo> {
+  return invoke('restore_backup', { fileName });
+}
+
+export async function deleteBackup(fileName: string): Promise<void> {
+  return invoke('delete_backup', { fileName });
+}
@@ -1,6 +1,20 @@
 // src/lib/api/config.ts — Tauri command wrappers for app configuration
 import { invoke } from '@tauri-apps/api/core';
 
+/** Chat template family used for raw prompts (see text_gen::template). */
+export type TemplateFormat = 'chatml' | 'llama3' | 'mistral' | 'gemma' | 'alpaca' | 'custom';
+
+export interface PromptTemplate {
+  bos: string;
+  system_prefix: string;
+  system_suffix: string;
+  user_prefix: string;
+  user_suffix: string;
+  assistant_prefix: string;
+  assistant_suffix: string;
+  stop: string[];
+}
+
 export interface AppConfig {
   sd_webui_path: string;
   ollama_url: string;
@@ -13,6 +27,36 @@ export interface AppConfig {
   controlnet_pose_strength: number;
   controlnet_pose_enabled: boolean;
   keep_alive: string;
+  stream_responses: boolean;
+  text_backend: 'ollama' | 'openai';
+  openai_api_url: string;
+  openai_api_key: string;
+  story_model: string;
+  temperature: number;
+  top_p: number | null;
+  top_k: number | null;
+  repeat_penalty: number | null;
+  num_ctx: number;
+  num_predict: number | null;
+  structured_output: boolean;
+  recall_enabled: boolean;
+  embedding_url: string;
+  embedding_model: string;
+  recall_top_k: number;
+  lore_token_budget: number;
+  /** Newest turn traces kept; 0 disables tracing */
+  trace_retention: number;
+  /** Hours between automatic database backups; 0 disables them */
+  backup_interval_hours: number;
+  /** Backups kept; older ones are deleted. 0 keeps all */
+  backup_retention: number;
+  /** Bundle generated images and portraits into backups */
+  backup_include_media: boolean;
+  /** Characters rendered with their own face reference in scene images (1-9) */
+  max_scene_characters: number;
+  /** Template per story model; unlisted models are detected from their name */
+  model_templates: Record<string, TemplateFormat>;
+  custom_template: PromptTemplate;
 }
 
 export async function getConfig(): Promise<AppConfig> {
@@ -0,0 +1,25 @@
+// src/lib/api/errors.ts — Helpers for command rejections (typed AppError or plain string)
+import type { AppError } from '$lib/types';
+import { GENERATION_CANCELLED } from './text-gen';
+
+export function isAppError(e: unknown): e is AppError {
+  return typeof e === 'object' && e !== null && 'kind' in e && 'message' in e;
+}
+
+/** Human-readable message for any caught error. */
+export function errorMessage(e: unknown): string {
+  if (isAppError(e)) return e.message;
+  if (e instanceof Error) return e.message;
+  return String(e);
+}
+
+/** Message plus the suggested fix, when the backend gave one. */
+export function describeError(e: unknown): string {
+  const message = errorMessage(e);
+  return isAppError(e) && e.remediation ? `${message} — ${e.remediation}` : message;
+}
+
+/** True when the user aborted the generation (see cancelGeneration). */
+export function isCancelled(e: unknown): boolean {
+  return isAppError(e) ? e.kind === 'cancelled' : errorMessage(e) === GENERATION_CANCELLED;
+}
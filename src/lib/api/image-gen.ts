@@ -1,5 +1,6 @@
 // src/lib/api/image-gen.ts — Tauri command wrappers for image generation
 import { invoke } from '@tauri-apps/api/core';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
 
 export interface MasterPortraitRequest {
   name: string;
@@ -35,8 +36,61 @@ export interface MasterPortraitResult {
   prompt_id: string;
 }
 
-export async function generateMasterPortrait(request: MasterPortraitRequest): Promise<MasterPortraitResult> {
-  return invoke('generate_master_portrait', { request });
+export interface WorkflowManifest {
+  positive_prompt?: string;
+  negative_prompt?: string;
+  latent?: string;
+  sampler?: string;
+  decode?: string;
+  output?: string;
+  insightface?: string;
+  reference_images: string[];
+  masks: string[];
+}
+
+export interface WorkflowValidation {
+  path: string;
+  manifest_found: boolean;
+  roles: WorkflowManifest;
+  unmapped: string[];
+  problems: string[];
+}
+
+export interface MissingRequirement {
+  kind: 'node_pack' | 'model';
+  name: string;
+  /** Dependency name for install_dependency, when setup can install it */
+  installer: string | null;
+  required_by: string[];
+}
+
+/** Payload of the `image-progress` event sent while ComfyUI renders a scene or portrait. */
+export interface ImageProgress {
+  job_id: string | null;
+  prompt_id: string;
+  status: 'Queued' | 'Running' | 'Completed' | 'Failed' | 'TimedOut';
+  /** Fraction of the current sampler's steps done, 0-1 */
+  progress?: number;
+  current_node?: string;
+  step?: number;
+  total?: number;
+  /** Latest step preview as a data URL */
+  preview?: string;
+}
+
+export function onImageProgress(handler: (progress: ImageProgress) => void): Promise<UnlistenFn> {
+  return listen<ImageProgress>('image-progress', (event) => handler(event.payload));
+}
+
+/** cancel_generation / image-progress job id of master portrait batches. */
+export const PORTRAIT_JOB_ID = 'portrait';
+
+/** `storyId` picks the story's model override to unload from VRAM first. */
+export async function generateMasterPortrait(
+  request: MasterPortraitRequest,
+  storyId?: number,
+): Promise<MasterPortraitResult> {
+  return invoke('generate_master_portrait', { request, storyId: storyId ?? null, jobId: PORTRAIT_JOB_ID });
 }
 
 export async function generateCharacterPortrait(prompt: string, style?: string): Promise<string> {
@@ -78,3 +132,11 @@ export async function previewPortraitPrompt(request: MasterPortraitRequest): Pro
 export async function saveMasterPortrait(request: SaveMasterPortraitRequest): Promise<string> {
   return invoke('save_master_portrait', { request });
 }
+
+export async function validateWorkflow(path: string, characters?: number): Promise<WorkflowValidation> {
+  return invoke('validate_workflow', { path, characters });
+}
+
+export async function checkComfyUIRequirements(workflow: unknown): Promise<MissingRequirement[]> {
+  return invoke('check_comfyui_requirements', { workflow });
+}
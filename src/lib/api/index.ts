@@ -7,3 +7,6 @@ export * from './image-gen';
 export * from './services';
 export * from './config';
 export * from './scene';
+export * from './lore';
+export * from './backup';
+export * from './errors';
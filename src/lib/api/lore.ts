@@ -0,0 +1,31 @@
+// src/lib/api/lore.ts — Lorebook (world info) API layer
+// Entries are injected into the story prompt when their keys appear in recent
+// turns. story_id null = global entry shared by every story.
+
+import { invoke } from '@tauri-apps/api/core';
+import type { LoreEntry, LoreEntryInput } from '../types';
+
+// ─── CRUD ────────────────────────────────────────────────────────────────────
+
+export async function createLoreEntry(entry: LoreEntryInput): Promise<number> {
+  return invoke('create_lore_entry', { entry });
+}
+
+export async function updateLoreEntry(id: number, entry: LoreEntryInput): Promise<void> {
+  return invoke('update_lore_entry', { id, entry });
+}
+
+export async function deleteLoreEntry(id: number): Promise<void> {
+  return invoke('delete_lore_entry', { id });
+}
+
+// ─── Queries ─────────────────────────────────────────────────────────────────
+
+/** Entries visible to a story (its own + global); global only when storyId is omitted. */
+export async function listLoreEntries(storyId?: number): Promise<LoreEntry[]> {
+  return invoke('list_lore_entries', { storyId: storyId ?? null });
+}
+
+export async function getLoreEntry(id: number): Promise<LoreEntry | null> {
+  return invoke('get_lore_entry', { id });
+}
@@ -1,6 +1,6 @@
 // src/lib/api/story.ts — Tauri command wrappers for story operations
 import { invoke } from '@tauri-apps/api/core';
-import type { StorySession, StorySummary, ExportFormat, StoryPremise, CompressedHistory, StoryImage } from '$lib/types';
+import type { StorySession, StorySummary, ExportFormat, StoryPremise, CompressedHistory, StoryImage, StoryBible, StoryBranch } from '$lib/types';
 
 export async function createStory(
   title: string,
@@ -20,6 +20,64 @@ export async function updateStoryRating(storyId: number, contentRating: string):
   return invoke('update_story_rating', { storyId, contentRating });
 }
 
+/** Per-story model / sampler overrides. `null` = use the app setting. */
+export interface GenerationOverrides {
+  model: string | null;
+  temperature: number | null;
+  top_p: number | null;
+  top_k: number | null;
+  repeat_penalty: number | null;
+  num_ctx: number | null;
+  num_predict: number | null;
+}
+
+export async function getStoryGenerationSettings(storyId: number): Promise<GenerationOverrides> {
+  return invoke('get_story_generation_settings', { storyId });
+}
+
+export async function updateStoryGenerationSettings(
+  storyId: number,
+  overrides: GenerationOverrides,
+): Promise<void> {
+  return invoke('update_story_generation_settings', { storyId, overrides });
+}
+
+export async function getStoryBible(storyId: number): Promise<StoryBible> {
+  return invoke('get_story_bible', { storyId });
+}
+
+export async function updateStoryBible(storyId: number, bible: StoryBible): Promise<void> {
+  return invoke('update_story_bible', { storyId, bible });
+}
+
+/** Fork the story at a message and make the new branch active. */
+export async function forkStoryAt(messageId: number, name?: string): Promise<StoryBranch> {
+  return invoke('fork_story_at', { messageId, name: name ?? null });
+}
+
+export async function listStoryBranches(storyId: number): Promise<StoryBranch[]> {
+  return invoke('list_story_branches', { storyId });
+}
+
+/** Switch the active timeline; reload the story afterwards. */
+export async function switchStoryBranch(storyId: number, chatId: number): Promise<void> {
+  return invoke('switch_story_branch', { storyId, chatId });
+}
+
+/**
+ * Rewind a chat to a past message (an assistant message keeps its turn, a user
+ * message drops it too). Scene, roster and emotions are restored. Returns the
+ * number of turns removed.
+ */
+export async function rewindStoryTo(chatId: number, messageId: number): Promise<number> {
+  return invoke('rewind_story_to', { chatId, messageId });
+}
+
+/** Edit a past message in place (story text only for assistant messages). */
+export async function editStoryMessage(messageId: number, content: string): Promise<void> {
+  return invoke('edit_story_message', { messageId, content });
+}
+
 export async function loadStory(storyId: number): Promise<StorySession> {
   return invoke('load_story', { storyId });
 }
@@ -53,8 +111,9 @@ export async function getStoryForChat(chatId: number): Promise<{ id: number; tit
   return invoke('get_story_for_chat', { chatId });
 }
 
-export async function freeVram(): Promise<void> {
-  return invoke('free_vram');
+/** Free GPU memory. `storyId` picks the story's model override to unload. */
+export async function freeVram(storyId?: number): Promise<void> {
+  return invoke('free_vram', { storyId: storyId ?? null });
 }
 
 // ---- Legacy commands (pre-story-manager) ----
@@ -5,6 +5,9 @@ import type {
   GenerationFlags,
   StoryTurnResult,
   OrchestratorCompressionInfo,
+  TurnCandidate,
+  TurnFilter,
+  TurnMatch,
 } from '$lib/types';
 
 // ---- Orchestrator ----
@@ -24,6 +27,7 @@ export async function generateSceneImageForTurn(
   characterPoses?: string[],
   positivePromptOverride?: string,
   negativePromptOverride?: string,
+  jobId?: string,
 ): Promise<string> {
   return invoke('generate_scene_image_for_turn', {
     scenePrompt,
@@ -32,6 +36,7 @@ export async function generateSceneImageForTurn(
     characterPoses: (characterPoses && characterPoses.length > 0) ? characterPoses : null,
     positivePromptOverride: positivePromptOverride ?? null,
     negativePromptOverride: negativePromptOverride ?? null,
+    jobId: jobId ?? null,
   });
 }
 
@@ -55,6 +60,7 @@ export async function illustrateSceneCustom(
   messageId: number,
   positivePrompt: string,
   negativePrompt: string,
+  jobId?: string,
 ): Promise<string> {
   return invoke('illustrate_scene_custom', {
     storyId,
@@ -62,6 +68,7 @@ export async function illustrateSceneCustom(
     messageId,
     positivePrompt,
     negativePrompt,
+    jobId: jobId ?? null,
   });
 }
 
@@ -69,6 +76,54 @@ export async function getCompressionDiagnostics(chatId: number): Promise<Orchest
   return invoke('get_compression_diagnostics', { chatId });
 }
 
+export interface ContextSummary {
+  id: number;
+  chat_id: number;
+  first_turn: number;
+  last_turn: number;
+  last_message_id: number;
+  summary: string;
+  created_at: string | null;
+}
+
+/** Stored compression summaries for a chat (the covered turns stay in history). */
+export async function getContextSummaries(chatId: number): Promise<ContextSummary[]> {
+  return invoke('get_context_summaries', { chatId });
+}
+
+export interface MemoryEntry {
+  id: number;
+  chat_id: number;
+  level: 'scene' | 'chapter' | 'arc';
+  scene_id: number | null;
+  first_turn: number;
+  last_turn: number;
+  last_message_id: number;
+  summary: string;
+  parent_id: number | null;
+}
+
+/** Hierarchical scene / chapter / arc summaries for a chat. */
+export async function getStoryMemory(chatId: number): Promise<MemoryEntry[]> {
+  return invoke('get_story_memory', { chatId });
+}
+
+export interface RecalledTurn {
+  turn_number: number;
+  message_id: number;
+  text: string;
+  score: number;
+}
+
+/** Semantic search over a chat's indexed turns (best match first). */
+export async function searchStoryMemories(
+  chatId: number,
+  query: string,
+  topK?: number
+): Promise<RecalledTurn[]> {
+  return invoke('search_story_memories', { chatId, query, topK: topK ?? null });
+}
+
 export async function regenerateStory(id: number, storyId?: number): Promise<StoryTurnResult> {
   return invoke('regenerate_story', { id, storyId: storyId ?? null });
 }
@@ -81,6 +136,135 @@ export async function regenerateStoryWithInput(
   return invoke('regenerate_story_with_input', { id, userInput, storyId: storyId ?? null });
 }
 
+/** Every response generated for the turn of an assistant message (oldest first). */
+export async function listTurnCandidates(messageId: number): Promise<TurnCandidate[]> {
+  return invoke('list_turn_candidates', { messageId });
+}
+
+/** Switch the latest turn to another candidate; reload the chat afterwards. */
+export async function selectTurnCandidate(messageId: number): Promise<void> {
+  return invoke('select_turn_candidate', { messageId });
+}
+
+/** Message of the error returned by any command aborted via cancelGeneration (see isCancelled). */
+export const GENERATION_CANCELLED = 'Generation cancelled';
+
+/**
+ * Cancel an in-flight story turn (by chatId) or image job (by jobId).
+ * With no arguments, cancels every running generation.
+ */
+export async function cancelGeneration(chatId?: number, jobId?: string): Promise<number> {
+  return invoke('cancel_generation', { chatId: chatId ?? null, jobId: jobId ?? null });
+}
+
+// ---- Parse statistics ----
+
+export interface ParseStatusCounts {
+  total: number;
+  ok: number;
+  partial: number;
+  fallback: number;
+  fallback_rate: number;
+}
+
+export interface ParseStats {
+  overall: ParseStatusCounts;
+  structured: ParseStatusCounts;
+  unstructured: ParseStatusCounts;
+}
+
+/** Parse outcome counts for one story (or all stories), split by structured output. */
+export async function getParseStats(storyId?: number): Promise<ParseStats> {
+  return invoke('get_parse_stats', { storyId: storyId ?? null });
+}
+
+/** Turns of a story's active timeline matching the filter, e.g. { location: 'tavern' }. */
+export async function searchTurns(storyId: number, filter: TurnFilter): Promise<TurnMatch[]> {
+  return invoke('search_turns', { storyId, filter });
+}
+
+// ---- Turn Traces ----
+
+/** Estimated tokens per prompt section. */
+export interface PromptBreakdown {
+  system: number;
+  premise: number;
+  summary: number;
+  characters: number;
+  scene: number;
+  lore: number;
+  history: number;
+  recall: number;
+  emotions: number;
+  input: number;
+}
+
+export interface TraceTimings {
+  context_ms: number;
+  generation_ms: number;
+  total_ms: number;
+  prompt_eval_tokens: number | null;
+  prompt_eval_ms: number | null;
+  eval_tokens: number | null;
+  eval_ms: number | null;
+  cache_reuse_pct: number | null;
+}
+
+export interface TurnTrace {
+  id: number;
+  chat_id: number;
+  story_id: number | null;
+  assistant_message_id: number | null;
+  user_input: string;
+  /** 'raw' (templated prompt) or 'chat' (message list as JSON) */
+  prompt_format: string;
+  prompt: string;
+  breakdown: PromptBreakdown;
+  raw_response: string;
+  /** 'ok' | 'partial' | 'fallback', or 'error' if generation failed */
+  parse_status: string;
+  parse_warnings: string[];
+  retries: string[];
+  error: string | null;
+  timings: TraceTimings;
+  backend: string;
+  model: string;
+  created_at: string | null;
+}
+
+export interface TurnTraceSummary {
+  id: number;
+  chat_id: number;
+  story_id: number | null;
+  assistant_message_id: number | null;
+  user_input: string;
+  parse_status: string;
+  prompt_tokens: number;
+  retry_count: number;
+  total_ms: number;
+  backend: string;
+  model: string;
+  created_at: string | null;
+}
+
+/** Traces of a story or chat (or all), newest first. */
+export async function listTurnTraces(
+  storyId?: number,
+  chatId?: number,
+  limit?: number
+): Promise<TurnTraceSummary[]> {
+  return invoke('list_turn_traces', { storyId: storyId ?? null, chatId: chatId ?? null, limit: limit ?? null });
+}
+
+export async function getTurnTrace(traceId: number): Promise<TurnTrace> {
+  return invoke('get_turn_trace', { traceId });
+}
+
+/** Writes the traces to a JSON file in the exports folder; returns its path. */
+export async function exportTurnTraces(storyId?: number, chatId?: number): Promise<string> {
+  return invoke('export_turn_traces', { storyId: storyId ?? null, chatId: chatId ?? null });
+}
+
 // ---- LLM Parser ----
 
 export async function parseStoryTurn(rawOutput: string): Promise<ParsedTurn> {
@@ -25,6 +25,7 @@ import {
   deleteStory as apiDeleteStory,
   exportStory as apiExportStory,
 } from '$lib/api/story';
+import { errorMessage } from '$lib/api/errors';
 
 // ============================================================================
 // STORES
@@ -100,7 +101,7 @@ export async function newStory(
 
     return storyId;
   } catch (e) {
-    const error = e instanceof Error ? e.message : String(e);
+    const error = errorMessage(e);
     storyState.update((s) => ({ ...s, lastError: error, isLoading: false }));
     console.error('[StoryStore] Failed to create story:', error);
     return null;
@@ -130,7 +131,7 @@ export async function loadStory(storyId: number): Promise<StorySession | null> {
 
     return session;
   } catch (e) {
-    const error = e instanceof Error ? e.message : String(e);
+    const error = errorMessage(e);
     storyState.update((s) => ({ ...s, lastError: error, isLoading: false }));
     console.error('[StoryStore] Failed to load story:', error);
     return null;
@@ -160,7 +161,7 @@ export async function saveStory(): Promise<boolean> {
     console.log(`[StoryStore] Saved story ${state.currentStory.story_id}`);
     return true;
   } catch (e) {
-    const error = e instanceof Error ? e.message : String(e);
+    const error = errorMessage(e);
     storyState.update((s) => ({ ...s, lastError: error }));
     console.error('[StoryStore] Failed to save story:', error);
     return false;
@@ -177,7 +178,7 @@ export async function refreshStoryList(contentRatingFilter?: string): Promise<St
     storyState.update((s) => ({ ...s, stories: list }));
     return list;
   } catch (e) {
-    const error = e instanceof Error ? e.message : String(e);
+    const error = errorMessage(e);
     storyState.update((s) => ({ ...s, lastError: error }));
     console.error('[StoryStore] Failed to list stories:', error);
     return [];
@@ -207,7 +208,7 @@ export async function deleteStory(storyId: number): Promise<boolean> {
     console.log(`[StoryStore] Deleted story ${storyId}`);
     return true;
   } catch (e) {
-    const error = e instanceof Error ? e.message : String(e);
+    const error = errorMessage(e);
     storyState.update((s) => ({ ...s, lastError: error, isLoading: false }));
     console.error('[StoryStore] Failed to delete story:', error);
     return false;
@@ -231,7 +232,7 @@ export async function exportStory(
     console.log(`[StoryStore] Exported story ${storyId} to ${filePath}`);
     return filePath;
   } catch (e) {
-    const error = e instanceof Error ? e.message : String(e);
+    const error = errorMessage(e);
     storyState.update((s) => ({ ...s, lastError: error, isLoading: false }));
     console.error('[StoryStore] Failed to export story:', error);
     return null;
@@ -201,8 +201,34 @@ export interface CreateStoryRequest {
 export type ExportFormat = 'json' | 'html';
 
 /** Exported story data (JSON format). */
+/** Story-level facts injected into every prompt for the story. */
+export interface StoryBible {
+  title: string;
+  premise: string;
+  genre: string | null;
+  tone: string | null;
+  setting: string | null;
+  themes: string[];
+  hard_rules: string[];
+}
+
+/** One timeline of a story; the entry without a parent is the original run. */
+export interface StoryBranch {
+  id: number;
+  story_id: number;
+  chat_id: number;
+  parent_chat_id: number | null;
+  /** Last parent message included in this branch's history */
+  shared_until_id: number | null;
+  name: string;
+  turn_count: number;
+  is_active: boolean;
+  created_at: string | null;
+}
+
 export interface ExportedStory {
   meta: ExportedMeta;
+  bible: StoryBible;
   characters: CharacterProfile[];
   compressed_history: CompressedHistory;
   turns: ExportedTurn[];
@@ -257,6 +283,38 @@ export interface Scene {
   created_at: string;
 }
 
+/** Where a lore entry is inserted in the story prompt. */
+export type LorePosition = 'after_premise' | 'after_characters' | 'before_input';
+
+/** Lorebook / world-info entry. story_id null = global. Mirrors LoreEntry in Rust. */
+export interface LoreEntry {
+  id: number;
+  story_id: number | null;
+  name: string;
+  keys: string[];
+  content: string;
+  priority: number;
+  position: LorePosition;
+  always_on: boolean;
+  enabled: boolean;
+  created_at: string | null;
+}
+
+/** Editable lore fields sent to create/update. */
+export type LoreEntryInput = Omit<LoreEntry, 'id' | 'created_at'>;
+
+/** A lore entry that fired on a turn. */
+export interface FiredLore {
+  id: number;
+  name: string;
+  position: LorePosition;
+  /** Matched key (null for always-on entries) */
+  trigger: string | null;
+  tokens: number;
+  /** False if it matched but didn't fit the lore token budget */
+  injected: boolean;
+}
+
 export interface SceneWithCharacters {
   scene: Scene;
   characters: CharacterProfile[];
@@ -399,6 +457,8 @@ export interface OrchestratorCompressionInfo {
   compression_threshold: number;
   needs_compression: boolean;
   compressed_summary_preview: string;
+  memory_entries: number;
+  lore_entries: FiredLore[];
 }
 
 /** Emotional state of a character at the end of a story turn. */
@@ -410,6 +470,56 @@ export interface CharacterEmotionalState {
   lingering_emotions: string[];
 }
 
+/** One alternative response ("swipe") for a turn. */
+export interface TurnCandidate {
+  message_id: number;
+  content: string;
+  /** The user input this candidate answers */
+  user_input: string;
+  image_path: string | null;
+  selected: boolean;
+  timestamp: string;
+}
+
+/** A row of the typed turns table: the parsed fields of one assistant message. */
+export interface TurnRecord {
+  id: number;
+  chat_id: number;
+  user_message_id: number | null;
+  assistant_message_id: number;
+  story_text: string;
+  summary_hint: string;
+  location: string;
+  scene: SceneJson | null;
+  characters: SceneCharacter[];
+  emotional_states: CharacterEmotionalState[];
+  flags: GenerationFlags | null;
+  /** The story's active scene after this turn */
+  scene_id: number | null;
+  image_id: number | null;
+  /** "legacy" for turns saved before the turns table existed */
+  parse_status: 'ok' | 'partial' | 'fallback' | 'legacy';
+  model: string | null;
+  backend: string | null;
+  elapsed_ms: number | null;
+  created_at: string | null;
+}
+
+/** Filters for searchTurns; every set filter must match (case-insensitive). */
+export interface TurnFilter {
+  location?: string;
+  character?: string;
+  text?: string;
+}
+
+/** A turn found by searchTurns. */
+export interface TurnMatch extends TurnRecord {
+  /** 1-based position in the story's timeline */
+  turn_number: number;
+  user_input: string;
+  image_path: string | null;
+}
+
 /** Complete result of a single story turn from the orchestrator. */
 export interface StoryTurnResult {
   /** Turn sequence number from the LLM. */
@@ -494,6 +604,12 @@ export interface StoryTurn {
   assistant_response: string;
   /** One-line summary from story_json.summary_hint */
   summary_hint: string;
+  /** Narrative text of the response */
+  story_text: string;
+  /** Location of the scene after this turn (may be empty) */
+  location: string;
+  /** Characters' emotional states at the end of this turn */
+  emotional_states: CharacterEmotionalState[];
   /** Approximate token count for this turn */
   token_estimate: number;
   /** DB message_id of the assistant message (null if not loaded from DB) */
@@ -540,6 +656,10 @@ export interface CompressionDiagnostics {
   needs_compression: boolean;
   /** First 200 chars of the compressed summary for preview */
   compressed_summary_preview: string;
+  /** Scene/chapter/arc summaries stored for this chat */
+  memory_entries: number;
+  /** Lorebook entries the recent turns would trigger */
+  lore_entries: FiredLore[];
 }
 
 /** Result from the context assembly function. */
@@ -600,4 +720,36 @@ export interface CustomPose {
   display_name: string;
   filename: string;
   created_at: string;
-}
\ No newline at end of file
+}
+// ============================================================================
+// ERRORS (see src-tauri/src/error.rs)
+// ============================================================================
+
+export type ErrorKind =
+  | 'backend_unavailable'
+  | 'model_not_found'
+  | 'context_overflow'
+  | 'generation_failed'
+  | 'comfy_unavailable'
+  | 'comfy_missing_node'
+  | 'workflow_not_found'
+  | 'invalid_workflow'
+  | 'image_generation_failed'
+  | 'timeout'
+  | 'cancelled'
+  | 'database'
+  | 'io'
+  | 'not_found'
+  | 'invalid_input'
+  | 'setup_failed'
+  | 'other';
+
+/** Rejection value of the story, image, portrait, setup and database commands. */
+export interface AppError {
+  kind: ErrorKind;
+  message: string;
+  /** Whether trying again may succeed */
+  retryable: boolean;
+  /** Suggested fix to show the user */
+  remediation: string | null;
+}